@@ -0,0 +1,122 @@
+//! 指令縮寫模組
+//!
+//! 與別名不同，縮寫表只列出完整指令，輸入任何不會混淆的前綴即展開為完整指令
+//! （如 `inv` → `inventory`）。
+
+use crate::paths::is_movement_command;
+
+/// 縮寫前綴的預設最短長度（避免單一字母與方向指令衝突）
+pub const DEFAULT_MIN_PREFIX: usize = 2;
+
+/// 指令縮寫表
+#[derive(Debug, Clone)]
+pub struct AbbreviationTable {
+    /// 完整指令（小寫）
+    words: Vec<String>,
+    /// 前綴最短長度
+    min_prefix: usize,
+}
+
+impl Default for AbbreviationTable {
+    fn default() -> Self {
+        Self {
+            words: Vec::new(),
+            min_prefix: DEFAULT_MIN_PREFIX,
+        }
+    }
+}
+
+impl AbbreviationTable {
+    /// 創建空的縮寫表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 設定前綴最短長度
+    pub fn with_min_prefix(mut self, min_prefix: usize) -> Self {
+        self.min_prefix = min_prefix.max(1);
+        self
+    }
+
+    /// 加入完整指令（重複或空白時忽略）
+    pub fn add(&mut self, word: &str) {
+        let word = word.trim().to_lowercase();
+        if !word.is_empty() && !word.contains(char::is_whitespace) && !self.words.contains(&word) {
+            self.words.push(word);
+        }
+    }
+
+    /// 移除完整指令
+    pub fn remove(&mut self, word: &str) -> bool {
+        let word = word.trim().to_lowercase();
+        let before = self.words.len();
+        self.words.retain(|w| *w != word);
+        self.words.len() != before
+    }
+
+    /// 所有完整指令
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// 展開輸入的第一個字；前綴只對應一個完整指令時才展開，其餘參數保持不變
+    ///
+    /// 已是完整指令、前綴過短、方向指令或有多個候選時回傳 `None`。
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let input = input.trim();
+        let (head, rest) = match input.split_once(char::is_whitespace) {
+            Some((head, rest)) => (head, Some(rest)),
+            None => (input, None),
+        };
+        if head.chars().count() < self.min_prefix || is_movement_command(head) {
+            return None;
+        }
+
+        let head = head.to_lowercase();
+        if self.words.contains(&head) {
+            return None;
+        }
+
+        let mut candidates = self.words.iter().filter(|w| w.starts_with(&head));
+        let word = candidates.next()?;
+        if candidates.next().is_some() {
+            return None;
+        }
+
+        Some(match rest {
+            Some(rest) => format!("{} {}", word, rest),
+            None => word.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unambiguous_expansion() {
+        let mut table = AbbreviationTable::new();
+        table.add("inventory");
+        table.add("information");
+        table.add("score");
+
+        assert_eq!(table.expand("inv"), Some("inventory".to_string()));
+        assert_eq!(table.expand("INFO"), Some("information".to_string()));
+        assert_eq!(table.expand("sc all"), Some("score all".to_string()));
+    }
+
+    #[test]
+    fn test_ambiguous_or_short_prefix_not_expanded() {
+        let mut table = AbbreviationTable::new();
+        table.add("inventory");
+        table.add("information");
+        table.add("news");
+
+        assert_eq!(table.expand("in"), None);
+        assert_eq!(table.expand("inventory"), None);
+        assert_eq!(table.expand("n"), None);
+        assert_eq!(table.expand("xyz"), None);
+        assert_eq!(AbbreviationTable::new().with_min_prefix(1).expand("q"), None);
+    }
+}
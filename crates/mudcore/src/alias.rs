@@ -1,9 +1,34 @@
 //! Alias（別名）模組
 //!
 //! 將簡短輸入展開為完整命令
+//!
+//! 模式中的 `$1`、`$2`… 各匹配一個參數（前面有空白時可省略），以引號包住的
+//! `'magic missile'` 或 `"magic missile"` 視為單一參數（引號保留）；最後一個參數
+//! 取得剩餘的所有文字，`$*` 匹配所有剩餘參數。替換內容中：
+//!
+//! - `$N` / `%N`：第 N 個參數，未提供時代入空字串
+//! - `$0` / `%0`：整段參數文字（第一個到最後一個有值的參數）
+//! - `$*` / `%*`：所有有值的參數，以空白連接
+//!
+//! 開啟 [`AliasManager::strict_args`] 時，參數少於模式所需會回報 [`AliasError::MissingArgs`]
+//! 而不送出指令。
+//!
+//! 正則別名（[`Alias::as_regex`]）的模式直接編譯為正則，替換內容可用 `$N` 與
+//! `${name}` 引用編號與具名群組，`$0` 為整個匹配的文字；正則無效時不會匹配。
 
 use regex::Regex;
 use std::collections::HashMap;
+use thiserror::Error;
+
+/// 單一參數：優先匹配引號包住的整段文字
+const ARG_PATTERN: &str = r#"('[^']*'|"[^"]*"|.*?)"#;
+
+/// 別名展開錯誤
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AliasError {
+    #[error("別名 {name} 需要 {expected} 個參數，只提供了 {given} 個")]
+    MissingArgs { name: String, expected: usize, given: usize },
+}
 
 /// 別名定義
 #[derive(Debug, Clone)]
@@ -12,14 +37,24 @@ pub struct Alias {
     pub name: String,
     /// 分類
     pub category: Option<String>,
+    /// 說明（備註用途，不影響匹配）
+    pub description: String,
     /// 匹配模式（支援 $1, $2 等參數佔位符）
     pub pattern: String,
     /// 展開後的命令
     pub replacement: String,
-    /// 是否啟用
+    /// 目前是否啟用（執行期狀態，腳本可切換）
     pub enabled: bool,
+    /// 啟動時是否啟用（寫入設定檔）
+    pub default_enabled: bool,
     /// 是否為 Lua 腳本
     pub is_script: bool,
+    /// 模式是否為正則表達式（否則為 `$1` 參數模式）
+    pub is_regex: bool,
+    /// 正則編譯失敗的錯誤訊息（有錯誤的別名永遠不會匹配）
+    pub error: Option<String>,
+    /// 模式中的參數數量（`$1`、`$2`…，不含 `$*`）
+    param_count: usize,
     /// 編譯後的正則表達式（內部使用）
     #[allow(dead_code)]
     compiled_regex: Option<Regex>,
@@ -32,6 +67,8 @@ pub enum AliasMatchResult {
     Replacement(String),
     /// Lua 腳本執行
     Script(String),
+    /// 已匹配但無法展開（如參數不足）
+    Error(AliasError),
     /// 未匹配
     None,
 }
@@ -41,14 +78,20 @@ impl Alias {
     pub fn new(name: impl Into<String>, pattern: impl Into<String>, replacement: impl Into<String>) -> Self {
         let pattern = pattern.into();
         let regex = Self::compile_pattern(&pattern);
-        
+        let param_count = count_placeholders(&pattern);
+
         Self {
             name: name.into(),
             category: None,
+            description: String::new(),
             pattern,
             replacement: replacement.into(),
             enabled: true,
+            default_enabled: true,
             is_script: false,
+            is_regex: false,
+            error: None,
+            param_count,
             compiled_regex: regex,
         }
     }
@@ -59,12 +102,45 @@ impl Alias {
         self
     }
 
+    /// 設定模式是否為正則表達式（重新編譯模式）
+    pub fn as_regex(mut self, is_regex: bool) -> Self {
+        self.is_regex = is_regex;
+        self.error = None;
+        if is_regex {
+            self.param_count = 0;
+            self.compiled_regex = match Regex::new(&self.pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    None
+                }
+            };
+        } else {
+            self.param_count = count_placeholders(&self.pattern);
+            self.compiled_regex = Self::compile_pattern(&self.pattern);
+        }
+        self
+    }
+
     /// 設定分類
     pub fn with_category(mut self, category: impl Into<String>) -> Self {
         self.category = Some(category.into());
         self
     }
 
+    /// 設定說明
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// 設定啟動時是否啟用（同時作為目前狀態）
+    pub fn with_default_enabled(mut self, enabled: bool) -> Self {
+        self.default_enabled = enabled;
+        self.enabled = enabled;
+        self
+    }
+
     /// 將別名模式編譯為正則表達式
     fn compile_pattern(pattern: &str) -> Option<Regex> {
         // 轉義特殊字符，但保留 $1, $2 等參數佔位符
@@ -79,9 +155,9 @@ impl Alias {
                         // 如果前面有空格，設為選用參數 (包含空格)
                         if regex_pattern.ends_with(' ') {
                             regex_pattern.pop();
-                            regex_pattern.push_str(r"(?: (.*?))?");
+                            regex_pattern.push_str(&format!("(?: {})?", ARG_PATTERN));
                         } else {
-                            regex_pattern.push_str(r"(.*?)");
+                            regex_pattern.push_str(ARG_PATTERN);
                         }
                         chars.next(); // 消耗數字
                         continue;
@@ -118,8 +194,13 @@ impl Alias {
         Regex::new(&regex_pattern).ok()
     }
 
-    /// 嘗試匹配輸入並展開別名
+    /// 嘗試匹配輸入並展開別名（缺少的參數代入空字串）
     pub fn try_expand(&self, input: &str) -> Option<String> {
+        self.expand_args(input, false)?.ok()
+    }
+
+    /// 嘗試匹配輸入並展開別名；`strict` 時參數少於模式所需回傳錯誤
+    pub fn expand_args(&self, input: &str, strict: bool) -> Option<Result<String, AliasError>> {
         if !self.enabled {
             return None;
         }
@@ -127,36 +208,70 @@ impl Alias {
         let regex = self.compiled_regex.as_ref()?;
         let captures = regex.captures(input)?;
 
-        let mut result = self.replacement.clone();
-        
-        // 替換 $1, $2 等佔位符
-        // 替換 $1, $2 等佔位符
-        for i in 1..captures.len() {
-            let placeholder = format!("${}", i);
-            // 如果捕獲組為 None (選用參數未匹配)，則替換為空字串
-            let val = captures.get(i).map_or("", |m| m.as_str());
-            result = result.replace(&placeholder, val);
+        let mut groups: Vec<&str> = (1..captures.len())
+            .map(|i| captures.get(i).map_or("", |m| m.as_str()))
+            .collect();
+        let given = groups.iter().filter(|g| !g.is_empty()).count();
+        if strict && given < self.param_count {
+            return Some(Err(AliasError::MissingArgs {
+                name: self.name.clone(),
+                expected: self.param_count,
+                given,
+            }));
         }
-        
-        // 替換 $* （所有參數）
-        if result.contains("$*") {
-            let all_args: String = (1..captures.len())
-                .filter_map(|i| captures.get(i).map(|m| m.as_str()))
-                .collect::<Vec<_>>()
-                .join(" ");
-            result = result.replace("$*", &all_args);
+
+        // $0 為整段參數文字（正則別名為整個匹配）
+        let whole = if self.is_regex {
+            captures.get(0).map_or("", |m| m.as_str())
+        } else {
+            let mut args = captures.iter().skip(1).flatten().filter(|m| !m.is_empty());
+            match args.next() {
+                Some(first) => &input[first.start()..args.last().unwrap_or(first).end()],
+                None => "",
+            }
+        };
+        let named: Vec<(&str, &str)> = regex
+            .capture_names()
+            .flatten()
+            .map(|name| (name, captures.name(name).map_or("", |m| m.as_str())))
+            .collect();
+
+        // 替換內容用到但模式沒有的參數也代入空字串，避免 $N 原樣送出
+        let referenced = count_placeholders(&self.replacement);
+        if groups.len() < referenced {
+            groups.resize(referenced, "");
         }
 
-        Some(result)
+        Some(Ok(crate::placeholder::expand_named_captures(&self.replacement, whole, &groups, &named)))
     }
 }
 
+/// 模板中最大的參數編號（`$N` / `%N`）
+fn count_placeholders(template: &str) -> usize {
+    let mut max = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' && c != '%' {
+            continue;
+        }
+        let mut digits = String::new();
+        while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            digits.push(d);
+            chars.next();
+        }
+        max = max.max(digits.parse().unwrap_or(0));
+    }
+    max
+}
+
 /// 別名管理器
 #[derive(Debug, Default)]
 pub struct AliasManager {
     pub aliases: HashMap<String, Alias>,
     /// 按優先級排序的別名列表（最長模式優先）
     pub sorted_aliases: Vec<String>,
+    /// 參數少於模式所需時回報錯誤而不展開（僅影響 [`AliasManager::process_match`]）
+    pub strict_args: bool,
 }
 
 impl AliasManager {
@@ -165,8 +280,11 @@ impl AliasManager {
         Self::default()
     }
 
-    /// 添加別名
+    /// 添加別名（正則無效時記錄警告，該別名不會匹配）
     pub fn add(&mut self, alias: Alias) {
+        if let Some(error) = &alias.error {
+            tracing::warn!("別名 {} 的正則無效，已略過: {}", alias.name, error);
+        }
         let name = alias.name.clone();
         self.aliases.insert(name.clone(), alias);
         self.rebuild_sorted_list();
@@ -181,17 +299,52 @@ impl AliasManager {
         alias
     }
 
+    /// 一次移除多個別名，回傳實際移除的數量
+    pub fn remove_many<S: AsRef<str>>(&mut self, names: &[S]) -> usize {
+        let before = self.aliases.len();
+        for name in names {
+            self.aliases.remove(name.as_ref());
+        }
+        let removed = before - self.aliases.len();
+        if removed > 0 {
+            self.rebuild_sorted_list();
+        }
+        removed
+    }
+
     /// 獲取別名
     pub fn get(&self, name: &str) -> Option<&Alias> {
         self.aliases.get(name)
     }
 
+    /// 重新命名分類，若新分類已存在則直接合併；新名稱為空時改為未分類
+    ///
+    /// 回傳受影響的別名數量。排序只依模式長度，無需重建 `sorted_aliases`。
+    pub fn rename_category(&mut self, old: &str, new: &str) -> usize {
+        let new = new.trim();
+        let mut count = 0;
+        for alias in self.aliases.values_mut() {
+            if alias.category.as_deref() == Some(old) {
+                alias.category = (!new.is_empty()).then(|| new.to_string());
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// 將目前的啟用狀態存為預設值
+    pub fn save_enabled_as_default(&mut self) {
+        for alias in self.aliases.values_mut() {
+            alias.default_enabled = alias.enabled;
+        }
+    }
+
     /// 獲取所有別名
     pub fn list(&self) -> Vec<&Alias> {
         self.aliases.values().collect()
     }
 
-    /// 嘗試展開輸入
+    /// 嘗試展開輸入（缺少的參數代入空字串）
     pub fn expand(&self, input: &str) -> Option<String> {
         for name in &self.sorted_aliases {
             if let Some(alias) = self.aliases.get(name) {
@@ -223,12 +376,11 @@ impl AliasManager {
     pub fn process_match(&self, input: &str) -> AliasMatchResult {
         for name in &self.sorted_aliases {
             if let Some(alias) = self.aliases.get(name) {
-                if let Some(expanded) = alias.try_expand(input) {
-                    if alias.is_script {
-                        return AliasMatchResult::Script(expanded);
-                    } else {
-                        return AliasMatchResult::Replacement(expanded);
-                    }
+                match alias.expand_args(input, self.strict_args) {
+                    Some(Ok(expanded)) if alias.is_script => return AliasMatchResult::Script(expanded),
+                    Some(Ok(expanded)) => return AliasMatchResult::Replacement(expanded),
+                    Some(Err(e)) => return AliasMatchResult::Error(e),
+                    None => {}
                 }
             }
         }
@@ -284,6 +436,50 @@ mod tests {
         assert_eq!(alias.try_expand("kk"), None);
     }
 
+    #[test]
+    fn test_default_enabled_vs_runtime() {
+        let mut manager = AliasManager::new();
+        manager.add(Alias::new("kk", "kk", "kill kobold").with_default_enabled(false));
+        assert_eq!(manager.process("kk"), "kk");
+
+        // 執行期啟用，預設值維持不變
+        manager.aliases.get_mut("kk").unwrap().enabled = true;
+        assert_eq!(manager.process("kk"), "kill kobold");
+        assert!(!manager.get("kk").unwrap().default_enabled);
+
+        manager.save_enabled_as_default();
+        assert!(manager.get("kk").unwrap().default_enabled);
+    }
+
+    #[test]
+    fn test_rename_and_merge_category() {
+        let mut manager = AliasManager::new();
+        manager.add(Alias::new("kk", "kk", "kill kobold").with_category("戰鬥"));
+        manager.add(Alias::new("kr", "kr", "kill rat").with_category("戰鬥"));
+        manager.add(Alias::new("gg", "gg", "get all").with_category("撿取"));
+
+        assert_eq!(manager.rename_category("戰鬥", "攻擊"), 2);
+        assert!(manager.list().iter().all(|a| a.category.as_deref() != Some("戰鬥")));
+        assert_eq!(manager.get("kk").unwrap().category.as_deref(), Some("攻擊"));
+
+        // 合併至既有分類，不產生重複項目
+        assert_eq!(manager.rename_category("撿取", "攻擊"), 1);
+        assert_eq!(manager.aliases.len(), 3);
+        assert_eq!(manager.sorted_aliases.len(), 3);
+        assert!(manager.list().iter().all(|a| a.category.as_deref() == Some("攻擊")));
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut manager = AliasManager::new();
+        manager.add(Alias::new("kk", "kk", "kill kobold"));
+        manager.add(Alias::new("kr", "kr", "kill rat"));
+        manager.add(Alias::new("gg", "gg", "get all"));
+
+        assert_eq!(manager.remove_many(&["kk", "gg", "missing"]), 2);
+        assert_eq!(manager.sorted_aliases, vec!["kr".to_string()]);
+    }
+
     #[test]
     fn test_alias_priority() {
         let mut manager = AliasManager::new();
@@ -306,4 +502,69 @@ mod tests {
         // 不帶參數 (應該也要能匹配，且 $1 展開為空字串)
         assert_eq!(alias.try_expand("cfr"), Some("c 'full ref' ".to_string()));
     }
+
+    #[test]
+    fn test_alias_percent_placeholders() {
+        let alias = Alias::new("cast", "c $1 $2", "cast %1 at %2 (%0) 100%% \\$1");
+        assert_eq!(
+            alias.try_expand("c fireball goblin"),
+            Some("cast fireball at goblin (fireball goblin) 100% $1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_regex_alias_numbered_and_named_groups() {
+        let mut manager = AliasManager::new();
+        manager.add(Alias::new("cast", r"^cast (\w+) on (\w+)$", "cast '$1' $2").as_regex(true));
+        manager.add(Alias::new("buy", r"^buy (?P<count>\d+) (?P<item>.+)$", "buy ${count} ${item} [$0] $3").as_regex(true));
+
+        assert_eq!(manager.process_match("cast heal on bob"), AliasMatchResult::Replacement("cast 'heal' bob".to_string()));
+        assert_eq!(manager.process_match("buy 3 red potion"), AliasMatchResult::Replacement("buy 3 red potion [buy 3 red potion] ".to_string()));
+        assert_eq!(manager.process_match("cast heal"), AliasMatchResult::None);
+
+        // 具名群組也可用編號引用
+        let alias = Alias::new("buy", r"^buy (?P<count>\d+) (?P<item>.+)$", "$2 x$1 ${missing}").as_regex(true);
+        assert_eq!(alias.try_expand("buy 2 bread"), Some("bread x2 ${missing}".to_string()));
+
+        // 無效的正則：不會匹配，也不影響其他別名
+        let broken = Alias::new("broken", r"^(oops", "x").as_regex(true);
+        assert!(broken.error.is_some());
+        manager.add(broken);
+        assert_eq!(manager.process_match("(oops"), AliasMatchResult::None);
+        assert_eq!(manager.process("cast fire on orc"), "cast 'fire' orc");
+    }
+
+    #[test]
+    fn test_alias_argument_counts() {
+        let alias = Alias::new("k", "k $1 $2", "kill $2 with $1 [$0] [$*] $3");
+
+        // 0 個參數：缺少的參數代入空字串
+        assert_eq!(alias.try_expand("k"), Some("kill  with  [] [] ".to_string()));
+        // 1 個參數
+        assert_eq!(alias.try_expand("k sword"), Some("kill  with sword [sword] [sword] ".to_string()));
+        // 3 個參數：最後一個參數取得剩餘文字
+        assert_eq!(
+            alias.try_expand("k sword big orc"),
+            Some("kill big orc with sword [sword big orc] [sword big orc] ".to_string())
+        );
+        // 引號包住的參數視為一個
+        assert_eq!(
+            alias.try_expand("k 'magic missile' orc"),
+            Some("kill orc with 'magic missile' ['magic missile' orc] ['magic missile' orc] ".to_string())
+        );
+
+        // 嚴格模式：參數不足時回報錯誤
+        assert_eq!(
+            alias.expand_args("k sword", true),
+            Some(Err(AliasError::MissingArgs { name: "k".to_string(), expected: 2, given: 1 }))
+        );
+        assert!(matches!(alias.expand_args("k sword orc", true), Some(Ok(_))));
+
+        let mut manager = AliasManager::new();
+        manager.add(alias);
+        assert!(matches!(manager.process_match("k"), AliasMatchResult::Replacement(_)));
+        manager.strict_args = true;
+        assert!(matches!(manager.process_match("k"), AliasMatchResult::Error(AliasError::MissingArgs { given: 0, .. })));
+        assert_eq!(manager.process("k"), "kill  with  [] [] ");
+    }
 }
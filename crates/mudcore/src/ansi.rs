@@ -0,0 +1,731 @@
+//! ANSI 轉義碼解析模組
+//!
+//! 將 MUD 伺服器發送的 ANSI 顏色碼解析為與 GUI 無關的文字片段，
+//! 供畫面渲染、HTML 日誌與觸發器高亮共用。
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// RGB 顏色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    /// 預設前景色
+    pub const DEFAULT_FG: Color = Color::rgb(200, 200, 200);
+
+    /// 建立 RGB 顏色
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// 解析顏色名稱（red/green/yellow/blue/magenta/cyan/white/gray 取亮色，black 取暗色）或 `#rrggbb`
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 {
+                return None;
+            }
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            return Some(Color::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+        }
+        let index = match name.as_str() {
+            "black" => return Some(basic_color(0, false)),
+            "gray" | "grey" => 0,
+            "red" => 1,
+            "green" => 2,
+            "yellow" => 3,
+            "blue" => 4,
+            "magenta" => 5,
+            "cyan" => 6,
+            "white" => 7,
+            _ => return None,
+        };
+        Some(basic_color(index, true))
+    }
+
+    /// 轉為 `#rrggbb` 格式（供 HTML 輸出）
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// ANSI 顏色解析後的文字片段
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    /// 前景色
+    pub fg: Color,
+    /// 背景色
+    pub bg: Option<Color>,
+    /// 雙色字的左半部顏色（顏色碼後緊接裸重置時記錄）
+    pub fg_left: Option<Color>,
+    pub blink: bool,
+    pub bold: bool,
+    /// 每個字元對應的原始編碼位元組數 (用於對齊校正)
+    pub byte_widths: Vec<u8>,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            fg: Color::DEFAULT_FG,
+            bg: None,
+            fg_left: None,
+            blink: false,
+            bold: false,
+            byte_widths: Vec::new(),
+        }
+    }
+}
+
+/// ANSI 解析器狀態（SGR 屬性，可跨行延續）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnsiState {
+    pub fg: Color,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub blink: bool,
+}
+
+impl Default for AnsiState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnsiState {
+    /// 未設定任何屬性的初始狀態
+    pub fn new() -> Self {
+        Self {
+            fg: Color::DEFAULT_FG,
+            bg: None,
+            bold: false,
+            blink: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 以目前狀態建立空白片段
+    fn span(&self, fg_left: Option<Color>) -> Span {
+        Span {
+            text: String::new(),
+            fg: self.fg,
+            bg: self.bg,
+            fg_left,
+            blink: self.blink,
+            bold: self.bold,
+            byte_widths: Vec::new(),
+        }
+    }
+
+    fn apply_code(&mut self, codes: &[u16]) {
+        let mut i = 0;
+        while i < codes.len() {
+            let code = codes[i];
+            match code {
+                0 => self.reset(),
+                1 => self.bold = true,
+                2 | 22 => self.bold = false,
+                5 => self.blink = true,
+                25 => self.blink = false,
+                // 前景色
+                30..=37 => self.fg = basic_color((code - 30) as u8, self.bold),
+                38 => {
+                    // Extended foreground
+                    if i + 2 < codes.len() && codes[i + 1] == 5 {
+                        self.fg = color_256(codes[i + 2] as u8);
+                        i += 2;
+                    } else if i + 4 < codes.len() && codes[i + 1] == 2 {
+                        self.fg = Color::rgb(codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8);
+                        i += 4;
+                    }
+                }
+                39 => self.fg = Color::DEFAULT_FG,
+                // 背景色
+                40..=47 => self.bg = Some(basic_color((code - 40) as u8, false)),
+                48 => {
+                    // Extended background
+                    if i + 2 < codes.len() && codes[i + 1] == 5 {
+                        self.bg = Some(color_256(codes[i + 2] as u8));
+                        i += 2;
+                    } else if i + 4 < codes.len() && codes[i + 1] == 2 {
+                        self.bg = Some(Color::rgb(codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8));
+                        i += 4;
+                    }
+                }
+                49 => self.bg = None,
+                // 高亮前景色 (90-97)
+                90..=97 => self.fg = basic_color((code - 90) as u8, true),
+                // 高亮背景色 (100-107)
+                100..=107 => self.bg = Some(basic_color((code - 100) as u8, true)),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// 基本 16 色
+fn basic_color(index: u8, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::rgb(0, 0, 0),       // Black
+        (0, true) => Color::rgb(128, 128, 128),  // Gray
+        (1, false) => Color::rgb(187, 0, 0),     // Red
+        (1, true) => Color::rgb(255, 85, 85),    // Bright Red
+        (2, false) => Color::rgb(0, 187, 0),     // Green
+        (2, true) => Color::rgb(85, 255, 85),    // Bright Green
+        (3, false) => Color::rgb(187, 187, 0),   // Yellow
+        (3, true) => Color::rgb(255, 255, 85),   // Bright Yellow
+        (4, false) => Color::rgb(0, 0, 187),     // Blue
+        (4, true) => Color::rgb(85, 85, 255),    // Bright Blue
+        (5, false) => Color::rgb(187, 0, 187),   // Magenta
+        (5, true) => Color::rgb(255, 85, 255),   // Bright Magenta
+        (6, false) => Color::rgb(0, 187, 187),   // Cyan
+        (6, true) => Color::rgb(85, 255, 255),   // Bright Cyan
+        (7, false) => Color::rgb(187, 187, 187), // White
+        (7, true) => Color::rgb(255, 255, 255),  // Bright White
+        _ => Color::rgb(160, 160, 160),
+    }
+}
+
+/// 256 色表
+fn color_256(index: u8) -> Color {
+    if index < 8 {
+        basic_color(index, false)
+    } else if index < 16 {
+        basic_color(index - 8, true)
+    } else if index < 232 {
+        // 6x6x6 color cube
+        let i = index - 16;
+        Color::rgb((i / 36) * 51, ((i / 6) % 6) * 51, (i % 6) * 51)
+    } else {
+        // Grayscale ramp
+        let gray = (index - 232) * 10 + 8;
+        Color::rgb(gray, gray, gray)
+    }
+}
+
+/// 解析 ANSI 轉義碼，返回帶顏色的文字片段
+pub fn parse(input: &str) -> Vec<Span> {
+    parse_with_widths(input, None)
+}
+
+/// 帶有原始位元組寬度資訊的 ANSI 解析
+///
+/// `byte_widths` 與輸入逐字元對應（含轉義碼字元），缺少時每字元視為 1。
+pub fn parse_with_widths(input: &str, byte_widths: Option<&[u8]>) -> Vec<Span> {
+    parse_with_state(input, byte_widths, &mut AnsiState::new())
+}
+
+/// 從指定的 SGR 狀態開始解析，結束後 `state` 更新為行尾的狀態
+///
+/// 伺服器設定的顏色在重置前會延續到下一行，逐行解析時以此串接。
+pub fn parse_with_state(input: &str, byte_widths: Option<&[u8]>, state: &mut AnsiState) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut current_span = state.span(None);
+
+    let mut pending_fg_left: Option<Color> = None;
+    let mut chars = input.chars().peekable();
+    let mut width_idx = 0;
+
+    while let Some(c) = chars.next() {
+        let current_w = byte_widths.and_then(|bw| bw.get(width_idx).copied()).unwrap_or(1);
+        width_idx += 1;
+
+        if c == '\x1b' {
+            match chars.peek() {
+                Some(&'[') => {
+                    chars.next(); // 消耗 '['
+                    width_idx += 1;
+
+                    let mut sequence_content = String::new();
+                    let mut cmd = '\0';
+                    while let Some(&ch) = chars.peek() {
+                        if ('\x40'..='\x7e').contains(&ch) {
+                            cmd = ch;
+                            chars.next();
+                            width_idx += 1;
+                            break;
+                        }
+                        sequence_content.push(ch);
+                        chars.next();
+                        width_idx += 1;
+                    }
+
+                    if cmd == 'm' {
+                        let was_empty = current_span.text.is_empty();
+                        if !was_empty {
+                            spans.push(current_span);
+                            pending_fg_left = None;
+                        }
+
+                        let params: Vec<u16> = sequence_content
+                            .split(';')
+                            .filter_map(|part| {
+                                part.chars()
+                                    .filter(|c| c.is_ascii_digit())
+                                    .collect::<String>()
+                                    .parse()
+                                    .ok()
+                            })
+                            .collect();
+
+                        let is_bare_reset = (params.is_empty() && sequence_content.is_empty())
+                            || (params.len() == 1 && params[0] == 0);
+
+                        if is_bare_reset && was_empty {
+                            // 雙色字：顏色碼後緊接裸重置，記住左半部顏色
+                            pending_fg_left = Some(state.fg);
+                            state.reset();
+                        } else if params.is_empty() && sequence_content.is_empty() {
+                            state.reset();
+                        } else {
+                            state.apply_code(&params);
+                        }
+
+                        current_span = state.span(pending_fg_left);
+                    }
+                }
+                Some(&'(') | Some(&')') => {
+                    chars.next(); // 消耗 '(' 或 ')'
+                    width_idx += 1;
+                    chars.next(); // 消耗字集識別碼
+                    width_idx += 1;
+                }
+                Some(&']') => {
+                    // OSC 序列：跳過至 BEL 或 ST (ESC \)
+                    width_idx += skip_osc(&mut chars);
+                }
+                _ => {}
+            }
+        } else if c >= ' ' || c == '\n' || c == '\r' || c == '\t' {
+            current_span.text.push(c);
+            current_span.byte_widths.push(current_w);
+        }
+    }
+
+    // 添加最後一個 span
+    if !current_span.text.is_empty() {
+        spans.push(current_span);
+    }
+
+    spans
+}
+
+/// 移除 ANSI 轉義碼和其他不可見字符，只保留純文字
+/// 用於清理從畫面複製的文字，避免發送帶有控制碼的訊息
+pub fn strip_ansi(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // 跳過 ANSI 序列
+            match chars.peek() {
+                Some(&'[') => {
+                    chars.next(); // 消耗 '['
+                    // 跳過直至終止符 (0x40-0x7E)
+                    for ch in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&ch) {
+                            break;
+                        }
+                    }
+                }
+                Some(&'(') | Some(&')') => {
+                    chars.next(); // 消耗 '(' 或 ')'
+                    chars.next(); // 消耗字集識別碼
+                }
+                Some(&']') => {
+                    skip_osc(&mut chars);
+                }
+                _ => {
+                    // 其他 ESC 序列，跳過 ESC 本身
+                }
+            }
+        } else if is_visible_char(c) {
+            result.push(c);
+        }
+        // 其他不可見控制字元被忽略
+    }
+
+    result
+}
+
+/// 跳過 OSC 序列（`]` 起至 BEL 或 ST 為止），回傳消耗的字元數
+fn skip_osc(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> usize {
+    let mut consumed = 0;
+    while let Some(ch) = chars.next() {
+        consumed += 1;
+        match ch {
+            '\x07' => break,
+            '\x1b' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                consumed += 1;
+                break;
+            }
+            _ => {}
+        }
+    }
+    consumed
+}
+
+/// 判斷字符是否為可見字符（應該保留）
+///
+/// 排除 ASCII/C1 控制字元（保留 `\n` `\r` `\t`）、零寬度字元與格式控制字元。
+fn is_visible_char(c: char) -> bool {
+    match c {
+        // 允許基本空白字符
+        '\n' | '\r' | '\t' => true,
+        // 排除 ASCII 控制字符
+        '\x00'..='\x1f' | '\x7f' => false,
+        // 排除 C1 控制字符
+        '\u{0080}'..='\u{009f}' => false,
+        // 排除零寬度字符和格式控制字符
+        '\u{200b}'..='\u{200f}' | // Zero width chars, LRM, RLM
+        '\u{2028}'..='\u{202f}' | // Line/paragraph separators, embedding controls
+        '\u{2060}'..='\u{206f}' | // Word joiner, invisible operators
+        '\u{feff}' => false,      // BOM / ZWNBSP
+        // 其他字符都允許
+        _ => true,
+    }
+}
+
+/// 文字中 SGR / 清除行轉義碼（`ESC[...m`、`ESC[...K`）的位元組區間
+pub fn sgr_escapes(text: &str) -> Vec<(usize, usize)> {
+    static SGR_RE: OnceLock<Regex> = OnceLock::new();
+    let re = SGR_RE.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[mK]").unwrap());
+    re.find_iter(text).map(|m| (m.start(), m.end())).collect()
+}
+
+/// 將替換觸發器套用到一行文字
+///
+/// 區間為去除 ANSI 後文字的位元組位置；重疊的區間以先出現者為準，
+/// 被取代範圍內的轉義碼保留在替換文字之後，以免影響後續顏色。
+pub fn apply_substitutions(
+    text: &str,
+    widths: &[u8],
+    mut substitutions: Vec<(std::ops::Range<usize>, String)>,
+) -> (String, Vec<u8>) {
+    substitutions.retain(|(range, _)| !range.is_empty());
+    substitutions.sort_by_key(|(range, _)| range.start);
+    let mut pending = substitutions.into_iter().peekable();
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut result_widths = Vec::with_capacity(widths.len());
+    let mut current: Option<std::ops::Range<usize>> = None;
+    let mut held = Vec::new();
+    let mut plain_pos = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        // 與 TriggerManager 的 ANSI 剝離規則一致：ESC 與其後的 CSI 序列不計入純文字位置
+        if chars[i] == '\x1b' {
+            let mut end = i + 1;
+            if chars.get(end) == Some(&'[') {
+                end += 1;
+                while end < chars.len() {
+                    end += 1;
+                    if chars[end - 1].is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            for (offset, &ch) in chars[i..end].iter().enumerate() {
+                let entry = (ch, widths.get(i + offset).copied().unwrap_or(1));
+                if current.is_some() {
+                    held.push(entry);
+                } else {
+                    result.push(entry.0);
+                    result_widths.push(entry.1);
+                }
+            }
+            i = end;
+            continue;
+        }
+
+        if current.as_ref().is_some_and(|range| plain_pos >= range.end) {
+            current = None;
+            for (ch, w) in held.drain(..) {
+                result.push(ch);
+                result_widths.push(w);
+            }
+        }
+        if current.is_none() {
+            while pending.next_if(|(range, _)| range.start < plain_pos).is_some() {}
+            if let Some((range, replacement)) = pending.next_if(|(range, _)| range.start == plain_pos) {
+                for ch in replacement.chars() {
+                    result.push(ch);
+                    result_widths.push(if ch.is_ascii() { 1 } else { 2 });
+                }
+                current = Some(range);
+            }
+        }
+
+        plain_pos += chars[i].len_utf8();
+        if current.is_none() {
+            result.push(chars[i]);
+            result_widths.push(widths.get(i).copied().unwrap_or(1));
+        }
+        i += 1;
+    }
+
+    for (ch, w) in held {
+        result.push(ch);
+        result_widths.push(w);
+    }
+    (result, result_widths)
+}
+
+/// 將觸發器高亮套用到一行文字：區間為去除 ANSI 後文字的位元組位置
+pub fn apply_trigger_highlights(
+    text: &str,
+    widths: &[u8],
+    highlights: Vec<(std::ops::Range<usize>, String)>,
+) -> (String, Vec<u8>) {
+    let escapes = sgr_escapes(text);
+
+    // 純文字位置對應到原始文字中可見字元的起點與終點
+    let mut starts = std::collections::HashMap::new();
+    let mut ends = std::collections::HashMap::new();
+    let mut plain_pos = 0;
+    for (pos, ch) in text.char_indices() {
+        if escapes.iter().any(|&(start, end)| (start..end).contains(&pos)) {
+            continue;
+        }
+        starts.insert(plain_pos, pos);
+        plain_pos += ch.len_utf8();
+        ends.insert(plain_pos, pos + ch.len_utf8());
+    }
+
+    let ranges = highlights
+        .into_iter()
+        .filter(|(range, _)| !range.is_empty())
+        .filter_map(|(range, code)| Some((*starts.get(&range.start)?, *ends.get(&range.end)?, code)))
+        .collect();
+    wrap_ranges(text, widths, &escapes, ranges)
+}
+
+/// 以 SGR 序列包住各區間 (起, 迄, 序列)，並同步插入對應的寬度
+///
+/// 區間為原始文字的位元組位置；與前一個區間重疊者略過。
+pub fn wrap_ranges(
+    text: &str,
+    widths: &[u8],
+    escapes: &[(usize, usize)],
+    mut ranges: Vec<(usize, usize, String)>,
+) -> (String, Vec<u8>) {
+    if ranges.is_empty() {
+        return (text.to_string(), widths.to_vec());
+    }
+    ranges.sort_by_key(|&(start, _, _)| start);
+
+    let mut out = String::with_capacity(text.len() + ranges.len() * 24);
+    let mut out_widths = Vec::with_capacity(widths.len() + ranges.len() * 24);
+    let push_code = |out: &mut String, out_widths: &mut Vec<u8>, code: &str| {
+        out.push_str(code);
+        out_widths.extend(std::iter::repeat_n(1, code.chars().count()));
+    };
+    let restore_code = |pos: usize| {
+        let mut code = String::from("\x1b[0m");
+        for &(start, end) in escapes.iter().take_while(|&&(_, end)| end <= pos) {
+            if text[start..end].ends_with('m') {
+                code.push_str(&text[start..end]);
+            }
+        }
+        code
+    };
+
+    let mut next = 0;
+    let mut active_end: Option<usize> = None;
+    for (idx, (pos, ch)) in text.char_indices().enumerate() {
+        if active_end == Some(pos) {
+            push_code(&mut out, &mut out_widths, &restore_code(pos));
+            active_end = None;
+        }
+        if active_end.is_none() {
+            // 跳過與前一個高亮重疊的區間
+            while next < ranges.len() && ranges[next].0 < pos {
+                next += 1;
+            }
+            if let Some((start, end, code)) = ranges.get(next) {
+                if *start == pos {
+                    push_code(&mut out, &mut out_widths, code);
+                    active_end = Some(*end);
+                    next += 1;
+                }
+            }
+        }
+        out.push(ch);
+        out_widths.push(widths.get(idx).copied().unwrap_or(1));
+    }
+    if active_end.is_some() {
+        push_code(&mut out, &mut out_widths, &restore_code(text.len()));
+    }
+
+    (out, out_widths)
+}
+
+/// 觸發器高亮的 SGR 序列（粗體、前景、背景依序組合）
+pub fn highlight_sgr(fg: Option<Color>, bg: Option<Color>, bold: bool) -> String {
+    let mut codes = Vec::new();
+    if bold {
+        codes.push("1".to_string());
+    }
+    if let Some(c) = fg {
+        codes.push(format!("38;2;{};{};{}", c.r, c.g, c.b));
+    }
+    if let Some(c) = bg {
+        codes.push(format!("48;2;{};{};{}", c.r, c.g, c.b));
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_text() {
+        let spans = parse("Hello World");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Hello World");
+        assert_eq!(spans[0].fg, Color::DEFAULT_FG);
+    }
+
+    #[test]
+    fn test_parse_colored_text() {
+        let spans = parse("\x1b[31mRed\x1b[0m Normal");
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].text, "Red");
+        assert_eq!(spans[0].fg, Color::rgb(187, 0, 0));
+        assert_eq!(spans[1].text, " Normal");
+        assert_eq!(spans[1].fg, Color::DEFAULT_FG);
+    }
+
+    #[test]
+    fn test_parse_bold_color() {
+        let spans = parse("\x1b[1;33mBold Yellow\x1b[0m");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Bold Yellow");
+        assert_eq!(spans[0].fg, Color::rgb(255, 255, 85));
+        assert!(spans[0].bold);
+    }
+
+    #[test]
+    fn test_parse_blink() {
+        let spans = parse("\x1b[5mBlink\x1b[0m");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Blink");
+        assert!(spans[0].blink);
+    }
+
+    #[test]
+    fn test_cursor_control_ignored() {
+        // 游標控制序列應該被忽略
+        let spans = parse("Hello\x1b[2J\x1b[HWorld");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "HelloWorld");
+    }
+
+    #[test]
+    fn test_background_and_extended_colors() {
+        let spans = parse("\x1b[41mRed BG\x1b[0m\x1b[38;5;196;48;2;1;2;3mX");
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].bg, Some(Color::rgb(187, 0, 0)));
+        assert_eq!(spans[1].fg, Color::rgb(255, 0, 0));
+        assert_eq!(spans[1].bg, Some(Color::rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn test_complex_csi_sequences() {
+        // [3B 游標下移（忽略），接 [33;36;40m 顏色序列
+        let spans = parse("\x1b[3B\x1b[33;36;40mColor\x1b[0m");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Color");
+        assert_eq!(spans[0].fg, Color::rgb(0, 187, 187));
+        assert_eq!(spans[0].bg, Some(Color::rgb(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_dual_color_keeps_first_color() {
+        // 雙色字模式：\x1b[31m\x1b[m蠻 → 紅色碼後跟裸重置
+        let spans = parse("\x1b[31m\x1b[m蠻\x1b[31m\x1b[m荒");
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].text, "蠻");
+        assert_eq!(spans[0].fg_left, Some(Color::rgb(187, 0, 0)));
+        assert_eq!(spans[0].fg, Color::DEFAULT_FG);
+    }
+
+    #[test]
+    fn test_dual_color_multi_sgr() {
+        // 雙色字：\x1b[1;31m\x1b[m\x1b[1m桃 → bold red + reset + bold
+        let spans = parse("\x1b[1;31m\x1b[m\x1b[1m桃");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "桃");
+        assert_eq!(spans[0].fg_left, Some(Color::rgb(255, 85, 85)));
+        assert_eq!(spans[0].fg, Color::DEFAULT_FG);
+        assert!(spans[0].bold);
+    }
+
+    #[test]
+    fn test_byte_widths_follow_characters() {
+        // 轉義碼字元（5 個）也佔寬度索引
+        let input = "\x1b[31m中a";
+        let widths = [1, 1, 1, 1, 1, 2, 1];
+        let spans = parse_with_widths(input, Some(&widths));
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].byte_widths, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31mHP\x1b[0m: 100\u{200b}\x07"), "HP: 100");
+        assert_eq!(strip_ansi("\x1b(Bok"), "ok");
+    }
+
+    #[test]
+    fn test_color_parse() {
+        assert_eq!(Color::parse("red"), Some(Color::rgb(255, 85, 85)));
+        assert_eq!(Color::parse("black"), Some(Color::rgb(0, 0, 0)));
+        assert_eq!(Color::parse("gray"), Some(Color::rgb(128, 128, 128)));
+        assert_eq!(Color::parse(" #00FF80 "), Some(Color::rgb(0, 255, 128)));
+        assert_eq!(Color::parse("#12345"), None);
+        assert_eq!(Color::parse("purple-ish"), None);
+    }
+
+    #[test]
+    fn test_osc_skipped() {
+        let spans = parse("\x1b]0;My MUD\x07Hi");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Hi");
+        assert_eq!(strip_ansi("A\x1b]2;t\x1b\\B"), "AB");
+    }
+
+    #[test]
+    fn test_parse_with_state_carries_over() {
+        let mut state = AnsiState::new();
+        parse_with_state("\x1b[1;31mHP", None, &mut state);
+        assert!(state.bold);
+
+        let spans = parse_with_state("下一行", None, &mut state);
+        assert_eq!(spans[0].fg, Color::rgb(255, 85, 85));
+
+        parse_with_state("結束\x1b[0m", None, &mut state);
+        assert_eq!(state, AnsiState::default());
+    }
+
+    #[test]
+    fn test_color_hex() {
+        assert_eq!(Color::rgb(255, 85, 0).to_hex(), "#ff5500");
+    }
+}
@@ -3,13 +3,25 @@
 //! 提供固定大小的環形緩衝區來儲存 MUD 訊息歷史
 
 use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// 帶有收到時間的訊息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferedMessage {
+    /// 收到訊息的時間
+    pub time: Instant,
+    /// 訊息內容
+    pub content: String,
+}
 
 /// 訊息緩衝區 - 使用環形緩衝區儲存歷史訊息
 ///
 /// 當緩衝區滿了時，最舊的訊息會被移除
 #[derive(Debug, Clone)]
 pub struct MessageBuffer {
-    messages: VecDeque<String>,
+    messages: VecDeque<BufferedMessage>,
     capacity: usize,
 }
 
@@ -33,18 +45,28 @@ impl MessageBuffer {
         }
     }
 
-    /// 添加訊息到緩衝區
+    /// 添加訊息到緩衝區，收到時間為現在
     ///
     /// 如果緩衝區已滿，最舊的訊息會被移除
     pub fn push(&mut self, message: String) {
+        self.push_at(Instant::now(), message);
+    }
+
+    /// 以指定的收到時間添加訊息
+    pub fn push_at(&mut self, time: Instant, message: String) {
         if self.messages.len() >= self.capacity {
             self.messages.pop_front();
         }
-        self.messages.push_back(message);
+        self.messages.push_back(BufferedMessage { time, content: message });
     }
 
     /// 獲取所有訊息的迭代器
     pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.messages.iter().map(|m| &m.content)
+    }
+
+    /// 獲取所有訊息（含收到時間）的迭代器
+    pub fn iter_with_time(&self) -> impl Iterator<Item = &BufferedMessage> {
         self.messages.iter()
     }
 
@@ -65,10 +87,80 @@ impl MessageBuffer {
 
     /// 獲取最後 n 條訊息
     pub fn last_n(&self, n: usize) -> Vec<&String> {
-        self.messages.iter().rev().take(n).collect::<Vec<_>>().into_iter().rev().collect()
+        self.last_n_with_time(n).into_iter().map(|m| &m.content).collect()
+    }
+
+    /// 獲取最後 n 條訊息（含收到時間）
+    pub fn last_n_with_time(&self, n: usize) -> Vec<&BufferedMessage> {
+        self.messages.iter().skip(self.messages.len().saturating_sub(n)).collect()
+    }
+
+    /// 寫入檔案（每行一則訊息）
+    ///
+    /// 訊息內容原樣保存（含 ANSI 碼），只將 `\`、`\n`、`\r` 跳脫以維持一行一則。
+    /// 收到時間不會保存，載入時以載入當下為準。
+    pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
+        let mut content = String::new();
+        for message in self.iter() {
+            content.push_str(&escape_line(message));
+            content.push('\n');
+        }
+        fs::write(path, content)
+    }
+
+    /// 從 [`save_to_file`](Self::save_to_file) 寫入的檔案載入，超過容量時只保留最新的訊息
+    ///
+    /// # Example
+    /// ```no_run
+    /// use mudcore::buffer::MessageBuffer;
+    /// use std::path::Path;
+    ///
+    /// let buffer = MessageBuffer::load_from_file(Path::new("main.scrollback"), 1000).unwrap();
+    /// assert!(buffer.len() <= 1000);
+    /// ```
+    pub fn load_from_file(path: &Path, capacity: usize) -> std::io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut buffer = Self::new(capacity);
+        for line in content.lines() {
+            buffer.push(unescape_line(line));
+        }
+        Ok(buffer)
     }
 }
 
+/// 跳脫反斜線與換行字元
+fn escape_line(message: &str) -> String {
+    let mut escaped = String::with_capacity(message.len());
+    for c in message.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 還原 [`escape_line`] 的跳脫
+fn unescape_line(line: &str) -> String {
+    let mut message = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            message.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => message.push('\n'),
+            Some('r') => message.push('\r'),
+            Some(other) => message.push(other),
+            None => message.push('\\'),
+        }
+    }
+    message
+}
+
 impl Default for MessageBuffer {
     fn default() -> Self {
         Self::new(1000) // 預設容量 1000 條訊息
@@ -78,6 +170,7 @@ impl Default for MessageBuffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn test_new_buffer() {
@@ -116,6 +209,26 @@ mod tests {
 
         let messages: Vec<_> = buffer.iter().collect();
         assert_eq!(messages, vec!["first", "second", "third"]);
+
+        // 預設時間為加入當下，依序不會倒退
+        let times: Vec<_> = buffer.iter_with_time().map(|m| m.time).collect();
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_push_at_keeps_time() {
+        let start = Instant::now();
+        let mut buffer = MessageBuffer::new(2);
+        buffer.push_at(start, "hit".to_string());
+        buffer.push_at(start + Duration::from_millis(1500), "miss".to_string());
+        buffer.push_at(start + Duration::from_secs(3), "kill".to_string());
+
+        let last = buffer.last_n_with_time(2);
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[0].content, "miss");
+        assert_eq!(last[1].time - last[0].time, Duration::from_millis(1500));
+        assert_eq!(buffer.iter_with_time().next().unwrap().time, start + Duration::from_millis(1500));
+        assert_eq!(buffer.last_n_with_time(5).len(), 2);
     }
 
     #[test]
@@ -137,6 +250,25 @@ mod tests {
         assert!(buffer.is_empty());
     }
 
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let path = std::env::temp_dir().join("test_mud_scrollback.txt");
+        let mut buffer = MessageBuffer::new(10);
+        buffer.push("\x1b[31m紅色\x1b[0m\r\n".to_string());
+        buffer.push("第一行\n第二行 C:\\path\\n".to_string());
+        buffer.push(String::new());
+        buffer.push("last".to_string());
+        buffer.save_to_file(&path).unwrap();
+
+        let loaded = MessageBuffer::load_from_file(&path, 10).unwrap();
+        assert_eq!(loaded.iter().collect::<Vec<_>>(), buffer.iter().collect::<Vec<_>>());
+
+        // 容量較小時只保留最新的訊息
+        let trimmed = MessageBuffer::load_from_file(&path, 2).unwrap();
+        assert_eq!(trimmed.iter().collect::<Vec<_>>(), vec!["", "last"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_default() {
         let buffer = MessageBuffer::default();
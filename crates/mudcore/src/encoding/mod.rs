@@ -1,8 +1,168 @@
-//! Big5/UTF-8 編解碼模組
+//! 伺服器文字編解碼模組
 //!
-//! 處理台灣 MUD 伺服器常用的 Big5 編碼轉換
+//! 處理台灣 MUD 伺服器常用的 Big5，以及大陸伺服器常用的 GBK / GB2312 編碼轉換
 
-use encoding_rs::BIG5;
+use encoding_rs::{BIG5, GBK};
+use serde::{Deserialize, Serialize};
+
+/// 伺服器文字編碼
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Encoding {
+    /// Big5（台灣 MUD 預設）
+    #[default]
+    Big5,
+    /// GBK（簡體中文 MUD 常用）
+    Gbk,
+    /// UTF-8
+    Utf8,
+    /// GB2312（以 GBK 解碼，GBK 為其超集）
+    Gb2312,
+}
+
+impl Encoding {
+    /// 所有可選的編碼（設定介面用）
+    pub const ALL: [Encoding; 4] = [Encoding::Big5, Encoding::Gbk, Encoding::Gb2312, Encoding::Utf8];
+
+    /// 顯示用名稱
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Big5 => "Big5",
+            Encoding::Gbk => "GBK",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Gb2312 => "GB2312",
+        }
+    }
+
+    /// 從名稱解析編碼（不分大小寫）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "big5" => Some(Encoding::Big5),
+            "gbk" | "cp936" => Some(Encoding::Gbk),
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "gb2312" | "euc-cn" => Some(Encoding::Gb2312),
+            _ => None,
+        }
+    }
+
+    /// 依此編碼解碼位元組
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        decode(bytes, *self)
+    }
+
+    /// 依此編碼編碼字串
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        encode(text, *self)
+    }
+}
+
+/// 依指定編碼將位元組轉換為 UTF-8 字串（無效字元會被替換為 U+FFFD）
+///
+/// # Example
+/// ```
+/// use mudcore::encoding::{decode, Encoding};
+///
+/// assert_eq!(decode(&[0xc4, 0xe3, 0xba, 0xc3], Encoding::Gbk), "你好");
+/// ```
+pub fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Big5 => BIG5.decode(bytes).0.into_owned(),
+        Encoding::Gbk | Encoding::Gb2312 => GBK.decode(bytes).0.into_owned(),
+        Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// 依指定編碼將字串轉換為位元組（無法編碼的字元會被替換）
+///
+/// # Example
+/// ```
+/// use mudcore::encoding::{encode, Encoding};
+///
+/// assert_eq!(encode("你好", Encoding::Gbk), vec![0xc4, 0xe3, 0xba, 0xc3]);
+/// ```
+pub fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Big5 => BIG5.encode(text).0.into_owned(),
+        Encoding::Gbk | Encoding::Gb2312 => GBK.encode(text).0.into_owned(),
+        Encoding::Utf8 => text.as_bytes().to_vec(),
+    }
+}
+
+/// Big5 文字中尾碼落在 0x40-0x7E 的雙位元組字元比例下限（GB2312 區不會出現這類尾碼）
+const BIG5_LOW_TRAIL_RATIO: f64 = 0.15;
+
+/// 達到滿信心度所需的雙位元組字元數
+const CONFIDENT_CHAR_COUNT: usize = 16;
+
+/// 偵測未知伺服器的文字編碼，回傳 (編碼, 信心度 0.0-1.0)
+///
+/// 先檢查是否為有效的 UTF-8；否則以雙位元組字元的尾碼分布區分 Big5 與 GBK：
+/// 尾碼 0x80-0xA0 在 Big5 中無效，尾碼 0x40-0x7E 在 Big5 常見但 GB2312 區不會出現。
+/// 取樣越短信心度越低；純 ASCII 時回傳預設編碼與信心度 0。
+///
+/// # Example
+/// ```
+/// use mudcore::encoding::{detect_encoding, encode, Encoding};
+///
+/// let (encoding, confidence) = detect_encoding(&encode("欢迎来到测试泥巴！请输入你的名字：", Encoding::Gbk));
+/// assert_eq!(encoding, Encoding::Gbk);
+/// assert!(confidence > 0.9);
+/// ```
+pub fn detect_encoding(bytes: &[u8]) -> (Encoding, f32) {
+    let high_bytes = bytes.iter().filter(|&&b| b >= 0x80).count();
+    if high_bytes == 0 {
+        return (Encoding::default(), 0.0);
+    }
+    if utf8_error_count(bytes) == 0 {
+        return (Encoding::Utf8, (high_bytes as f32 / CONFIDENT_CHAR_COUNT as f32).min(1.0));
+    }
+
+    let (mut pairs, mut low_trails, mut big5_invalid) = (0usize, 0usize, 0usize);
+    let mut iter = bytes.iter().copied();
+    while let Some(lead) = iter.next() {
+        if !(0x81..=0xFE).contains(&lead) {
+            continue;
+        }
+        let Some(trail) = iter.next() else {
+            break;
+        };
+        pairs += 1;
+        match trail {
+            0x40..=0x7E => low_trails += 1,
+            0x80..=0xA0 => big5_invalid += 1,
+            _ => {}
+        }
+    }
+    if pairs == 0 {
+        return (Encoding::default(), 0.0);
+    }
+
+    let low_ratio = low_trails as f64 / pairs as f64;
+    let (encoding, confidence) = if big5_invalid > 0 {
+        (Encoding::Gbk, 1.0)
+    } else if low_ratio >= BIG5_LOW_TRAIL_RATIO {
+        (Encoding::Big5, (low_ratio / (BIG5_LOW_TRAIL_RATIO * 2.0)).min(1.0))
+    } else {
+        (Encoding::Gbk, 1.0 - low_ratio / BIG5_LOW_TRAIL_RATIO)
+    };
+    let size_factor = (pairs as f64 / CONFIDENT_CHAR_COUNT as f64).min(1.0);
+    (encoding, (confidence * size_factor) as f32)
+}
+
+/// 計算無效的 UTF-8 位元組數
+fn utf8_error_count(mut bytes: &[u8]) -> usize {
+    let mut errors = 0;
+    while let Err(e) = std::str::from_utf8(bytes) {
+        match e.error_len() {
+            Some(len) => {
+                errors += len;
+                bytes = &bytes[e.valid_up_to() + len..];
+            }
+            // 取樣截斷在多位元組字元中間
+            None => break,
+        }
+    }
+    errors
+}
 
 /// 將 Big5 編碼的位元組轉換為 UTF-8 字串
 ///
@@ -21,8 +181,7 @@ use encoding_rs::BIG5;
 /// assert!(!text.is_empty());
 /// ```
 pub fn decode_big5(bytes: &[u8]) -> String {
-    let (decoded, _, _) = BIG5.decode(bytes);
-    decoded.into_owned()
+    decode(bytes, Encoding::Big5)
 }
 
 /// 將 UTF-8 字串轉換為 Big5 編碼的位元組
@@ -42,8 +201,7 @@ pub fn decode_big5(bytes: &[u8]) -> String {
 /// assert!(!bytes.is_empty());
 /// ```
 pub fn encode_big5(text: &str) -> Vec<u8> {
-    let (encoded, _, _) = BIG5.encode(text);
-    encoded.into_owned()
+    encode(text, Encoding::Big5)
 }
 
 #[cfg(test)]
@@ -94,4 +252,53 @@ mod tests {
         let decoded = decode_big5(&encoded);
         assert_eq!(decoded, original);
     }
+
+    #[test]
+    fn test_detect_truncated_utf8_sample() {
+        let bytes = "歡迎來到測試泥巴！請輸入你的名字：".as_bytes();
+        // 截斷最後一個字元的最後一個位元組
+        assert_eq!(detect_encoding(&bytes[..bytes.len() - 1]).0, Encoding::Utf8);
+    }
+
+    #[test]
+    fn test_encoding_from_name() {
+        assert_eq!(Encoding::from_name("UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("big5"), Some(Encoding::Big5));
+        assert_eq!(Encoding::from_name("GBK"), Some(Encoding::Gbk));
+        assert_eq!(Encoding::from_name("gb2312"), Some(Encoding::Gb2312));
+        assert_eq!(Encoding::from_name("latin1"), None);
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let (encoding, confidence) = detect_encoding(&encode_big5("歡迎來到測試泥巴！請輸入你的名字："));
+        assert_eq!(encoding, Encoding::Big5);
+        assert!(confidence > 0.9);
+
+        // 繁體字以 GBK 編碼時會出現 Big5 無效的尾碼
+        let (encoding, _) = detect_encoding(&encode("這是一個很長的泥巴遊戲", Encoding::Gbk));
+        assert_eq!(encoding, Encoding::Gbk);
+
+        let (encoding, confidence) = detect_encoding("歡迎光臨".as_bytes());
+        assert_eq!(encoding, Encoding::Utf8);
+        assert!(confidence < 1.0);
+
+        // 取樣太短時信心度偏低，純 ASCII 時為 0
+        let (encoding, confidence) = detect_encoding(&encode_big5("你好"));
+        assert_eq!(encoding, Encoding::Big5);
+        assert!(confidence < 0.5);
+        assert_eq!(detect_encoding(b"Welcome"), (Encoding::Big5, 0.0));
+    }
+
+    #[test]
+    fn test_gbk_roundtrip() {
+        // "你好" in GBK: 0xc4 0xe3 0xba 0xc3
+        assert_eq!(encode("你好", Encoding::Gbk), vec![0xc4, 0xe3, 0xba, 0xc3]);
+        assert_eq!(decode(&[0xc4, 0xe3, 0xba, 0xc3], Encoding::Gb2312), "你好");
+
+        let original = "欢迎来到MUD";
+        for encoding in [Encoding::Gbk, Encoding::Gb2312] {
+            assert_eq!(decode(&encode(original, encoding), encoding), original);
+        }
+    }
 }
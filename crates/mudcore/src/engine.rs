@@ -0,0 +1,689 @@
+//! 無介面的核心引擎
+//!
+//! 把 Telnet 解析、觸發器、別名、路徑、縮寫與腳本串成一條處理管線，不依賴 GUI，
+//! 可供機器人腳本與整合測試直接驅動。GUI 的 `Session` 持有一個 `Engine`，
+//! 再於其上加入視窗、日誌、客戶端指令等介面功能。
+
+use crate::abbreviation::AbbreviationTable;
+use crate::alias::{AliasManager, AliasMatchResult};
+use crate::ansi::{apply_substitutions, apply_trigger_highlights, highlight_sgr, strip_ansi};
+use crate::map::RoomGraph;
+use crate::paths::PathManager;
+use crate::script::{MudContext, ScriptEngine};
+use crate::speedwalk::parse_speedwalk;
+use crate::telnet::{TelnetClient, TelnetConfig};
+use crate::trigger::{TriggerAction, TriggerManager, TriggerMatch, TriggerPattern};
+use regex::RegexSet;
+use std::ops::Range;
+
+/// 指令遞迴展開的深度上限（別名、分號拆分、觸發器互相呼叫時避免無限迴圈）
+const MAX_DEPTH: usize = 50;
+
+/// 引擎送出的事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    /// 要送到伺服器的指令（已完成展開，不含換行）
+    Send(String),
+    /// 要回應給伺服器的 Telnet 協商位元組
+    Negotiation(Vec<u8>),
+    /// 伺服器送來且未被抑制的一行文字（保留 ANSI）
+    Line(String),
+    /// 腳本的本地顯示（mud.echo）
+    Echo(String),
+    /// 引擎本身的提示（如腳本錯誤）
+    System(String),
+}
+
+/// 引擎事件回呼
+pub type EventHandler = Box<dyn FnMut(&EngineEvent)>;
+
+/// 觸發器處理一行伺服器文字後，需由前端執行的動作
+#[derive(Debug, Clone, Default)]
+pub struct LineActions {
+    /// 不顯示本行
+    pub gag: bool,
+    /// 另外送往的子視窗（已展開擷取群組）
+    pub windows: Vec<String>,
+    /// 替換區間（去除 ANSI 後文字的位元組位置）與替換文字
+    pub substitutions: Vec<(Range<usize>, String)>,
+    /// 高亮區間（去除 ANSI 後文字的位元組位置）與 SGR 序列
+    pub highlights: Vec<(Range<usize>, String)>,
+    /// 附加到筆記的文字
+    pub notes: Vec<String>,
+    /// 附加到檔案的內容 (路徑, 文字)
+    pub file_appends: Vec<(String, String)>,
+    /// 要經過完整輸入處理送出的指令
+    pub commands: Vec<String>,
+    /// 原樣送出的指令
+    pub raw_commands: Vec<String>,
+    /// 要執行的腳本 (程式碼, 捕獲)
+    pub scripts: Vec<(String, Vec<String>)>,
+    /// 命中的觸發器（依觸發順序，供除錯顯示）
+    pub matches: Vec<TriggerMatch>,
+    /// `on_server_message` 鉤子的執行結果
+    pub hook: Option<MudContext>,
+    /// `on_server_message` 鉤子的錯誤訊息
+    pub hook_error: Option<String>,
+}
+
+impl LineActions {
+    /// 將高亮與替換套用到一行文字（高亮位置以原始文字為準，須在替換之前）
+    pub fn render(&self, line: &str, widths: &[u8]) -> (String, Vec<u8>) {
+        let (mut text, mut widths) = (line.to_string(), widths.to_vec());
+        if !self.highlights.is_empty() {
+            (text, widths) = apply_trigger_highlights(&text, &widths, self.highlights.clone());
+        }
+        if !self.substitutions.is_empty() {
+            (text, widths) = apply_substitutions(&text, &widths, self.substitutions.clone());
+        }
+        (text, widths)
+    }
+}
+
+/// 玩家輸入展開後的單一步驟
+#[derive(Debug)]
+pub enum InputStep {
+    /// 展開完成的指令（尚未經過縮寫與送出鉤子）
+    Command(String),
+    /// 需再以 [`Engine::expand_input`] 展開的指令（深度加一）
+    Expand {
+        text: String,
+        /// 是否由別名展開而來
+        from_alias: bool,
+    },
+    /// 原樣送出的指令（輸入觸發器的 SendRawCommand）
+    Raw(String),
+    /// 輸入觸發器或別名腳本的執行結果
+    Script(Box<MudContext>),
+    /// 展開時的錯誤（遞迴過深、別名參數不足、腳本錯誤）
+    Error(String),
+    /// 命中的輸入觸發器（供除錯顯示）
+    Matched(Vec<TriggerMatch>),
+}
+
+/// 縮寫展開與送出鉤子處理後的指令
+#[derive(Debug)]
+pub enum PreparedCommand {
+    /// 可送出；送出鉤子執行過時附上其腳本結果
+    Send { command: String, context: Option<MudContext> },
+    /// 送出鉤子取消了指令
+    Cancelled { command: String, context: MudContext },
+    /// 送出鉤子出錯，應照原樣送出
+    HookError { command: String, error: String },
+}
+
+/// 無介面的 MUD 核心引擎
+///
+/// 以 [`Engine::feed_bytes`] 餵入伺服器資料、以 [`Engine::send`] 處理玩家輸入，
+/// 結果透過 [`Engine::on_event`] 註冊的回呼送出；引擎本身不負責網路連線。
+pub struct Engine {
+    /// 別名管理器
+    pub alias_manager: AliasManager,
+    /// 觸發器管理器
+    pub trigger_manager: TriggerManager,
+    /// 路徑管理器
+    pub path_manager: PathManager,
+    /// 自動地圖（走過的房間與出口）
+    pub room_graph: RoomGraph,
+    /// 腳本引擎
+    pub script_engine: ScriptEngine,
+    /// 指令縮寫表
+    pub abbreviations: AbbreviationTable,
+    /// Telnet 協定與文字解碼（不建立連線）
+    telnet: TelnetClient,
+    /// 隱藏清單（預先編譯；符合的伺服器文字不經過鉤子與觸發器）
+    gag_set: Option<RegexSet>,
+    /// 尚未收到換行的文字
+    partial_line: String,
+    /// 尚未收到換行的文字中各字元的原始位元組寬度
+    partial_widths: Vec<u8>,
+    /// 事件回呼
+    handlers: Vec<EventHandler>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    /// 創建空的引擎
+    pub fn new() -> Self {
+        Self {
+            alias_manager: AliasManager::new(),
+            trigger_manager: TriggerManager::new(),
+            path_manager: PathManager::new(),
+            room_graph: RoomGraph::new(),
+            script_engine: ScriptEngine::new(),
+            abbreviations: AbbreviationTable::new(),
+            telnet: TelnetClient::new(TelnetConfig::default()),
+            gag_set: None,
+            partial_line: String::new(),
+            partial_widths: Vec::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// 註冊事件回呼（依註冊順序呼叫）
+    pub fn on_event(&mut self, handler: impl FnMut(&EngineEvent) + 'static) {
+        self.handlers.push(Box::new(handler));
+    }
+
+    /// Telnet 解析器（可調整編碼與協商策略）
+    pub fn telnet_mut(&mut self) -> &mut TelnetClient {
+        &mut self.telnet
+    }
+
+    /// 設定隱藏清單（以 `/.../` 包住的為正則，其餘為字面子字串；無效的正則會略過）
+    pub fn set_gag_patterns(&mut self, patterns: &[String]) {
+        self.gag_set = compile_gag_patterns(patterns);
+    }
+
+    /// 餵入伺服器送來的原始位元組，逐行處理完整的文字
+    ///
+    /// 不完整的 Telnet 序列與未換行的文字留待下次呼叫；收到 GA/EOR 時未換行的文字視為提示符。
+    pub fn feed_bytes(&mut self, data: &[u8]) {
+        let (text, widths, responses) = self.telnet.feed_bytes(data);
+        if !responses.is_empty() {
+            self.emit(EngineEvent::Negotiation(responses));
+        }
+
+        for (line, _) in self.push_text(&text, &widths) {
+            self.handle_line(&line, false);
+        }
+
+        // GA / EOR：尚未換行的文字即為提示符
+        if self.telnet.take_prompt_end() {
+            if let Some((prompt, _)) = self.take_partial_line() {
+                self.handle_line(&prompt, true);
+            }
+        }
+    }
+
+    /// 接上暫存的未換行文字，取出其中完整的行（不含換行）與各字元的位元組寬度
+    ///
+    /// 結尾未換行的部分繼續暫存，待下次呼叫或以 [`Engine::take_partial_line`] 取走。
+    /// 供已自行解碼文字的前端（如 GUI 的網路執行緒）使用。
+    pub fn push_text(&mut self, text: &str, widths: &[u8]) -> Vec<(String, Vec<u8>)> {
+        self.partial_line.push_str(text);
+        self.partial_widths.extend_from_slice(widths);
+
+        let mut lines = Vec::new();
+        while let Some(pos) = self.partial_line.find('\n') {
+            let line: String = self.partial_line.drain(..=pos).collect();
+            let count = line.chars().count().min(self.partial_widths.len());
+            let mut line_widths: Vec<u8> = self.partial_widths.drain(..count).collect();
+            let line = line.trim_end_matches(['\r', '\n']).to_string();
+            line_widths.truncate(line.chars().count());
+            lines.push((line, line_widths));
+        }
+        lines
+    }
+
+    /// 取走尚未收到換行的文字（收到 GA/EOR、逾時或斷線時視為完整一行）
+    pub fn take_partial_line(&mut self) -> Option<(String, Vec<u8>)> {
+        let widths = std::mem::take(&mut self.partial_widths);
+        if self.partial_line.is_empty() {
+            return None;
+        }
+        Some((std::mem::take(&mut self.partial_line), widths))
+    }
+
+    /// 處理一行伺服器文字：經過隱藏清單、鉤子與觸發器，未被抑制時送出套用替換與高亮後的 `Line` 事件
+    ///
+    /// `is_prompt` 表示該行以 GA/EOR 結尾（提示符），供觸發器的 `fire_on` 過濾。
+    /// 子視窗、筆記、檔案附加等介面相關動作交由前端處理，這裡略過。
+    pub fn handle_line(&mut self, line: &str, is_prompt: bool) {
+        let Some(mut actions) = self.process_line(line, is_prompt) else {
+            return;
+        };
+        if let Some(error) = actions.hook_error.take() {
+            self.emit(EngineEvent::System(format!("on_server_message 鉤子錯誤: {}", error)));
+        }
+        let mut gagged = actions.gag;
+
+        // 腳本可能要求抑制本行，先執行完再決定是否顯示
+        let mut contexts: Vec<MudContext> = actions.hook.take().into_iter().collect();
+        for (code, captures) in &actions.scripts {
+            if let Some(context) = self.run_script(code, line, captures) {
+                gagged |= context.gag;
+                contexts.push(context);
+            }
+        }
+
+        if !gagged {
+            let widths = vec![1; line.chars().count()];
+            let (text, _) = actions.render(line, &widths);
+            self.emit(EngineEvent::Line(text));
+        }
+        for cmd in actions.commands {
+            self.send_with_depth(&cmd, 1);
+        }
+        for cmd in actions.raw_commands {
+            self.emit(EngineEvent::Send(cmd));
+        }
+        for context in contexts {
+            self.apply_context(context);
+        }
+    }
+
+    /// 伺服器文字的共用處理：隱藏清單、`on_server_message` 鉤子與觸發器
+    ///
+    /// 符合隱藏清單時回傳 `None`，不經過鉤子與觸發器（前端只需寫入日誌）。
+    /// 鉤子的腳本結果放在 [`LineActions::hook`]，要求抑制本行時一併設定 [`LineActions::gag`]。
+    pub fn process_line(&mut self, line: &str, is_prompt: bool) -> Option<LineActions> {
+        let clean = strip_ansi(line);
+        if self.gag_set.as_ref().is_some_and(|set| set.is_match(&clean)) {
+            return None;
+        }
+
+        // 全域鉤子讓腳本直接處理每一行伺服器訊息，無需透過觸發器
+        let hook = self.script_engine.invoke_hook("on_server_message", line, &clean);
+        let mut actions = self.line_actions(line, is_prompt);
+        match hook {
+            Ok(Some(context)) => {
+                actions.gag |= context.gag;
+                actions.hook = Some(context);
+            }
+            Ok(None) => {}
+            Err(e) => actions.hook_error = Some(e.to_string()),
+        }
+        Some(actions)
+    }
+
+    /// 以觸發器處理一行伺服器文字，回傳需由前端執行的動作
+    ///
+    /// 設定/累加變數直接寫入腳本引擎；送出指令、腳本與顯示相關動作則收集到 [`LineActions`]。
+    pub fn line_actions(&mut self, line: &str, is_prompt: bool) -> LineActions {
+        let mut actions = LineActions::default();
+        for (trigger, m) in self.trigger_manager.process(line, is_prompt) {
+            for action in &trigger.actions {
+                match action {
+                    TriggerAction::SendCommand(cmd) => actions.commands.push(m.expand(cmd)),
+                    TriggerAction::SendRawCommand(cmd) => actions.raw_commands.push(m.expand(cmd)),
+                    TriggerAction::ExecuteScript(code) => actions.scripts.push((code.clone(), m.captures.clone())),
+                    TriggerAction::RouteToWindow(win_id) => {
+                        // 支援以擷取群組命名視窗（如 chat_$1）
+                        let win_id = m.expand(win_id);
+                        if !actions.windows.contains(&win_id) {
+                            actions.windows.push(win_id);
+                        }
+                    }
+                    TriggerAction::Gag => actions.gag = true,
+                    TriggerAction::SetVariable { name, value } => {
+                        self.script_engine.set_variable(name, &m.expand(value));
+                    }
+                    TriggerAction::IncrementVariable { name, by } => {
+                        self.script_engine.increment_variable(name, *by);
+                    }
+                    TriggerAction::Substitute { replacement } => {
+                        actions.substitutions.push((m.span.clone(), m.expand(replacement)));
+                    }
+                    TriggerAction::Highlight { fg, bg, bold } => {
+                        actions.highlights.push((m.span.clone(), highlight_sgr(*fg, *bg, *bold)));
+                    }
+                    TriggerAction::AppendNote(note) => actions.notes.push(m.expand(note)),
+                    TriggerAction::AppendFile { path, text } => {
+                        actions.file_appends.push((m.expand(path), m.expand(text)));
+                    }
+                    _ => {}
+                }
+            }
+            actions.matches.push(m);
+        }
+        actions
+    }
+
+    /// 處理玩家輸入：分號拆分、變數展開、輸入觸發器、別名、路徑、縮寫與送出鉤子
+    pub fn send(&mut self, input: &str) {
+        self.send_with_depth(input, 0);
+    }
+
+    fn send_with_depth(&mut self, input: &str, depth: usize) {
+        for step in self.expand_input(input, depth) {
+            match step {
+                InputStep::Expand { text, .. } => self.send_with_depth(&text, depth + 1),
+                InputStep::Command(text) => match self.prepare_command(text) {
+                    PreparedCommand::Send { command, context } => {
+                        if let Some(context) = context {
+                            self.apply_context(context);
+                        }
+                        self.emit(EngineEvent::Send(command));
+                    }
+                    PreparedCommand::Cancelled { context, .. } => self.apply_context(context),
+                    PreparedCommand::HookError { command, error } => {
+                        self.emit(EngineEvent::System(format!("on_send 鉤子錯誤: {}", error)));
+                        self.emit(EngineEvent::Send(command));
+                    }
+                },
+                InputStep::Raw(cmd) => self.emit(EngineEvent::Send(cmd)),
+                InputStep::Script(context) => self.apply_context(*context),
+                InputStep::Error(message) => self.emit(EngineEvent::System(message)),
+                InputStep::Matched(_) => {}
+            }
+        }
+    }
+
+    /// 展開一層玩家輸入：分號拆分、變數展開、輸入觸發器、別名、路徑與 speedwalk
+    ///
+    /// 回傳依序要處理的步驟。拆分或展開出的指令以 [`InputStep::Expand`] 交回，
+    /// 由呼叫端逐一再展開，讓前一段的副作用（如設定變數）對後一段可見；
+    /// 無需再展開的指令以 [`InputStep::Command`] 交回，前端可在此攔截客戶端指令，
+    /// 其餘再交給 [`Engine::prepare_command`]。
+    pub fn expand_input(&mut self, input: &str, depth: usize) -> Vec<InputStep> {
+        let input = input.trim();
+        if depth > MAX_DEPTH {
+            return vec![InputStep::Error(format!("指令遞迴過深，已略過: {}", input))];
+        }
+
+        if input.contains(';') {
+            return input.split(';').map(|part| expand_step(part, false)).collect();
+        }
+
+        // 變數展開在最前，確保觸發器與別名都看到展開後的內容
+        let input = self.script_engine.expand_variables(input);
+        let mut steps = Vec::new();
+
+        // 輸入觸發器：針對別名展開前的指令；別名展開後的指令再展開時會再觸發一次
+        let mut pending_commands = Vec::new();
+        let mut pending_raw_commands = Vec::new();
+        let mut pending_scripts = Vec::new();
+        let mut matches = Vec::new();
+        for (trigger, m) in self.trigger_manager.process_input(&input) {
+            for action in &trigger.actions {
+                match action {
+                    TriggerAction::SendCommand(cmd) => pending_commands.push(m.expand(cmd)),
+                    TriggerAction::SendRawCommand(cmd) => pending_raw_commands.push(m.expand(cmd)),
+                    TriggerAction::ExecuteScript(code) => pending_scripts.push((code.clone(), m.captures.clone())),
+                    _ => {}
+                }
+            }
+            matches.push(m);
+        }
+        if !matches.is_empty() {
+            steps.push(InputStep::Matched(matches));
+        }
+        for (code, captures) in pending_scripts {
+            steps.push(self.script_step(&code, &input, &captures));
+        }
+        steps.extend(pending_commands.iter().map(|cmd| expand_step(cmd, false)));
+        steps.extend(pending_raw_commands.into_iter().map(InputStep::Raw));
+
+        // 別名
+        match self.alias_manager.process_match(&input) {
+            AliasMatchResult::Replacement(expanded) => {
+                steps.push(expand_step(&expanded, true));
+                return steps;
+            }
+            AliasMatchResult::Script(code) => {
+                steps.push(self.script_step(&code, &input, &[]));
+                return steps;
+            }
+            AliasMatchResult::Error(e) => {
+                steps.push(InputStep::Error(e.to_string()));
+                return steps;
+            }
+            AliasMatchResult::None => {}
+        }
+
+        // 路徑與 speedwalk
+        let path_value = self.path_manager.get(&input).map_or_else(|| input.clone(), |path| path.value.clone());
+        if let Some(commands) = parse_speedwalk(&path_value) {
+            steps.extend(commands.iter().map(|cmd| expand_step(cmd, false)));
+        } else if path_value != input {
+            steps.push(expand_step(&path_value, false));
+        } else {
+            steps.push(InputStep::Command(input));
+        }
+        steps
+    }
+
+    /// 執行輸入觸發器或別名的腳本，出錯時轉為錯誤步驟
+    fn script_step(&mut self, code: &str, message: &str, captures: &[String]) -> InputStep {
+        match self.script_engine.execute_inline(code, message, captures, false) {
+            Ok(context) => InputStep::Script(Box::new(context)),
+            Err(e) => InputStep::Error(format!("腳本錯誤: {}", e)),
+        }
+    }
+
+    /// 送出前的最後處理：縮寫展開與送出鉤子（mud.on_send）
+    pub fn prepare_command(&mut self, command: String) -> PreparedCommand {
+        let command = self.abbreviations.expand(&command).unwrap_or(command);
+        match self.script_engine.run_send_hook(&command) {
+            Ok(None) => PreparedCommand::Send { command, context: None },
+            Ok(Some((Some(rewritten), context))) => PreparedCommand::Send { command: rewritten, context: Some(context) },
+            Ok(Some((None, context))) => PreparedCommand::Cancelled { command, context },
+            Err(e) => PreparedCommand::HookError { command, error: e.to_string() },
+        }
+    }
+
+    /// 執行腳本，出錯時送出 `System` 事件並回傳 `None`
+    fn run_script(&mut self, code: &str, message: &str, captures: &[String]) -> Option<MudContext> {
+        match self.script_engine.execute_inline(code, message, captures, false) {
+            Ok(context) => Some(context),
+            Err(e) => {
+                self.emit(EngineEvent::System(format!("腳本錯誤: {}", e)));
+                None
+            }
+        }
+    }
+
+    /// 套用腳本結果中與介面無關的部分（回顯、觸發器開關、送出指令）
+    fn apply_context(&mut self, context: MudContext) {
+        for echo in context.echos {
+            self.emit(EngineEvent::Echo(echo));
+        }
+        for (name, enabled) in context.trigger_updates {
+            if let Some(trigger) = self.trigger_manager.get_mut(&name) {
+                trigger.enabled = enabled;
+            }
+        }
+        for (pattern, code) in context.temp_triggers {
+            self.trigger_manager
+                .add_temp(TriggerPattern::Regex(pattern), TriggerAction::ExecuteScript(code));
+        }
+        for cmd in context.commands {
+            self.emit(EngineEvent::Send(cmd));
+        }
+    }
+
+    fn emit(&mut self, event: EngineEvent) {
+        for handler in &mut self.handlers {
+            handler(&event);
+        }
+    }
+}
+
+fn expand_step(text: &str, from_alias: bool) -> InputStep {
+    InputStep::Expand { text: text.to_string(), from_alias }
+}
+
+/// 隱藏清單項目轉為正則：以 `/.../` 包住的視為正則，其餘為字面子字串
+pub fn gag_pattern_source(pattern: &str) -> String {
+    let pattern = pattern.trim();
+    match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+        Some(re) if !re.is_empty() => re.to_string(),
+        _ => regex::escape(pattern),
+    }
+}
+
+/// 編譯隱藏清單（略過空白與無效的正則）；清單為空時回傳 `None`
+fn compile_gag_patterns(patterns: &[String]) -> Option<RegexSet> {
+    let sources: Vec<String> = patterns
+        .iter()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| gag_pattern_source(p))
+        .filter(|source| match regex::Regex::new(source) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!("無效的隱藏正則 '{}': {}", source, e);
+                false
+            }
+        })
+        .collect();
+    if sources.is_empty() {
+        return None;
+    }
+    RegexSet::new(&sources).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::Alias;
+    use crate::telnet::{TelnetCommand, TelnetOption};
+    use crate::trigger::{FireScope, Trigger};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// 建立引擎並收集所有事件
+    fn recording_engine() -> (Engine, Rc<RefCell<Vec<EngineEvent>>>) {
+        let mut engine = Engine::new();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&events);
+        engine.on_event(move |event| sink.borrow_mut().push(event.clone()));
+        (engine, events)
+    }
+
+    fn sent(events: &Rc<RefCell<Vec<EngineEvent>>>) -> Vec<String> {
+        events
+            .borrow_mut()
+            .drain(..)
+            .filter_map(|event| match event {
+                EngineEvent::Send(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scripted_trigger_sends_command() {
+        let (mut engine, events) = recording_engine();
+        engine.alias_manager.add(Alias::new("gr", "gr $1", "greet $1;bow $1"));
+        engine.trigger_manager.add(
+            Trigger::new("arrive", TriggerPattern::Regex(r"^(\w+) arrives\.$".to_string()))
+                .add_action(TriggerAction::ExecuteScript(r#"mud.send("say hi " .. captures[1])"#.to_string()))
+                .add_action(TriggerAction::SendCommand("gr $1".to_string())),
+        );
+
+        // 協商與文字混在一起、並拆成兩段送達
+        let mut data = vec![0xFF, TelnetCommand::Will as u8, TelnetOption::SuppressGoAhead.as_byte()];
+        data.extend_from_slice(b"Bob arr");
+        engine.feed_bytes(&data);
+        assert!(matches!(events.borrow().as_slice(), [EngineEvent::Negotiation(_)]));
+        events.borrow_mut().clear();
+
+        engine.feed_bytes(b"ives.\r\n");
+        assert_eq!(events.borrow()[0], EngineEvent::Line("Bob arrives.".to_string()));
+        assert_eq!(sent(&events), vec!["greet Bob", "bow Bob", "say hi Bob"]);
+    }
+
+    #[test]
+    fn test_input_expands_one_part_at_a_time() {
+        let (mut engine, events) = recording_engine();
+        engine.alias_manager.add(Alias::new("mark", "mark $1", "mud.variables['target'] = '$1'").as_script(true));
+
+        // 別名腳本設定的變數要對同一行後面的指令生效
+        engine.send("mark rat;kill $target");
+        assert_eq!(sent(&events), vec!["kill rat"]);
+
+        let steps = engine.expand_input("n;s", 0);
+        assert!(matches!(steps.as_slice(), [InputStep::Expand { .. }, InputStep::Expand { .. }]));
+    }
+
+    #[test]
+    fn test_script_registers_temp_trigger() {
+        let (mut engine, events) = recording_engine();
+        engine.trigger_manager.add(
+            Trigger::new("quest", TriggerPattern::Contains("accept the quest".to_string()))
+                .add_action(TriggerAction::ExecuteScript(r#"mud.temp_trigger("guard nods", "mud.send('north')")"#.to_string())),
+        );
+
+        engine.handle_line("You accept the quest.", false);
+        engine.handle_line("The guard nods.", false);
+        engine.handle_line("The guard nods.", false);
+        assert_eq!(sent(&events), vec!["north"]);
+    }
+
+    #[test]
+    fn test_prompt_line_and_display_actions() {
+        let (mut engine, events) = recording_engine();
+        engine.trigger_manager.add(
+            Trigger::new("gauge", TriggerPattern::Regex(r"^HP:(\d+)>".to_string()))
+                .with_fire_on(FireScope::PromptOnly)
+                .add_action(TriggerAction::SendCommand("status $1".to_string())),
+        );
+        engine.trigger_manager.add(
+            Trigger::new("orc", TriggerPattern::Contains("orc".to_string()))
+                .add_action(TriggerAction::Substitute { replacement: "ORC".to_string() }),
+        );
+
+        // 沒有 GA/EOR 的行不是提示符
+        engine.feed_bytes(b"HP:80> an orc\n");
+        assert_eq!(events.borrow()[0], EngineEvent::Line("HP:80> an ORC".to_string()));
+        events.borrow_mut().clear();
+
+        let mut data = b"HP:90> ".to_vec();
+        data.extend_from_slice(&[0xFF, TelnetCommand::GoAhead as u8]);
+        engine.feed_bytes(&data);
+        assert_eq!(events.borrow()[0], EngineEvent::Line("HP:90> ".to_string()));
+        assert_eq!(sent(&events), vec!["status 90"]);
+    }
+
+    #[test]
+    fn test_gag_raw_and_input_pipeline() {
+        let (mut engine, events) = recording_engine();
+        engine.alias_manager.add(Alias::new("k", "k $1", "kill $1"));
+        engine.trigger_manager.add(
+            Trigger::new("spam", TriggerPattern::Contains("advertises".to_string()))
+                .add_action(TriggerAction::Gag)
+                .add_action(TriggerAction::SendRawCommand("k spammer".to_string())),
+        );
+        engine.abbreviations.add("inventory");
+
+        engine.feed_bytes(b"Bob advertises a shop.\n");
+        assert_eq!(sent(&events), vec!["k spammer"], "抑制的行不顯示，直接送出不經別名");
+
+        engine.send("k orc;inv;/2n");
+        assert_eq!(sent(&events), vec!["kill orc", "inventory", "recall", "n", "n"]);
+    }
+
+    #[test]
+    fn test_gag_list_and_server_message_hook() {
+        let (mut engine, events) = recording_engine();
+        engine.set_gag_patterns(&["[OOC]".to_string(), r"/^\w+ yawns\.$/".to_string(), "/(/".to_string()]);
+        engine.trigger_manager.add(
+            Trigger::new("channel", TriggerPattern::Regex(r"^\[(\w+)\]".to_string()))
+                .add_action(TriggerAction::SendCommand("reply $1".to_string())),
+        );
+        engine
+            .script_engine
+            .execute_inline(
+                r#"function on_server_message(msg, clean)
+    if clean:find("weather") then mud.gag_message() end
+    if clean:find("waves") then mud.send("wave") end
+end"#,
+                "",
+                &[],
+                false,
+            )
+            .unwrap();
+
+        // 隱藏清單：不顯示，也不經過鉤子與觸發器
+        engine.feed_bytes(b"\x1b[1;33m[OOC] spam\x1b[0m\nBob yawns.\n");
+        assert!(events.borrow().is_empty());
+
+        // 鉤子要求抑制的行不顯示，鉤子送出的指令照常送出
+        engine.feed_bytes(b"The weather is fine.\nBob waves.\nBob yawns loudly.\n");
+        assert_eq!(
+            events.borrow()[..2],
+            [EngineEvent::Line("Bob waves.".to_string()), EngineEvent::Send("wave".to_string())]
+        );
+        assert_eq!(events.borrow()[2], EngineEvent::Line("Bob yawns loudly.".to_string()));
+
+        assert_eq!(gag_pattern_source("a.b"), r"a\.b");
+        assert_eq!(gag_pattern_source("/a.b/"), "a.b");
+        assert!(compile_gag_patterns(&["  ".to_string()]).is_none());
+    }
+}
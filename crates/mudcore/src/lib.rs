@@ -1,35 +1,45 @@
 //! MUD Core Library
 //!
 //! 提供 MUD 客戶端的核心功能：
+//! - `ansi`: ANSI 顏色碼解析
 //! - `telnet`: Telnet 協定連線與資料處理
-//! - `encoding`: Big5/UTF-8 編解碼
+//! - `encoding`: Big5/GBK/UTF-8 編解碼
 //! - `buffer`: 訊息歷史緩衝區
+//! - `engine`: 無介面的核心引擎（供機器人與整合測試使用）
 //! - `alias`: 命令別名系統
+//! - `abbreviation`: 指令縮寫（最短不混淆前綴）
 //! - `logger`: 日誌記錄
+//! - `placeholder`: 捕獲佔位符（`$1` / `%1`）展開
 //! - `trigger`: 觸發器系統
 //! - `script`: Python 腳本支援
 //! - `window`: 多視窗管理
 
+pub mod abbreviation;
 pub mod alias;
+pub mod ansi;
 pub mod buffer;
 pub mod debug_log;
 pub mod encoding;
+pub mod engine;
 pub mod logger;
 pub mod map;
 pub mod paths;
+pub mod placeholder;
 pub mod script;
 pub mod speedwalk;
 pub mod telnet;
 pub mod trigger;
 pub mod window;
 
-pub use alias::{Alias, AliasManager};
-pub use buffer::MessageBuffer;
-pub use encoding::{decode_big5, encode_big5};
-pub use logger::{LogFormat, Logger};
-pub use paths::{Path, PathManager, PathRecorder, LoopStatus};
-pub use speedwalk::parse_speedwalk;
-pub use script::{MudContext, ScriptEngine};
-pub use telnet::TelnetClient;
-pub use trigger::{Trigger, TriggerAction, TriggerManager, TriggerPattern};
-pub use window::{SubWindow, WindowManager, WindowMessage};
+pub use abbreviation::AbbreviationTable;
+pub use alias::{Alias, AliasError, AliasManager};
+pub use buffer::{BufferedMessage, MessageBuffer};
+pub use encoding::{decode_big5, encode_big5, Encoding};
+pub use engine::{Engine, EngineEvent, EventHandler, InputStep, LineActions, PreparedCommand};
+pub use logger::{expand_log_path, LogFormat, Logger, DEFAULT_LOG_PATH_TEMPLATE};
+pub use paths::{is_movement_command, Path, PathManager, PathRecorder, LoopStatus};
+pub use speedwalk::{parse_speedwalk, try_parse_speedwalk, SpeedwalkError};
+pub use script::{ConnectEvent, MudContext, PendingTimer, ScriptEngine};
+pub use telnet::{GmcpMessage, RoomInfo, TelnetClient, Vitals};
+pub use trigger::{FireScope, Trigger, TriggerAction, TriggerManager, TriggerMatch, TriggerPattern, TriggerScope};
+pub use window::{Severity, SubWindow, WindowManager, WindowMessage};
@@ -5,18 +5,72 @@
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+/// 預設的日誌路徑樣板
+pub const DEFAULT_LOG_PATH_TEMPLATE: &str = "logs/{profile}_{date}_{time}.txt";
+
 /// 日誌記錄錯誤
 #[derive(Debug, Error)]
 pub enum LogError {
     #[error("IO 錯誤: {0}")]
     Io(#[from] io::Error),
+
+    #[error("無法建立日誌目錄 {}: {source}", .path.display())]
+    CreateDir { path: PathBuf, source: io::Error },
     
     #[error("日誌未開啟")]
     NotOpen,
 }
 
+/// 展開日誌路徑樣板
+///
+/// 支援 `{profile}`（Profile 名稱，路徑分隔字元換成 `_`）、`{date}`（`YYYY-MM-DD`）
+/// 與 `{time}`（`HHMMSS`）；日期時間以 UTC 計算。
+///
+/// # Example
+/// ```
+/// use mudcore::logger::expand_log_path;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+/// assert_eq!(
+///     expand_log_path("logs/{profile}/{date}.txt", "dragon", now).to_str(),
+///     Some("logs/dragon/2023-11-14.txt")
+/// );
+/// ```
+pub fn expand_log_path(template: &str, profile: &str, now: SystemTime) -> PathBuf {
+    let secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    let profile: String = profile
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':') { '_' } else { c })
+        .collect();
+
+    PathBuf::from(
+        template
+            .replace("{profile}", &profile)
+            .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+            .replace("{time}", &format!("{:02}{:02}{:02}", time / 3600, time / 60 % 60, time % 60)),
+    )
+}
+
+/// 自 1970-01-01 起的天數轉為 (年, 月, 日)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
 /// 日誌格式
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum LogFormat {
@@ -86,8 +140,11 @@ impl Logger {
         let path = path.as_ref();
         
         // 確保目錄存在
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|source| LogError::CreateDir {
+                path: parent.to_path_buf(),
+                source,
+            })?;
         }
 
         let file = OpenOptions::new()
@@ -428,4 +485,39 @@ mod tests {
 
         let _ = fs::remove_file(&log_path);
     }
+
+    #[test]
+    fn test_expand_log_path() {
+        use std::time::Duration;
+
+        // 2024-02-29 13:05:09 UTC
+        let now = UNIX_EPOCH + Duration::from_secs(1_709_211_909);
+        assert_eq!(
+            expand_log_path("logs/{profile}/{date}_{time}.txt", "dragon", now),
+            PathBuf::from("logs/dragon/2024-02-29_130509.txt")
+        );
+        assert_eq!(expand_log_path("{profile}.log", "a/b:c", now), PathBuf::from("a_b_c.log"));
+        assert_eq!(expand_log_path("plain.txt", "x", UNIX_EPOCH), PathBuf::from("plain.txt"));
+        assert_eq!(expand_log_path("{date}", "x", UNIX_EPOCH), PathBuf::from("1970-01-01"));
+    }
+
+    #[test]
+    fn test_start_creates_directories() {
+        let root = std::env::temp_dir().join("mudclient_test_log_dirs");
+        let _ = fs::remove_dir_all(&root);
+        let log_path = root.join("dragon").join("2024").join("session.txt");
+
+        let mut logger = Logger::new();
+        logger.start(&log_path).unwrap();
+        logger.log("hello").unwrap();
+        logger.stop().unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "hello\n");
+
+        // 父路徑是檔案時無法建立目錄，回傳明確的錯誤
+        let blocked = log_path.join("nested.txt");
+        let err = Logger::new().start(&blocked).unwrap_err();
+        assert!(matches!(err, LogError::CreateDir { ref path, .. } if *path == log_path));
+
+        let _ = fs::remove_dir_all(&root);
+    }
 }
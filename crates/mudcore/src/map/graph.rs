@@ -0,0 +1,130 @@
+//! 自動地圖
+//!
+//! 依玩家走過的房間與移動方向建立房間之間的出口連結，供 `#go` 計算路線。
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::paths::short_direction;
+
+/// 自動地圖：記錄走過的房間與出口連結，用於計算兩個房間之間的路線
+///
+/// 房間以 [`Room::hash`](super::Room::hash) 產生的 ID 識別。
+#[derive(Debug, Clone, Default)]
+pub struct RoomGraph {
+    /// 房間 ID -> 房間名稱
+    rooms: HashMap<String, String>,
+    /// 房間 ID -> (方向縮寫 -> 目的房間 ID)
+    exits: HashMap<String, BTreeMap<String, String>>,
+}
+
+impl RoomGraph {
+    /// 建立空的地圖
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 記錄房間（已存在時更新名稱）
+    pub fn add_room(&mut self, id: &str, name: &str) {
+        self.rooms.insert(id.to_string(), name.to_string());
+    }
+
+    /// 記錄從 `from` 往 `direction` 走會到達 `to`（方向會轉為縮寫）
+    pub fn link(&mut self, from: &str, direction: &str, to: &str) {
+        self.exits
+            .entry(from.to_string())
+            .or_default()
+            .insert(short_direction(direction).to_string(), to.to_string());
+    }
+
+    /// 已記錄的房間數
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    /// 是否尚未記錄任何房間
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    /// 以名稱（不分大小寫）尋找房間 ID
+    pub fn find_rooms(&self, name: &str) -> Vec<&str> {
+        let name = name.trim();
+        self.rooms
+            .iter()
+            .filter(|(_, room_name)| room_name.trim().eq_ignore_ascii_case(name))
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+
+    /// 計算從 `from` 到名稱為 `target` 的房間的最短路線（方向縮寫序列）
+    ///
+    /// 以廣度優先搜尋走已知的出口；同名房間取最近的一間。
+    /// 找不到路線時回傳 `None`，已在目標房間時回傳空序列。
+    pub fn route(&self, from: &str, target: &str) -> Option<Vec<String>> {
+        let goals: HashSet<&str> = self.find_rooms(target).into_iter().collect();
+        if goals.is_empty() {
+            return None;
+        }
+
+        // 房間 ID -> (上一個房間 ID, 方向)
+        let mut came_from: HashMap<&str, (&str, &str)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        let mut visited = HashSet::from([from]);
+
+        while let Some(current) = queue.pop_front() {
+            if goals.contains(current) {
+                let mut steps = Vec::new();
+                let mut node = current;
+                while let Some(&(prev, direction)) = came_from.get(node) {
+                    steps.push(direction.to_string());
+                    node = prev;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+            for (direction, next) in self.exits.get(current).into_iter().flatten() {
+                let next = next.as_str();
+                if visited.insert(next) {
+                    came_from.insert(next, (current, direction.as_str()));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn town() -> RoomGraph {
+        let mut graph = RoomGraph::new();
+        graph.add_room("square", "Town Square");
+        graph.add_room("street", "Main Street");
+        graph.add_room("inn", "The Inn");
+        graph.add_room("gate", "North Gate");
+        graph.link("square", "north", "street");
+        graph.link("street", "s", "square");
+        graph.link("street", "e", "inn");
+        graph.link("inn", "w", "street");
+        graph.link("street", "n", "gate");
+        graph
+    }
+
+    #[test]
+    fn test_route_shortest_path() {
+        let graph = town();
+        assert_eq!(graph.route("square", "the inn"), Some(vec!["n".to_string(), "e".to_string()]));
+        assert_eq!(graph.route("inn", "Town Square"), Some(vec!["w".to_string(), "s".to_string()]));
+        assert_eq!(graph.route("square", "Town Square"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_route_unknown_or_unreachable() {
+        let graph = town();
+        assert_eq!(graph.route("square", "Castle"), None);
+        // 只記錄了往北的出口，無法從城門走回去
+        assert_eq!(graph.route("gate", "Main Street"), None);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod graph;
 pub mod room;
 
+pub use graph::RoomGraph;
 pub use room::Room;
@@ -107,6 +107,8 @@ pub struct PathRecorder {
     pub visited_locations: Vec<(u64, (i32, i32, i32))>,
     /// 是否啟用迴圈偵測
     pub enable_loop_detection: bool,
+    /// 最近一次的迴圈偵測結果
+    pub last_loop_status: LoopStatus,
 }
 
 impl Default for PathRecorder {
@@ -117,6 +119,7 @@ impl Default for PathRecorder {
             current_pos: (0, 0, 0),
             visited_locations: Vec::new(),
             enable_loop_detection: true,
+            last_loop_status: LoopStatus::None,
         }
     }
 }
@@ -132,6 +135,7 @@ impl PathRecorder {
         self.recorded_commands.clear();
         self.current_pos = (0, 0, 0);
         self.visited_locations.clear();
+        self.last_loop_status = LoopStatus::None;
     }
 
     /// 停止記錄
@@ -151,6 +155,7 @@ impl PathRecorder {
         for (hash, pos) in &self.visited_locations {
             if *hash == content_hash {
                 if *pos == self.current_pos {
+                    self.last_loop_status = LoopStatus::ExactLoop;
                     return LoopStatus::ExactLoop;
                 }
                 potential_match = true;
@@ -160,11 +165,13 @@ impl PathRecorder {
         // 記錄此地點
         self.visited_locations.push((content_hash, self.current_pos));
 
-        if potential_match {
+        let status = if potential_match {
             LoopStatus::PotentialLoop
         } else {
             LoopStatus::None
-        }
+        };
+        self.last_loop_status = status.clone();
+        status
     }
 
     /// 嘗試記錄指令 (若是移動指令則記錄)
@@ -211,6 +218,7 @@ impl PathRecorder {
         self.recorded_commands.clear();
         self.visited_locations.clear();
         self.current_pos = (0, 0, 0);
+        self.last_loop_status = LoopStatus::None;
     }
 
     /// 取得目前路徑字串 (Speedwalk 格式)
@@ -219,6 +227,29 @@ impl PathRecorder {
         self.recorded_commands.join(";")
     }
 
+    /// 產生精簡的 Speedwalk 字串 (e.g., n, n, n, w -> 3n1w)
+    ///
+    /// 完整方向名稱會轉為縮寫，不含開頭的 `/`（recall）
+    pub fn to_speedwalk(&self) -> String {
+        let mut result = String::new();
+        let mut iter = self.recorded_commands.iter().map(|c| short_direction(c)).peekable();
+
+        while let Some(dir) = iter.next() {
+            let mut count = 1;
+            while iter.peek() == Some(&dir) {
+                iter.next();
+                count += 1;
+            }
+            // n/s 後接 e/w 時補上次數，避免被解析為 ne/nw/se/sw
+            let ambiguous = result.ends_with('n') || result.ends_with('s');
+            if count > 1 || (ambiguous && (dir.starts_with('e') || dir.starts_with('w'))) {
+                result.push_str(&count.to_string());
+            }
+            result.push_str(dir);
+        }
+        result
+    }
+
     /// 產生回溯路徑 (反向指令序列)
     pub fn get_reverse_path(&self) -> Vec<String> {
         let mut reverse_cmds = Vec::new();
@@ -231,13 +262,7 @@ impl PathRecorder {
     }
 
     fn is_movement_command(&self, cmd: &str) -> bool {
-        matches!(
-            cmd,
-            "n" | "s" | "e" | "w" | "u" | "d" | 
-            "ne" | "nw" | "se" | "sw" |
-            "north" | "south" | "east" | "west" | "up" | "down" |
-            "northeast" | "northwest" | "southeast" | "southwest"
-        )
+        is_movement_command(cmd)
     }
 
     fn get_reverse_direction(&self, cmd: &str) -> Option<String> {
@@ -291,6 +316,34 @@ impl PathRecorder {
     }
 }
 
+/// 判斷是否為移動指令（方向全名或縮寫）
+pub fn is_movement_command(cmd: &str) -> bool {
+    matches!(
+        cmd,
+        "n" | "s" | "e" | "w" | "u" | "d" |
+        "ne" | "nw" | "se" | "sw" |
+        "north" | "south" | "east" | "west" | "up" | "down" |
+        "northeast" | "northwest" | "southeast" | "southwest"
+    )
+}
+
+/// 將方向指令轉為縮寫
+pub(crate) fn short_direction(cmd: &str) -> &str {
+    match cmd {
+        "north" => "n",
+        "south" => "s",
+        "east" => "e",
+        "west" => "w",
+        "up" => "u",
+        "down" => "d",
+        "northeast" => "ne",
+        "northwest" => "nw",
+        "southeast" => "se",
+        "southwest" => "sw",
+        other => other,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -377,5 +430,20 @@ mod tests {
         recorder.record("s");
         recorder.record("s"); // (0, 0, 0)
         assert_eq!(recorder.record_room(hash1), LoopStatus::ExactLoop);
+        assert_eq!(recorder.last_loop_status, LoopStatus::ExactLoop);
+    }
+
+    #[test]
+    fn test_to_speedwalk() {
+        let mut recorder = PathRecorder::new();
+        recorder.start();
+        for cmd in ["n", "north", "n", "e", "se", "se", "u"] {
+            recorder.record(cmd);
+        }
+        assert_eq!(recorder.to_speedwalk(), "3n1e2seu");
+        assert_eq!(
+            crate::parse_speedwalk(&format!("/{}", recorder.to_speedwalk())).map(|c| c.len()),
+            Some(8)
+        );
     }
 }
@@ -0,0 +1,127 @@
+//! 捕獲佔位符展開
+//!
+//! 觸發器動作與別名替換共用的模板展開，同時支援 `$1` 與其他客戶端常見的 `%1` 寫法。
+
+/// 展開模板中的捕獲佔位符
+///
+/// - `$N` / `%N`：第 N 個捕獲群組，`$0` / `%0` 為整個匹配；超出範圍時保留原文
+/// - `$*` / `%*`：所有非空的捕獲群組（以空白連接）
+/// - `%%` 輸出 `%`，`\$` 輸出 `$`
+///
+/// # Example
+/// ```
+/// use mudcore::placeholder::expand_captures;
+///
+/// assert_eq!(expand_captures("kill $1; get %2", "orc gold", &["orc", "gold"]), "kill orc; get gold");
+/// assert_eq!(expand_captures("say 100%% \\$1", "", &["x"]), "say 100% $1");
+/// ```
+pub fn expand_captures(template: &str, whole: &str, groups: &[&str]) -> String {
+    expand_named_captures(template, whole, groups, &[])
+}
+
+/// 展開模板中的捕獲佔位符，並支援 `${name}` 具名群組
+///
+/// `${N}` 與 `$N` 相同；找不到的名稱保留原文。
+pub fn expand_named_captures(template: &str, whole: &str, groups: &[&str], named: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                chars.next();
+                result.push('$');
+            }
+            '%' if chars.peek() == Some(&'%') => {
+                chars.next();
+                result.push('%');
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut name = String::new();
+                let mut closed = false;
+                for ch in chars.by_ref() {
+                    if ch == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(ch);
+                }
+                let value = match name.parse::<usize>() {
+                    Ok(0) => Some(whole),
+                    Ok(n) => groups.get(n - 1).copied(),
+                    Err(_) => named.iter().find(|(key, _)| *key == name).map(|(_, value)| *value),
+                };
+                match value {
+                    Some(value) if closed => result.push_str(value),
+                    _ => {
+                        result.push_str("${");
+                        result.push_str(&name);
+                        if closed {
+                            result.push('}');
+                        }
+                    }
+                }
+            }
+            '$' | '%' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let all: Vec<&str> = groups.iter().copied().filter(|g| !g.is_empty()).collect();
+                result.push_str(&all.join(" "));
+            }
+            '$' | '%' => {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                match digits.parse::<usize>() {
+                    Ok(0) => result.push_str(whole),
+                    Ok(n) if n <= groups.len() => result.push_str(groups[n - 1]),
+                    _ => {
+                        result.push(c);
+                        result.push_str(&digits);
+                    }
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dollar_and_percent_placeholders() {
+        let groups = ["Bob", "hi"];
+        let whole = "Bob tells you hi";
+        assert_eq!(expand_captures("reply $1 $2", whole, &groups), "reply Bob hi");
+        assert_eq!(expand_captures("reply %1 %2", whole, &groups), "reply Bob hi");
+        assert_eq!(expand_captures("echo [$0] [%0]", whole, &groups), format!("echo [{}] [{}]", whole, whole));
+        assert_eq!(expand_captures("say $* / %*", whole, &groups), "say Bob hi / Bob hi");
+
+        // 超出範圍或不是佔位符的保留原文
+        assert_eq!(expand_captures("$3 $10 $x 50%", whole, &groups), "$3 $10 $x 50%");
+        let many: Vec<String> = (1..=10).map(|i| format!("g{}", i)).collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        assert_eq!(expand_captures("$10 %1", "", &many), "g10 g1");
+    }
+
+    #[test]
+    fn test_named_placeholders() {
+        let named = [("item", "sword")];
+        assert_eq!(expand_named_captures("get ${item} ${1} ${0}", "all", &["x"], &named), "get sword x all");
+        assert_eq!(expand_named_captures("${other} ${item", "", &[], &named), "${other} ${item");
+        assert_eq!(expand_captures("say ${item}", "", &[]), "say ${item}");
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(expand_captures("say 100%%", "", &[]), "say 100%");
+        assert_eq!(expand_captures("say %%1 \\$1", "", &["x"]), "say %1 $1");
+        assert_eq!(expand_captures("say \\x", "", &[]), "say \\x");
+    }
+}
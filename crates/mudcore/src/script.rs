@@ -1,8 +1,35 @@
+use crate::ansi::Color;
+use crate::encoding::Encoding;
+use crate::window::Severity;
 use mlua::Lua;
 use std::collections::HashMap;
 use thiserror::Error;
 use std::cell::RefCell;
 
+/// `mud.on_send` 註冊的鉤子存放的全域名稱（跨執行保留在同一個 Lua 實例）
+const SEND_HOOK: &str = "_mud_on_send";
+/// 送出鉤子的回傳值暫存的全域名稱
+const SEND_HOOK_RESULT: &str = "_mud_on_send_result";
+
+/// 連線事件（`mud.on_connect` / `mud.on_disconnect` 註冊的處理函數）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectEvent {
+    /// 連線建立（自動登入之後）
+    Connected,
+    /// 已連線後中斷
+    Disconnected,
+}
+
+impl ConnectEvent {
+    /// 處理函數存放的全域名稱
+    fn hook_name(&self) -> &'static str {
+        match self {
+            ConnectEvent::Connected => "_mud_on_connect",
+            ConnectEvent::Disconnected => "_mud_on_disconnect",
+        }
+    }
+}
+
 /// 腳本執行錯誤
 #[derive(Debug, Error)]
 pub enum ScriptError {
@@ -50,13 +77,37 @@ pub struct MudContext {
     /// 日誌控制指令
     pub log_control: Option<LogControl>,
     
-    /// 延遲執行的 Timer (delay_ms, lua_code)
-    pub timers: Vec<(u64, String)>,
+    /// 延遲執行的 Timer
+    pub timers: Vec<PendingTimer>,
     
     /// 觸發器狀態更新 (name, enabled)
     pub trigger_updates: Vec<(String, bool)>,
+
+    /// 要註冊的一次性觸發器 (正則, Lua 代碼)（mud.temp_trigger）
+    pub temp_triggers: Vec<(String, String)>,
+
+    /// 是否要求斷線（mud.disconnect）
+    pub disconnect: bool,
+
+    /// 註冊的高亮 (正則, 顏色)（mud.highlight）
+    pub highlights: Vec<(String, Color)>,
+
+    /// 是否先清除既有高亮（mud.clear_highlights）
+    pub clear_highlights: bool,
+
+    /// 要切換的文字編碼（mud.set_encoding）
+    pub encoding: Option<Encoding>,
+
+    /// 附加到 Session 筆記的文字（mud.note）
+    pub notes: Vec<String>,
+
+    /// 送到重要事件彙整視窗的訊息（mud.alert）
+    pub alerts: Vec<(String, Severity)>,
 }
 
+/// 腳本註冊的計時器 (delay_ms, lua_code, 觸發時注入的全域變數)
+pub type PendingTimer = (u64, String, Option<HashMap<String, String>>);
+
 impl MudContext {
     pub fn new() -> Self {
         Self::default()
@@ -110,6 +161,47 @@ impl ScriptEngine {
         self.scripts.remove(name).is_some()
     }
 
+    /// 取得持久化變數
+    pub fn get_variable(&self, name: &str) -> Option<String> {
+        self.persistent_vars.borrow().get(name).cloned()
+    }
+
+    /// 設定持久化變數
+    pub fn set_variable(&self, name: &str, value: &str) {
+        self.persistent_vars
+            .borrow_mut()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    /// 移除持久化變數
+    pub fn remove_variable(&self, name: &str) -> bool {
+        self.persistent_vars.borrow_mut().remove(name).is_some()
+    }
+
+    /// 取得所有持久化變數的快照（依名稱排序）
+    pub fn variables(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<_> = self
+            .persistent_vars
+            .borrow()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    /// 累加數值變數，返回新值（不存在或非數值時視為 0）
+    pub fn increment_variable(&self, name: &str, by: i64) -> i64 {
+        let mut vars = self.persistent_vars.borrow_mut();
+        let current = vars
+            .get(name)
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        let updated = current.saturating_add(by);
+        vars.insert(name.to_string(), updated.to_string());
+        updated
+    }
+
     /// 展開變數 (將 $var 替換為變數值)
     pub fn expand_variables(&self, text: &str) -> String {
         let mut result = text.to_string();
@@ -139,7 +231,7 @@ impl ScriptEngine {
             .ok_or_else(|| ScriptError::NotFound(script_name.to_string()))?;
 
         // 執行腳本時也預設 clean_message = message
-        self.run_code(code, message, message, captures, is_echo)
+        self.run_code(code, message, message, captures, is_echo, &HashMap::new())
     }
 
     /// 執行內聯代碼
@@ -152,7 +244,44 @@ impl ScriptEngine {
     ) -> Result<MudContext, ScriptError> {
         // inline 執行通常只有 message，沒有特定的 clean_message 來源，預設與 message 相同或空
         // 這裡為了兼容現有調用，將 clean_message 設為與 message 相同
-        self.run_code(code, message, message, captures, is_echo)
+        self.run_code(code, message, message, captures, is_echo, &HashMap::new())
+    }
+
+    /// 執行內聯代碼，並在執行前將 `globals` 設為 Lua 全域變數（計時器觸發時使用）
+    pub fn execute_inline_with_globals(
+        &self,
+        code: &str,
+        message: &str,
+        captures: &[String],
+        is_echo: bool,
+        globals: &HashMap<String, String>,
+    ) -> Result<MudContext, ScriptError> {
+        self.run_code(code, message, message, captures, is_echo, globals)
+    }
+
+    /// 試跑代碼：收集動作但不保留對持久化變數、全域變數與鉤子的修改，供預覽使用
+    pub fn dry_run(&self, code: &str, message: &str, captures: &[String]) -> Result<MudContext, ScriptError> {
+        let saved_vars = self.persistent_vars.borrow().clone();
+        // 鉤子（mud.on_send 等）也存放在全域表，一併快照後整體還原
+        let lua_globals = self.lua.globals();
+        let saved_globals = lua_globals
+            .pairs::<mlua::Value, mlua::Value>()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result = self.run_code(code, message, message, captures, false, &HashMap::new());
+
+        *self.persistent_vars.borrow_mut() = saved_vars;
+        let current_keys = lua_globals
+            .pairs::<mlua::Value, mlua::Value>()
+            .map(|pair| pair.map(|(key, _)| key))
+            .collect::<Result<Vec<_>, _>>()?;
+        for key in current_keys {
+            lua_globals.raw_set(key, mlua::Value::Nil)?;
+        }
+        for (key, value) in saved_globals {
+            lua_globals.raw_set(key, value)?;
+        }
+        result
     }
 
     /// 運行 Lua 代碼
@@ -163,10 +292,18 @@ impl ScriptEngine {
         clean_message: &str,
         captures: &[String],
         is_echo: bool,
+        globals: &HashMap<String, String>,
     ) -> Result<MudContext, ScriptError> {
         let mut context = MudContext::new();
 
-        self.lua.scope(|scope| {
+        // 記下被注入變數覆蓋的全域值，執行後還原，避免洩漏到之後的腳本
+        let lua_globals = self.lua.globals();
+        let shadowed = globals
+            .keys()
+            .map(|key| Ok((key.as_str(), lua_globals.get::<mlua::Value>(key.as_str())?)))
+            .collect::<Result<Vec<_>, mlua::Error>>()?;
+
+        let result = self.lua.scope(|scope| {
             // 創建 mud 表用於存放 API
             let mud = self.lua.create_table()?;
             
@@ -192,6 +329,11 @@ impl ScriptEngine {
             // 創建 log_messages 表
             let log_messages = self.lua.create_table()?;
             mud.set("log_messages", log_messages)?;
+
+            // 創建 notes 表（附加到筆記）
+            mud.set("_notes", self.lua.create_table()?)?;
+            mud.set("_alerts", self.lua.create_table()?)?;
+            mud.set("_temp_triggers", self.lua.create_table()?)?;
             
             // 創建 timers 表
             let timers = self.lua.create_table()?;
@@ -204,6 +346,13 @@ impl ScriptEngine {
             // gag 標記
             mud.set("gag", false)?;
 
+            // 斷線標記
+            mud.set("_disconnect", false)?; // Internal use
+
+            // 高亮註冊
+            mud.set("_highlights", self.lua.create_table()?)?; // Internal use
+            mud.set("_clear_highlights", false)?; // Internal use
+
             // Log Control
             let log_control = self.lua.create_table()?;
             mud.set("_log_control", log_control)?; // Internal use
@@ -266,6 +415,53 @@ impl ScriptEngine {
             })?;
             mud.set("log", log_fn)?;
 
+            // mud.note(text) 函數 - 附加一行到 Session 筆記
+            let note_fn = scope.create_function(|lua, text: String| {
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                let notes: mlua::Table = mud.get("_notes")?;
+                let len = notes.len()? + 1;
+                notes.set(len, text)?;
+                Ok(())
+            })?;
+            mud.set("note", note_fn)?;
+
+            // mud.on_send(function(cmd) ... end) 函數 - 註冊送出指令前的鉤子（傳入 nil 取消註冊）
+            let on_send_fn = scope.create_function(|lua, hook: Option<mlua::Function>| {
+                lua.globals().set(SEND_HOOK, hook)?;
+                Ok(())
+            })?;
+            mud.set("on_send", on_send_fn)?;
+
+            // mud.on_connect(fn) / mud.on_disconnect(fn) 函數 - 註冊連線事件處理函數（傳入 nil 取消註冊）
+            for event in [ConnectEvent::Connected, ConnectEvent::Disconnected] {
+                let register_fn = scope.create_function(move |lua, handler: Option<mlua::Function>| {
+                    lua.globals().set(event.hook_name(), handler)?;
+                    Ok(())
+                })?;
+                let name = match event {
+                    ConnectEvent::Connected => "on_connect",
+                    ConnectEvent::Disconnected => "on_disconnect",
+                };
+                mud.set(name, register_fn)?;
+            }
+
+            // mud.alert(text, level) 函數 - 送到重要事件視窗（level: info/warn/alert，預設 info）
+            let alert_fn = scope.create_function(|lua, (text, level): (String, Option<String>)| {
+                let severity = match level.as_deref() {
+                    None => Severity::Info,
+                    Some(name) => Severity::from_name(name)
+                        .ok_or_else(|| mlua::Error::RuntimeError(format!("未知的嚴重程度: {}", name)))?,
+                };
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                let alerts: mlua::Table = mud.get("_alerts")?;
+                let pair = lua.create_table()?;
+                pair.set(1, text)?;
+                pair.set(2, severity.name())?;
+                alerts.set(alerts.len()? + 1, pair)?;
+                Ok(())
+            })?;
+            mud.set("alert", alert_fn)?;
+
             // mud.start_log(path)
             let start_log_fn = scope.create_function(|lua, path: String| {
                 let mud: mlua::Table = lua.globals().get("mud")?;
@@ -285,18 +481,98 @@ impl ScriptEngine {
             })?;
             mud.set("stop_log", stop_log_fn)?;
             
-            // mud.timer(seconds, code) 函數 - 延遲執行
-            let timer_fn = scope.create_function(|lua, (seconds, lua_code): (f64, String)| {
+            // mud.timer(seconds, code, context) 函數 - 延遲執行
+            // context 表的內容會在計時器觸發時設為全域變數（數字與布林值轉為字串）
+            let timer_fn = scope.create_function(|lua, (seconds, lua_code, vars): (f64, String, Option<mlua::Table>)| {
                 let mud: mlua::Table = lua.globals().get("mud")?;
                 let timers: mlua::Table = mud.get("timers")?;
                 let len = timers.len()? + 1;
                 let pair = lua.create_table()?;
                 pair.set(1, (seconds * 1000.0) as u64)?; // 轉換為毫秒
                 pair.set(2, lua_code)?;
+                if let Some(vars) = vars {
+                    let converted = lua.create_table()?;
+                    for entry in vars.pairs::<String, mlua::Value>() {
+                        let (key, value) = entry?;
+                        let text = match value {
+                            mlua::Value::String(s) => s.to_str()?.to_string(),
+                            mlua::Value::Integer(n) => n.to_string(),
+                            mlua::Value::Number(n) => n.to_string(),
+                            mlua::Value::Boolean(b) => b.to_string(),
+                            other => {
+                                return Err(mlua::Error::RuntimeError(format!(
+                                    "mud.timer 的 context 只能包含字串、數字或布林值（{} 為 {}）",
+                                    key,
+                                    other.type_name()
+                                )))
+                            }
+                        };
+                        converted.set(key, text)?;
+                    }
+                    pair.set(3, converted)?;
+                }
                 timers.set(len, pair)?;
                 Ok(())
             })?;
             mud.set("timer", timer_fn)?;
+
+            // mud.disconnect() 函數 - 主動斷線
+            let disconnect_fn = scope.create_function(|lua, ()| {
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                mud.set("_disconnect", true)?;
+                Ok(())
+            })?;
+            mud.set("disconnect", disconnect_fn)?;
+
+            // mud.disconnect_after(seconds) 函數 - 透過計時器延遲斷線
+            // 計時器存放於 Session 而非 Lua 狀態，因此不受引擎重複執行影響
+            let disconnect_after_fn = scope.create_function(|lua, seconds: f64| {
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                let timers: mlua::Table = mud.get("timers")?;
+                let len = timers.len()? + 1;
+                let pair = lua.create_table()?;
+                pair.set(1, (seconds * 1000.0) as u64)?;
+                pair.set(2, "mud.disconnect()")?;
+                timers.set(len, pair)?;
+                Ok(())
+            })?;
+            mud.set("disconnect_after", disconnect_after_fn)?;
+
+            // mud.highlight(pattern, color) 函數 - 註冊持續生效的高亮
+            let highlight_fn = scope.create_function(|lua, (pattern, color): (String, String)| {
+                if Color::parse(&color).is_none() {
+                    return Err(mlua::Error::RuntimeError(format!("未知的顏色: {}", color)));
+                }
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                let highlights: mlua::Table = mud.get("_highlights")?;
+                let len = highlights.len()? + 1;
+                let pair = lua.create_table()?;
+                pair.set(1, pattern)?;
+                pair.set(2, color)?;
+                highlights.set(len, pair)?;
+                Ok(())
+            })?;
+            mud.set("highlight", highlight_fn)?;
+
+            // mud.clear_highlights() 函數 - 清除所有高亮（含本次稍早註冊的）
+            let clear_highlights_fn = scope.create_function(|lua, ()| {
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                mud.set("_highlights", lua.create_table()?)?;
+                mud.set("_clear_highlights", true)?;
+                Ok(())
+            })?;
+            mud.set("clear_highlights", clear_highlights_fn)?;
+
+            // mud.set_encoding(name) 函數 - 連線中切換文字編碼
+            let set_encoding_fn = scope.create_function(|lua, name: String| {
+                let Some(encoding) = Encoding::from_name(&name) else {
+                    return Err(mlua::Error::RuntimeError(format!("未知的編碼: {}", name)));
+                };
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                mud.set("_encoding", encoding.name())?;
+                Ok(())
+            })?;
+            mud.set("set_encoding", set_encoding_fn)?;
             
             // mud.enable_trigger(name, enabled) 函數 - 啟用/禁用觸發器
             let enable_trigger_fn = scope.create_function(|lua, (name, enabled): (String, bool)| {
@@ -311,6 +587,21 @@ impl ScriptEngine {
             })?;
             mud.set("enable_trigger", enable_trigger_fn)?;
 
+            // mud.temp_trigger(pattern, code) 函數 - 註冊觸發一次後自動移除的觸發器
+            let temp_trigger_fn = scope.create_function(|lua, (pattern, code): (String, String)| {
+                if let Err(e) = regex::Regex::new(&pattern) {
+                    return Err(mlua::Error::RuntimeError(format!("無效的正則: {}", e)));
+                }
+                let mud: mlua::Table = lua.globals().get("mud")?;
+                let temp_triggers: mlua::Table = mud.get("_temp_triggers")?;
+                let pair = lua.create_table()?;
+                pair.set(1, pattern)?;
+                pair.set(2, code)?;
+                temp_triggers.set(temp_triggers.len()? + 1, pair)?;
+                Ok(())
+            })?;
+            mud.set("temp_trigger", temp_trigger_fn)?;
+
             // mud.get_room_id(name, desc, exits) -> string
             let get_room_id_fn = scope.create_function(|_lua, (name, desc, exits): (String, String, Vec<String>)| {
                 let room = crate::map::Room::new(&name, &desc, exits);
@@ -337,6 +628,11 @@ impl ScriptEngine {
             }
             self.lua.globals().set("captures", captures_table)?;
 
+            // 設置額外注入的全域變數
+            for (key, value) in globals {
+                self.lua.globals().set(key.as_str(), value.as_str())?;
+            }
+
             // 覆寫 dofile：支援從 scripts_dir 查找腳本
             if let Some(dir) = &self.scripts_dir {
                 self.lua.globals().set("__scripts_dir", dir.as_str())?;
@@ -383,6 +679,28 @@ impl ScriptEngine {
             
             // 收集 gag 狀態
             context.gag = mud.get::<bool>("gag").unwrap_or(false);
+
+            // 收集斷線要求
+            context.disconnect = mud.get::<bool>("_disconnect").unwrap_or(false);
+
+            // 收集編碼切換
+            context.encoding = mud
+                .get::<Option<String>>("_encoding")
+                .ok()
+                .flatten()
+                .and_then(|name| Encoding::from_name(&name));
+
+            // 收集高亮註冊
+            context.clear_highlights = mud.get::<bool>("_clear_highlights").unwrap_or(false);
+            if let Ok(highlights) = mud.get::<mlua::Table>("_highlights") {
+                for tbl in highlights.sequence_values::<mlua::Table>().flatten() {
+                    if let (Ok(pattern), Ok(color)) = (tbl.get::<String>(1), tbl.get::<String>(2)) {
+                        if let Some(color) = Color::parse(&color) {
+                            context.highlights.push((pattern, color));
+                        }
+                    }
+                }
+            }
             
             // 收集 commands
             if let Ok(cmds) = mud.get::<mlua::Table>("commands") {
@@ -433,6 +751,32 @@ impl ScriptEngine {
                 }
             }
 
+            // 收集筆記
+            if let Ok(notes) = mud.get::<mlua::Table>("_notes") {
+                for note in notes.sequence_values::<String>().flatten() {
+                    context.notes.push(note);
+                }
+            }
+
+            // 收集重要事件
+            if let Ok(alerts) = mud.get::<mlua::Table>("_alerts") {
+                for pair in alerts.sequence_values::<mlua::Table>().flatten() {
+                    if let (Ok(text), Ok(level)) = (pair.get::<String>(1), pair.get::<String>(2)) {
+                        let severity = Severity::from_name(&level).unwrap_or_default();
+                        context.alerts.push((text, severity));
+                    }
+                }
+            }
+
+            // 收集一次性觸發器
+            if let Ok(temp_triggers) = mud.get::<mlua::Table>("_temp_triggers") {
+                for pair in temp_triggers.sequence_values::<mlua::Table>().flatten() {
+                    if let (Ok(pattern), Ok(code)) = (pair.get::<String>(1), pair.get::<String>(2)) {
+                        context.temp_triggers.push((pattern, code));
+                    }
+                }
+            }
+
             // 收集 log_control
             if let Ok(ctrl) = mud.get::<mlua::Table>("_log_control") {
                 if let Ok(action) = ctrl.get::<String>("action") {
@@ -451,8 +795,11 @@ impl ScriptEngine {
                 for pair in timers.pairs::<i64, mlua::Table>() {
                     if let Ok((_, tbl)) = pair {
                         if let (Ok(delay_ms), Ok(code)) = (tbl.get::<u64>(1), tbl.get::<String>(2)) {
+                            let vars = tbl.get::<Option<mlua::Table>>(3).ok().flatten().map(|vars| {
+                                vars.pairs::<String, String>().flatten().collect::<HashMap<_, _>>()
+                            });
                             // 計時器觸發永遠不被視為回顯
-                            context.timers.push((delay_ms, code));
+                            context.timers.push((delay_ms, code, vars));
                         }
                     }
                 }
@@ -470,7 +817,12 @@ impl ScriptEngine {
             }
             
             Ok::<_, mlua::Error>(())
-        })?;
+        });
+
+        for (key, value) in shadowed {
+            lua_globals.set(key, value)?;
+        }
+        result?;
 
         Ok(context)
     }
@@ -500,7 +852,40 @@ impl ScriptEngine {
         let adapter_code = format!("if _G['{0}'] then _G['{0}'](message, clean_message) end", hook_name);
         // 注意：這裡我們依賴 execute_inline 將 message 注入到全局
         
-        self.run_code(&adapter_code, arg, clean_arg, &[], false).map(Some)
+        self.run_code(&adapter_code, arg, clean_arg, &[], false, &HashMap::new()).map(Some)
+    }
+
+    /// 執行連線事件的處理函數；未註冊時回傳 `None`
+    pub fn run_connect_hook(&self, event: ConnectEvent) -> Result<Option<MudContext>, ScriptError> {
+        self.invoke_hook(event.hook_name(), "", "")
+    }
+
+    /// 是否已以 `mud.on_send` 註冊送出鉤子
+    pub fn has_send_hook(&self) -> bool {
+        self.lua.globals().contains_key(SEND_HOOK).unwrap_or(false)
+    }
+
+    /// 以 `mud.on_send` 註冊的鉤子處理即將送出的指令
+    ///
+    /// 未註冊時回傳 `None`；否則回傳改寫後的指令（鉤子回傳 `nil`/`false` 時為 `None`，表示取消送出；
+    /// 回傳 `true` 時維持原指令）與鉤子執行期間產生的動作。
+    pub fn run_send_hook(&self, command: &str) -> Result<Option<(Option<String>, MudContext)>, ScriptError> {
+        if !self.has_send_hook() {
+            return Ok(None);
+        }
+        let adapter_code = format!(
+            "local r = {hook}(message)\n\
+             if r == nil or r == false then {result} = nil \
+             elseif r == true then {result} = message \
+             else {result} = tostring(r) end",
+            hook = SEND_HOOK,
+            result = SEND_HOOK_RESULT,
+        );
+        let context = self.run_code(&adapter_code, command, command, &[], false, &HashMap::new())?;
+        let globals = self.lua.globals();
+        let rewritten: Option<String> = globals.get(SEND_HOOK_RESULT)?;
+        globals.set(SEND_HOOK_RESULT, mlua::Value::Nil)?;
+        Ok(Some((rewritten, context)))
     }
 }
 
@@ -520,6 +905,32 @@ mod tests {
         assert!(engine.validate("local x = 1").is_ok());
     }
 
+    #[test]
+    fn test_dry_run_discards_hooks_and_globals() {
+        let engine = ScriptEngine::new();
+        engine.execute_inline("kept = 1", "", &[], false).unwrap();
+
+        engine
+            .dry_run(
+                r#"
+mud.on_send(function(cmd) return "x" end)
+mud.on_connect(function() mud.send("hi") end)
+kept = 2
+leaked = true
+"#,
+                "",
+                &[],
+            )
+            .unwrap();
+
+        assert!(!engine.has_send_hook());
+        assert!(engine.run_connect_hook(ConnectEvent::Connected).unwrap().is_none());
+        let ctx = engine
+            .execute_inline(r#"mud.send(tostring(kept) .. tostring(leaked))"#, "", &[], false)
+            .unwrap();
+        assert_eq!(ctx.commands, vec!["1nil"]);
+    }
+
     #[test]
     fn test_script_gag() {
         let engine = ScriptEngine::new();
@@ -539,6 +950,165 @@ end
         assert!(result.gag);
     }
 
+    #[test]
+    fn test_highlight_registration() {
+        let engine = ScriptEngine::new();
+        let result = engine
+            .execute_inline(r##"mud.highlight("\\bOrc\\b", "red"); mud.highlight("gold", "#ffd700")"##, "", &[], false)
+            .unwrap();
+        assert!(!result.clear_highlights);
+        assert_eq!(
+            result.highlights,
+            vec![
+                (r"\bOrc\b".to_string(), Color::rgb(255, 85, 85)),
+                ("gold".to_string(), Color::rgb(255, 215, 0)),
+            ]
+        );
+
+        // 清除會一併捨棄同次稍早的註冊
+        let result = engine
+            .execute_inline(r#"mud.highlight("x", "blue"); mud.clear_highlights(); mud.highlight("y", "green")"#, "", &[], false)
+            .unwrap();
+        assert!(result.clear_highlights);
+        assert_eq!(result.highlights, vec![("y".to_string(), Color::rgb(85, 255, 85))]);
+
+        assert!(engine.execute_inline(r#"mud.highlight("x", "nope")"#, "", &[], false).is_err());
+    }
+
+    #[test]
+    fn test_set_encoding() {
+        let engine = ScriptEngine::new();
+        let result = engine.execute_inline(r#"mud.set_encoding("utf8")"#, "", &[], false).unwrap();
+        assert_eq!(result.encoding, Some(Encoding::Utf8));
+
+        let result = engine.execute_inline("", "", &[], false).unwrap();
+        assert_eq!(result.encoding, None);
+        assert!(engine.execute_inline(r#"mud.set_encoding("latin1")"#, "", &[], false).is_err());
+    }
+
+    #[test]
+    fn test_temp_trigger() {
+        let engine = ScriptEngine::new();
+        let result = engine
+            .execute_inline(r#"mud.temp_trigger("守衛點了點頭", "mud.send('north')")"#, "", &[], false)
+            .unwrap();
+        assert_eq!(
+            result.temp_triggers,
+            vec![("守衛點了點頭".to_string(), "mud.send('north')".to_string())]
+        );
+        assert!(engine.execute_inline(r#"mud.temp_trigger("(", "")"#, "", &[], false).is_err());
+    }
+
+    #[test]
+    fn test_note() {
+        let engine = ScriptEngine::new();
+        let captures = vec!["龍之牙".to_string()];
+        let result = engine
+            .execute_inline(r#"mud.note("取得 " .. captures[1]); mud.note("完成")"#, "", &captures, false)
+            .unwrap();
+        assert_eq!(result.notes, vec!["取得 龍之牙", "完成"]);
+    }
+
+    #[test]
+    fn test_connect_hooks() {
+        let engine = ScriptEngine::new();
+        assert!(engine.run_connect_hook(ConnectEvent::Connected).unwrap().is_none());
+
+        engine
+            .execute_inline(
+                r#"mud.on_connect(function() mud.send("score"); mud.send("inventory") end)
+                   mud.on_disconnect(function() mud.variables.online = "no" end)"#,
+                "",
+                &[],
+                false,
+            )
+            .unwrap();
+
+        let context = engine.run_connect_hook(ConnectEvent::Connected).unwrap().unwrap();
+        assert_eq!(context.commands, vec!["score", "inventory"]);
+        let context = engine.run_connect_hook(ConnectEvent::Disconnected).unwrap().unwrap();
+        assert_eq!(context.variables.get("online").map(String::as_str), Some("no"));
+    }
+
+    #[test]
+    fn test_send_hook() {
+        let engine = ScriptEngine::new();
+        assert!(engine.run_send_hook("say hi").unwrap().is_none());
+
+        engine
+            .execute_inline(
+                r#"mud.on_send(function(cmd)
+                    if cmd == "quit" then return nil end
+                    if cmd:match("^say ") then mud.echo("改寫"); return cmd:upper() end
+                    return true
+                end)"#,
+                "",
+                &[],
+                false,
+            )
+            .unwrap();
+        assert!(engine.has_send_hook());
+
+        let (command, context) = engine.run_send_hook("say hi").unwrap().unwrap();
+        assert_eq!(command.as_deref(), Some("SAY HI"));
+        assert_eq!(context.echos, vec!["改寫"]);
+        assert_eq!(engine.run_send_hook("look").unwrap().unwrap().0.as_deref(), Some("look"));
+        assert_eq!(engine.run_send_hook("quit").unwrap().unwrap().0, None);
+
+        engine.execute_inline("mud.on_send(nil)", "", &[], false).unwrap();
+        assert!(!engine.has_send_hook());
+    }
+
+    #[test]
+    fn test_alert() {
+        let engine = ScriptEngine::new();
+        let result = engine
+            .execute_inline(r#"mud.alert("有人密你"); mud.alert("HP 過低", "ALERT")"#, "", &[], false)
+            .unwrap();
+        assert_eq!(
+            result.alerts,
+            vec![("有人密你".to_string(), Severity::Info), ("HP 過低".to_string(), Severity::Alert)]
+        );
+        assert!(engine.execute_inline(r#"mud.alert("x", "loud")"#, "", &[], false).is_err());
+    }
+
+    #[test]
+    fn test_timer_context_values_and_globals_cleanup() {
+        let engine = ScriptEngine::new();
+        let result = engine
+            .execute_inline(r#"mud.timer(1, "x", { target = "orc", count = 3, ready = true })"#, "", &[], false)
+            .unwrap();
+        let vars = result.timers[0].2.clone().unwrap();
+        assert_eq!(vars.get("target").map(String::as_str), Some("orc"));
+        assert_eq!(vars.get("count").map(String::as_str), Some("3"));
+        assert_eq!(vars.get("ready").map(String::as_str), Some("true"));
+
+        // 無法轉為字串的值回報錯誤
+        assert!(engine.execute_inline(r#"mud.timer(1, "x", { t = {} })"#, "", &[], false).is_err());
+
+        // 注入的全域變數只在該次執行有效
+        let globals = HashMap::from([("target".to_string(), "orc".to_string())]);
+        let result = engine.execute_inline_with_globals("mud.send(target)", "", &[], false, &globals).unwrap();
+        assert_eq!(result.commands, vec!["orc"]);
+        let result = engine.execute_inline("mud.send(tostring(target))", "", &[], false).unwrap();
+        assert_eq!(result.commands, vec!["nil"]);
+    }
+
+    #[test]
+    fn test_disconnect_after_registers_timer() {
+        let engine = ScriptEngine::new();
+        let result = engine
+            .execute_inline("mud.disconnect_after(5)", "", &[], false)
+            .unwrap();
+
+        assert!(!result.disconnect);
+        assert_eq!(result.timers, vec![(5000, "mud.disconnect()".to_string(), None)]);
+
+        let (_, code, _) = &result.timers[0];
+        let fired = engine.execute_inline(code, "TIMER_EXPIRED", &[], false).unwrap();
+        assert!(fired.disconnect);
+    }
+
     #[test]
     fn test_script_validation() {
         let engine = ScriptEngine::new();
@@ -549,4 +1119,37 @@ end
         // 無效語法
         assert!(engine.validate("function broken(").is_err());
     }
+
+    #[test]
+    fn test_increment_variable() {
+        let engine = ScriptEngine::new();
+        assert_eq!(engine.increment_variable("kills", 1), 1);
+        assert_eq!(engine.increment_variable("kills", 2), 3);
+
+        engine.set_variable("gold", "abc");
+        assert_eq!(engine.increment_variable("gold", 5), 5);
+        assert_eq!(engine.get_variable("kills").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_variables_snapshot_and_edit() {
+        let engine = ScriptEngine::new();
+        engine.set_variable("target", "rat");
+        engine.set_variable("hp", "100");
+        assert_eq!(
+            engine.variables(),
+            vec![("hp".to_string(), "100".to_string()), ("target".to_string(), "rat".to_string())]
+        );
+
+        // 檢視器編輯後寫回引擎
+        for (name, value) in engine.variables() {
+            if name == "target" {
+                engine.set_variable(&name, &value.replace("rat", "kobold"));
+            }
+        }
+        assert!(engine.remove_variable("hp"));
+        assert!(!engine.remove_variable("hp"));
+        assert_eq!(engine.variables(), vec![("target".to_string(), "kobold".to_string())]);
+        assert_eq!(engine.expand_variables("kill $target"), "kill kobold");
+    }
 }
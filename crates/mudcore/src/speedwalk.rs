@@ -1,5 +1,16 @@
 use regex::Regex;
 use std::sync::OnceLock;
+use thiserror::Error;
+
+/// Speedwalk 解析錯誤
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SpeedwalkError {
+    #[error("Speedwalk 必須以 / 開頭")]
+    MissingPrefix,
+
+    #[error("第 {position} 個字元無法解析為方向: {token}")]
+    InvalidToken { position: usize, token: String },
+}
 
 /// 解析 Speedwalk路徑字串
 ///
@@ -11,20 +22,22 @@ use std::sync::OnceLock;
 ///    - 數字 (可選) + 方向 (n, s, e, w, ne, nw, se, sw, u, d)
 ///    - 方向不區分大小寫
 ///
-/// 如果解析失敗或格式不符，回傳 None
+/// 如果解析失敗或格式不符，回傳 None；需要錯誤細節時請用 [`try_parse_speedwalk`]
 pub fn parse_speedwalk(input: &str) -> Option<Vec<String>> {
+    try_parse_speedwalk(input).ok()
+}
+
+/// 解析 Speedwalk路徑字串，失敗時回報無效的位置與片段
+///
+/// 位置以字元計算（從 1 開始），片段為該位置起到下一個空白前的內容。
+pub fn try_parse_speedwalk(input: &str) -> Result<Vec<String>, SpeedwalkError> {
     // 必須以 '/' 開頭
-    if !input.starts_with('/') {
-        return None;
-    }
+    let Some(remainder) = input.strip_prefix('/') else {
+        return Err(SpeedwalkError::MissingPrefix);
+    };
 
     // 移除開頭的 '/'，並加入 recall 指令
     let mut commands = vec!["recall".to_string()];
-    let remainder = &input[1..];
-
-    if remainder.is_empty() {
-        return Some(commands);
-    }
 
     // 正則表達式：匹配 (數字)? (方向)
     // 方向包含: n, s, e, w, ne, nw, se, sw, u, d
@@ -56,24 +69,29 @@ pub fn parse_speedwalk(input: &str) -> Option<Vec<String>> {
             // 前進
             parsing_slice = &parsing_slice[full_match.end()..];
         } else {
-            // 如果遇到無法解析的字元，視為無效路徑？
-            // 還是忽略？ 為求嚴謹，若中間有垃圾字元，視為解析失敗可能較好，避免誤操作
-            // 但如果使用者輸入 `/3w 2n` (有空格)，是否該支援？
-            // 目前 regex 沒處理空格。
-            // 嘗試跳過空格
+            // 允許以空格分隔（如 `/3w 2n`）
             if let Some(first_char) = parsing_slice.chars().next() {
                  if first_char.is_whitespace() {
-                     parsing_slice = &parsing_slice[1..];
+                     parsing_slice = &parsing_slice[first_char.len_utf8()..];
                      continue;
                  }
             }
-            
-            // 遇到非空格且無法匹配方向的字元 -> 解析失敗
-            return None;
+
+            // 遇到非空格且無法匹配方向的字元 -> 解析失敗，避免誤操作
+            let offset = input.len() - parsing_slice.len();
+            let token = parsing_slice
+                .split(char::is_whitespace)
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            return Err(SpeedwalkError::InvalidToken {
+                position: input[..offset].chars().count() + 1,
+                token,
+            });
         }
     }
 
-    Some(commands)
+    Ok(commands)
 }
 
 #[cfg(test)]
@@ -159,4 +177,28 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_try_parse_reports_invalid_token() {
+        assert_eq!(try_parse_speedwalk("3w"), Err(SpeedwalkError::MissingPrefix));
+
+        let err = try_parse_speedwalk("/3w 2x4n").unwrap_err();
+        assert_eq!(
+            err,
+            SpeedwalkError::InvalidToken { position: 5, token: "2x4n".to_string() }
+        );
+        assert_eq!(err.to_string(), "第 5 個字元無法解析為方向: 2x4n");
+    }
+
+    #[test]
+    fn test_try_parse_expands_valid_path() {
+        assert_eq!(
+            try_parse_speedwalk("/2ne u"),
+            Ok(vec![
+                "recall".to_string(),
+                "ne".to_string(), "ne".to_string(),
+                "u".to_string()
+            ])
+        );
+    }
 }
@@ -2,10 +2,13 @@
 //!
 //! 非同步 Telnet 連線管理
 
+use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
 use std::time::Duration;
 
+use flate2::{Decompress, FlushDecompress, Status};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -13,8 +16,18 @@ use tokio::sync::mpsc;
 use tokio::time::timeout;
 use tracing::{debug, error, info, warn};
 
-use super::protocol::{generate_refusal, parse_telnet_data, TelnetEvent};
-use crate::encoding::encode_big5;
+use super::gmcp::{self, GmcpMessage};
+use super::protocol::{
+    encode_naws, parse_telnet_data, NegotiationPolicy, NegotiationTable, OptionState, OptionStatus, TelnetCommand, TelnetEvent,
+    TelnetOption, TerminalTypeCycle, IAC, MTTS_256_COLORS, MTTS_ANSI, MTTS_UTF8, TTYPE_SEND,
+};
+use crate::encoding::Encoding;
+
+/// 用於編碼偵測的取樣位元組上限
+pub const ENCODING_SAMPLE_SIZE: usize = 2048;
+
+/// OSC 序列的最大長度，超過仍未結束即丟棄
+const MAX_OSC_LEN: usize = 1024;
 
 /// Telnet 客戶端錯誤
 #[derive(Debug, Error)]
@@ -40,6 +53,29 @@ pub enum ConnectionState {
     Connected,
 }
 
+/// 送出指令時附加的換行字元
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineEnding {
+    /// `\r\n`（Telnet 標準）
+    #[default]
+    Crlf,
+    /// `\n`
+    Lf,
+    /// `\r`
+    Cr,
+}
+
+impl LineEnding {
+    /// 換行字元的位元組
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            LineEnding::Crlf => b"\r\n",
+            LineEnding::Lf => b"\n",
+            LineEnding::Cr => b"\r",
+        }
+    }
+}
+
 /// Telnet 客戶端配置
 #[derive(Debug, Clone)]
 pub struct TelnetConfig {
@@ -47,6 +83,14 @@ pub struct TelnetConfig {
     pub connect_timeout: Duration,
     /// 讀取緩衝區大小
     pub read_buffer_size: usize,
+    /// 送出指令時附加的換行字元
+    pub line_ending: LineEnding,
+    /// 連線建立後（自動登入前）立即送出的字串；`None` 或空字串時不送
+    pub connect_send: Option<String>,
+    /// 伺服器詢問終端機類型（TTYPE）時回報的客戶端名稱
+    pub client_name: String,
+    /// 伺服器文字編碼
+    pub encoding: Encoding,
 }
 
 impl Default for TelnetConfig {
@@ -54,6 +98,10 @@ impl Default for TelnetConfig {
         Self {
             connect_timeout: Duration::from_secs(30),
             read_buffer_size: 8192,
+            line_ending: LineEnding::default(),
+            connect_send: None,
+            client_name: "MudClient-rs".to_string(),
+            encoding: Encoding::default(),
         }
     }
 }
@@ -71,13 +119,46 @@ pub struct TelnetClient {
     pending_ansi: Vec<(String, usize)>,
     /// 暫存尚未完整的 ANSI 序列 (以 ESC \x1b 開頭)
     ansi_buffer: Vec<u8>,
+    /// 伺服器透過 OSC 設定、尚未被取走的視窗標題
+    pending_title: Option<String>,
+    /// 上次取走後是否收到 GA / EOR
+    prompt_ended: bool,
+    /// 本次連線是否收到過 EOR（SGA 啟用時之後的 GA 視為重複）
+    eor_seen: bool,
+    /// 上次取走後是否收到文字中的 BEL (0x07)
+    bell_received: bool,
+    /// 伺服器是否已啟用 GMCP
+    gmcp_enabled: bool,
+    /// 尚未被取走的 GMCP 訊息
+    pending_gmcp: Vec<GmcpMessage>,
     /// Big5 解碼器（保留用於相容，但已切換為手動狀態機處理）
     _decoder: encoding_rs::Decoder,
+    /// 目前使用的文字編碼
+    encoding: Encoding,
+    /// 連線初期收到的文字位元組取樣（供編碼偵測）
+    encoding_sample: Vec<u8>,
+    /// 選項協商狀態
+    option_state: OptionState,
+    /// 選項協商策略表
+    negotiation: NegotiationTable,
+    /// 各選項的協商結果（供診斷顯示）
+    option_statuses: HashMap<TelnetOption, OptionStatus>,
+    /// 上次取走後協商結果是否有變化
+    options_changed: bool,
+    /// MCCP2 解壓縮器（伺服器開始壓縮後才建立，跨讀取保留狀態）
+    decompressor: Option<Decompress>,
+    /// 終端機類型回報循環
+    ttype: TerminalTypeCycle,
+    /// 目前的視窗大小（欄數, 列數），供 NAWS 回報
+    window_size: Option<(u16, u16)>,
+    /// 伺服器是否已要求 NAWS（DO NAWS）且我方同意
+    naws_active: bool,
 }
 
 impl TelnetClient {
     /// 創建新的 Telnet 客戶端
     pub fn new(config: TelnetConfig) -> Self {
+        let encoding = config.encoding;
         Self {
             stream: None,
             config,
@@ -86,10 +167,127 @@ impl TelnetClient {
             text_buffer: Vec::new(),
             pending_ansi: Vec::new(),
             ansi_buffer: Vec::new(),
+            pending_title: None,
+            prompt_ended: false,
+            eor_seen: false,
+            bell_received: false,
+            gmcp_enabled: false,
+            pending_gmcp: Vec::new(),
             _decoder: encoding_rs::BIG5.new_decoder(),
+            encoding,
+            encoding_sample: Vec::new(),
+            option_state: OptionState::default(),
+            negotiation: NegotiationTable::default(),
+            option_statuses: HashMap::new(),
+            options_changed: false,
+            decompressor: None,
+            ttype: TerminalTypeCycle::default(),
+            window_size: None,
+            naws_active: false,
         }
     }
 
+    /// 取得目前使用的文字編碼
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// 切換文字編碼
+    ///
+    /// 尚未完成的多位元組字元會先以舊編碼送出，回傳送出的文字與寬度；
+    /// 之後收到的位元組改以新編碼解碼。
+    pub fn set_encoding(&mut self, encoding: Encoding) -> (String, Vec<u8>) {
+        let flushed = self.flush_partial_char();
+        self.encoding = encoding;
+        flushed
+    }
+
+    /// 以目前編碼送出緩衝中的不完整字元與夾在其中的 ANSI 序列
+    fn flush_partial_char(&mut self) -> (String, Vec<u8>) {
+        let mut output = String::new();
+        let mut widths = Vec::new();
+        if !self.text_buffer.is_empty() {
+            for ch in self.encoding.decode(&self.text_buffer).chars() {
+                output.push(ch);
+                widths.push(if ch.is_ascii() { 1 } else { 2 });
+            }
+            self.text_buffer.clear();
+        }
+        for (s, _) in self.pending_ansi.drain(..) {
+            for ch in s.chars() {
+                output.push(ch);
+                widths.push(0);
+            }
+        }
+        (output, widths)
+    }
+
+    /// 取得連線初期的位元組取樣
+    pub fn encoding_sample(&self) -> &[u8] {
+        &self.encoding_sample
+    }
+
+    /// 取樣是否足以猜測編碼
+    ///
+    /// 取樣已滿 [`ENCODING_SAMPLE_SIZE`] 即可；`banner_done`（收到 GA/EOR 或連線後閒置）時，
+    /// 只要取樣含非 ASCII 位元組也可提前猜測。純 ASCII 的 banner 無從判斷，繼續等待。
+    pub fn encoding_sample_ready(&self, banner_done: bool) -> bool {
+        self.encoding_sample.len() >= ENCODING_SAMPLE_SIZE
+            || (banner_done && self.encoding_sample.iter().any(|&b| b >= 0x80))
+    }
+
+    /// 取走伺服器最近一次以 OSC (`ESC ] 0 ; 標題 BEL`) 設定的視窗標題
+    pub fn take_title(&mut self) -> Option<String> {
+        self.pending_title.take()
+    }
+
+    /// 取走伺服器送來的 GMCP 訊息（伺服器不支援 GMCP 時永遠為空）
+    pub fn take_gmcp(&mut self) -> Vec<GmcpMessage> {
+        std::mem::take(&mut self.pending_gmcp)
+    }
+
+    /// 取走「已收到提示符結尾 (GA / EOR)」旗標
+    pub fn take_prompt_end(&mut self) -> bool {
+        std::mem::take(&mut self.prompt_ended)
+    }
+
+    /// 取走「已收到 BEL 提示音」旗標（OSC 結尾的 BEL 不算）
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell_received)
+    }
+
+    /// 伺服器是否已同意不送 GA（SGA 已啟用）
+    pub fn suppress_go_ahead(&self) -> bool {
+        self.option_state.remote_sga
+    }
+
+    /// 伺服器送來的資料目前是否經 MCCP2 壓縮
+    pub fn compression_enabled(&self) -> bool {
+        self.decompressor.is_some()
+    }
+
+    /// 伺服器是否負責回顯（WILL ECHO）
+    pub fn server_echo(&self) -> bool {
+        self.option_state.remote_echo
+    }
+
+    /// 各選項目前的協商結果，依選項編號排序
+    pub fn option_states(&self) -> Vec<(TelnetOption, OptionStatus)> {
+        let mut states: Vec<_> = self.option_statuses.iter().map(|(o, s)| (*o, *s)).collect();
+        states.sort_by_key(|(option, _)| option.as_byte());
+        states
+    }
+
+    /// 取走「協商結果有變化」旗標
+    pub fn take_options_changed(&mut self) -> bool {
+        std::mem::take(&mut self.options_changed)
+    }
+
+    /// 取得選項協商策略表（可於連線前調整）
+    pub fn negotiation_mut(&mut self) -> &mut NegotiationTable {
+        &mut self.negotiation
+    }
+
     /// 獲取連線狀態
     pub fn state(&self) -> ConnectionState {
         self.state
@@ -135,6 +333,19 @@ impl TelnetClient {
         info!("已連線到 {}:{}", host, port);
         self.stream = Some(stream);
         self.state = ConnectionState::Connected;
+        self.option_state = OptionState::default();
+        self.eor_seen = false;
+        self.option_statuses.clear();
+        self.options_changed = true;
+        self.gmcp_enabled = false;
+        self.pending_gmcp.clear();
+        self.decompressor = None;
+        self.ttype = TerminalTypeCycle::default();
+        self.naws_active = false;
+
+        if let Some(text) = self.config.connect_send.clone().filter(|t| !t.is_empty()) {
+            self.send(&text).await?;
+        }
 
         Ok(())
     }
@@ -148,13 +359,11 @@ impl TelnetClient {
         info!("已斷開連線");
     }
 
-    /// 發送文字到伺服器（會自動編碼為 Big5 並加上 CRLF）
+    /// 發送文字到伺服器（會依目前編碼轉換並加上 CRLF）
     pub async fn send(&mut self, text: &str) -> Result<(), TelnetError> {
+        let data = self.encode_line(text);
         let stream = self.stream.as_mut().ok_or(TelnetError::NotConnected)?;
 
-        let mut data = encode_big5(text);
-        data.extend_from_slice(b"\r\n");
-
         stream.write_all(&data).await?;
         stream.flush().await?;
 
@@ -162,6 +371,25 @@ impl TelnetClient {
         Ok(())
     }
 
+    /// 將一行指令編碼為要送出的位元組（含設定的換行字元）
+    pub fn encode_line(&self, text: &str) -> Vec<u8> {
+        let mut data = self.encoding.encode(text);
+        data.extend_from_slice(self.config.line_ending.as_bytes());
+        data
+    }
+
+    /// 設定視窗大小（欄數, 列數）；大小改變且伺服器已同意 NAWS 時立即送出
+    pub async fn set_window_size(&mut self, cols: u16, rows: u16) -> Result<(), TelnetError> {
+        if self.window_size == Some((cols, rows)) {
+            return Ok(());
+        }
+        self.window_size = Some((cols, rows));
+        if self.naws_active && self.stream.is_some() {
+            self.send_raw(&encode_naws(cols, rows)).await?;
+        }
+        Ok(())
+    }
+
     /// 發送原始位元組到伺服器
     pub async fn send_raw(&mut self, data: &[u8]) -> Result<(), TelnetError> {
         let stream = self.stream.as_mut().ok_or(TelnetError::NotConnected)?;
@@ -184,27 +412,153 @@ impl TelnetClient {
             return Err(TelnetError::NotConnected);
         }
 
-        self.raw_buffer.extend_from_slice(&buffer[..n]);
         crate::debug_log::DebugLogger::log_bytes("READ_RAW", &buffer[..n]);
+        let (text, widths, responses) = self.feed_bytes(&buffer[..n]);
+        if !responses.is_empty() {
+            let _ = self.send_raw(&responses).await;
+        }
+        Ok((text, widths))
+    }
+
+    /// 處理收到的原始位元組（不需連線，可供離線解析與測試）
+    ///
+    /// 返回 (解碼後的 UTF-8 文字, 每個字元的原始位元組寬度, 應回應給伺服器的協商位元組)；
+    /// 不完整的 Telnet 序列留待下次呼叫。伺服器啟用 MCCP2 後，資料先解壓縮再解析。
+    pub fn feed_bytes(&mut self, data: &[u8]) -> (String, Vec<u8>, Vec<u8>) {
+        let mut final_output = String::new();
+        let mut final_widths = Vec::new();
+        let mut responses = Vec::new();
+        let mut incoming = data.to_vec();
+
+        // 壓縮開始或結束時，剩下的位元組要依新的狀態重新處理
+        loop {
+            let mut remainder = Vec::new();
+            match self.decompressor.as_mut() {
+                Some(decompressor) => match inflate(decompressor, &incoming) {
+                    Ok((inflated, consumed, stream_end)) => {
+                        self.raw_buffer.extend_from_slice(&inflated);
+                        if stream_end {
+                            debug!("MCCP2 壓縮結束");
+                            self.decompressor = None;
+                            remainder = incoming[consumed..].to_vec();
+                        }
+                    }
+                    Err(e) => {
+                        warn!("MCCP2 解壓縮失敗，停止解壓縮: {}", e);
+                        self.decompressor = None;
+                    }
+                },
+                None => self.raw_buffer.extend_from_slice(&incoming),
+            }
 
-        let (text_bytes, events, consumed) = parse_telnet_data(&self.raw_buffer);
-        self.raw_buffer.drain(..consumed);
+            let (text_bytes, events, consumed) = parse_telnet_data(&self.raw_buffer);
+            self.raw_buffer.drain(..consumed);
 
-        // 處理 Telnet 事件
-        for event in events {
-            if let TelnetEvent::Command(cmd, option) = event {
-                let response = generate_refusal(cmd, option);
-                if !response.is_empty() {
-                    let _ = self.send_raw(&response).await;
+            if self.encoding_sample.len() < ENCODING_SAMPLE_SIZE {
+                let take = (ENCODING_SAMPLE_SIZE - self.encoding_sample.len()).min(text_bytes.len());
+                self.encoding_sample.extend_from_slice(&text_bytes[..take]);
+            }
+
+            // 處理 Telnet 事件
+            for event in events {
+                if let TelnetEvent::PromptEnd(marker) = event {
+                    self.note_prompt_end(marker);
+                } else if let TelnetEvent::Command(cmd, option) = event {
+                    responses.extend(self.negotiate(cmd, option));
+                } else if let TelnetEvent::Gmcp(package, data) = event {
+                    self.pending_gmcp.push(GmcpMessage { package, data });
+                } else if let TelnetEvent::Subnegotiation(option, payload) = event {
+                    if option == TelnetOption::TerminalType && payload.first() == Some(&TTYPE_SEND) {
+                        let mtts = self.mtts_flags();
+                        responses.extend(self.ttype.next_response(&self.config.client_name, mtts));
+                    } else if option == TelnetOption::Mccp2 {
+                        // 緩衝區中 SB 之後的資料皆已壓縮
+                        debug!("MCCP2 開始壓縮");
+                        self.decompressor = Some(Decompress::new(true));
+                        let mut compressed = std::mem::take(&mut self.raw_buffer);
+                        compressed.extend(remainder);
+                        remainder = compressed;
+                    } else {
+                        debug!("略過未處理的 Sub-negotiation {:?} ({} bytes)", option, payload.len());
+                    }
                 }
             }
+
+            let (output, widths) = self.process_byte_stream(&text_bytes);
+            final_output.push_str(&output);
+            final_widths.extend(widths);
+
+            if remainder.is_empty() {
+                break;
+            }
+            incoming = remainder;
+        }
+
+        (final_output, final_widths, responses)
+    }
+
+    /// 回報給伺服器的 MTTS 旗標（ANSI、256 色，使用 UTF-8 時加上 UTF-8）
+    fn mtts_flags(&self) -> u32 {
+        let utf8 = if self.encoding == Encoding::Utf8 { MTTS_UTF8 } else { 0 };
+        MTTS_ANSI | MTTS_256_COLORS | utf8
+    }
+
+    /// 處理伺服器的選項協商指令並記錄結果，回傳應送出的回應
+    /// 記錄提示符結尾；SGA 已啟用且伺服器送過 EOR 時忽略 GA，避免同一提示符處理兩次
+    fn note_prompt_end(&mut self, marker: TelnetCommand) {
+        if marker == TelnetCommand::EndOfRecord {
+            self.eor_seen = true;
+        } else if self.eor_seen && self.suppress_go_ahead() {
+            return;
+        }
+        self.prompt_ended = true;
+    }
+
+    fn negotiate(&mut self, cmd: TelnetCommand, option: TelnetOption) -> Vec<u8> {
+        let policy = self.negotiation.policy(option);
+        if let Some(status) = OptionStatus::from_negotiation(cmd, policy) {
+            if self.option_statuses.insert(option, status) != Some(status) {
+                self.options_changed = true;
+            }
         }
 
-        let (final_output, final_widths) = self.process_byte_stream(&text_bytes);
-        Ok((final_output, final_widths))
+        match (option, policy) {
+            (TelnetOption::SuppressGoAhead, NegotiationPolicy::Accept) => self.option_state.handle_sga(cmd),
+            (TelnetOption::Echo, NegotiationPolicy::Accept) => self
+                .option_state
+                .handle_echo(cmd)
+                .unwrap_or_else(|| self.negotiation.respond(cmd, option)),
+            // 第一次同意 GMCP 時一併送出握手訊息，重複的 WILL 不再回應
+            // 同意 NAWS 後立即回報目前的視窗大小，重複的 DO 只重送大小
+            (TelnetOption::Naws, NegotiationPolicy::Accept) if cmd == TelnetCommand::Do => {
+                let mut response = if self.naws_active { Vec::new() } else { self.negotiation.respond(cmd, option) };
+                self.naws_active = true;
+                if let Some((cols, rows)) = self.window_size {
+                    response.extend(encode_naws(cols, rows));
+                }
+                response
+            }
+            (TelnetOption::Naws, _) if cmd == TelnetCommand::Dont => {
+                self.naws_active = false;
+                Vec::new()
+            }
+            // MCCP2 只壓縮伺服器送來的資料，不接受由客戶端壓縮
+            (TelnetOption::Mccp2, _) if cmd == TelnetCommand::Do => vec![IAC, TelnetCommand::Wont as u8, option.as_byte()],
+            (TelnetOption::Gmcp, NegotiationPolicy::Accept) if cmd == TelnetCommand::Will => {
+                if self.gmcp_enabled {
+                    Vec::new()
+                } else {
+                    self.gmcp_enabled = true;
+                    let mut response = self.negotiation.respond(cmd, option);
+                    response.extend(gmcp::handshake());
+                    response
+                }
+            }
+            _ => self.negotiation.respond(cmd, option),
+        }
     }
 
-    /// 處理位元組流：處理 Big5 解碼與 ANSI 序列
+    /// 處理位元組流：處理多位元組字元解碼與 ANSI 序列
     /// 公開此方法以便測試
     pub fn process_byte_stream(&mut self, text_bytes: &[u8]) -> (String, Vec<u8>) {
         let mut final_output = String::new();
@@ -219,6 +573,17 @@ impl TelnetClient {
                 self.ansi_buffer.push(b);
                 i += 1;
                 
+                // OSC 序列 (ESC ] ...)：以 BEL 或 ST (ESC \) 結束，不輸出到畫面
+                if self.ansi_buffer.len() > 1 && self.ansi_buffer[1] == b']' {
+                    if b == 0x07 || self.ansi_buffer.ends_with(b"\x1b\\") {
+                        self.finish_osc();
+                    } else if self.ansi_buffer.len() > MAX_OSC_LEN {
+                        // 沒有結束符的異常序列，直接丟棄避免無限累積
+                        self.ansi_buffer.clear();
+                    }
+                    continue;
+                }
+
                 let is_complete = if self.ansi_buffer.len() > 1 && self.ansi_buffer[1] == b'[' {
                     // CSI 序列 (ESC [ ...): 必須至少 3 字元，且最後一個是 0x40-0x7E
                     self.ansi_buffer.len() > 2 && (0x40..=0x7E).contains(&b)
@@ -255,25 +620,30 @@ impl TelnetClient {
                 continue;
             }
 
-            // 3. 數據位元組：進入 Big5 重組流程
+            // 3. 換行正規化：\r\n → \n，孤立的 \r 直接移除
+            // （Big5 尾位元組與 UTF-8 後續位元組皆不會是 0x0D，只需在字元邊界檢查）
+            if b == b'\r' && self.text_buffer.is_empty() {
+                i += 1;
+                continue;
+            }
+
+            // 4. 文字中的 BEL：不顯示，記錄為提示音（OSC 的 BEL 結尾已在上方處理）
+            if b == 0x07 && self.text_buffer.is_empty() {
+                self.bell_received = true;
+                i += 1;
+                continue;
+            }
+
+            // 5. 數據位元組：進入多位元組重組流程
             self.text_buffer.push(b);
             i += 1;
 
-            let first = self.text_buffer[0];
-            // Big5 定義：Leading 0x81-0xFE, Trailing 0x40-0x7E, 0xA1-0xFE
-            // 簡化判斷：如果是先導位元組且緩衝區還只有 1 字元，則等待
-            let is_complete = if first < 0x81 || first == 0xFF {
-                true // ASCII 或其他特殊位元組
-            } else {
-                self.text_buffer.len() >= 2
-            };
+            let is_complete = self.text_buffer.len() >= self.expected_char_len(self.text_buffer[0]);
 
             if is_complete {
-                // 解碼目前緩衝區中的 1-2 位元組
+                // 解碼目前緩衝區中的位元組
                 // 使用 stateless 解碼避免 decoder 狀態不一致問題
-                use encoding_rs::BIG5;
-                let (res, _read, _replaced) = BIG5.decode(&self.text_buffer);
-                let ch_str = res.to_string();
+                let ch_str = self.encoding.decode(&self.text_buffer);
 
                 // 啟發式 ANSI 放置法則
                 // [m (Bare Reset) 通常用於雙色字技巧，必須放在字元前
@@ -313,6 +683,41 @@ impl TelnetClient {
         (final_output, final_widths)
     }
 
+    /// 解析已完整的 OSC 序列；目前僅處理設定標題 (`0;` / `2;`)
+    fn finish_osc(&mut self) {
+        let mut body = &self.ansi_buffer[2..];
+        body = body
+            .strip_suffix(b"\x07")
+            .or_else(|| body.strip_suffix(b"\x1b\\"))
+            .unwrap_or(body);
+
+        if let Some(title) = body.strip_prefix(b"0;").or_else(|| body.strip_prefix(b"2;")) {
+            self.pending_title = Some(self.encoding.decode(title));
+        }
+        self.ansi_buffer.clear();
+    }
+
+    /// 依先導位元組判斷目前編碼下一個字元的位元組數
+    fn expected_char_len(&self, first: u8) -> usize {
+        match self.encoding {
+            // Big5 定義：Leading 0x81-0xFE, Trailing 0x40-0x7E, 0xA1-0xFE
+            // GBK 定義：Leading 0x81-0xFE, Trailing 0x40-0xFE（GB2312 為其子集）
+            Encoding::Big5 | Encoding::Gbk | Encoding::Gb2312 => {
+                if first < 0x81 || first == 0xFF {
+                    1 // ASCII 或其他特殊位元組
+                } else {
+                    2
+                }
+            }
+            Encoding::Utf8 => match first {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            },
+        }
+    }
+
     /// 向後相容的 read
     pub async fn read(&mut self) -> Result<String, TelnetError> {
         self.read_with_widths().await.map(|(s, _)| s)
@@ -359,9 +764,30 @@ impl Default for TelnetClient {
     }
 }
 
+/// 解壓縮一段 MCCP2 資料，返回 (解壓後的位元組, 用掉的輸入位元組數, 壓縮串流是否結束)
+fn inflate(decompressor: &mut Decompress, input: &[u8]) -> Result<(Vec<u8>, usize, bool), flate2::DecompressError> {
+    let mut output = Vec::with_capacity(input.len() * 4);
+    let mut consumed = 0;
+    loop {
+        output.reserve(4096);
+        let (before_in, before_out) = (decompressor.total_in(), decompressor.total_out());
+        let status = decompressor.decompress_vec(&input[consumed..], &mut output, FlushDecompress::None)?;
+        consumed += (decompressor.total_in() - before_in) as usize;
+        if status == Status::StreamEnd {
+            return Ok((output, consumed, true));
+        }
+        let progressed = decompressor.total_in() != before_in || decompressor.total_out() != before_out;
+        // 輸入用完且輸出空間未填滿，代表目前沒有更多資料可解
+        if !progressed || (consumed >= input.len() && output.len() < output.capacity()) {
+            return Ok((output, consumed, false));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::telnet::protocol::IAC;
 
     #[test]
     fn test_client_creation() {
@@ -383,6 +809,53 @@ mod tests {
         assert!(matches!(result, Err(TelnetError::NotConnected)));
     }
 
+    #[tokio::test]
+    async fn test_connect_send_is_first_data() {
+        use tokio::io::AsyncReadExt;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0u8; 64];
+            while !received.ends_with(b"bob\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            received
+        });
+
+        let mut client = TelnetClient::new(TelnetConfig {
+            connect_send: Some("hello".to_string()),
+            ..Default::default()
+        });
+        client.connect("127.0.0.1", port).await.unwrap();
+        client.send("bob").await.unwrap();
+        assert_eq!(server.await.unwrap(), b"hello\r\nbob\r\n");
+
+        // 空字串不送出任何資料
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            let n = socket.read(&mut buf).await.unwrap();
+            buf[..n].to_vec()
+        });
+        let mut client = TelnetClient::new(TelnetConfig {
+            connect_send: Some(String::new()),
+            ..Default::default()
+        });
+        client.connect("127.0.0.1", port).await.unwrap();
+        client.send("bob").await.unwrap();
+        assert_eq!(server.await.unwrap(), b"bob\r\n");
+    }
+
     #[tokio::test]
     async fn test_read_without_connection() {
         let mut client = TelnetClient::default();
@@ -390,6 +863,73 @@ mod tests {
         assert!(matches!(result, Err(TelnetError::NotConnected)));
     }
 
+    #[tokio::test]
+    async fn test_naws_reports_window_size() {
+        let mut client = TelnetClient::default();
+        client.set_window_size(100, 30).await.unwrap();
+
+        let response = client.negotiate(TelnetCommand::Do, TelnetOption::Naws);
+        let mut expected = vec![IAC, TelnetCommand::Will as u8, 31];
+        expected.extend(encode_naws(100, 30));
+        assert_eq!(response, expected);
+
+        // 重複的 DO 只重送大小，不再回 WILL
+        assert_eq!(client.negotiate(TelnetCommand::Do, TelnetOption::Naws), encode_naws(100, 30));
+    }
+
+    #[test]
+    fn test_terminal_type_negotiation() {
+        let mut client = TelnetClient::new(TelnetConfig {
+            client_name: "TestClient".to_string(),
+            ..Default::default()
+        });
+        let (_, _, responses) = client.feed_bytes(&[IAC, TelnetCommand::Do as u8, 24]);
+        assert_eq!(responses, vec![IAC, TelnetCommand::Will as u8, 24]);
+
+        let send = [IAC, TelnetCommand::Sb as u8, 24, TTYPE_SEND, IAC, TelnetCommand::Se as u8];
+        let mut names = Vec::new();
+        for _ in 0..4 {
+            let (_, _, responses) = client.feed_bytes(&send);
+            // 去掉 IAC SB 24 IS 與結尾的 IAC SE
+            names.push(String::from_utf8(responses[4..responses.len() - 2].to_vec()).unwrap());
+        }
+        assert_eq!(names, vec!["TestClient", "XTERM-256COLOR", "MTTS 9", "MTTS 9"]);
+
+        client.set_encoding(Encoding::Utf8);
+        let (_, _, responses) = client.feed_bytes(&send);
+        assert!(responses.ends_with(b"MTTS 13\xff\xf0"));
+    }
+
+    #[test]
+    fn test_mccp2_decompresses_across_reads() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut client = TelnetClient::default();
+        let (_, _, responses) = client.feed_bytes(&[IAC, TelnetCommand::Will as u8, 86]);
+        assert_eq!(responses, vec![IAC, TelnetCommand::Do as u8, 86]);
+        let (_, _, responses) = client.feed_bytes(&[IAC, TelnetCommand::Do as u8, 86]);
+        assert_eq!(responses, vec![IAC, TelnetCommand::Wont as u8, 86]);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&crate::encoding::encode_big5("你好，世界\n")).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // SB 與部分壓縮資料同一包送達，其餘分成下一包，串流結束後恢復未壓縮
+        let mut first = b"Hi ".to_vec();
+        first.extend_from_slice(&[IAC, TelnetCommand::Sb as u8, 86, IAC, TelnetCommand::Se as u8]);
+        first.extend_from_slice(&compressed[..5]);
+        let (out1, _, _) = client.feed_bytes(&first);
+        assert!(client.compression_enabled());
+
+        let mut second = compressed[5..].to_vec();
+        second.extend_from_slice(b"bye");
+        let (out2, _, _) = client.feed_bytes(&second);
+        assert_eq!(format!("{}{}", out1, out2), "Hi 你好，世界\nbye");
+        assert!(!client.compression_enabled());
+    }
+
     #[test]
     fn test_big5_split_with_ansi_across_calls() {
         // 模擬 "泉" 分兩次送達，且中間夾帶 ANSI
@@ -434,4 +974,183 @@ mod tests {
         // [m (Bare Reset) 應該放在字元前，以觸發雙色字技巧 (如蠻荒之刃)
         assert_eq!(out2, "\x1b[m泉");
     }
+
+    #[test]
+    fn test_utf8_split_across_calls() {
+        let mut client = TelnetClient::default();
+        client.set_encoding(Encoding::Utf8);
+        let bytes = "泉".as_bytes();
+
+        let (out1, _) = client.process_byte_stream(&bytes[..2]);
+        assert_eq!(out1, "");
+
+        let (out2, widths) = client.process_byte_stream(&bytes[2..]);
+        assert_eq!(out2, "泉");
+        assert_eq!(widths, vec![2]);
+    }
+
+    #[test]
+    fn test_encode_line_endings() {
+        assert_eq!(TelnetClient::default().encode_line("look"), b"look\r\n");
+        for (line_ending, expected) in [
+            (LineEnding::Crlf, &b"look\r\n"[..]),
+            (LineEnding::Lf, &b"look\n"[..]),
+            (LineEnding::Cr, &b"look\r"[..]),
+        ] {
+            let client = TelnetClient::new(TelnetConfig { line_ending, ..Default::default() });
+            assert_eq!(client.encode_line("look"), expected, "{:?}", line_ending);
+        }
+    }
+
+    #[test]
+    fn test_option_states_snapshot() {
+        let mut client = TelnetClient::default();
+        assert!(client.option_states().is_empty());
+
+        let response = client.negotiate(TelnetCommand::Will, TelnetOption::SuppressGoAhead);
+        assert_eq!(response, vec![IAC, TelnetCommand::Do as u8, TelnetOption::SuppressGoAhead.as_byte()]);
+        client.negotiate(TelnetCommand::Do, TelnetOption::TerminalSpeed);
+        client.negotiate(TelnetCommand::Will, TelnetOption::Echo);
+        assert!(client.take_options_changed());
+
+        assert_eq!(
+            client.option_states(),
+            vec![
+                (TelnetOption::Echo, OptionStatus::Active),
+                (TelnetOption::SuppressGoAhead, OptionStatus::Active),
+                (TelnetOption::TerminalSpeed, OptionStatus::Do),
+            ]
+        );
+
+        // 伺服器關閉回顯
+        client.negotiate(TelnetCommand::Wont, TelnetOption::Echo);
+        assert!(client.take_options_changed());
+        assert_eq!(client.option_states()[0], (TelnetOption::Echo, OptionStatus::Wont));
+        assert!(!client.server_echo());
+    }
+
+    #[test]
+    fn test_switch_encoding_mid_stream() {
+        let mut client = TelnetClient::default();
+        let big5 = Encoding::Big5.encode("泉");
+        let (out, _) = client.process_byte_stream(&[&big5[..], &big5[..1]].concat());
+        assert_eq!(out, "泉");
+
+        // 切換前送出殘留的半個字元，不與之後的位元組拼接
+        let (flushed, widths) = client.set_encoding(Encoding::Utf8);
+        assert_eq!(flushed, "\u{FFFD}");
+        assert_eq!(widths, vec![2]);
+        assert_eq!(client.encoding(), Encoding::Utf8);
+
+        let (out, widths) = client.process_byte_stream("泉水".as_bytes());
+        assert_eq!(out, "泉水");
+        assert_eq!(widths, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_gbk_split_across_reads() {
+        let mut client = TelnetClient::new(TelnetConfig {
+            encoding: Encoding::Gbk,
+            ..Default::default()
+        });
+        let gbk = Encoding::Gbk.encode("欢迎");
+        let (out, _) = client.process_byte_stream(&gbk[..3]);
+        assert_eq!(out, "欢");
+        let (out, widths) = client.process_byte_stream(&gbk[3..]);
+        assert_eq!(out, "迎");
+        assert_eq!(widths, vec![2]);
+        assert!(client.encode_line("欢迎").starts_with(&gbk));
+    }
+
+    #[test]
+    fn test_osc_title_stripped() {
+        let mut client = TelnetClient::default();
+        let (output, widths) = client.process_byte_stream(b"\x1b]0;My MUD\x07Welcome\x1b]2;Other\x1b\\!");
+
+        assert_eq!(output, "Welcome!");
+        assert_eq!(widths.len(), output.chars().count());
+        assert_eq!(client.take_title().as_deref(), Some("Other"));
+        assert_eq!(client.take_title(), None);
+    }
+
+    #[test]
+    fn test_bell_stripped_and_flagged() {
+        let mut client = TelnetClient::default();
+        client.set_encoding(Encoding::Utf8);
+        let (output, widths) = client.process_byte_stream(b"\x1b]0;Title\x07Hi");
+        assert_eq!(output, "Hi");
+        assert_eq!(widths.len(), 2);
+        assert!(!client.take_bell(), "OSC 結尾的 BEL 不是提示音");
+
+        let (output, widths) = client.process_byte_stream("有人告訴你\x07：嗨\n".as_bytes());
+        assert_eq!(output, "有人告訴你：嗨\n");
+        assert_eq!(widths.len(), output.chars().count());
+        assert!(client.take_bell());
+        assert!(!client.take_bell());
+    }
+
+    #[test]
+    fn test_go_ahead_ignored_after_eor_with_sga() {
+        let mut client = TelnetClient::default();
+        client.set_encoding(Encoding::Utf8);
+        client.feed_bytes(&[IAC, TelnetCommand::Will as u8, TelnetOption::SuppressGoAhead.as_byte()]);
+        assert!(client.suppress_go_ahead());
+
+        client.feed_bytes(&[b'>', IAC, TelnetCommand::EndOfRecord as u8]);
+        assert!(client.take_prompt_end());
+        client.feed_bytes(&[IAC, TelnetCommand::GoAhead as u8]);
+        assert!(!client.take_prompt_end(), "EOR 之後的 GA 不應再算一次提示符");
+
+        client.feed_bytes(&[IAC, TelnetCommand::Wont as u8, TelnetOption::SuppressGoAhead.as_byte()]);
+        client.feed_bytes(&[b'>', IAC, TelnetCommand::GoAhead as u8]);
+        assert!(client.take_prompt_end(), "SGA 關閉後 GA 仍是提示符結尾");
+    }
+
+    #[test]
+    fn test_encoding_sample_ready() {
+        let mut client = TelnetClient { encoding_sample: b"Welcome!\r\nLogin: ".to_vec(), ..Default::default() };
+        assert!(!client.encoding_sample_ready(false));
+        assert!(!client.encoding_sample_ready(true), "純 ASCII 的 banner 不足以判斷");
+
+        client.encoding_sample.extend_from_slice("歡迎光臨".as_bytes());
+        assert!(!client.encoding_sample_ready(false));
+        assert!(client.encoding_sample_ready(true));
+
+        client.encoding_sample = vec![b'a'; ENCODING_SAMPLE_SIZE];
+        assert!(client.encoding_sample_ready(false));
+    }
+
+    #[test]
+    fn test_osc_title_split_across_calls() {
+        let mut client = TelnetClient::default();
+        let (out1, _) = client.process_byte_stream(b"Hi\x1b]0;My ");
+        assert_eq!(out1, "Hi");
+        assert_eq!(client.take_title(), None);
+
+        let (out2, _) = client.process_byte_stream(b"MUD\x07\x1b[31mred");
+        assert_eq!(out2, "\x1b[31mred");
+        assert_eq!(client.take_title().as_deref(), Some("My MUD"));
+    }
+
+    #[test]
+    fn test_carriage_return_normalization() {
+        let mut client = TelnetClient::default();
+        let (output, widths) = client.process_byte_stream(b"A\r\nB\rC");
+
+        assert_eq!(output, "A\nBC");
+        assert_eq!(widths.len(), output.chars().count());
+        assert_eq!(widths, vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_carriage_return_split_across_calls() {
+        let mut client = TelnetClient::default();
+        let (out1, widths1) = client.process_byte_stream(b"hp 100\r");
+        let (out2, widths2) = client.process_byte_stream(b"\n");
+
+        assert_eq!(out1, "hp 100");
+        assert_eq!(widths1.len(), 6);
+        assert_eq!(out2, "\n");
+        assert_eq!(widths2, vec![1]);
+    }
 }
@@ -0,0 +1,219 @@
+//! GMCP (Generic MUD Communication Protocol)
+//!
+//! 解析伺服器以 `IAC SB GMCP <Package.Message> <JSON> IAC SE` 送出的結構化資料，
+//! 並提供常用訊息（如 `Room.Info`）的型別。
+
+use serde_json::Value;
+
+use super::protocol::{TelnetCommand, TelnetOption, IAC};
+
+/// 一則 GMCP 訊息
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmcpMessage {
+    /// 套件與訊息名稱（如 `Room.Info`）
+    pub package: String,
+    /// JSON 資料（未附資料時為 `Null`）
+    pub data: Value,
+}
+
+impl GmcpMessage {
+    /// 解析 Sub-negotiation 內容；JSON 格式錯誤時回傳 `None`
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let text = String::from_utf8_lossy(payload);
+        let text = text.trim();
+        let (package, data) = match text.split_once(char::is_whitespace) {
+            Some((package, data)) => (package, serde_json::from_str(data.trim()).ok()?),
+            None => (text, Value::Null),
+        };
+        if package.is_empty() {
+            return None;
+        }
+        Some(Self {
+            package: package.to_string(),
+            data,
+        })
+    }
+
+    /// 套件名稱是否相符（不分大小寫）
+    pub fn is(&self, package: &str) -> bool {
+        self.package.eq_ignore_ascii_case(package)
+    }
+
+    /// 編碼為完整的 `IAC SB GMCP ... IAC SE` 序列
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![IAC, TelnetCommand::Sb as u8, TelnetOption::Gmcp.as_byte()];
+        bytes.extend_from_slice(self.package.as_bytes());
+        if !self.data.is_null() {
+            bytes.push(b' ');
+            bytes.extend_from_slice(self.data.to_string().as_bytes());
+        }
+        bytes.extend_from_slice(&[IAC, TelnetCommand::Se as u8]);
+        bytes
+    }
+}
+
+/// 同意 GMCP 後送出的握手訊息（`Core.Hello` 與 `Core.Supports.Set`）
+pub fn handshake() -> Vec<u8> {
+    let hello = GmcpMessage {
+        package: "Core.Hello".to_string(),
+        data: serde_json::json!({ "client": "mudclient-rs", "version": env!("CARGO_PKG_VERSION") }),
+    };
+    let supports = GmcpMessage {
+        package: "Core.Supports.Set".to_string(),
+        data: serde_json::json!(["Char 1", "Room 1"]),
+    };
+    let mut bytes = hello.encode();
+    bytes.extend(supports.encode());
+    bytes
+}
+
+/// `Room.Info` 提供的房間資訊
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoomInfo {
+    /// 房間編號
+    pub num: Option<i64>,
+    /// 房間名稱
+    pub name: String,
+    /// 區域名稱
+    pub area: Option<String>,
+    /// 出口方向
+    pub exits: Vec<String>,
+}
+
+impl RoomInfo {
+    /// 從 `Room.Info` 訊息取得房間資訊；其他訊息或缺少名稱時回傳 `None`
+    ///
+    /// 出口可為 `{"n": 123}` 物件或 `["n", "s"]` 陣列。
+    pub fn from_gmcp(message: &GmcpMessage) -> Option<Self> {
+        if !message.is("Room.Info") {
+            return None;
+        }
+        let data = &message.data;
+        let name = data.get("name")?.as_str()?.trim().to_string();
+        let exits = match data.get("exits") {
+            Some(Value::Object(map)) => map.keys().cloned().collect(),
+            Some(Value::Array(list)) => list.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        };
+        Some(Self {
+            num: data.get("num").and_then(Value::as_i64),
+            name,
+            area: data.get("area").and_then(Value::as_str).map(str::to_string),
+            exits,
+        })
+    }
+}
+
+/// `Char.Vitals` 提供的角色狀態
+///
+/// 伺服器常只送出有變化的欄位，未送出的欄位為 `None`，可用 [`Vitals::merge`] 累積。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Vitals {
+    /// 目前生命值
+    pub hp: Option<i64>,
+    /// 生命值上限
+    pub max_hp: Option<i64>,
+    /// 目前法力值
+    pub mp: Option<i64>,
+    /// 法力值上限
+    pub max_mp: Option<i64>,
+}
+
+impl Vitals {
+    /// 從 `Char.Vitals` 訊息取得數值；其他訊息回傳 `None`
+    ///
+    /// 數值可為數字或數字字串，常見的欄位別名（如 `maxhp` / `max_hp`）皆可。
+    pub fn from_gmcp(message: &GmcpMessage) -> Option<Self> {
+        if !message.is("Char.Vitals") {
+            return None;
+        }
+        let field = |keys: &[&str]| {
+            keys.iter().filter_map(|key| message.data.get(key)).find_map(|value| match value {
+                Value::Number(n) => n.as_i64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            })
+        };
+        Some(Self {
+            hp: field(&["hp"]),
+            max_hp: field(&["maxhp", "max_hp"]),
+            mp: field(&["mp", "mana", "sp"]),
+            max_mp: field(&["maxmp", "max_mp", "maxmana", "maxsp"]),
+        })
+    }
+
+    /// 以新訊息中有送出的欄位更新
+    pub fn merge(&mut self, update: Vitals) {
+        self.hp = update.hp.or(self.hp);
+        self.max_hp = update.max_hp.or(self.max_hp);
+        self.mp = update.mp.or(self.mp);
+        self.max_mp = update.max_mp.or(self.max_mp);
+    }
+
+    /// 狀態列顯示文字（如 `HP 80/100 MP 20`）
+    pub fn summary(&self) -> String {
+        let part = |label: &str, current: Option<i64>, max: Option<i64>| match (current, max) {
+            (Some(current), Some(max)) => Some(format!("{} {}/{}", label, current, max)),
+            (Some(current), None) => Some(format!("{} {}", label, current)),
+            _ => None,
+        };
+        [part("HP", self.hp, self.max_hp), part("MP", self.mp, self.max_mp)]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_room_info() {
+        let payload = br#"Room.Info { "num": 1234, "name": "Town Square", "area": "Midgaard", "exits": { "n": 1235, "e": 1240 } }"#;
+        let message = GmcpMessage::parse(payload).unwrap();
+        assert!(message.is("room.info"));
+
+        let room = RoomInfo::from_gmcp(&message).unwrap();
+        assert_eq!(room.num, Some(1234));
+        assert_eq!(room.name, "Town Square");
+        assert_eq!(room.area.as_deref(), Some("Midgaard"));
+        let mut exits = room.exits.clone();
+        exits.sort();
+        assert_eq!(exits, vec!["e", "n"]);
+
+        // 陣列形式的出口、缺少名稱、非 Room.Info
+        let message = GmcpMessage::parse(r#"Room.Info {"name":"廣場","exits":["n","s"]}"#.as_bytes()).unwrap();
+        assert_eq!(RoomInfo::from_gmcp(&message).unwrap().exits, vec!["n", "s"]);
+        let message = GmcpMessage::parse(br#"Room.Info {"num":1}"#).unwrap();
+        assert_eq!(RoomInfo::from_gmcp(&message), None);
+        let message = GmcpMessage::parse(br#"Char.Vitals {"hp":100}"#).unwrap();
+        assert_eq!(RoomInfo::from_gmcp(&message), None);
+    }
+
+    #[test]
+    fn test_vitals_merge_partial_updates() {
+        let message = GmcpMessage::parse(br#"Char.Vitals {"hp":80,"maxhp":"100","mp":20,"maxmp":50}"#).unwrap();
+        let mut vitals = Vitals::from_gmcp(&message).unwrap();
+        assert_eq!(vitals.summary(), "HP 80/100 MP 20/50");
+
+        let message = GmcpMessage::parse(br#"Char.Vitals {"hp":65}"#).unwrap();
+        vitals.merge(Vitals::from_gmcp(&message).unwrap());
+        assert_eq!(vitals.summary(), "HP 65/100 MP 20/50");
+
+        let message = GmcpMessage::parse(br#"Room.Info {"name":"x"}"#).unwrap();
+        assert_eq!(Vitals::from_gmcp(&message), None);
+    }
+
+    #[test]
+    fn test_parse_and_encode() {
+        assert_eq!(GmcpMessage::parse(b"Room.Info {broken"), None);
+        let message = GmcpMessage::parse(b"Core.Ping").unwrap();
+        assert_eq!(message.data, Value::Null);
+        assert_eq!(
+            message.encode(),
+            [&[IAC, TelnetCommand::Sb as u8, 201][..], b"Core.Ping", &[IAC, TelnetCommand::Se as u8]].concat()
+        );
+    }
+}
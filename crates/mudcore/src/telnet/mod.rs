@@ -3,7 +3,9 @@
 //! 實作 Telnet 連線管理和基本協定處理
 
 mod client;
+mod gmcp;
 mod protocol;
 
-pub use client::TelnetClient;
-pub use protocol::{TelnetCommand, TelnetOption};
+pub use client::{LineEnding, TelnetClient, TelnetConfig, ENCODING_SAMPLE_SIZE};
+pub use gmcp::{GmcpMessage, RoomInfo, Vitals};
+pub use protocol::{NegotiationPolicy, NegotiationTable, OptionStatus, TelnetCommand, TelnetOption};
@@ -2,6 +2,10 @@
 //!
 //! 實作 RFC 854 Telnet 協定的基本命令
 
+use std::collections::HashMap;
+
+use super::gmcp::GmcpMessage;
+
 /// Telnet IAC (Interpret As Command) - 0xFF
 pub const IAC: u8 = 255;
 
@@ -9,6 +13,8 @@ pub const IAC: u8 = 255;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum TelnetCommand {
+    /// End of Record（EOR 選項啟用時標示提示符結尾）
+    EndOfRecord = 239,
     /// Sub-negotiation End
     Se = 240,
     /// No Operation
@@ -45,6 +51,7 @@ impl TelnetCommand {
     /// 從位元組解析 Telnet 命令
     pub fn from_byte(byte: u8) -> Option<Self> {
         match byte {
+            239 => Some(Self::EndOfRecord),
             240 => Some(Self::Se),
             241 => Some(Self::Nop),
             242 => Some(Self::DataMark),
@@ -66,7 +73,7 @@ impl TelnetCommand {
 }
 
 /// Telnet 選項
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum TelnetOption {
     /// Binary Transmission
@@ -151,6 +158,10 @@ pub enum TelnetEvent {
     Command(TelnetCommand, TelnetOption),
     /// Sub-negotiation 資料
     Subnegotiation(TelnetOption, Vec<u8>),
+    /// GMCP 訊息（套件名稱, JSON 資料）
+    Gmcp(String, serde_json::Value),
+    /// 提示符結尾（IAC GA 或 IAC EOR，附帶實際收到的命令）
+    PromptEnd(TelnetCommand),
 }
 
 /// 解析 Telnet 資料流，分離出文字和命令
@@ -193,35 +204,64 @@ pub fn parse_telnet_data(input: &[u8]) -> (Vec<u8>, Vec<TelnetEvent>, usize) {
                         }
                     }
                     TelnetCommand::Sb => {
-                        // 尋找 Sub-negotiation 結束 (IAC SE)
-                        if i + 2 < input.len() {
-                            let option = TelnetOption::from_byte(input[i + 2]);
-                            let mut sub_data = Vec::new();
-                            let mut j = i + 3;
-                            let mut found_se = false;
-
-                            while j + 1 < input.len() {
-                                if input[j] == IAC && input[j + 1] == TelnetCommand::Se as u8 {
-                                    events.push(TelnetEvent::Subnegotiation(option, sub_data));
-                                    i = j + 2;
-                                    last_consumed = i;
-                                    found_se = true;
-                                    break;
-                                }
+                        // 收集到未跳脫的 IAC SE 為止（IAC IAC 為跳脫的 0xFF），內容一律不混入文字
+                        if i + 2 >= input.len() {
+                            break; // 不完整的 SB，留在緩衝區
+                        }
+                        let option = TelnetOption::from_byte(input[i + 2]);
+                        let mut sub_data = Vec::new();
+                        let mut j = i + 3;
+                        let mut end = None;
+
+                        while j < input.len() {
+                            if input[j] != IAC {
                                 sub_data.push(input[j]);
                                 j += 1;
+                                continue;
                             }
-                            
-                            if !found_se {
-                                break; // 尚未收到 SE，留在緩衝區
+                            match input.get(j + 1) {
+                                None => break,
+                                Some(&IAC) => {
+                                    sub_data.push(IAC);
+                                    j += 2;
+                                }
+                                Some(&byte) if byte == TelnetCommand::Se as u8 => {
+                                    end = Some(j + 2);
+                                    break;
+                                }
+                                // SB 內不應出現其他命令，連同 IAC 一併略過
+                                Some(_) => j += 2,
                             }
-                            continue;
-                        } else {
-                            break; // 不完整的 SB，留在緩衝區
                         }
+
+                        let Some(end) = end else {
+                            break; // 尚未收到 SE，留在緩衝區
+                        };
+                        match option {
+                            TelnetOption::Unknown(code) => {
+                                tracing::debug!("略過未知選項 {} 的 Sub-negotiation ({} bytes)", code, sub_data.len());
+                            }
+                            TelnetOption::Gmcp => match GmcpMessage::parse(&sub_data) {
+                                Some(message) => events.push(TelnetEvent::Gmcp(message.package, message.data)),
+                                None => tracing::warn!("無法解析 GMCP 訊息: {}", String::from_utf8_lossy(&sub_data)),
+                            },
+                            _ => events.push(TelnetEvent::Subnegotiation(option, sub_data)),
+                        }
+                        i = end;
+                        last_consumed = i;
+                        if option == TelnetOption::Mccp2 {
+                            break; // 之後的資料已壓縮，須先解壓縮才能解析
+                        }
+                        continue;
+                    }
+                    TelnetCommand::GoAhead | TelnetCommand::EndOfRecord => {
+                        events.push(TelnetEvent::PromptEnd(cmd));
+                        i += 2;
+                        last_consumed = i;
+                        continue;
                     }
                     _ => {
-                        // 其他命令（如 GA, NOP），直接消耗
+                        // 其他命令（如 NOP），直接消耗
                         i += 2;
                         last_consumed = i;
                         continue;
@@ -241,35 +281,224 @@ pub fn parse_telnet_data(input: &[u8]) -> (Vec<u8>, Vec<TelnetEvent>, usize) {
     (data, events, last_consumed)
 }
 
-/// 生成 Telnet 拒絕回應（對所有選項回應 WONT/DONT）
-pub fn generate_refusal(cmd: TelnetCommand, option: TelnetOption) -> Vec<u8> {
-    let response_cmd = match cmd {
-        TelnetCommand::Will | TelnetCommand::Do => TelnetCommand::Wont,
-        TelnetCommand::Wont | TelnetCommand::Dont => return vec![], // 不需要回應
-        _ => return vec![],
-    };
-
-    // 對於 ECHO 和 SGA，我們接受
-    let response_cmd = match option {
-        TelnetOption::Echo | TelnetOption::SuppressGoAhead => {
-            if cmd == TelnetCommand::Will {
-                TelnetCommand::Do
-            } else if cmd == TelnetCommand::Do {
-                TelnetCommand::Will
-            } else {
-                response_cmd
-            }
+/// Telnet 選項協商狀態
+///
+/// 目前追蹤 SUPPRESS-GO-AHEAD（選項 3）。
+///
+/// SGA 與提示符偵測的關係：
+/// - 雙方未啟用 SGA 時，伺服器會在每個提示符後送出 IAC GA，可作為提示符結尾。
+/// - 伺服器回覆 WILL SGA 後便不再送 GA，提示符結尾只能依 EOR（選項 25）或文字規則判斷。
+/// - 部分伺服器即使同意 SGA 仍會送 GA，因此提示符偵測以 `remote_sga` 為準：
+///   SGA 已啟用且伺服器送過 EOR 後便忽略 GA，只採用 EOR，避免同一提示符被處理兩次
+///   （見 `TelnetClient::feed_bytes`）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptionState {
+    /// 伺服器已同意不送 GA（伺服器 WILL SGA，我方 DO SGA）
+    pub remote_sga: bool,
+    /// 我方已同意不送 GA（伺服器 DO SGA，我方 WILL SGA）
+    pub local_sga: bool,
+    /// 伺服器負責回顯（伺服器 WILL ECHO，常見於輸入密碼時）
+    pub remote_echo: bool,
+}
+
+impl OptionState {
+    /// 處理 SGA 協商，回傳應送出的回應
+    ///
+    /// 狀態未改變時不回應，避免與伺服器形成協商迴圈（RFC 1143）。
+    pub fn handle_sga(&mut self, cmd: TelnetCommand) -> Vec<u8> {
+        let (state, enable, accept, reject) = match cmd {
+            TelnetCommand::Will => (&mut self.remote_sga, true, TelnetCommand::Do, TelnetCommand::Dont),
+            TelnetCommand::Wont => (&mut self.remote_sga, false, TelnetCommand::Do, TelnetCommand::Dont),
+            TelnetCommand::Do => (&mut self.local_sga, true, TelnetCommand::Will, TelnetCommand::Wont),
+            TelnetCommand::Dont => (&mut self.local_sga, false, TelnetCommand::Will, TelnetCommand::Wont),
+            _ => return vec![],
+        };
+
+        if *state == enable {
+            return vec![];
         }
-        _ => {
-            if cmd == TelnetCommand::Do {
-                TelnetCommand::Wont
-            } else {
-                TelnetCommand::Dont
-            }
+        *state = enable;
+
+        let response = if enable { accept } else { reject };
+        vec![IAC, response as u8, TelnetOption::SuppressGoAhead.as_byte()]
+    }
+}
+
+impl OptionState {
+    /// 處理伺服器的 WILL/WONT ECHO，回傳應送出的回應
+    ///
+    /// DO/DONT ECHO（要求我方回顯）不在此處理，回傳 `None` 交由協商表決定。
+    pub fn handle_echo(&mut self, cmd: TelnetCommand) -> Option<Vec<u8>> {
+        let (enable, response) = match cmd {
+            TelnetCommand::Will => (true, TelnetCommand::Do),
+            TelnetCommand::Wont => (false, TelnetCommand::Dont),
+            _ => return None,
+        };
+
+        if self.remote_echo == enable {
+            return Some(vec![]);
+        }
+        self.remote_echo = enable;
+        Some(vec![IAC, response as u8, TelnetOption::Echo.as_byte()])
+    }
+}
+
+/// 編碼 NAWS 子協商 `IAC SB NAWS <寬> <高> IAC SE`
+///
+/// 寬高為 16 位元大端序，其中的 0xFF 位元組需重複一次跳脫。
+pub fn encode_naws(cols: u16, rows: u16) -> Vec<u8> {
+    let mut bytes = vec![IAC, TelnetCommand::Sb as u8, TelnetOption::Naws.as_byte()];
+    for byte in cols.to_be_bytes().into_iter().chain(rows.to_be_bytes()) {
+        bytes.push(byte);
+        if byte == IAC {
+            bytes.push(IAC);
         }
-    };
+    }
+    bytes.extend_from_slice(&[IAC, TelnetCommand::Se as u8]);
+    bytes
+}
+
+/// TTYPE 子協商：回報終端機類型
+pub const TTYPE_IS: u8 = 0;
+/// TTYPE 子協商：伺服器要求回報
+pub const TTYPE_SEND: u8 = 1;
+/// 回報循環第二步的終端機類型
+pub const TERMINAL_TYPE: &str = "XTERM-256COLOR";
+/// MTTS 旗標：支援 ANSI 顏色
+pub const MTTS_ANSI: u32 = 1;
+/// MTTS 旗標：使用 UTF-8
+pub const MTTS_UTF8: u32 = 4;
+/// MTTS 旗標：支援 256 色
+pub const MTTS_256_COLORS: u32 = 8;
+
+/// 終端機類型（TTYPE）的回報循環
+///
+/// 依 MTTS 慣例，伺服器每次送出 `SEND` 依序回報客戶端名稱、終端機類型、`MTTS <旗標>`，
+/// 之後重複最後一項，伺服器看到重複即知已列完。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TerminalTypeCycle {
+    step: u8,
+}
+
+impl TerminalTypeCycle {
+    /// 產生下一個 `IAC SB TTYPE IS <名稱> IAC SE` 回應
+    pub fn next_response(&mut self, client_name: &str, mtts: u32) -> Vec<u8> {
+        let name = match self.step {
+            0 => client_name.to_string(),
+            1 => TERMINAL_TYPE.to_string(),
+            _ => format!("MTTS {}", mtts),
+        };
+        self.step = (self.step + 1).min(2);
+
+        let mut response = vec![IAC, TelnetCommand::Sb as u8, TelnetOption::TerminalType.as_byte(), TTYPE_IS];
+        response.extend_from_slice(name.as_bytes());
+        response.extend_from_slice(&[IAC, TelnetCommand::Se as u8]);
+        response
+    }
+}
+
+/// 單一選項的協商結果（供診斷顯示）
+///
+/// 以伺服器最後送出的指令為準：我方同意 WILL/DO 時為 `Active`，
+/// 拒絕時保留伺服器提出的 `Will` / `Do`，伺服器關閉時為 `Wont` / `Dont`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionStatus {
+    /// 伺服器提出 WILL，我方拒絕
+    Will,
+    /// 伺服器 WONT（不啟用）
+    Wont,
+    /// 伺服器提出 DO，我方拒絕
+    Do,
+    /// 伺服器 DONT（要求我方不啟用）
+    Dont,
+    /// 雙方同意，選項已啟用
+    Active,
+}
+
+impl OptionStatus {
+    /// 依收到的指令與協商策略決定狀態；非協商指令回傳 `None`
+    pub fn from_negotiation(cmd: TelnetCommand, policy: NegotiationPolicy) -> Option<Self> {
+        let accept = policy == NegotiationPolicy::Accept;
+        Some(match cmd {
+            TelnetCommand::Will | TelnetCommand::Do if accept => Self::Active,
+            TelnetCommand::Will => Self::Will,
+            TelnetCommand::Wont => Self::Wont,
+            TelnetCommand::Do => Self::Do,
+            TelnetCommand::Dont => Self::Dont,
+            _ => return None,
+        })
+    }
+
+    /// 顯示用名稱
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Will => "WILL（已拒絕）",
+            Self::Wont => "WONT",
+            Self::Do => "DO（已拒絕）",
+            Self::Dont => "DONT",
+            Self::Active => "已啟用",
+        }
+    }
+}
+
+/// 選項協商策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NegotiationPolicy {
+    /// 同意（回應 DO / WILL）
+    Accept,
+    /// 拒絕（回應 DONT / WONT）
+    #[default]
+    Refuse,
+}
+
+/// 選項協商表
+///
+/// 集中設定各選項的協商策略，未列出的選項一律拒絕。
+/// 實作新選項（GMCP、NAWS 等）時只需將其設為 `Accept`。
+#[derive(Debug, Clone)]
+pub struct NegotiationTable {
+    policies: HashMap<TelnetOption, NegotiationPolicy>,
+}
+
+impl Default for NegotiationTable {
+    fn default() -> Self {
+        let mut table = Self {
+            policies: HashMap::new(),
+        };
+        table.set(TelnetOption::Echo, NegotiationPolicy::Accept);
+        table.set(TelnetOption::SuppressGoAhead, NegotiationPolicy::Accept);
+        table.set(TelnetOption::Gmcp, NegotiationPolicy::Accept);
+        table.set(TelnetOption::Mccp2, NegotiationPolicy::Accept);
+        table.set(TelnetOption::TerminalType, NegotiationPolicy::Accept);
+        table.set(TelnetOption::Naws, NegotiationPolicy::Accept);
+        table
+    }
+}
+
+impl NegotiationTable {
+    /// 設定選項的協商策略
+    pub fn set(&mut self, option: TelnetOption, policy: NegotiationPolicy) {
+        self.policies.insert(option, policy);
+    }
+
+    /// 取得選項的協商策略（未設定者為 Refuse）
+    pub fn policy(&self, option: TelnetOption) -> NegotiationPolicy {
+        self.policies.get(&option).copied().unwrap_or_default()
+    }
 
-    vec![IAC, response_cmd as u8, option.as_byte()]
+    /// 依策略產生對 WILL/DO 的回應；WONT/DONT 不需回應
+    pub fn respond(&self, cmd: TelnetCommand, option: TelnetOption) -> Vec<u8> {
+        let accept = self.policy(option) == NegotiationPolicy::Accept;
+        let response_cmd = match cmd {
+            TelnetCommand::Will if accept => TelnetCommand::Do,
+            TelnetCommand::Will => TelnetCommand::Dont,
+            TelnetCommand::Do if accept => TelnetCommand::Will,
+            TelnetCommand::Do => TelnetCommand::Wont,
+            _ => return vec![],
+        };
+
+        vec![IAC, response_cmd as u8, option.as_byte()]
+    }
 }
 
 #[cfg(test)]
@@ -315,18 +544,115 @@ mod tests {
         assert_eq!(events.len(), 1);
     }
 
+    #[test]
+    fn test_parse_prompt_end() {
+        let input = [b'>', b' ', IAC, TelnetCommand::GoAhead as u8, b'x', IAC, TelnetCommand::EndOfRecord as u8];
+        let (data, events, consumed) = parse_telnet_data(&input);
+        assert_eq!(data, b"> x");
+        assert_eq!(
+            events,
+            vec![
+                TelnetEvent::PromptEnd(TelnetCommand::GoAhead),
+                TelnetEvent::PromptEnd(TelnetCommand::EndOfRecord)
+            ]
+        );
+        assert_eq!(consumed, input.len());
+    }
+
+    #[test]
+    fn test_unknown_subnegotiation_skipped() {
+        let sb = |option: u8, payload: &[u8]| {
+            [&[IAC, TelnetCommand::Sb as u8, option][..], payload, &[IAC, TelnetCommand::Se as u8]].concat()
+        };
+        // Payload 含跳脫的 IAC 與看似 SE 的位元組
+        let payload = [b'x', IAC, IAC, TelnetCommand::Se as u8, b'y', 0x1b, b'['];
+        let input = [&b"Hello "[..], &sb(99, &payload), b"World", &sb(99, b""), b"!"].concat();
+
+        let (data, events, consumed) = parse_telnet_data(&input);
+        assert_eq!(data, b"Hello World!");
+        assert!(events.is_empty());
+        assert_eq!(consumed, input.len());
+
+        // 已知選項照常回報，內容還原跳脫的 IAC
+        let (data, events, _) = parse_telnet_data(&sb(TelnetOption::Charset.as_byte(), &payload));
+        assert!(data.is_empty());
+        assert_eq!(
+            events,
+            vec![TelnetEvent::Subnegotiation(TelnetOption::Charset, vec![b'x', IAC, TelnetCommand::Se as u8, b'y', 0x1b, b'['])]
+        );
+    }
+
+    #[test]
+    fn test_gmcp_event() {
+        let mut input = vec![IAC, TelnetCommand::Sb as u8, TelnetOption::Gmcp.as_byte()];
+        input.extend_from_slice(br#"Char.Vitals {"hp":80,"maxhp":100}"#);
+        input.extend_from_slice(&[IAC, TelnetCommand::Se as u8]);
+        input.extend_from_slice(b"ok");
+
+        let (data, events, consumed) = parse_telnet_data(&input);
+        assert_eq!(data, b"ok");
+        assert_eq!(
+            events,
+            vec![TelnetEvent::Gmcp("Char.Vitals".to_string(), serde_json::json!({"hp": 80, "maxhp": 100}))]
+        );
+        assert_eq!(consumed, input.len());
+    }
+
+    #[test]
+    fn test_parsing_stops_at_compression_start() {
+        let mut input = b"Hi".to_vec();
+        input.extend_from_slice(&[IAC, TelnetCommand::Sb as u8, TelnetOption::Mccp2.as_byte(), IAC, TelnetCommand::Se as u8]);
+        input.extend_from_slice(&[0x78, 0x9c, IAC, IAC]);
+
+        let (data, events, consumed) = parse_telnet_data(&input);
+        assert_eq!(data, b"Hi");
+        assert_eq!(events, vec![TelnetEvent::Subnegotiation(TelnetOption::Mccp2, Vec::new())]);
+        assert_eq!(consumed, 7, "壓縮資料留在緩衝區");
+    }
+
+    #[test]
+    fn test_split_subnegotiation_waits_for_se() {
+        let mut input = b"Hi ".to_vec();
+        input.extend_from_slice(&[IAC, TelnetCommand::Sb as u8, 99, b'a', IAC]);
+        let (data, events, consumed) = parse_telnet_data(&input);
+        assert_eq!(data, b"Hi ");
+        assert!(events.is_empty());
+        assert_eq!(consumed, 3, "未完成的 SB 留在緩衝區");
+
+        input.extend_from_slice(&[TelnetCommand::Se as u8, b'!']);
+        let (data, _, consumed) = parse_telnet_data(&input[3..]);
+        assert_eq!(data, b"!");
+        assert_eq!(consumed, input.len() - 3);
+    }
+
     #[test]
     fn test_generate_refusal_for_unknown_option() {
-        let response = generate_refusal(TelnetCommand::Do, TelnetOption::Mccp2);
-        assert_eq!(response, vec![IAC, TelnetCommand::Wont as u8, TelnetOption::Mccp2.as_byte()]);
+        let table = NegotiationTable::default();
+        let response = table.respond(TelnetCommand::Do, TelnetOption::Mccp3);
+        assert_eq!(response, vec![IAC, TelnetCommand::Wont as u8, TelnetOption::Mccp3.as_byte()]);
+
+        let response = table.respond(TelnetCommand::Will, TelnetOption::Unknown(99));
+        assert_eq!(response, vec![IAC, TelnetCommand::Dont as u8, 99]);
     }
 
     #[test]
     fn test_accept_echo() {
-        let response = generate_refusal(TelnetCommand::Will, TelnetOption::Echo);
+        let response = NegotiationTable::default().respond(TelnetCommand::Will, TelnetOption::Echo);
         assert_eq!(response, vec![IAC, TelnetCommand::Do as u8, TelnetOption::Echo.as_byte()]);
     }
 
+    #[test]
+    fn test_negotiation_table_accept() {
+        let mut table = NegotiationTable::default();
+        table.set(TelnetOption::Gmcp, NegotiationPolicy::Accept);
+
+        let response = table.respond(TelnetCommand::Will, TelnetOption::Gmcp);
+        assert_eq!(response, vec![IAC, TelnetCommand::Do as u8, TelnetOption::Gmcp.as_byte()]);
+        let response = table.respond(TelnetCommand::Do, TelnetOption::Gmcp);
+        assert_eq!(response, vec![IAC, TelnetCommand::Will as u8, TelnetOption::Gmcp.as_byte()]);
+        assert!(table.respond(TelnetCommand::Wont, TelnetOption::Gmcp).is_empty());
+    }
+
     #[test]
     fn test_telnet_option_roundtrip() {
         for byte in 0..=255u8 {
@@ -334,4 +660,61 @@ mod tests {
             assert_eq!(option.as_byte(), byte);
         }
     }
+
+    #[test]
+    fn test_sga_will_replies_do() {
+        let mut state = OptionState::default();
+        let response = state.handle_sga(TelnetCommand::Will);
+        assert_eq!(response, vec![IAC, TelnetCommand::Do as u8, 3]);
+        assert!(state.remote_sga);
+
+        // 重複的 WILL 不再回應
+        assert!(state.handle_sga(TelnetCommand::Will).is_empty());
+
+        let response = state.handle_sga(TelnetCommand::Wont);
+        assert_eq!(response, vec![IAC, TelnetCommand::Dont as u8, 3]);
+        assert!(!state.remote_sga);
+    }
+
+    #[test]
+    fn test_sga_do_replies_will() {
+        let mut state = OptionState::default();
+        let response = state.handle_sga(TelnetCommand::Do);
+        assert_eq!(response, vec![IAC, TelnetCommand::Will as u8, 3]);
+        assert!(state.local_sga);
+        assert!(!state.remote_sga);
+    }
+
+    #[test]
+    fn test_encode_naws() {
+        let (sb, se) = (TelnetCommand::Sb as u8, TelnetCommand::Se as u8);
+        assert_eq!(encode_naws(80, 24), vec![IAC, sb, 31, 0, 80, 0, 24, IAC, se]);
+        // 0xFF 需跳脫
+        assert_eq!(encode_naws(255, 0x01FF), vec![IAC, sb, 31, 0, IAC, IAC, 1, IAC, IAC, IAC, se]);
+    }
+
+    #[test]
+    fn test_terminal_type_cycle() {
+        let is = |name: &str| {
+            [&[IAC, TelnetCommand::Sb as u8, 24, TTYPE_IS][..], name.as_bytes(), &[IAC, TelnetCommand::Se as u8]].concat()
+        };
+        let mut cycle = TerminalTypeCycle::default();
+        assert_eq!(cycle.next_response("MudClient-rs", 9), is("MudClient-rs"));
+        assert_eq!(cycle.next_response("MudClient-rs", 9), is("XTERM-256COLOR"));
+        assert_eq!(cycle.next_response("MudClient-rs", 9), is("MTTS 9"));
+        // 列完後重複最後一項
+        assert_eq!(cycle.next_response("MudClient-rs", 9), is("MTTS 9"));
+    }
+
+    #[test]
+    fn test_server_echo_state() {
+        let mut state = OptionState::default();
+        let response = state.handle_echo(TelnetCommand::Will);
+        assert_eq!(response, Some(vec![IAC, TelnetCommand::Do as u8, 1]));
+        assert!(state.remote_echo);
+
+        assert_eq!(state.handle_echo(TelnetCommand::Wont), Some(vec![IAC, TelnetCommand::Dont as u8, 1]));
+        assert!(!state.remote_echo);
+        assert_eq!(state.handle_echo(TelnetCommand::Do), None);
+    }
 }
@@ -2,16 +2,23 @@
 //!
 //! 自動偵測訊息並執行動作
 
+use crate::ansi::Color;
 use regex::Regex;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 /// 觸發器動作
 #[derive(Debug, Clone)]
 pub enum TriggerAction {
-    /// 發送命令到 MUD
+    /// 發送命令到 MUD（如同玩家輸入，會再經過分號拆分、別名與輸入觸發器）
     SendCommand(String),
-    /// 高亮顯示（前景色 RGB）
-    Highlight { r: u8, g: u8, b: u8 },
+    /// 直接發送命令到 MUD（不經別名與觸發器，可避免連鎖觸發）
+    SendRawCommand(String),
+    /// 以 ANSI 顏色標示匹配範圍（只改變匹配的區段，不影響整行）
+    Highlight { fg: Option<Color>, bg: Option<Color>, bold: bool },
     /// 抑制訊息（不顯示）
     Gag,
     /// 播放音效（路徑）
@@ -20,6 +27,16 @@ pub enum TriggerAction {
     ExecuteScript(String),
     /// 路由到子視窗
     RouteToWindow(String),
+    /// 設定腳本變數（value 支援 $1 捕獲替換）
+    SetVariable { name: String, value: String },
+    /// 累加數值變數（不存在或非數值時視為 0）
+    IncrementVariable { name: String, by: i64 },
+    /// 以替換文字取代顯示行中的匹配範圍（支援 $1 捕獲替換）
+    Substitute { replacement: String },
+    /// 附加一行到 Session 筆記（支援 $1 捕獲替換）
+    AppendNote(String),
+    /// 附加一行到檔案（path 與 text 皆支援 $1 捕獲替換）
+    AppendFile { path: String, text: String },
 }
 
 /// 觸發器匹配模式
@@ -33,6 +50,92 @@ pub enum TriggerPattern {
     EndsWith(String),
     /// 正則表達式
     Regex(String),
+    /// 任一子模式符合即可（捕獲取自第一個符合者）
+    AnyOf(Vec<TriggerPattern>),
+    /// 所有子模式皆須符合（捕獲依序合併）
+    AllOf(Vec<TriggerPattern>),
+    /// 連續多行：子模式依序比對最近的幾行，最後一個比對目前這行（捕獲依行序合併）
+    ///
+    /// 只能作為最外層模式，最多比對 [`MULTILINE_MAX_LINES`] 行。
+    MultiLine(Vec<TriggerPattern>),
+}
+
+/// 多行模式最多可跨越的行數（`TriggerManager` 保留的近期輸出行數）
+pub const MULTILINE_MAX_LINES: usize = 10;
+
+impl TriggerPattern {
+    /// 模式文字；組合模式以 `, ` 連接各子模式
+    pub fn source(&self) -> String {
+        match self {
+            TriggerPattern::Contains(s)
+            | TriggerPattern::StartsWith(s)
+            | TriggerPattern::EndsWith(s)
+            | TriggerPattern::Regex(s) => s.clone(),
+            TriggerPattern::AnyOf(patterns) | TriggerPattern::AllOf(patterns) | TriggerPattern::MultiLine(patterns) => {
+                patterns.iter().map(TriggerPattern::source).collect::<Vec<_>>().join(", ")
+            }
+        }
+    }
+
+    /// 收集所有（含巢狀）正則模式的原始文字
+    fn regex_sources<'a>(&'a self, sources: &mut Vec<&'a str>) {
+        match self {
+            TriggerPattern::Regex(re) => sources.push(re),
+            TriggerPattern::AnyOf(patterns) | TriggerPattern::AllOf(patterns) | TriggerPattern::MultiLine(patterns) => {
+                for pattern in patterns {
+                    pattern.regex_sources(sources);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 觸發器的觸發範圍（依是否為提示符行）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FireScope {
+    /// 所有行
+    #[default]
+    AnyLine,
+    /// 僅提示符行（如狀態列數值更新）
+    PromptOnly,
+    /// 僅非提示符行（避免提示符造成雜訊）
+    NonPromptOnly,
+}
+
+impl FireScope {
+    /// 此範圍是否接受該行
+    pub fn allows(&self, is_prompt: bool) -> bool {
+        match self {
+            FireScope::AnyLine => true,
+            FireScope::PromptOnly => is_prompt,
+            FireScope::NonPromptOnly => !is_prompt,
+        }
+    }
+}
+
+/// 觸發器的比對對象（伺服器輸出或玩家輸入）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TriggerScope {
+    /// 僅伺服器輸出
+    #[default]
+    Output,
+    /// 僅玩家輸入的指令（如指令巨集）
+    Input,
+    /// 兩者皆比對
+    Both,
+}
+
+impl TriggerScope {
+    /// 是否比對伺服器輸出
+    pub fn allows_output(&self) -> bool {
+        matches!(self, TriggerScope::Output | TriggerScope::Both)
+    }
+
+    /// 是否比對玩家輸入
+    pub fn allows_input(&self) -> bool {
+        matches!(self, TriggerScope::Input | TriggerScope::Both)
+    }
 }
 
 /// 觸發器定義
@@ -42,31 +145,74 @@ pub struct Trigger {
     pub name: String,
     /// 分類
     pub category: Option<String>,
+    /// 說明（備註用途，不影響匹配）
+    pub description: String,
     /// 匹配模式
     pub pattern: TriggerPattern,
     /// 執行動作列表
     pub actions: Vec<TriggerAction>,
-    /// 是否啟用
+    /// 目前是否啟用（執行期狀態，腳本可切換）
     pub enabled: bool,
-    /// 編譯後的正則（內部使用）
-    compiled_regex: Option<Regex>,
+    /// 啟動時是否啟用（寫入設定檔）
+    pub default_enabled: bool,
+    /// 觸發範圍
+    pub fire_on: FireScope,
+    /// 比對對象
+    pub scope: TriggerScope,
+    /// 觸發次數上限，達到後自動停用（`None` 表示不限）
+    pub max_fires: Option<u32>,
+    /// 啟用後已觸發的次數（執行期狀態，不寫入設定檔）
+    pub fire_count: u32,
+    /// 前置觸發器：須在指定時間內觸發過才會觸發（序列觸發）
+    pub requires_prior: Option<(String, Duration)>,
+    /// 優先級：數字大者先比對、先執行，同優先級依加入順序
+    pub priority: i32,
+    /// 一次性觸發器：觸發後停用，並在下次比對前移除
+    pub one_shot: bool,
+    /// 冷卻時間：上次觸發後此時間內不再觸發（零表示不限）
+    pub cooldown: Duration,
+    /// 正則編譯失敗的錯誤訊息（有錯誤的正則永遠不會匹配）
+    pub error: Option<String>,
+    /// 編譯後的正則，以原始文字為鍵（內部使用）
+    compiled_regexes: HashMap<String, Regex>,
 }
 
 impl Trigger {
     /// 創建新的觸發器
     pub fn new(name: impl Into<String>, pattern: TriggerPattern) -> Self {
-        let compiled = match &pattern {
-            TriggerPattern::Regex(re) => Regex::new(re).ok(),
-            _ => None,
-        };
+        let mut sources = Vec::new();
+        pattern.regex_sources(&mut sources);
+        let mut error = None;
+        let mut compiled = HashMap::new();
+        for re in sources {
+            match Regex::new(re) {
+                Ok(regex) => {
+                    compiled.insert(re.to_string(), regex);
+                }
+                Err(e) => {
+                    error.get_or_insert_with(|| e.to_string());
+                }
+            }
+        }
 
         Self {
             name: name.into(),
             category: None,
+            description: String::new(),
             pattern,
             actions: Vec::new(),
             enabled: true,
-            compiled_regex: compiled,
+            default_enabled: true,
+            fire_on: FireScope::default(),
+            scope: TriggerScope::default(),
+            max_fires: None,
+            fire_count: 0,
+            requires_prior: None,
+            priority: 0,
+            one_shot: false,
+            cooldown: Duration::ZERO,
+            error,
+            compiled_regexes: compiled,
         }
     }
 
@@ -76,6 +222,70 @@ impl Trigger {
         self
     }
 
+    /// 設定說明
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// 設定啟動時是否啟用（同時作為目前狀態）
+    pub fn with_default_enabled(mut self, enabled: bool) -> Self {
+        self.default_enabled = enabled;
+        self.enabled = enabled;
+        self
+    }
+
+    /// 設定觸發範圍
+    pub fn with_fire_on(mut self, fire_on: FireScope) -> Self {
+        self.fire_on = fire_on;
+        self
+    }
+
+    /// 設定比對對象
+    pub fn with_scope(mut self, scope: TriggerScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// 設定觸發次數上限
+    pub fn with_max_fires(mut self, max_fires: u32) -> Self {
+        self.max_fires = Some(max_fires);
+        self
+    }
+
+    /// 設定前置觸發器：`name` 須在 `within` 內觸發過
+    pub fn with_requires_prior(mut self, name: impl Into<String>, within: Duration) -> Self {
+        self.requires_prior = Some((name.into(), within));
+        self
+    }
+
+    /// 設定優先級
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 設定冷卻時間
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// 設為一次性觸發器
+    pub fn with_one_shot(mut self) -> Self {
+        self.one_shot = true;
+        self
+    }
+
+    /// 記錄一次觸發；用完次數時停用並歸零，重新啟用後可再觸發 N 次
+    fn record_fire(&mut self) {
+        self.fire_count += 1;
+        if self.max_fires.is_some_and(|max| self.fire_count >= max) {
+            self.enabled = false;
+            self.fire_count = 0;
+        }
+    }
+
     /// 添加動作
     pub fn add_action(mut self, action: TriggerAction) -> Self {
         self.actions.push(action);
@@ -84,60 +294,94 @@ impl Trigger {
 
     /// 嘗試匹配訊息，返回捕獲的群組（如果有）
     pub fn try_match(&self, message: &str) -> Option<TriggerMatch> {
+        self.try_match_lines(&[message])
+    }
+
+    /// 以最近的幾行（最後一行為目前這行）嘗試匹配
+    ///
+    /// 多行模式的 `matched_text` 為各行匹配文字以 `\n` 連接，`span` 為目前這行的匹配範圍。
+    pub fn try_match_lines(&self, lines: &[&str]) -> Option<TriggerMatch> {
         if !self.enabled {
             return None;
         }
+        let message = *lines.last()?;
 
-        match &self.pattern {
+        let TriggerPattern::MultiLine(patterns) = &self.pattern else {
+            let (span, captures) = self.match_pattern(&self.pattern, message)?;
+            return Some(TriggerMatch {
+                trigger_name: self.name.clone(),
+                matched_text: message[span.clone()].to_string(),
+                captures,
+                span,
+            });
+        };
+
+        if patterns.is_empty() {
+            return None;
+        }
+        let window = &lines[lines.len().checked_sub(patterns.len())?..];
+        let mut texts = Vec::with_capacity(patterns.len());
+        let mut captures = Vec::new();
+        let mut span = 0..0;
+        for (pattern, line) in patterns.iter().zip(window) {
+            let (line_span, line_captures) = self.match_pattern(pattern, line)?;
+            texts.push(&line[line_span.clone()]);
+            captures.extend(line_captures);
+            span = line_span;
+        }
+        Some(TriggerMatch {
+            trigger_name: self.name.clone(),
+            matched_text: texts.join("\n"),
+            captures,
+            span,
+        })
+    }
+
+    /// 比對單一模式，返回匹配範圍與捕獲群組
+    fn match_pattern(&self, pattern: &TriggerPattern, message: &str) -> Option<(Range<usize>, Vec<String>)> {
+        match pattern {
             TriggerPattern::Contains(s) => {
-                if message.contains(s) {
-                    Some(TriggerMatch {
-                        trigger_name: self.name.clone(),
-                        matched_text: s.clone(),
-                        captures: vec![],
-                    })
-                } else {
-                    None
-                }
-            }
-            TriggerPattern::StartsWith(s) => {
-                if message.starts_with(s) {
-                    Some(TriggerMatch {
-                        trigger_name: self.name.clone(),
-                        matched_text: s.clone(),
-                        captures: vec![],
-                    })
-                } else {
-                    None
-                }
+                let start = message.find(s.as_str())?;
+                Some((start..start + s.len(), vec![]))
             }
+            TriggerPattern::StartsWith(s) => message.starts_with(s.as_str()).then_some((0..s.len(), vec![])),
             TriggerPattern::EndsWith(s) => {
-                if message.ends_with(s) {
-                    Some(TriggerMatch {
-                        trigger_name: self.name.clone(),
-                        matched_text: s.clone(),
-                        captures: vec![],
-                    })
+                if message.ends_with(s.as_str()) {
+                    Some((message.len() - s.len()..message.len(), vec![]))
                 } else {
                     None
                 }
             }
-            TriggerPattern::Regex(_) => {
-                let regex = self.compiled_regex.as_ref()?;
+            TriggerPattern::Regex(re) => {
+                let regex = self.compiled_regexes.get(re)?;
                 let captures = regex.captures(message)?;
-                
+
                 let groups: Vec<String> = captures
                     .iter()
                     .skip(1)
                     .filter_map(|m| m.map(|m| m.as_str().to_string()))
                     .collect();
 
-                Some(TriggerMatch {
-                    trigger_name: self.name.clone(),
-                    matched_text: captures.get(0)?.as_str().to_string(),
-                    captures: groups,
-                })
+                Some((captures.get(0)?.range(), groups))
             }
+            TriggerPattern::AnyOf(patterns) => patterns.iter().find_map(|p| self.match_pattern(p, message)),
+            TriggerPattern::AllOf(patterns) => {
+                // 匹配範圍取涵蓋所有子模式的最小區間
+                let mut result: Option<(Range<usize>, Vec<String>)> = None;
+                for p in patterns {
+                    let (span, captures) = self.match_pattern(p, message)?;
+                    result = Some(match result {
+                        Some((merged, mut all)) => {
+                            all.extend(captures);
+                            (merged.start.min(span.start)..merged.end.max(span.end), all)
+                        }
+                        None => (span, captures),
+                    });
+                }
+                result
+            }
+            // 多行模式只在最外層由 try_match_lines 處理
+            TriggerPattern::MultiLine(_) => None,
         }
     }
 }
@@ -151,14 +395,34 @@ pub struct TriggerMatch {
     pub matched_text: String,
     /// 捕獲的群組（僅 Regex 模式）
     pub captures: Vec<String>,
+    /// 匹配範圍（去除 ANSI 後文字的位元組位置）
+    pub span: Range<usize>,
+}
+
+impl TriggerMatch {
+    /// 將模板中的 `$1` / `%1`... 替換為捕獲群組，`$0` / `%0` 為整個匹配
+    ///
+    /// 規則見 [`crate::placeholder::expand_captures`]。
+    pub fn expand(&self, template: &str) -> String {
+        let groups: Vec<&str> = self.captures.iter().map(String::as_str).collect();
+        crate::placeholder::expand_captures(template, &self.matched_text, &groups)
+    }
 }
 
 /// 觸發器管理器
 #[derive(Debug, Default)]
 pub struct TriggerManager {
     pub triggers: HashMap<String, Trigger>,
-    /// 按優先級排序的觸發器列表
+    /// 觸發器加入順序（同優先級時依此順序比對）
     pub order: Vec<String>,
+    /// 各觸發器最後一次觸發的時間（供序列觸發判斷）
+    last_fired: HashMap<String, Instant>,
+    /// 已觸發、等待移除的一次性觸發器
+    spent: Vec<String>,
+    /// 最近的輸出行（已去除 ANSI，含目前這行），供多行模式比對
+    recent_lines: VecDeque<String>,
+    /// 下一個暫時觸發器的編號
+    next_temp_id: u32,
 }
 
 impl TriggerManager {
@@ -176,12 +440,33 @@ impl TriggerManager {
         }
     }
 
+    /// 添加一次性觸發器並自動命名（`temp:N`），回傳名稱
+    pub fn add_temp(&mut self, pattern: TriggerPattern, action: TriggerAction) -> String {
+        self.next_temp_id += 1;
+        let name = format!("temp:{}", self.next_temp_id);
+        self.add(Trigger::new(name.clone(), pattern).with_one_shot().add_action(action));
+        name
+    }
+
     /// 移除觸發器
     pub fn remove(&mut self, name: &str) -> Option<Trigger> {
         self.order.retain(|n| n != name);
+        self.last_fired.remove(name);
         self.triggers.remove(name)
     }
 
+    /// 一次移除多個觸發器，回傳實際移除的數量
+    pub fn remove_many<S: AsRef<str>>(&mut self, names: &[S]) -> usize {
+        let before = self.triggers.len();
+        for name in names {
+            self.triggers.remove(name.as_ref());
+            self.last_fired.remove(name.as_ref());
+        }
+        let triggers = &self.triggers;
+        self.order.retain(|n| triggers.contains_key(n));
+        before - self.triggers.len()
+    }
+
     /// 獲取觸發器
     pub fn get(&self, name: &str) -> Option<&Trigger> {
         self.triggers.get(name)
@@ -192,6 +477,28 @@ impl TriggerManager {
         self.triggers.get_mut(name)
     }
 
+    /// 重新命名分類，若新分類已存在則直接合併；新名稱為空時改為未分類
+    ///
+    /// 回傳受影響的觸發器數量。`order` 與分類無關，維持不變。
+    pub fn rename_category(&mut self, old: &str, new: &str) -> usize {
+        let new = new.trim();
+        let mut count = 0;
+        for trigger in self.triggers.values_mut() {
+            if trigger.category.as_deref() == Some(old) {
+                trigger.category = (!new.is_empty()).then(|| new.to_string());
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// 將目前的啟用狀態存為預設值
+    pub fn save_enabled_as_default(&mut self) {
+        for trigger in self.triggers.values_mut() {
+            trigger.default_enabled = trigger.enabled;
+        }
+    }
+
     /// 獲取所有觸發器
     pub fn list(&self) -> Vec<&Trigger> {
         self.order
@@ -200,22 +507,112 @@ impl TriggerManager {
             .collect()
     }
 
-    /// 處理訊息，返回所有匹配的觸發器及其動作
-    pub fn process(&self, message: &str) -> Vec<(&Trigger, TriggerMatch)> {
-        let mut matches = Vec::new();
-        
+    /// 處理伺服器訊息，返回所有匹配的觸發器及其動作
+    ///
+    /// `is_prompt` 表示此行是否為提示符，用於過濾觸發範圍（`FireScope`）。
+    /// 匹配的觸發器會累計觸發次數，用完 `max_fires` 時自動停用。
+    /// 每行都會記入近期輸出，供多行模式跨行比對。
+    pub fn process(&mut self, message: &str, is_prompt: bool) -> Vec<(&Trigger, TriggerMatch)> {
         // 剝離 ANSI 控制碼以支援純文字模式匹配帶色訊息
-        let stripped = Self::strip_ansi(message);
+        if self.recent_lines.len() >= MULTILINE_MAX_LINES {
+            self.recent_lines.pop_front();
+        }
+        self.recent_lines.push_back(Self::strip_ansi(message));
+        // 比對期間暫時取出近期輸出，直接借用各行而不必複製
+        let recent = std::mem::take(&mut self.recent_lines);
+        let lines: Vec<&str> = recent.iter().map(String::as_str).collect();
+        let fired = self.match_triggers(|trigger| {
+            if !trigger.scope.allows_output() || !trigger.fire_on.allows(is_prompt) {
+                return None;
+            }
+            trigger.try_match_lines(&lines)
+        });
+        self.recent_lines = recent;
+        self.collect_fired(fired)
+    }
+
+    /// 處理玩家輸入的指令，僅比對 `Input` / `Both` 的觸發器
+    pub fn process_input(&mut self, input: &str) -> Vec<(&Trigger, TriggerMatch)> {
+        self.fire(|trigger| trigger.scope.allows_input().then(|| trigger.try_match(input)).flatten())
+    }
 
-        for name in &self.order {
-            if let Some(trigger) = self.triggers.get(name) {
-                if let Some(m) = trigger.try_match(&stripped) {
-                    matches.push((trigger, m));
+    /// 依優先級比對觸發器並記錄觸發次數，返回觸發的觸發器（停用前的動作仍會執行）
+    ///
+    /// 設有前置觸發器者，前置觸發器未在時間內觸發過時略過；仍在冷卻時間內的觸發器亦略過。
+    /// 有 Gag 動作的觸發器觸發後，優先級較低的觸發器不再比對（同優先級仍會執行）。
+    fn fire(&mut self, matcher: impl Fn(&Trigger) -> Option<TriggerMatch>) -> Vec<(&Trigger, TriggerMatch)> {
+        let fired = self.match_triggers(matcher);
+        self.collect_fired(fired)
+    }
+
+    /// 依序比對並記錄觸發，回傳觸發的觸發器名稱與匹配結果
+    fn match_triggers(&mut self, matcher: impl Fn(&Trigger) -> Option<TriggerMatch>) -> Vec<(String, TriggerMatch)> {
+        // 上次已觸發的一次性觸發器在此移除，避免在回傳借用期間變動
+        let spent = std::mem::take(&mut self.spent);
+        self.remove_many(&spent);
+
+        let now = Instant::now();
+        let mut names: Vec<&String> = self.order.iter().collect();
+        names.sort_by_key(|name| Reverse(self.triggers.get(*name).map_or(0, |t| t.priority)));
+        let mut gag_priority = None;
+        let mut fired = Vec::new();
+        for name in names {
+            if let Some(trigger) = self.triggers.get_mut(name) {
+                if gag_priority.is_some_and(|priority| trigger.priority < priority) {
+                    break;
+                }
+                let cooling = !trigger.cooldown.is_zero()
+                    && self
+                        .last_fired
+                        .get(name.as_str())
+                        .is_some_and(|at| now.saturating_duration_since(*at) < trigger.cooldown);
+                if cooling {
+                    continue;
+                }
+                if let Some((prior, within)) = &trigger.requires_prior {
+                    let recent = self
+                        .last_fired
+                        .get(prior)
+                        .is_some_and(|at| now.saturating_duration_since(*at) <= *within);
+                    if !recent {
+                        continue;
+                    }
+                }
+                if let Some(m) = matcher(trigger) {
+                    if trigger.actions.iter().any(|a| matches!(a, TriggerAction::Gag)) {
+                        gag_priority.get_or_insert(trigger.priority);
+                    }
+                    trigger.record_fire();
+                    if trigger.one_shot {
+                        trigger.enabled = false;
+                        self.spent.push(name.clone());
+                    }
+                    self.last_fired.insert(name.clone(), now);
+                    fired.push((name.clone(), m));
                 }
             }
         }
+        fired
+    }
 
-        matches
+    /// 將觸發的名稱轉回觸發器參照
+    fn collect_fired(&self, fired: Vec<(String, TriggerMatch)>) -> Vec<(&Trigger, TriggerMatch)> {
+        fired
+            .into_iter()
+            .filter_map(|(name, m)| self.triggers.get(&name).map(|trigger| (trigger, m)))
+            .collect()
+    }
+
+    /// 找出會匹配此輸出行的已啟用觸發器名稱（不分觸發範圍）
+    pub fn matching_names(&self, line: &str) -> Vec<String> {
+        let stripped = Self::strip_ansi(line);
+        self.order
+            .iter()
+            .filter_map(|name| self.triggers.get(name))
+            .filter(|trigger| trigger.scope.allows_output())
+            .filter(|trigger| trigger.try_match(&stripped).is_some())
+            .map(|trigger| trigger.name.clone())
+            .collect()
     }
 
     /// 移除 ANSI 轉義碼
@@ -244,18 +641,14 @@ impl TriggerManager {
     }
 
     /// 收集需要發送的命令
-    pub fn collect_commands(&self, message: &str) -> Vec<String> {
+    pub fn collect_commands(&mut self, message: &str) -> Vec<String> {
         let mut commands = Vec::new();
 
-        for (trigger, m) in self.process(message) {
+        for (trigger, m) in self.process(message, false) {
             for action in &trigger.actions {
-                if let TriggerAction::SendCommand(cmd) = action {
-                    let mut expanded = cmd.clone();
+                if let TriggerAction::SendCommand(cmd) | TriggerAction::SendRawCommand(cmd) = action {
                     // 替換捕獲群組
-                    for (i, cap) in m.captures.iter().enumerate() {
-                        expanded = expanded.replace(&format!("${}", i + 1), cap);
-                    }
-                    commands.push(expanded);
+                    commands.push(m.expand(cmd));
                 }
             }
         }
@@ -264,8 +657,8 @@ impl TriggerManager {
     }
 
     /// 檢查訊息是否應該被抑制（Gag）
-    pub fn should_gag(&self, message: &str) -> bool {
-        for (trigger, _) in self.process(message) {
+    pub fn should_gag(&mut self, message: &str) -> bool {
+        for (trigger, _) in self.process(message, false) {
             for action in &trigger.actions {
                 if matches!(action, TriggerAction::Gag) {
                     return true;
@@ -283,7 +676,7 @@ mod tests {
     #[test]
     fn test_contains_trigger() {
         let trigger = Trigger::new("hp_warn", TriggerPattern::Contains("你受傷了".to_string()))
-            .add_action(TriggerAction::Highlight { r: 255, g: 0, b: 0 });
+            .add_action(TriggerAction::Highlight { fg: Some(Color::rgb(255, 0, 0)), bg: None, bold: false });
 
         assert!(trigger.try_match("戰鬥中你受傷了！").is_some());
         assert!(trigger.try_match("你恢復了健康").is_none());
@@ -301,6 +694,19 @@ mod tests {
         assert_eq!(m.captures, vec!["100".to_string()]);
     }
 
+    #[test]
+    fn test_invalid_regex_records_error() {
+        let trigger = Trigger::new("bad", TriggerPattern::AnyOf(vec![
+            TriggerPattern::Contains("ok".to_string()),
+            TriggerPattern::Regex(r"hp (\d+".to_string()),
+        ]));
+        assert!(trigger.error.as_deref().is_some_and(|e| e.contains("unclosed group")));
+        assert!(trigger.try_match("hp 100").is_none());
+
+        let trigger = Trigger::new("good", TriggerPattern::Regex(r"hp (\d+)".to_string()));
+        assert_eq!(trigger.error, None);
+    }
+
     #[test]
     fn test_trigger_manager() {
         let mut manager = TriggerManager::new();
@@ -335,6 +741,38 @@ mod tests {
         assert!(trigger.try_match("this is a test").is_none());
     }
 
+    #[test]
+    fn test_default_enabled_vs_runtime() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("loot", TriggerPattern::Contains("倒下".to_string())).with_default_enabled(false));
+        assert!(!manager.get("loot").unwrap().enabled);
+
+        // 執行期切換不影響預設值
+        manager.get_mut("loot").unwrap().enabled = true;
+        assert!(!manager.get("loot").unwrap().default_enabled);
+
+        manager.save_enabled_as_default();
+        assert!(manager.get("loot").unwrap().default_enabled);
+    }
+
+    #[test]
+    fn test_rename_and_merge_category() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("a", TriggerPattern::Contains("a".to_string())).with_category("戰鬥"));
+        manager.add(Trigger::new("b", TriggerPattern::Contains("b".to_string())).with_category("戰鬥"));
+        manager.add(Trigger::new("c", TriggerPattern::Contains("c".to_string())).with_category("撿取"));
+
+        assert_eq!(manager.rename_category("戰鬥", "攻擊"), 2);
+        assert_eq!(manager.get("a").unwrap().category.as_deref(), Some("攻擊"));
+        assert_eq!(manager.get("b").unwrap().category.as_deref(), Some("攻擊"));
+
+        assert_eq!(manager.rename_category("撿取", "攻擊"), 1);
+        assert_eq!(manager.order, vec!["a", "b", "c"]);
+        assert!(manager.list().iter().all(|t| t.category.as_deref() == Some("攻擊")));
+
+        assert_eq!(manager.rename_category("不存在", "x"), 0);
+    }
+
     #[test]
     fn test_multiple_triggers() {
         let mut manager = TriggerManager::new();
@@ -342,7 +780,284 @@ mod tests {
         manager.add(Trigger::new("a", TriggerPattern::Contains("你".to_string())));
         manager.add(Trigger::new("b", TriggerPattern::Contains("金".to_string())));
 
-        let matches = manager.process("你獲得金幣");
+        let matches = manager.process("你獲得金幣", false);
         assert_eq!(matches.len(), 2);
     }
+
+    #[test]
+    fn test_match_expand_many_captures() {
+        let m = TriggerMatch {
+            trigger_name: "t".to_string(),
+            matched_text: String::new(),
+            captures: (1..=10).map(|i| format!("c{}", i)).collect(),
+            span: 0..0,
+        };
+        assert_eq!(m.expand("$1 $10"), "c1 c10");
+    }
+
+    #[test]
+    fn test_fire_scope() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("any", TriggerPattern::Contains("HP".to_string())));
+        manager.add(
+            Trigger::new("prompt", TriggerPattern::Contains("HP".to_string()))
+                .with_fire_on(FireScope::PromptOnly),
+        );
+        manager.add(
+            Trigger::new("normal", TriggerPattern::Contains("HP".to_string()))
+                .with_fire_on(FireScope::NonPromptOnly),
+        );
+
+        fn names(matches: Vec<(&Trigger, TriggerMatch)>) -> Vec<String> {
+            matches.into_iter().map(|(t, _)| t.name.clone()).collect()
+        }
+
+        assert_eq!(names(manager.process("(HP 100/100)", true)), vec!["any", "prompt"]);
+        assert_eq!(names(manager.process("你的 HP 回復了", false)), vec!["any", "normal"]);
+    }
+
+    #[test]
+    fn test_trigger_scope() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("out", TriggerPattern::Contains("kill".to_string())));
+        manager.add(
+            Trigger::new("in", TriggerPattern::Contains("kill".to_string())).with_scope(TriggerScope::Input),
+        );
+        manager.add(
+            Trigger::new("both", TriggerPattern::Contains("kill".to_string())).with_scope(TriggerScope::Both),
+        );
+
+        fn names(matches: Vec<(&Trigger, TriggerMatch)>) -> Vec<String> {
+            matches.into_iter().map(|(t, _)| t.name.clone()).collect()
+        }
+
+        assert_eq!(names(manager.process("kill rat", false)), vec!["out", "both"]);
+        assert_eq!(names(manager.process_input("kill rat")), vec!["in", "both"]);
+    }
+
+    #[test]
+    fn test_match_span() {
+        let contains = Trigger::new("c", TriggerPattern::Contains("金".to_string()));
+        assert_eq!(contains.try_match("你獲得金幣").unwrap().span, 9..12);
+
+        let ends = Trigger::new("e", TriggerPattern::EndsWith("幣".to_string()));
+        assert_eq!(ends.try_match("你獲得金幣").unwrap().span, 12..15);
+
+        let regex = Trigger::new("r", TriggerPattern::Regex(r"(\d+) 金".to_string()));
+        let m = regex.try_match("得到 30 金幣").unwrap();
+        assert_eq!(&"得到 30 金幣"[m.span.clone()], "30 金");
+    }
+
+    #[test]
+    fn test_max_fires_disables_trigger() {
+        let mut manager = TriggerManager::new();
+        manager.add(
+            Trigger::new("greet", TriggerPattern::Contains("向你打招呼".to_string()))
+                .with_max_fires(3)
+                .add_action(TriggerAction::SendCommand("wave".to_string())),
+        );
+
+        let fired: usize = (0..4).map(|_| manager.collect_commands("阿明向你打招呼").len()).sum();
+        assert_eq!(fired, 3);
+        assert!(!manager.get("greet").unwrap().enabled);
+
+        // 重新啟用後可再觸發 3 次
+        manager.get_mut("greet").unwrap().enabled = true;
+        assert_eq!(manager.process("阿明向你打招呼", false).len(), 1);
+        assert_eq!(manager.get("greet").unwrap().fire_count, 1);
+    }
+
+    fn sequence_manager() -> TriggerManager {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("poison", TriggerPattern::Contains("你中毒了".to_string())));
+        manager.add(
+            Trigger::new("dizzy", TriggerPattern::Contains("你感到一陣暈眩".to_string()))
+                .with_requires_prior("poison", Duration::from_secs(2))
+                .add_action(TriggerAction::SendCommand("quaff antidote".to_string())),
+        );
+        manager
+    }
+
+    #[test]
+    fn test_requires_prior_within_window() {
+        let mut manager = sequence_manager();
+        // 前置觸發器未觸發過
+        assert!(manager.collect_commands("你感到一陣暈眩").is_empty());
+
+        assert_eq!(manager.process("你中毒了！", false).len(), 1);
+        assert_eq!(manager.collect_commands("你感到一陣暈眩"), vec!["quaff antidote"]);
+    }
+
+    #[test]
+    fn test_requires_prior_window_lapsed() {
+        let mut manager = sequence_manager();
+        manager.process("你中毒了！", false);
+        manager
+            .last_fired
+            .insert("poison".to_string(), Instant::now() - Duration::from_secs(3));
+
+        assert!(manager.collect_commands("你感到一陣暈眩").is_empty());
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut manager = TriggerManager::new();
+        for name in ["a", "b", "c"] {
+            manager.add(Trigger::new(name, TriggerPattern::Contains(name.to_string())));
+        }
+
+        assert_eq!(manager.remove_many(&["a".to_string(), "c".to_string(), "x".to_string()]), 2);
+        assert_eq!(manager.order, vec!["b".to_string()]);
+        assert!(manager.get("a").is_none());
+    }
+
+    #[test]
+    fn test_matching_names() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("hp", TriggerPattern::Contains("HP".to_string())));
+        manager.add(
+            Trigger::new("prompt", TriggerPattern::Regex(r"^\(HP \d+".to_string()))
+                .with_fire_on(FireScope::PromptOnly),
+        );
+        manager.add(Trigger::new("mp", TriggerPattern::Contains("MP".to_string())));
+        manager.add(
+            Trigger::new("off", TriggerPattern::Contains("HP".to_string())).with_default_enabled(false),
+        );
+
+        assert_eq!(manager.matching_names("\x1b[31m(HP 100/100)\x1b[0m"), vec!["hp", "prompt"]);
+        assert_eq!(manager.matching_names("你的 MP 回復了"), vec!["mp"]);
+        assert!(manager.matching_names("nothing").is_empty());
+    }
+
+    #[test]
+    fn test_any_of_matches_any_alternative() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new(
+            "flee",
+            TriggerPattern::AnyOf(vec![
+                TriggerPattern::Contains("你快死了".to_string()),
+                TriggerPattern::Regex(r"^(\w+) hits you very hard".to_string()),
+            ]),
+        ));
+
+        let fired = manager.process("orc hits you very hard!", false);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].1.captures, vec!["orc"]);
+        assert_eq!(manager.process("你快死了！", false).len(), 1);
+        assert!(manager.process("orc misses you", false).is_empty());
+        assert!(Trigger::new("empty", TriggerPattern::AnyOf(vec![])).try_match("x").is_none());
+    }
+
+    #[test]
+    fn test_priority_order_and_gag() {
+        let mut manager = TriggerManager::new();
+        manager.add(
+            Trigger::new("attack", TriggerPattern::Contains("orc".to_string()))
+                .add_action(TriggerAction::SendCommand("kill orc".to_string())),
+        );
+        manager.add(
+            Trigger::new("flee", TriggerPattern::Contains("orc".to_string()))
+                .with_priority(10)
+                .add_action(TriggerAction::SendCommand("flee".to_string())),
+        );
+        manager.add(Trigger::new("log", TriggerPattern::Contains("orc".to_string())));
+
+        let names: Vec<_> = manager.process("An orc arrives.", false).iter().map(|(t, _)| t.name.clone()).collect();
+        assert_eq!(names, vec!["flee", "attack", "log"]);
+
+        // 高優先級的 Gag 擋下較低優先級的觸發器
+        manager.add(
+            Trigger::new("hide", TriggerPattern::Contains("orc".to_string()))
+                .with_priority(5)
+                .add_action(TriggerAction::Gag),
+        );
+        let names: Vec<_> = manager.process("An orc arrives.", false).iter().map(|(t, _)| t.name.clone()).collect();
+        assert_eq!(names, vec!["flee", "hide"]);
+    }
+
+    #[test]
+    fn test_one_shot_trigger_removed_after_firing() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new("nod", TriggerPattern::Contains("守衛點了點頭".to_string())).with_one_shot());
+        let temp = manager.add_temp(
+            TriggerPattern::Contains("守衛".to_string()),
+            TriggerAction::SendCommand("north".to_string()),
+        );
+        assert_eq!(temp, "temp:1");
+        assert!(manager.process("守衛看了你一眼", false).iter().any(|(t, _)| t.name == temp));
+
+        let fired = manager.process("守衛點了點頭。", false);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].0.name, "nod");
+        assert!(manager.process("守衛點了點頭。", false).is_empty());
+        assert!(manager.get("nod").is_none());
+        assert!(manager.get(&temp).is_none());
+        assert!(manager.order.is_empty());
+        assert!(manager.last_fired.is_empty(), "移除後不應留下冷卻記錄");
+    }
+
+    #[test]
+    fn test_cooldown_skips_repeated_fire() {
+        let mut manager = TriggerManager::new();
+        manager.add(
+            Trigger::new("heal", TriggerPattern::Contains("you are wounded".to_string()))
+                .with_cooldown(Duration::from_secs(5))
+                .add_action(TriggerAction::SendCommand("quaff potion".to_string())),
+        );
+        manager.add(Trigger::new("count", TriggerPattern::Contains("you are wounded".to_string())));
+
+        let names = |fired: Vec<(&Trigger, TriggerMatch)>| fired.iter().map(|(t, _)| t.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(manager.process("Ouch, you are wounded.", false)), vec!["heal", "count"]);
+        assert_eq!(names(manager.process("Ouch, you are wounded.", false)), vec!["count"]);
+
+        // 冷卻結束後可再觸發
+        manager.last_fired.insert("heal".to_string(), Instant::now() - Duration::from_secs(6));
+        assert_eq!(names(manager.process("Ouch, you are wounded.", false)), vec!["heal", "count"]);
+    }
+
+    #[test]
+    fn test_multiline_pattern_across_two_lines() {
+        let mut manager = TriggerManager::new();
+        manager.add(Trigger::new(
+            "room",
+            TriggerPattern::MultiLine(vec![
+                TriggerPattern::Regex(r"^(\S+)廣場$".to_string()),
+                TriggerPattern::Regex(r"^\[出口: (.+)\]$".to_string()),
+            ]),
+        ));
+
+        assert!(manager.process("台北廣場", false).is_empty());
+        let fired = manager.process("\x1b[36m[出口: 北 南]\x1b[0m", false);
+        assert_eq!(fired.len(), 1);
+        let m = &fired[0].1;
+        assert_eq!(m.captures, vec!["台北", "北 南"]);
+        assert_eq!(m.matched_text, "台北廣場\n[出口: 北 南]");
+        assert_eq!(m.expand("$1: $2"), "台北: 北 南");
+        assert_eq!(m.span, 0..17);
+
+        // 兩行之間夾了其他行就不算
+        manager.process("台北廣場", false);
+        manager.process("這裡人來人往。", false);
+        assert!(manager.process("[出口: 北 南]", false).is_empty());
+        assert_eq!(manager.get("room").unwrap().pattern.source(), r"^(\S+)廣場$, ^\[出口: (.+)\]$");
+    }
+
+    #[test]
+    fn test_all_of_requires_every_pattern() {
+        let trigger = Trigger::new(
+            "loot",
+            TriggerPattern::AllOf(vec![
+                TriggerPattern::Regex(r"(\w+) is DEAD".to_string()),
+                TriggerPattern::Regex(r"(\d+) gold".to_string()),
+            ]),
+        );
+
+        let m = trigger.try_match("The goblin is DEAD! You find 25 gold.").unwrap();
+        assert_eq!(m.captures, vec!["goblin", "25"]);
+        assert_eq!(m.matched_text, "goblin is DEAD! You find 25 gold");
+        assert_eq!(m.expand("$1: $2"), "goblin: 25");
+        assert!(trigger.try_match("The goblin is DEAD!").is_none());
+        assert!(trigger.try_match("You find 25 gold.").is_none());
+        assert_eq!(trigger.pattern.source(), r"(\w+) is DEAD, (\d+) gold");
+    }
 }
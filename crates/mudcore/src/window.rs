@@ -3,10 +3,55 @@
 //! 支援將訊息路由到不同的子視窗
 
 use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::ansi::AnsiState;
 
 /// 子視窗 ID
 pub type WindowId = String;
 
+/// 訊息嚴重程度（依序遞增）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warn,
+    Alert,
+}
+
+impl Severity {
+    /// 從名稱解析（不分大小寫）；未知名稱回傳 `None`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "info" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "alert" => Some(Self::Alert),
+            _ => None,
+        }
+    }
+
+    /// 名稱
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Alert => "alert",
+        }
+    }
+
+    /// 彙整視窗中使用的 SGR 顏色碼
+    pub fn sgr(&self) -> &'static str {
+        match self {
+            Self::Info => "37",
+            Self::Warn => "1;33",
+            Self::Alert => "1;31",
+        }
+    }
+}
+
+/// 重要事件彙整視窗 ID
+pub const ALERT_WINDOW_ID: &str = "alerts";
+
 /// 視窗訊息
 #[derive(Debug, Clone)]
 pub struct WindowMessage {
@@ -18,6 +63,10 @@ pub struct WindowMessage {
     pub byte_widths: Vec<u8>,
     /// 重複計數 (Log Folding)
     pub repeat_count: usize,
+    /// 延續自上一行的 SGR 狀態（加入視窗時設定）
+    pub start_state: AnsiState,
+    /// 嚴重程度
+    pub severity: Severity,
 }
 
 impl WindowMessage {
@@ -27,6 +76,8 @@ impl WindowMessage {
             preserve_ansi: true,
             byte_widths: Vec::new(),
             repeat_count: 1,
+            start_state: AnsiState::default(),
+            severity: Severity::Info,
         }
     }
     
@@ -34,6 +85,19 @@ impl WindowMessage {
         self.byte_widths = widths;
         self
     }
+
+    /// 設置嚴重程度
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// 供複製用的純文字（移除 ANSI 碼、不可見字元與 `\r`）
+    pub fn plain_text(&self) -> String {
+        let mut text = crate::ansi::strip_ansi(&self.content);
+        text.retain(|c| c != '\r');
+        text
+    }
 }
 
 /// 子視窗定義
@@ -49,6 +113,16 @@ pub struct SubWindow {
     messages: VecDeque<WindowMessage>,
     /// 是否可見
     pub visible: bool,
+    /// 收到訊息時移除 ANSI 顏色，只保存純文字
+    pub strip_ansi: bool,
+    /// ASCII 藝術模式：顯示時每個字元佔一格，不做 CJK 寬度補位與置中
+    pub ascii_art: bool,
+    /// 是否由路由自動建立（可被自動關閉）
+    auto_created: bool,
+    /// 最後收到訊息的時間
+    last_activity: Instant,
+    /// 目前延續中的 SGR 狀態
+    ansi_state: AnsiState,
 }
 
 impl SubWindow {
@@ -60,6 +134,11 @@ impl SubWindow {
             capacity: 1000,
             messages: VecDeque::new(),
             visible: true,
+            strip_ansi: false,
+            ascii_art: false,
+            auto_created: false,
+            last_activity: Instant::now(),
+            ansi_state: AnsiState::default(),
         }
     }
 
@@ -69,12 +148,55 @@ impl SubWindow {
         self
     }
 
+    /// 設置是否移除 ANSI 顏色
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// 設置 ASCII 藝術模式
+    pub fn with_ascii_art(mut self, ascii_art: bool) -> Self {
+        self.ascii_art = ascii_art;
+        self
+    }
+
     /// 添加訊息
-    pub fn push(&mut self, message: WindowMessage) {
+    pub fn push(&mut self, mut message: WindowMessage) {
+        // 單色視窗：移除 ANSI 碼，寬度映射隨之對齊
+        if self.strip_ansi && message.preserve_ansi {
+            let widths = (!message.byte_widths.is_empty()).then_some(message.byte_widths.as_slice());
+            let spans = crate::ansi::parse_with_widths(&message.content, widths);
+            if widths.is_some() {
+                message.byte_widths = spans.iter().flat_map(|s| s.byte_widths.iter().copied()).collect();
+            }
+            message.content = spans.into_iter().map(|s| s.text).collect();
+            message.preserve_ansi = false;
+        }
+        // 帶色訊息承接上一行未重置的顏色，並更新行尾狀態
+        if message.preserve_ansi {
+            message.start_state = self.ansi_state.clone();
+            crate::ansi::parse_with_state(&message.content, None, &mut self.ansi_state);
+        }
         if self.messages.len() >= self.capacity {
             self.messages.pop_front();
         }
         self.messages.push_back(message);
+        self.last_activity = Instant::now();
+    }
+
+    /// 最後收到訊息的時間
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+
+    /// 是否由路由自動建立
+    pub fn is_auto_created(&self) -> bool {
+        self.auto_created
+    }
+
+    /// 固定視窗，使其不再被自動關閉
+    pub fn pin(&mut self) {
+        self.auto_created = false;
     }
 
     /// 獲取所有訊息
@@ -109,6 +231,10 @@ pub struct WindowManager {
     order: Vec<WindowId>,
     /// 主視窗 ID
     main_window_id: WindowId,
+    /// 自動建立視窗的數量上限（None 表示不限）
+    max_auto_windows: Option<usize>,
+    /// 彙整視窗收錄的最低嚴重程度
+    min_severity: Severity,
 }
 
 impl WindowManager {
@@ -119,6 +245,8 @@ impl WindowManager {
             windows: HashMap::new(),
             order: vec![main_id.clone()],
             main_window_id: main_id.clone(),
+            max_auto_windows: None,
+            min_severity: Severity::Info,
         };
         
         // 創建主視窗
@@ -139,6 +267,87 @@ impl WindowManager {
         self.windows.insert(id, window);
     }
 
+    /// 設定自動建立視窗的數量上限
+    pub fn set_max_auto_windows(&mut self, max: Option<usize>) {
+        self.max_auto_windows = max;
+    }
+
+    /// 設定彙整視窗收錄的最低嚴重程度
+    pub fn set_min_severity(&mut self, severity: Severity) {
+        self.min_severity = severity;
+    }
+
+    /// 彙整視窗收錄的最低嚴重程度
+    pub fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    /// 將事件依嚴重程度上色後送到重要事件彙整視窗（不存在時建立）
+    ///
+    /// 低於最低嚴重程度的事件會被略過，回傳是否已送出。
+    pub fn post_alert(&mut self, text: &str, severity: Severity) -> bool {
+        if severity < self.min_severity {
+            return false;
+        }
+        if !self.windows.contains_key(ALERT_WINDOW_ID) {
+            self.add_window(SubWindow::new(ALERT_WINDOW_ID, "重要事件"));
+        }
+        let content = format!("\x1b[{}m[{}] {}\x1b[0m\n", severity.sgr(), severity.name(), text.trim_end());
+        self.route_message(ALERT_WINDOW_ID, WindowMessage::new(content).with_severity(severity));
+        true
+    }
+
+    /// 確保視窗存在，不存在時以 ID 作為標題自動建立
+    ///
+    /// 自動建立的視窗超過上限時，先關閉最久未活動的自動視窗。
+    pub fn ensure_window(&mut self, id: &str) -> &mut SubWindow {
+        if !self.windows.contains_key(id) {
+            if let Some(max) = self.max_auto_windows {
+                while self.auto_window_count() >= max.max(1) {
+                    let Some(oldest) = self
+                        .windows
+                        .values()
+                        .filter(|w| w.auto_created)
+                        .min_by_key(|w| w.last_activity)
+                        .map(|w| w.id.clone())
+                    else {
+                        break;
+                    };
+                    self.remove_window(&oldest);
+                }
+            }
+
+            let mut window = SubWindow::new(id, id);
+            window.auto_created = true;
+            self.add_window(window);
+        }
+        self.windows.get_mut(id).unwrap()
+    }
+
+    /// 自動建立視窗的數量
+    pub fn auto_window_count(&self) -> usize {
+        self.windows.values().filter(|w| w.auto_created).count()
+    }
+
+    /// 關閉閒置超過 `max_age` 的自動建立視窗，回傳被關閉的視窗 ID
+    ///
+    /// 主視窗與手動新增的視窗不受影響。
+    pub fn prune_idle(&mut self, max_age: Duration) -> Vec<WindowId> {
+        let now = Instant::now();
+        let idle: Vec<WindowId> = self
+            .windows
+            .values()
+            .filter(|w| w.auto_created && w.id != self.main_window_id)
+            .filter(|w| now.saturating_duration_since(w.last_activity) > max_age)
+            .map(|w| w.id.clone())
+            .collect();
+
+        for id in &idle {
+            self.remove_window(id);
+        }
+        idle
+    }
+
     /// 移除子視窗
     pub fn remove_window(&mut self, id: &str) -> Option<SubWindow> {
         if id == self.main_window_id {
@@ -197,6 +406,8 @@ impl WindowManager {
             preserve_ansi: true,
             byte_widths: Vec::new(),
             repeat_count: 1,
+            start_state: AnsiState::default(),
+            severity: Severity::Info,
         });
     }
 }
@@ -205,6 +416,13 @@ impl WindowManager {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_message_plain_text() {
+        let msg = WindowMessage::new("\x1b[1;33m【金幣】\x1b[0m 100\u{200b}\r\n│ 狀態 │\r\n")
+            .with_widths(vec![1; 40]);
+        assert_eq!(msg.plain_text(), "【金幣】 100\n│ 狀態 │\n");
+    }
+
     #[test]
     fn test_window_manager_creation() {
         let manager = WindowManager::new();
@@ -231,17 +449,122 @@ mod tests {
             preserve_ansi: true,
             byte_widths: Vec::new(),
             repeat_count: 1,
+            start_state: AnsiState::default(),
+            severity: Severity::Info,
         });
         
         assert_eq!(manager.get("chat").unwrap().message_count(), 1);
     }
 
+    #[test]
+    fn test_strip_ansi_window() {
+        let mut manager = WindowManager::new();
+        manager.add_window(SubWindow::new("chat", "聊天").with_strip_ansi(true));
+
+        let line = "\x1b[1;32m【閒聊】\x1b[0m 你好";
+        let widths: Vec<u8> = line.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).collect();
+        manager.route_message("chat", WindowMessage::new(line).with_widths(widths.clone()));
+        manager.route_message("main", WindowMessage::new(line).with_widths(widths));
+
+        let chat = manager.get("chat").unwrap().messages().next().unwrap();
+        assert_eq!(chat.content, "【閒聊】 你好");
+        assert_eq!(chat.byte_widths, vec![2, 2, 2, 2, 1, 2, 2]);
+        assert!(!chat.preserve_ansi);
+
+        let main = manager.main_window().messages().next().unwrap();
+        assert_eq!(main.content, line);
+    }
+
+    #[test]
+    fn test_ensure_window() {
+        let mut manager = WindowManager::new();
+        manager.ensure_window("chat_OOC");
+        manager.ensure_window("chat_OOC");
+
+        assert_eq!(manager.windows().len(), 2);
+        assert_eq!(manager.get("chat_OOC").unwrap().title, "chat_OOC");
+    }
+
+    #[test]
+    fn test_prune_idle_windows() {
+        let mut manager = WindowManager::new();
+        for id in ["chat_a", "chat_b", "chat_c"] {
+            manager.ensure_window(id);
+        }
+        manager.add_window(SubWindow::new("status", "狀態"));
+
+        let aged = Instant::now() - Duration::from_secs(120);
+        for id in ["main", "chat_a", "chat_b", "status"] {
+            manager.get_mut(id).unwrap().last_activity = aged;
+        }
+
+        let mut pruned = manager.prune_idle(Duration::from_secs(60));
+        pruned.sort();
+        assert_eq!(pruned, vec!["chat_a", "chat_b"]);
+        assert!(manager.get("main").is_some());
+        assert!(manager.get("status").is_some());
+        assert!(manager.get("chat_c").is_some());
+        assert_eq!(manager.windows().len(), 3);
+    }
+
+    #[test]
+    fn test_max_auto_windows_evicts_oldest() {
+        let mut manager = WindowManager::new();
+        manager.set_max_auto_windows(Some(2));
+        manager.ensure_window("chat_a");
+        manager.ensure_window("chat_b");
+        manager.get_mut("chat_a").unwrap().last_activity = Instant::now() - Duration::from_secs(10);
+
+        manager.ensure_window("chat_c");
+        assert_eq!(manager.auto_window_count(), 2);
+        assert!(manager.get("chat_a").is_none());
+        assert!(manager.get("chat_b").is_some());
+        assert!(manager.get("chat_c").is_some());
+    }
+
     #[test]
     fn test_cannot_remove_main_window() {
         let mut manager = WindowManager::new();
         assert!(manager.remove_window("main").is_none());
     }
 
+    #[test]
+    fn test_sgr_state_carries_over() {
+        let mut window = SubWindow::new("main", "主視窗");
+        window.push(WindowMessage::new("\x1b[31m紅色"));
+        window.push(WindowMessage::new("延續\x1b[0m"));
+        window.push(WindowMessage::new("已重置"));
+
+        let states: Vec<_> = window.messages().map(|m| m.start_state.clone()).collect();
+        assert_eq!(states[0], AnsiState::default());
+        assert_eq!(states[1].fg, crate::ansi::Color::rgb(187, 0, 0));
+        assert_eq!(states[2], AnsiState::default());
+    }
+
+    #[test]
+    fn test_post_alert_honors_min_severity() {
+        let mut manager = WindowManager::new();
+        assert_eq!(Severity::from_name("WARNING"), Some(Severity::Warn));
+        assert_eq!(Severity::from_name("loud"), None);
+
+        manager.set_min_severity(Severity::Warn);
+        assert!(!manager.post_alert("口渴了", Severity::Info));
+        assert!(manager.get(ALERT_WINDOW_ID).is_none());
+
+        assert!(manager.post_alert("生命值低於 30%", Severity::Warn));
+        assert!(manager.post_alert("被攻擊了", Severity::Alert));
+        assert!(!manager.post_alert("肚子餓了", Severity::Info));
+
+        let window = manager.get(ALERT_WINDOW_ID).unwrap();
+        assert!(!window.is_auto_created());
+        let messages: Vec<_> = window.messages().collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].plain_text(), "[warn] 生命值低於 30%\n");
+        assert_eq!(messages[1].severity, Severity::Alert);
+        assert!(messages[1].content.starts_with("\x1b[1;31m"));
+        assert_eq!(manager.main_window().message_count(), 0);
+    }
+
     #[test]
     fn test_window_capacity() {
         let mut window = SubWindow::new("test", "Test").with_capacity(3);
@@ -252,6 +575,8 @@ mod tests {
                 preserve_ansi: false,
                 byte_widths: Vec::new(),
                 repeat_count: 1,
+                start_state: AnsiState::default(),
+                severity: Severity::Info,
             });
         }
         
@@ -1,20 +1,27 @@
 //! MUD Client 主要 UI 邏輯
 
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
 use eframe::egui::{self, Color32, FontId, RichText, ScrollArea, TextEdit};
 use eframe::egui::text::LayoutJob;
 use egui_extras::{Column, TableBuilder};
 use mudcore::{
-    Alias, TelnetClient, Trigger, TriggerAction,
-    TriggerPattern, Path,
+    Alias, Encoding, FireScope, TelnetClient, Trigger, TriggerAction, TriggerScope,
+    TriggerPattern, Path, LoopStatus,
 };
+use mudcore::engine::gag_pattern_source;
+use mudcore::telnet::{LineEnding, OptionStatus, TelnetConfig};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 
 // 移除未使用匯入
-use crate::config::{GlobalConfig, ProfileManager, TriggerConfig};
-use crate::session::SessionManager;
+use crate::config::{
+    AntiSpamAction, AntiSpamConfig, CompletionKey, GlobalConfig, LocalEchoMode, PatternCombine, ProfileManager, ProfileSort, SettingsTab, SidePanelTab,
+    MergeStrategy, SharePack, TriggerActionType, TriggerConfig,
+};
+use crate::ansi::{cell_width, is_box_drawing};
+use crate::session::{word_at, ScrollPosition, SessionId, SessionManager};
 
 
 /// MUD 客戶端 GUI 應用程式
@@ -35,37 +42,36 @@ pub struct MudApp {
     /// 是否顯示 Profile 選擇視窗
     show_profile_window: bool,
     /// 待連線的 Profile 名稱（用於在 UI 循環外處理連線）
-    pending_connect_profile: Option<String>,
+    pending_connect_profiles: Vec<String>,
 
     // === UI 臨時狀態 ===
     /// 當前選中的視窗 ID
     active_window_id: String,
+    /// 各 Session 各視窗的捲動位置
+    scroll_positions: HashMap<(SessionId, String), ScrollPosition>,
+    /// 上一幀顯示的 Session 與視窗（用於偵測切換）
+    scroll_key: Option<(SessionId, String)>,
+    /// 目前套用的原生視窗標題
+    viewport_title: String,
     
     // === 別名編輯狀態 ===
     show_alias_window: bool,
     editing_alias_name: Option<String>,
-    alias_edit_pattern: String,
-    alias_edit_replacement: String,
-    alias_edit_category: String,
-    alias_edit_is_script: bool,
+    alias_edit: AliasEditorState,
     alias_search_text: String,
 
     // === 觸發器編輯狀態 ===
     show_trigger_window: bool,
     editing_trigger_name: Option<String>,
-    trigger_edit_name: String,
-    trigger_edit_pattern: String,
-    trigger_edit_action: String,
-    trigger_edit_category: String,
-    trigger_edit_is_script: bool,
+    trigger_edit: TriggerEditorState,
     trigger_search_text: String,
 
     // === 路徑編輯狀態 ===
     show_path_window: bool,
     editing_path_name: Option<String>,
-    path_edit_name: String,
-    path_edit_value: String,
-    path_edit_category: String,
+    path_edit: PathEditorState,
+    /// 側邊欄路徑記錄的儲存名稱
+    path_save_name: String,
 
     // === Profile 編輯狀態 ===
     show_profile_edit_window: bool,
@@ -76,12 +82,65 @@ pub struct MudApp {
     profile_edit_port: String,
     profile_edit_username: String,
     profile_edit_password: String,
+    profile_edit_local_echo: LocalEchoMode,
+    profile_edit_line_ending: LineEnding,
+    profile_edit_encoding: Encoding,
+    profile_edit_auto_detect_encoding: bool,
+    profile_edit_quiet_connect: bool,
+    profile_edit_wait_username_echo: bool,
+    profile_edit_auto_connect: bool,
+    profile_edit_log_original_text: bool,
+    profile_edit_capture_banner: bool,
+    profile_edit_pin_prompt: bool,
+    profile_edit_collapse_blank_lines: bool,
+    profile_edit_room_in_title: bool,
+    profile_edit_bell_alert: bool,
+    profile_edit_persist_history: bool,
+    profile_edit_persist_scrollback: bool,
+    profile_edit_alias_strict_args: bool,
+    profile_edit_abbreviations: String,
+    profile_edit_on_disconnect: String,
+    profile_edit_click_command: String,
+    profile_edit_on_connect: String,
+    profile_edit_log_path: String,
+    profile_edit_connect_send: String,
+    profile_edit_prompt_regex: String,
+    profile_edit_chat_pattern: String,
+    profile_edit_gag_patterns: Vec<String>,
+    profile_edit_max_capture_windows: usize,
+    profile_edit_capture_window_idle_secs: u64,
+    profile_edit_max_reconnect_attempts: u32,
+    profile_edit_anti_spam: AntiSpamConfig,
 
     /// 設定視窗開關
     show_settings_window: bool,
 
+    /// 變數檢視視窗開關
+    show_variable_window: bool,
+    show_telnet_window: bool,
+    /// 中央區域顯示聊天彙整分頁
+    show_chat_aggregate: bool,
+    /// 正在編輯的變數（名稱, 編輯中的值）
+    variable_editing: Option<(String, String)>,
+
     /// 設定範圍 (Global/Profile)
     settings_scope: SettingsScope,
+    /// 別名/觸發器表格中多選的名稱
+    settings_selection: HashSet<String>,
+    /// 多選所屬的分頁與範圍（切換時清除選取）
+    settings_selection_key: (SettingsTab, SettingsScope),
+    /// TinTin++ 匯入檔案路徑
+    import_path: String,
+    /// 最近一次匯入的結果摘要
+    import_report: Option<String>,
+    /// 分享包檔案路徑
+    share_path: String,
+    /// 匯入分享包時名稱衝突的處理方式
+    share_strategy: MergeStrategy,
+    /// 匯出觸發器時一併匯出用到的別名
+    share_with_aliases: bool,
+    /// 分類重新命名輸入框
+    category_rename_input: String,
     
     // === 側邊欄狀態 ===
     side_panel_tab: SidePanelTab,
@@ -99,22 +158,144 @@ enum SettingsScope {
     Global,
 }
 
-/// 設定中心標籤頁
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SettingsTab {
-    Alias,
-    Trigger,
-    Path,
-    Logger,
-    General,
+/// 別名編輯器的輸入欄位
+#[derive(Debug, Clone, Default)]
+struct AliasEditorState {
+    /// 比對模式
+    pattern: String,
+    /// 替換內容或 Lua 腳本
+    replacement: String,
+    /// 分類
+    category: String,
+    /// 說明
+    description: String,
+    /// 替換內容為 Lua 腳本
+    is_script: bool,
+    /// 比對模式為正則表達式
+    is_regex: bool,
+}
+
+/// 觸發器編輯器的輸入欄位
+#[derive(Debug, Clone)]
+struct TriggerEditorState {
+    /// 名稱
+    name: String,
+    /// 匹配文字
+    pattern: String,
+    /// 多個匹配文字的組合方式
+    combine: PatternCombine,
+    /// 動作內容
+    action: String,
+    /// 分類
+    category: String,
+    /// 說明
+    description: String,
+    /// 動作為 Lua 腳本
+    is_script: bool,
+    /// 動作類型
+    action_type: TriggerActionType,
+    /// 設定/累加變數時的變數名稱
+    variable: String,
+    /// 提示符行的觸發範圍
+    fire_on: FireScope,
+    /// 比對伺服器輸出或玩家輸入
+    scope: TriggerScope,
+    /// 觸發次數上限（0 為不限）
+    max_fires: u32,
+    /// 優先序
+    priority: i32,
+    /// 冷卻時間（毫秒）
+    cooldown_ms: u64,
+    /// 需先觸發的觸發器名稱
+    requires_prior: String,
+    /// 先決觸發器的有效時間（毫秒）
+    requires_prior_ms: u64,
+}
+
+impl Default for TriggerEditorState {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pattern: String::new(),
+            combine: PatternCombine::Single,
+            action: String::new(),
+            category: String::new(),
+            description: String::new(),
+            is_script: false,
+            action_type: TriggerActionType::Command,
+            variable: String::new(),
+            fire_on: FireScope::AnyLine,
+            scope: TriggerScope::Output,
+            max_fires: 0,
+            priority: 0,
+            cooldown_ms: 0,
+            requires_prior: String::new(),
+            requires_prior_ms: 2000,
+        }
+    }
+}
+
+/// 路徑編輯器的輸入欄位
+#[derive(Debug, Clone, Default)]
+struct PathEditorState {
+    /// 路徑名稱
+    name: String,
+    /// 路徑內容
+    value: String,
+    /// 分類
+    category: String,
 }
 
-/// 側邊欄標籤頁
+/// 設定表格的批次操作
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SidePanelTab {
-    Tools,
-    Guide,
-    Notes,
+enum BulkOp {
+    Enable,
+    Disable,
+    Delete,
+    Export,
+}
+
+/// 補齊按鍵對應的修飾鍵與按鍵（停用時為 `None`）
+fn completion_shortcut(key: CompletionKey) -> Option<(egui::Modifiers, egui::Key)> {
+    match key {
+        CompletionKey::Tab => Some((egui::Modifiers::NONE, egui::Key::Tab)),
+        CompletionKey::CtrlSpace => Some((egui::Modifiers::CTRL, egui::Key::Space)),
+        CompletionKey::Off => None,
+    }
+}
+
+/// 以連線取樣猜測編碼；與目前編碼不同時回傳要通知 Session 的狀態更新
+fn encoding_update(client: &TelnetClient) -> Option<crate::session::StatusUpdate> {
+    let (detected, confidence) = mudcore::encoding::detect_encoding(client.encoding_sample());
+    (detected != client.encoding())
+        .then_some(crate::session::StatusUpdate::EncodingDetected(detected, confidence))
+}
+
+/// 有選取項目時繪製批次操作工具列
+fn render_bulk_toolbar(ui: &mut egui::Ui, selection: &mut HashSet<String>) -> Option<BulkOp> {
+    if selection.is_empty() {
+        return None;
+    }
+    let mut op = None;
+    ui.horizontal(|ui| {
+        ui.label(format!("已選取 {} 項:", selection.len()));
+        if ui.button("✅ 啟用").clicked() {
+            op = Some(BulkOp::Enable);
+        }
+        if ui.button("⛔ 停用").clicked() {
+            op = Some(BulkOp::Disable);
+        }
+        if ui.button("🗑️ 刪除").clicked() {
+            op = Some(BulkOp::Delete);
+        }
+        if ui.button("📤 匯出").on_hover_text("將選取的項目匯出為分享包（路徑見上方）").clicked() {
+            op = Some(BulkOp::Export);
+        }
+        if ui.button("取消選取").clicked() {
+            selection.clear();
+        }
+    });
+    op
 }
 
 /// 發送給網路執行緒的命令
@@ -136,38 +317,51 @@ impl MudApp {
         // 創建 Tokio 運行時
         let runtime = Runtime::new().expect("無法創建 Tokio 運行時");
 
+        // 啟動時自動連線的 Profile，於第一次 update 時建立連線
+        let profile_manager = ProfileManager::new();
+        let mut auto_connect_profiles: Vec<String> = profile_manager
+            .list()
+            .iter()
+            .filter(|p| p.auto_connect)
+            .map(|p| p.name.clone())
+            .collect();
+        auto_connect_profiles.sort();
+
+        // 還原上次的介面狀態
+        let global_config = GlobalConfig::load();
+        let active_window_id = Some(global_config.ui.active_window.trim())
+            .filter(|id| !id.is_empty())
+            .unwrap_or("main")
+            .to_string();
+
         Self {
             runtime,
-            settings_tab: SettingsTab::Alias,
+            settings_tab: global_config.ui.settings_tab,
             // 多帳號系統
-            profile_manager: ProfileManager::new(),
+            profile_manager,
             session_manager: SessionManager::new(),
-            global_config: GlobalConfig::load(),
+            side_panel_tab: global_config.ui.side_panel_tab,
+            global_config,
             show_profile_window: false,
-            pending_connect_profile: None,
+            pending_connect_profiles: auto_connect_profiles,
 
             // UI 狀態
-            active_window_id: "main".to_string(),
+            active_window_id,
+            scroll_positions: HashMap::new(),
+            scroll_key: None,
+            viewport_title: "MUD Client".to_string(),
             show_alias_window: false,
             editing_alias_name: None,
-            alias_edit_pattern: String::new(),
-            alias_edit_replacement: String::new(),
-            alias_edit_category: String::new(),
-            alias_edit_is_script: false,
+            alias_edit: AliasEditorState::default(),
             show_trigger_window: false,
             editing_trigger_name: None,
-            trigger_edit_name: String::new(),
-            trigger_edit_pattern: String::new(),
-            trigger_edit_action: String::new(),
-            trigger_edit_category: String::new(),
-            trigger_edit_is_script: false,
+            trigger_edit: TriggerEditorState::default(),
             
             // 路徑狀態
             show_path_window: false,
             editing_path_name: None,
-            path_edit_name: String::new(),
-            path_edit_value: String::new(),
-            path_edit_category: String::new(),
+            path_edit: PathEditorState::default(),
+            path_save_name: String::new(),
             
             // Profile 編輯狀態初始化
             show_profile_edit_window: false,
@@ -178,13 +372,53 @@ impl MudApp {
             profile_edit_port: String::new(),
             profile_edit_username: String::new(),
             profile_edit_password: String::new(),
+            profile_edit_local_echo: LocalEchoMode::Auto,
+            profile_edit_line_ending: LineEnding::Crlf,
+            profile_edit_encoding: Encoding::default(),
+            profile_edit_auto_detect_encoding: false,
+            profile_edit_quiet_connect: false,
+            profile_edit_wait_username_echo: false,
+            profile_edit_auto_connect: false,
+            profile_edit_log_original_text: false,
+            profile_edit_capture_banner: false,
+            profile_edit_pin_prompt: false,
+            profile_edit_collapse_blank_lines: false,
+            profile_edit_room_in_title: false,
+            profile_edit_bell_alert: false,
+            profile_edit_persist_history: false,
+            profile_edit_persist_scrollback: false,
+            profile_edit_alias_strict_args: false,
+            profile_edit_abbreviations: String::new(),
+            profile_edit_on_disconnect: String::new(),
+            profile_edit_click_command: String::new(),
+            profile_edit_on_connect: String::new(),
+            profile_edit_log_path: String::new(),
+            profile_edit_connect_send: String::new(),
+            profile_edit_prompt_regex: String::new(),
+            profile_edit_chat_pattern: String::new(),
+            profile_edit_gag_patterns: Vec::new(),
+            profile_edit_max_capture_windows: 20,
+            profile_edit_capture_window_idle_secs: 0,
+            profile_edit_max_reconnect_attempts: 0,
+            profile_edit_anti_spam: AntiSpamConfig::default(),
 
             show_settings_window: false,
+            show_variable_window: false,
+            show_chat_aggregate: false,
+            show_telnet_window: false,
+            variable_editing: None,
             settings_scope: SettingsScope::Profile,
+            settings_selection: HashSet::new(),
+            settings_selection_key: (SettingsTab::Alias, SettingsScope::Profile),
+            import_path: String::new(),
+            import_report: None,
+            share_path: "share_pack.json".to_string(),
+            share_strategy: MergeStrategy::default(),
+            share_with_aliases: true,
+            category_rename_input: String::new(),
             alias_search_text: String::new(),
             trigger_search_text: String::new(),
             
-            side_panel_tab: SidePanelTab::Tools,
             guide_file_list: Vec::new(),
             active_guide_content: String::new(),
             active_guide_name: None,
@@ -199,16 +433,18 @@ impl MudApp {
             
             // 1. 同步 Alias
             let mut new_aliases = Vec::new();
-            for name in &session.alias_manager.sorted_aliases {
-                if let Some(a) = session.alias_manager.get(name) {
+            for name in &session.engine.alias_manager.sorted_aliases {
+                if let Some(a) = session.engine.alias_manager.get(name) {
                     // 檢查是否與全域設定相同 (完全相同則不儲存，實現 Clean Save)
                     let is_global_identical = self.global_config.global_aliases.iter().any(|ga| {
                         ga.name == a.name && 
                         ga.pattern == a.pattern && 
                         ga.replacement == a.replacement && 
                         ga.is_script == a.is_script &&
-                        ga.enabled == a.enabled &&
-                        ga.category == a.category
+                        ga.is_regex == a.is_regex &&
+                        ga.default_enabled == a.default_enabled &&
+                        ga.category == a.category &&
+                        ga.description == a.description
                     });
 
                     if !is_global_identical {
@@ -217,8 +453,10 @@ impl MudApp {
                             pattern: a.pattern.clone(),
                             replacement: a.replacement.clone(),
                             category: a.category.clone(),
+                            description: a.description.clone(),
                             is_script: a.is_script,
-                            enabled: a.enabled,
+                            is_regex: a.is_regex,
+                            default_enabled: a.default_enabled,
                         });
                     }
                 }
@@ -226,49 +464,24 @@ impl MudApp {
 
             // 2. 同步 Trigger
             let mut new_triggers = Vec::new();
-            for name in &session.trigger_manager.order {
-                 if let Some(t) = session.trigger_manager.get(name) {
-                     let (action_str, is_script) = if let Some(first_action) = t.actions.first() {
-                         match first_action {
-                             TriggerAction::SendCommand(s) => (s.clone(), false),
-                             TriggerAction::ExecuteScript(s) => (s.clone(), true),
-                             _ => (String::new(), false),
-                         }
-                     } else {
-                         (String::new(), false)
-                     };
-                     
-                     let pat_str = match &t.pattern {
-                         TriggerPattern::Contains(s) | TriggerPattern::StartsWith(s) | TriggerPattern::EndsWith(s) | TriggerPattern::Regex(s) => s.clone(),
-                     };
-                     
+            for name in &session.engine.trigger_manager.order {
+                 // 一次性觸發器只存在於執行期
+                 if let Some(t) = session.engine.trigger_manager.get(name).filter(|t| !t.one_shot) {
+                     let config = crate::session::Session::config_from_trigger(t);
+
                      // 檢查是否與全域設定相同
-                     let is_global_identical = self.global_config.global_triggers.iter().any(|gt| {
-                         gt.name == t.name && 
-                         gt.pattern == pat_str && 
-                         gt.action == action_str && 
-                         gt.is_script == is_script &&
-                         gt.enabled == t.enabled &&
-                         gt.category == t.category
-                     });
+                     let is_global_identical = self.global_config.global_triggers.contains(&config);
 
                      if !is_global_identical {
-                         new_triggers.push(crate::config::TriggerConfig {
-                             name: t.name.clone(),
-                             pattern: pat_str,
-                             action: action_str,
-                             category: t.category.clone(),
-                             is_script,
-                             enabled: t.enabled,
-                         });
+                         new_triggers.push(config);
                      }
                  }
              }
 
              // 3. 同步 Path
              let mut new_paths = Vec::new();
-             for name in &session.path_manager.sorted_keys {
-                 if let Some(p) = session.path_manager.get(name) {
+             for name in &session.engine.path_manager.sorted_keys {
+                 if let Some(p) = session.engine.path_manager.get(name) {
                      new_paths.push(crate::config::PathConfig {
                          name: p.name.clone(),
                          value: p.value.clone(),
@@ -277,11 +490,15 @@ impl MudApp {
                  }
              }
 
-             // 4. 更新 ProfileManager 並儲存
+             // 4. 同步子視窗配置
+             let new_windows = session.window_layout();
+
+             // 5. 更新 ProfileManager 並儲存
               if let Some(profile) = self.profile_manager.get_mut(&profile_name) {
                   profile.aliases = new_aliases;
                   profile.triggers = new_triggers;
                   profile.paths = new_paths;
+                  profile.windows = new_windows;
                   profile.notes = session.notes.clone();
                   
                   // 儲存到磁碟
@@ -294,12 +511,21 @@ impl MudApp {
              }
         }
         
-        // 儲存全域設定
+        // 儲存全域設定（含目前的介面狀態）
+        self.global_config.ui.active_window = self.active_window_id.clone();
+        self.global_config.ui.side_panel_tab = self.side_panel_tab;
+        self.global_config.ui.settings_tab = self.settings_tab;
         if let Err(e) = self.global_config.save() {
             tracing::error!("Failed to save global config: {}", e);
         }
     }
 
+    /// 關閉程式前：儲存設定、停止日誌並通知網路執行緒斷線
+    fn shutdown(&mut self) {
+        self.save_config();
+        self.session_manager.shutdown_all();
+    }
+
     /// 初始化字型設定
     fn setup_fonts(ctx: &egui::Context) {
         let mut fonts = egui::FontDefinitions::default();
@@ -347,7 +573,9 @@ impl MudApp {
             
             // 顯示本地訊息
             if let Some(session) = self.session_manager.get_mut(session_id) {
-                session.handle_text(&format!(">>> 已建立 Profile 會話: {} ({}:{})\n", profile_name, session.host, session.port), true);
+                if !session.quiet_connect {
+                    session.handle_text(&format!(">>> 已建立 Profile 會話: {} ({}:{})\n", profile_name, session.host, session.port), true);
+                }
             }
         } else {
             tracing::warn!("找不到 Profile: {}", profile_name);
@@ -360,8 +588,7 @@ impl MudApp {
         let pattern = TriggerPattern::Regex(config.pattern.clone());
         
         // 建立 Trigger
-        let mut trigger = Trigger::new(config.name.clone(), pattern);
-        trigger.enabled = config.enabled;
+        let mut trigger = Trigger::new(config.name.clone(), pattern).with_default_enabled(config.default_enabled);
         
         // 根據 is_script 判斷 action 類型
         let action = if config.is_script {
@@ -376,93 +603,169 @@ impl MudApp {
 
     /// 啟動指定 Session 的網路連線
     fn start_connection(&mut self, session_id: crate::session::SessionId, ctx: egui::Context) {
-        let (host, port, username, password) = {
+        let (connect_command, line_ending, encoding, connect_send, window_size) = {
             let session = match self.session_manager.get(session_id) {
                 Some(s) => s,
                 None => return,
             };
-            (
-                session.host.clone(), 
-                session.port.parse::<u16>().unwrap_or(7777),
-                session.username.clone(),
-                session.password.clone(),
-            )
+            // 已有連線（或正在連線）時忽略，避免自動重連與手動連線同時觸發
+            if session.connection_active() {
+                return;
+            }
+            (session.connect_command(), session.line_ending, session.encoding, session.connect_send.clone(), session.window_size)
         };
 
         // 創建 channels
         use crate::session::Command as SessionCommand;
+        use crate::session::{username_echoed, NetworkMessage, StatusUpdate, ENCODING_IDLE_DETECT, LOGOUT_GRACE, USERNAME_ECHO_TIMEOUT};
         let (cmd_tx, mut cmd_rx) = mpsc::channel::<SessionCommand>(32);
-        let (msg_tx, msg_rx) = mpsc::channel::<(String, Vec<u8>)>(1024);
+        let (msg_tx, msg_rx) = mpsc::channel::<NetworkMessage>(1024);
+        let msg_tx = RepaintingSender { tx: msg_tx, ctx };
 
         if let Some(session) = self.session_manager.get_mut(session_id) {
             session.command_tx = Some(cmd_tx.clone());
             session.message_rx = Some(msg_rx);
-            session.status = crate::session::ConnectionStatus::Connecting;
+            session.begin_connecting();
         }
 
         // 啟動網路執行緒
         self.runtime.spawn(async move {
-            let mut client = TelnetClient::default();
+            let mut client = TelnetClient::new(TelnetConfig { line_ending, encoding, connect_send, ..Default::default() });
+            if let Some((cols, rows)) = window_size {
+                let _ = client.set_window_size(cols, rows).await;
+            }
 
             // 處理命令
             loop {
                 tokio::select! {
                     Some(cmd) = cmd_rx.recv() => {
                         match cmd {
-                            SessionCommand::Connect(h, p, u, pwd) => {
+                            SessionCommand::Connect(h, p, u, pwd, wait_echo) => {
                                 match client.connect(&h, p).await {
                                     Ok(_) => {
-                                        let _ = msg_tx.send((format!(">>> 已連線到 {}:{}\n", h, p), Vec::new())).await;
+                                        let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::Connected { host: h.clone(), port: p })).await;
 
                                         // 自動登入邏輯
                                         if let Some(username) = u {
                                             // 稍微延遲一點點確保連線穩定（簡易版）
                                             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                                             if let Err(e) = client.send(&username).await {
-                                                let _ = msg_tx.send((format!(">>> 自動登入(帳號)失敗: {}\n", e), Vec::new())).await;
-                                            } else {
-                                                // let _ = msg_tx.send(">>> 已發送帳號\n".to_string()).await;
+                                                let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::AutoLoginFailed { step: "帳號", error: e.to_string() })).await;
                                             }
 
                                             if let Some(password) = pwd {
-                                                tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+                                                if wait_echo {
+                                                    // 等待伺服器回顯帳號再送密碼，逾時則直接送出（退回固定延遲行為）
+                                                    let deadline = tokio::time::Instant::now() + USERNAME_ECHO_TIMEOUT;
+                                                    let mut seen = String::new();
+                                                    while let Ok(Ok((text, widths))) = tokio::time::timeout_at(deadline, client.read_with_widths()).await {
+                                                        if text.is_empty() {
+                                                            break;
+                                                        }
+                                                        seen.push_str(&text);
+                                                        let _ = msg_tx.send(NetworkMessage::Text(text, widths)).await;
+                                                        if username_echoed(&seen, &username) {
+                                                            break;
+                                                        }
+                                                    }
+                                                } else {
+                                                    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+                                                }
                                                 if let Err(e) = client.send(&password).await {
-                                                     let _ = msg_tx.send((format!(">>> 自動登入(密碼)失敗: {}\n", e), Vec::new())).await;
+                                                     let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::AutoLoginFailed { step: "密碼", error: e.to_string() })).await;
                                                 } else {
-                                                    let _ = msg_tx.send((">>> 已嘗試自動登入\n".to_string(), Vec::new())).await;
+                                                    let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::AutoLoginSent)).await;
                                                 }
                                             }
                                         }
 
                                         // 開始讀取迴圈
+                                        let mut encoding_checked = false;
+                                        let mut server_echo = false;
                                         loop {
                                             tokio::select! {
                                                 result = client.read_with_widths() => {
+                                                    // 伺服器回顯狀態改變時通知 Session（決定是否本地回顯）
+                                                    if client.server_echo() != server_echo {
+                                                        server_echo = client.server_echo();
+                                                        let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::EchoChanged(server_echo))).await;
+                                                    }
+                                                    // 伺服器以 OSC 設定標題
+                                                    if let Some(title) = client.take_title() {
+                                                        let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::TitleChanged(title))).await;
+                                                    }
+                                                    if client.take_bell() {
+                                                        let _ = msg_tx.send(NetworkMessage::Bell).await;
+                                                    }
+                                                    for message in client.take_gmcp() {
+                                                        let _ = msg_tx.send(NetworkMessage::Gmcp(message)).await;
+                                                    }
+                                                    if client.take_options_changed() {
+                                                        let _ = msg_tx.send(NetworkMessage::OptionStates(client.option_states())).await;
+                                                    }
                                                     match result {
-                                                        Ok((text, widths)) if !text.is_empty() => {
-                                                            let _ = msg_tx.send((text, widths)).await;
-                                                            ctx.request_repaint();
-                                                        }
-                                                        Ok(_) => {
-                                                            let _ = msg_tx.send((">>> 連線已關閉\n".to_string(), Vec::new())).await;
-                                                            break;
+                                                        Ok((text, widths)) => {
+                                                            if !text.is_empty() {
+                                                                let _ = msg_tx.send(NetworkMessage::Text(text, widths)).await;
+                                                            }
+                                                            // GA / EOR 表示提示符已完整，讓 Session 處理暫存的行
+                                                            let prompt_end = client.take_prompt_end();
+                                                            if prompt_end {
+                                                                let _ = msg_tx.send(NetworkMessage::PromptEnd).await;
+                                                            }
+
+                                                            // 取樣足夠（或 banner 已以 GA/EOR 結束）後猜測一次編碼，
+                                                            // 與目前不同時由 Session 決定提示或直接套用
+                                                            if !encoding_checked && client.encoding_sample_ready(prompt_end) {
+                                                                encoding_checked = true;
+                                                                if let Some(update) = encoding_update(&client) {
+                                                                    let _ = msg_tx.send(NetworkMessage::Status(update)).await;
+                                                                }
+                                                            }
                                                         }
                                                         Err(e) => {
-                                                            let _ = msg_tx.send((format!(">>> 連線已關閉 (錯誤: {})\n", e), Vec::new())).await;
+                                                            let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::Closed(Some(e.to_string())))).await;
                                                             break;
                                                         }
                                                     }
                                                 }
+                                                // 連線後伺服器閒置：banner 已送完，以現有取樣猜測編碼
+                                                _ = tokio::time::sleep(ENCODING_IDLE_DETECT), if !encoding_checked && client.encoding_sample_ready(true) => {
+                                                    encoding_checked = true;
+                                                    if let Some(update) = encoding_update(&client) {
+                                                        let _ = msg_tx.send(NetworkMessage::Status(update)).await;
+                                                    }
+                                                }
                                                 Some(cmd) = cmd_rx.recv() => {
                                                     match cmd {
                                                         SessionCommand::Send(text) => {
                                                             if let Err(e) = client.send(&text).await {
-                                                                let _ = msg_tx.send((format!(">>> 發送失敗: {}\n", e), Vec::new())).await;
+                                                                let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::SendFailed(e.to_string()))).await;
+                                                            }
+                                                        }
+                                                        SessionCommand::SetEncoding(encoding) => {
+                                                            let (text, widths) = client.set_encoding(encoding);
+                                                            if !text.is_empty() {
+                                                                let _ = msg_tx.send(NetworkMessage::Text(text, widths)).await;
+                                                            }
+                                                        }
+                                                        SessionCommand::WindowSize(cols, rows) => {
+                                                            if let Err(e) = client.set_window_size(cols, rows).await {
+                                                                let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::SendFailed(e.to_string()))).await;
+                                                            }
+                                                        }
+                                                        SessionCommand::Logout(text) => {
+                                                            // 先登出，給伺服器一點時間處理再關閉連線
+                                                            if client.send(&text).await.is_ok() {
+                                                                tokio::time::sleep(LOGOUT_GRACE).await;
                                                             }
+                                                            client.disconnect().await;
+                                                            let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::Disconnected)).await;
+                                                            break;
                                                         }
                                                         SessionCommand::Disconnect => {
                                                             client.disconnect().await;
-                                                            let _ = msg_tx.send((">>> 已斷開連線\n".to_string(), Vec::new())).await;
+                                                            let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::Disconnected)).await;
                                                             break;
                                                         }
                                                         _ => {}
@@ -472,11 +775,14 @@ impl MudApp {
                                         }
                                     }
                                     Err(e) => {
-                                        let _ = msg_tx.send((format!(">>> 連線已關閉 (連線失敗: {})\n", e), Vec::new())).await;
+                                        let _ = msg_tx.send(NetworkMessage::Status(StatusUpdate::ConnectFailed(e.to_string()))).await;
                                     }
                                 }
                             }
-                            SessionCommand::Disconnect => break,
+                            SessionCommand::Disconnect | SessionCommand::Logout(_) => break,
+                            SessionCommand::WindowSize(cols, rows) => {
+                                let _ = client.set_window_size(cols, rows).await;
+                            }
                             _ => {}
                         }
                     }
@@ -486,7 +792,7 @@ impl MudApp {
         });
 
         // 發送初始連線命令
-        let _ = cmd_tx.blocking_send(SessionCommand::Connect(host, port, username, password));
+        let _ = cmd_tx.blocking_send(connect_command);
     }
 
     /// 發送訊息（針對指定 Session）
@@ -497,18 +803,12 @@ impl MudApp {
         let text = session.input.clone();
 
         // 只有非空訊息才儲存到歷史
-        if !text.is_empty() {
-            session.input_history.push(text.clone());
-            // 限制歷史記錄數量，避免記憶體無上限增長
-            if session.input_history.len() > 500 {
-                session.input_history.drain(..session.input_history.len() - 500);
-            }
-        }
+        session.record_history(&text);
         session.history_index = None;
 
         // 別名處理
         let clean_text = crate::ansi::strip_ansi(&text);
-        let expanded = session.alias_manager.process(&clean_text);
+        let expanded = session.engine.alias_manager.process(&clean_text);
 
         // 處理本地回顯與觸發
         if expanded.is_empty() {
@@ -578,28 +878,8 @@ impl MudApp {
             // 處理收集到的訊息
             if !messages.is_empty() {
                 if let Some(session) = self.session_manager.get_mut(id) {
-                    for (text, widths) in messages {
-                        if widths.is_empty() {
-                            session.handle_text(&text, false);
-                        } else {
-                            session.handle_text_with_widths(&text, false, Some(&widths));
-                        }
-
-                        use crate::session::ConnectionStatus as SessionStatus;
-                        if text.contains("已連線到") {
-                            let info = text.replace(">>> 已連線到 ", "").replace("\n", "");
-                            session.status = SessionStatus::Connected(info);
-                            session.connected_at = Some(Instant::now());
-                        } else if text.contains("連線已關閉") || text.contains("已斷開連線") {
-                            session.connected_at = None;
-                            if session.auto_reconnect {
-                                use std::time::Duration;
-                                session.reconnect_delay_until = Some(Instant::now() + Duration::from_secs(3));
-                                session.status = SessionStatus::Reconnecting;
-                            } else {
-                                session.status = SessionStatus::Disconnected;
-                            }
-                        }
+                    for message in messages {
+                        session.handle_network_message(message);
                     }
                 }
             }
@@ -607,22 +887,46 @@ impl MudApp {
     }
 
     /// 繪製訊息顯示區（支援 ANSI 顏色）
-    fn render_message_area(ui: &mut egui::Ui, session: &mut crate::session::Session, active_window_id: &str) {
+    ///
+    /// 切換視窗或分頁時恢復該視窗上次的捲動位置（原本在底部則繼續跟隨底部）。
+    ///
+    /// 回傳 true 表示使用者透過右鍵選單停用了觸發器，需儲存設定
+    fn render_message_area(
+        ui: &mut egui::Ui,
+        session: &mut crate::session::Session,
+        active_window_id: &str,
+        scroll_positions: &mut HashMap<(SessionId, String), ScrollPosition>,
+        scroll_key: &mut Option<(SessionId, String)>,
+    ) -> bool {
         let available_height = ui.available_height() - 40.0; // 保留輸入區空間
 
+        // 剛切換到此視窗時取出保存的位置（未保存過則捲到底部）
+        let key = (session.id, active_window_id.to_string());
+        let restore = if scroll_key.as_ref() != Some(&key) {
+            Some(scroll_positions.get(&key).copied().unwrap_or(ScrollPosition { offset: 0.0, at_bottom: true }))
+        } else {
+            None
+        };
+
         // 檢查是否需要強制捲到底部
-        let force_scroll_to_bottom = session.scroll_to_bottom_on_next_frame;
+        let force_scroll_to_bottom = session.scroll_to_bottom_on_next_frame || restore.is_some_and(|p| p.at_bottom);
         session.scroll_to_bottom_on_next_frame = false;
 
         // 使用固定 ID 以便後續操作 State
         let scroll_area_id = egui::Id::new("main_message_scroll_area");
+        let context_line_id = scroll_area_id.with("context_line");
+        let mut trigger_disabled = false;
 
-        let output = ScrollArea::vertical()
+        let mut scroll_area = ScrollArea::vertical()
             .id_salt(scroll_area_id)
             .auto_shrink([false, false])
             .max_height(available_height)
-            .stick_to_bottom(true)
-            .show(ui, |ui| {
+            .stick_to_bottom(true);
+        if let Some(position) = restore.filter(|_| !force_scroll_to_bottom) {
+            scroll_area = scroll_area.vertical_scroll_offset(position.offset);
+        }
+
+        let output = scroll_area.show(ui, |ui| {
                 let font_size = 14.0;
                 let font_id = FontId::monospace(font_size);
                 let bold_font_id = FontId::new(font_size, egui::FontFamily::Name("cjk_bold".into()));
@@ -630,6 +934,14 @@ impl MudApp {
                 // 穩定測量：使用空格寬度作為 Mono 單元格寬度基準
                 // 穩定測量：使用空格寬度作為 Mono 單元格寬度基準
                 let cell_w = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+                // 以訊息區可容納的字元數回報視窗大小（NAWS）
+                if cell_w > 0.0 {
+                    let cols = (ui.available_width() / cell_w) as u16;
+                    let rows = (available_height / (font_size + 4.0)) as u16;
+                    session.report_window_size(cols.max(1), rows.max(1));
+                }
+                // ASCII 藝術模式：每個字元一格，不做寬度補位、置中與 2x 框線字型
+                let ascii_art = session.window_manager.get(active_window_id).is_some_and(|w| w.ascii_art);
 
                 let mut main_job = LayoutJob::default();
                 let mut overlay_job = LayoutJob::default();
@@ -650,8 +962,8 @@ impl MudApp {
                     let total = window.message_count();
                     let skip = total.saturating_sub(visible_lines);
                     for msg in window.messages().skip(skip) {
-                        use crate::ansi::parse_ansi_with_widths;
-                        let spans = parse_ansi_with_widths(&msg.content, Some(&msg.byte_widths));
+                        use crate::ansi::parse_ansi_with_state;
+                        let spans = parse_ansi_with_state(&msg.content, Some(&msg.byte_widths), &msg.start_state);
 
                          
                         for span in spans {
@@ -680,7 +992,11 @@ impl MudApp {
                             // 非雙色字渲染
                             if !is_real_dual_color {
                                 for (idx, ch) in span.text.chars().enumerate() {
-                                    if ch == '\n' || ch == '\r' {
+                                    // `\r` 不放入可選取文字，避免複製時夾帶
+                                    if ch == '\r' {
+                                        continue;
+                                    }
+                                    if ch == '\n' {
                                         let fmt = egui::TextFormat { font_id: current_font_id.clone(), color: render_color, background, italics, line_height: Some(font_size + 4.0), ..Default::default() };
                                         section_fg_colors.push(render_color);
                                         main_job.append(&ch.to_string(), pending_trailing_space, fmt.clone());
@@ -688,19 +1004,7 @@ impl MudApp {
                                         pending_trailing_space = 0.0;
                                         continue;
                                     }
-                                    let u_w = if let Some(bw) = span.byte_widths.get(idx).copied() {
-                                        bw as usize
-                                    } else {
-                                        if ch.is_ascii() || ch == '|' { 1 }
-                                        else if ch == '\u{2103}' || ch == '\u{00a7}' { 2 }
-                                        else {
-                                            use unicode_width::UnicodeWidthChar;
-                                            ch.width().unwrap_or(1).max(1)
-                                        }
-                                    };
-
-                                    // CJK 終端環境：框線繪圖字元始終佔 2 列寬
-                                    let u_w = if ch >= '\u{2500}' && ch <= '\u{259f}' { u_w.max(2) } else { u_w };
+                                    let u_w = cell_width(ch, span.byte_widths.get(idx).copied(), ascii_art);
                                     let target_w = (u_w as f32) * cell_w;
                                     let actual_w = *glyph_cache.entry((ch, span.bold)).or_insert_with(|| {
                                         ui.fonts(|f| f.glyph_width(&current_font_id, ch))
@@ -710,9 +1014,11 @@ impl MudApp {
                                     // 1. 框線字元 (\u2500-\u259f) 或原本就佔滿 2 單元的 CJK：不置中，維持靠左以確保接縫對齊
                                     // 2. 窄字元 (如 §, \u2103) 但宣告為 2 單元寬：置中補位
                                     let extra = (if actual_w <= 0.0 { target_w } else { target_w - actual_w }).max(0.0);
-                                    let is_box_or_full_cjk = (ch >= '\u{2500}' && ch <= '\u{259f}') || (u_w >= 2 && actual_w >= target_w * 0.9);
+                                    let is_box_or_full_cjk = is_box_drawing(ch) || (u_w >= 2 && actual_w >= target_w * 0.9);
                                     
-                                    let (current_leading, next_trailing) = if is_box_or_full_cjk {
+                                    let (current_leading, next_trailing) = if ascii_art {
+                                        (0.0, 0.0)
+                                    } else if is_box_or_full_cjk {
                                         (extra + pending_trailing_space, 0.0)
                                     } else {
                                         (extra / 2.0 + pending_trailing_space, extra / 2.0)
@@ -725,7 +1031,7 @@ impl MudApp {
                                     } else {
                                         render_color
                                     };
-                                    let glyph_color = if ch >= '\u{2500}' && ch <= '\u{259f}' {
+                                    let glyph_color = if is_box_drawing(ch) && !ascii_art {
                                         Color32::TRANSPARENT
                                     } else {
                                         char_color
@@ -747,7 +1053,10 @@ impl MudApp {
 
                             // 雙色字逐字元網格對齊模式
                             for (idx, ch) in span.text.chars().enumerate() {
-                                if ch == '\n' || ch == '\r' {
+                                if ch == '\r' {
+                                    continue;
+                                }
+                                if ch == '\n' {
                                     let fmt = egui::TextFormat { font_id: current_font_id.clone(), color: render_color, background, italics, line_height: Some(font_size + 4.0), ..Default::default() };
                                     section_fg_colors.push(render_color);
                                     main_job.append(&ch.to_string(), pending_trailing_space, fmt.clone());
@@ -756,28 +1065,18 @@ impl MudApp {
                                     continue;
                                 }
 
-                                let u_w = if let Some(bw) = span.byte_widths.get(idx).copied() {
-                                    bw as usize
-                                } else {
-                                    if ch.is_ascii() || ch == '|' { 1 }
-                                    else if ch == '\u{2103}' || ch == '\u{00a7}' { 2 }
-                                    else {
-                                        use unicode_width::UnicodeWidthChar;
-                                        ch.width().unwrap_or(1).max(1)
-                                    }
-                                };
-
-                                // CJK 終端環境：框線繪圖字元始終佔 2 列寬
-                                let u_w = if ch >= '\u{2500}' && ch <= '\u{259f}' { u_w.max(2) } else { u_w };
+                                let u_w = cell_width(ch, span.byte_widths.get(idx).copied(), ascii_art);
                                 let target_w = (u_w as f32) * cell_w;
                                 let actual_w = *glyph_cache.entry((ch, span.bold)).or_insert_with(|| {
                                     ui.fonts(|f| f.glyph_width(&current_font_id, ch))
                                 });
                                 
                                 let extra = (if actual_w <= 0.0 { target_w } else { target_w - actual_w }).max(0.0);
-                                let is_box_or_full_cjk = (ch >= '\u{2500}' && ch <= '\u{259f}') || (u_w >= 2 && actual_w >= target_w * 0.9);
+                                let is_box_or_full_cjk = is_box_drawing(ch) || (u_w >= 2 && actual_w >= target_w * 0.9);
                                 
-                                let (current_leading, next_trailing) = if is_box_or_full_cjk {
+                                let (current_leading, next_trailing) = if ascii_art {
+                                    (0.0, 0.0)
+                                } else if is_box_or_full_cjk {
                                     (extra + pending_trailing_space, 0.0)
                                 } else {
                                     (extra / 2.0 + pending_trailing_space, extra / 2.0)
@@ -838,18 +1137,37 @@ impl MudApp {
                         .wrap_mode(egui::TextWrapMode::Extend)
                 );
                 let rect = label_response.rect;
+
+                // Ctrl+點擊單字：以點擊指令模板送出
+                if label_response.clicked() && ui.input(|i| i.modifiers.command) {
+                    if let Some(pos) = label_response.interact_pointer_pos() {
+                        let cursor = main_galley.cursor_from_pos(pos - rect.min);
+                        if let Some(word) = word_at(main_galley.text(), cursor.ccursor.index) {
+                            session.send_clicked_word(&word);
+                        }
+                    }
+                }
+
+                // 記下右鍵點擊的那一行，供選單查詢匹配的觸發器
+                if label_response.secondary_clicked() {
+                    if let Some(pos) = label_response.interact_pointer_pos() {
+                        let cursor = main_galley.cursor_from_pos(pos - rect.min);
+                        let chars: Vec<char> = main_galley.text().chars().collect();
+                        let idx = cursor.ccursor.index.min(chars.len());
+                        let start = chars[..idx].iter().rposition(|&c| c == '\n').map_or(0, |p| p + 1);
+                        let end = chars[idx..].iter().position(|&c| c == '\n').map_or(chars.len(), |p| idx + p);
+                        let text: String = chars[start..end].iter().collect();
+                        ui.data_mut(|d| d.insert_temp(context_line_id, text));
+                    }
+                }
                 
-                // 右鍵選單：複製全文
+                // 右鍵選單：複製全文、停用匹配此行的觸發器
                 label_response.context_menu(|ui| {
                     if ui.button("複製全文").clicked() {
                         let mut all_text = String::new();
                         if let Some(window) = session.window_manager.get(active_window_id) {
                             for msg in window.messages() {
-                                use crate::ansi::parse_ansi_with_widths;
-                                let spans = parse_ansi_with_widths(&msg.content, Some(&msg.byte_widths));
-                                for span in &spans {
-                                    all_text.push_str(&span.text);
-                                }
+                                all_text.push_str(&msg.plain_text());
                                 if !all_text.ends_with('\n') {
                                     all_text.push('\n');
                                 }
@@ -858,15 +1176,31 @@ impl MudApp {
                         ui.output_mut(|o| o.copied_text = all_text);
                         ui.close_menu();
                     }
+
+                    let line: String = ui.data(|d| d.get_temp(context_line_id)).unwrap_or_default();
+                    let matched = session.engine.trigger_manager.matching_names(&line);
+                    if !matched.is_empty() {
+                        ui.separator();
+                        for name in matched {
+                            if ui.button(format!("停用觸發器「{}」", name)).clicked() {
+                                if let Some(trigger) = session.engine.trigger_manager.get_mut(&name) {
+                                    trigger.enabled = false;
+                                    trigger.default_enabled = false;
+                                }
+                                trigger_disabled = true;
+                                ui.close_menu();
+                            }
+                        }
+                    }
                 });
                     
                     // 2x 字型純文字渲染框線字元（取代幾何線段）
                     let painter = ui.painter();
                     let box_font = FontId::monospace(font_size * 2.0);
-                    for row in &main_galley.rows {
+                    for row in main_galley.rows.iter().filter(|_| !ascii_art) {
                         for glyph in &row.glyphs {
                             let ch = glyph.chr;
-                            if ch < '\u{2500}' || ch > '\u{259f}' { continue; }
+                            if !is_box_drawing(ch) { continue; }
                             
                             let fg_color = section_fg_colors.get(glyph.section_index as usize)
                                 .copied().unwrap_or(Color32::WHITE);
@@ -947,9 +1281,10 @@ impl MudApp {
             });
 
         // 如果需要強制捲到底部，直接設定 offset
+        let content_size = output.content_size;
+        let inner_rect = output.inner_rect;
+        let mut offset = output.state.offset.y;
         if force_scroll_to_bottom {
-            let content_size = output.content_size;
-            let inner_rect = output.inner_rect;
             let max_scroll = (content_size.y - inner_rect.height()).max(0.0);
             
             // 載入並修改 state
@@ -957,7 +1292,14 @@ impl MudApp {
                 state.offset.y = max_scroll;
                 state.store(ui.ctx(), output.id);
             }
+            offset = max_scroll;
         }
+
+        // 記錄此視窗目前的捲動位置
+        scroll_positions.insert(key.clone(), ScrollPosition::capture(offset, content_size.y, inner_rect.height()));
+        *scroll_key = Some(key);
+
+        trigger_disabled
     }
 
 
@@ -967,10 +1309,7 @@ impl MudApp {
         session_opt: Option<&mut crate::session::Session>,
         global_config_opt: Option<&mut GlobalConfig>,
         editing_alias_name: &mut Option<String>,
-        alias_edit_pattern: &mut String,
-        alias_edit_replacement: &mut String,
-        alias_edit_category: &mut String,
-        alias_edit_is_script: &mut bool,
+        alias_edit: &mut AliasEditorState,
         show_alias_window: &mut bool,
         needs_save_flag: &mut bool,
     ) {
@@ -980,11 +1319,16 @@ impl MudApp {
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("觸發詞:");
-                    ui.text_edit_singleline(alias_edit_pattern);
+                    ui.text_edit_singleline(&mut alias_edit.pattern);
                 });
 
                 ui.horizontal(|ui| {
-                    ui.checkbox(alias_edit_is_script, "使用 Lua 腳本");
+                    ui.checkbox(&mut alias_edit.is_regex, "正則表達式")
+                        .on_hover_text("觸發詞直接作為正則比對，如 ^cast (\\w+) on (\\w+)$");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut alias_edit.is_script, "使用 Lua 腳本");
                     ui.label(
                         egui::RichText::new("(勾選後可撰寫多行程式碼)")
                             .size(11.0)
@@ -993,17 +1337,17 @@ impl MudApp {
                 });
 
                 ui.horizontal(|ui| {
-                    ui.label(if *alias_edit_is_script { "Lua 腳本:" } else { "替換為:" });
-                    if *alias_edit_is_script {
-                        ui.text_edit_multiline(alias_edit_replacement);
+                    ui.label(if alias_edit.is_script { "Lua 腳本:" } else { "替換為:" });
+                    if alias_edit.is_script {
+                        ui.text_edit_multiline(&mut alias_edit.replacement);
                     } else {
-                        ui.text_edit_singleline(alias_edit_replacement);
+                        ui.text_edit_singleline(&mut alias_edit.replacement);
                     }
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("分類:");
-                    ui.text_edit_singleline(alias_edit_category);
+                    ui.text_edit_singleline(&mut alias_edit.category);
 
                     // 分類選擇選單
                     ui.menu_button("▼", |ui| {
@@ -1013,8 +1357,8 @@ impl MudApp {
                         let mut categories: Vec<String> = Vec::new();
 
                         if let Some(session) = session_opt.as_ref() {
-                            categories.extend(session.trigger_manager.list().iter().filter_map(|t| t.category.clone()));
-                            categories.extend(session.alias_manager.list().iter().filter_map(|a| a.category.clone()));
+                            categories.extend(session.engine.trigger_manager.list().iter().filter_map(|t| t.category.clone()));
+                            categories.extend(session.engine.alias_manager.list().iter().filter_map(|a| a.category.clone()));
                         } else if let Some(global) = global_config_opt.as_ref() {
                              categories.extend(global.global_triggers.iter().filter_map(|t| t.category.clone()));
                              categories.extend(global.global_aliases.iter().filter_map(|a| a.category.clone()));
@@ -1031,7 +1375,7 @@ impl MudApp {
                             ui.separator();
                             for cat in categories {
                                 if ui.button(&cat).clicked() {
-                                    *alias_edit_category = cat;
+                                    alias_edit.category = cat;
                                     ui.close_menu();
                                 }
                             }
@@ -1039,32 +1383,43 @@ impl MudApp {
                     });
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("說明:");
+                    ui.text_edit_singleline(&mut alias_edit.description);
+                });
+
                 ui.add_space(10.0);
-                ui.label("提示: 使用 $1, $2 等作為參數佔位符");
+                if alias_edit.is_regex {
+                    ui.label("提示: 使用 $1, $2 引用擷取群組，${name} 引用具名群組，$0 為整個匹配");
+                } else {
+                    ui.label("提示: 使用 $1, $2 等作為參數佔位符，$0 為整段參數，$* 為所有參數");
+                }
 
                 ui.add_space(20.0);
 
                 ui.horizontal(|ui| {
                     if ui.button("💾 儲存").clicked() {
-                        if !alias_edit_pattern.is_empty() {
+                        if !alias_edit.pattern.is_empty() {
                             if let Some(session) = session_opt {
                                 // 如果是編輯模式，先刪除舊的
                                 if let Some(ref old_name) = editing_alias_name {
                                     if !old_name.is_empty() {
-                                        session.alias_manager.remove(old_name);
+                                        session.engine.alias_manager.remove(old_name);
                                     }
                                 }
                                 // 新增別名
                                 let mut alias = Alias::new(
-                                    alias_edit_pattern.clone(),
-                                    alias_edit_pattern.clone(),
-                                    alias_edit_replacement.clone(),
-                                );
-                                alias.is_script = *alias_edit_is_script;
-                                if !alias_edit_category.is_empty() {
-                                    alias.category = Some(alias_edit_category.clone());
+                                    alias_edit.pattern.clone(),
+                                    alias_edit.pattern.clone(),
+                                    alias_edit.replacement.clone(),
+                                )
+                                .as_regex(alias_edit.is_regex);
+                                alias.is_script = alias_edit.is_script;
+                                if !alias_edit.category.is_empty() {
+                                    alias.category = Some(alias_edit.category.clone());
                                 }
-                                session.alias_manager.add(alias);
+                                alias.description = alias_edit.description.trim().to_string();
+                                session.engine.alias_manager.add(alias);
                                 *needs_save_flag = true;
                             } else if let Some(global) = global_config_opt {
                                 // Global Config Logic
@@ -1074,20 +1429,22 @@ impl MudApp {
                                         global.global_aliases.retain(|a| &a.name != old_name);
                                         old_name.clone()
                                     } else {
-                                        alias_edit_pattern.clone()
+                                        alias_edit.pattern.clone()
                                     }
                                 } else {
-                                    alias_edit_pattern.clone()
+                                    alias_edit.pattern.clone()
                                 };
                                 
                                 // Push new
                                 global.global_aliases.push(crate::config::AliasConfig {
                                     name,
-                                    pattern: alias_edit_pattern.clone(),
-                                    replacement: alias_edit_replacement.clone(),
-                                    category: if alias_edit_category.is_empty() { None } else { Some(alias_edit_category.clone()) },
-                                    is_script: *alias_edit_is_script,
-                                    enabled: true,
+                                    pattern: alias_edit.pattern.clone(),
+                                    replacement: alias_edit.replacement.clone(),
+                                    category: if alias_edit.category.is_empty() { None } else { Some(alias_edit.category.clone()) },
+                                    description: alias_edit.description.trim().to_string(),
+                                    is_script: alias_edit.is_script,
+                                    is_regex: alias_edit.is_regex,
+                                    default_enabled: true,
                                 });
                                 *needs_save_flag = true;
                             }
@@ -1108,11 +1465,7 @@ impl MudApp {
         session_opt: Option<&mut crate::session::Session>,
         global_config_opt: Option<&mut GlobalConfig>,
         editing_trigger_name: &mut Option<String>,
-        trigger_edit_name: &mut String,
-        trigger_edit_pattern: &mut String,
-        trigger_edit_action: &mut String,
-        trigger_edit_category: &mut String,
-        trigger_edit_is_script: &mut bool,
+        trigger_edit: &mut TriggerEditorState,
         show_trigger_window: &mut bool,
         needs_save_flag: &mut bool,
     ) {
@@ -1122,39 +1475,160 @@ impl MudApp {
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("名稱:");
-                    ui.text_edit_singleline(trigger_edit_name);
+                    ui.text_edit_singleline(&mut trigger_edit.name);
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("匹配文字:");
-                    ui.text_edit_singleline(trigger_edit_pattern);
+                    ui.text_edit_singleline(&mut trigger_edit.pattern);
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("多個模式:");
+                    ui.radio_value(&mut trigger_edit.combine, PatternCombine::Single, "單一");
+                    ui.radio_value(&mut trigger_edit.combine, PatternCombine::AnyOf, "任一符合");
+                    ui.radio_value(&mut trigger_edit.combine, PatternCombine::AllOf, "全部符合");
+                    ui.radio_value(&mut trigger_edit.combine, PatternCombine::MultiLine, "連續多行");
+                })
+                .response
+                .on_hover_text("任一 / 全部符合 / 連續多行時，匹配文字以逗號分隔多個模式；連續多行依序比對連續的幾行，最後一個比對目前這行");
+
                 ui.add_space(5.0);
-                
-                // 1. Lua 選項上移
+
+                // 0. 動作類型
                 ui.horizontal(|ui| {
-                    ui.checkbox(trigger_edit_is_script, "使用 Lua 腳本模式");
-                    ui.label(
-                        egui::RichText::new("(勾選後可撰寫多行程式碼)")
-                            .size(11.0)
-                            .color(egui::Color32::GRAY)
-                    );
+                    ui.label("動作類型:");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::Command, "指令/腳本");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::RawCommand, "直接送出")
+                        .on_hover_text("不經別名與觸發器，原樣送到伺服器");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::SetVariable, "設定變數");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::IncrementVariable, "累加變數");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::Substitute, "替換顯示");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::Highlight, "標示顏色");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::AppendNote, "附加筆記");
+                    ui.radio_value(&mut trigger_edit.action_type, TriggerActionType::AppendFile, "附加檔案");
                 });
 
-                // 2. 執行命令 (根據模式切換單行/多行)
-                ui.horizontal(|ui| {
-                    ui.label("執行內容:");
-                    if *trigger_edit_is_script {
-                        ui.text_edit_multiline(trigger_edit_action);
-                    } else {
-                        ui.text_edit_singleline(trigger_edit_action);
+                match trigger_edit.action_type {
+                    TriggerActionType::Command => {
+                        // 1. Lua 選項上移
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut trigger_edit.is_script, "使用 Lua 腳本模式");
+                            ui.label(
+                                egui::RichText::new("(勾選後可撰寫多行程式碼)")
+                                    .size(11.0)
+                                    .color(egui::Color32::GRAY)
+                            );
+                        });
+
+                        // 2. 執行命令 (根據模式切換單行/多行)
+                        ui.horizontal(|ui| {
+                            ui.label("執行內容:");
+                            if trigger_edit.is_script {
+                                ui.text_edit_multiline(&mut trigger_edit.action);
+                            } else {
+                                ui.text_edit_singleline(&mut trigger_edit.action);
+                            }
+                        });
+                    }
+                    TriggerActionType::RawCommand => {
+                        ui.horizontal(|ui| {
+                            ui.label("送出內容:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("可使用 $1 代入捕獲"));
+                        });
+                    }
+                    TriggerActionType::SetVariable => {
+                        ui.horizontal(|ui| {
+                            ui.label("變數名稱:");
+                            ui.text_edit_singleline(&mut trigger_edit.variable);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("設定值:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("可使用 $1 代入捕獲"));
+                        });
+                    }
+                    TriggerActionType::IncrementVariable => {
+                        ui.horizontal(|ui| {
+                            ui.label("變數名稱:");
+                            ui.text_edit_singleline(&mut trigger_edit.variable);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("增量:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("1"));
+                        });
+                    }
+                    TriggerActionType::Substitute => {
+                        ui.horizontal(|ui| {
+                            ui.label("替換為:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("可使用 $1 代入捕獲"));
+                        });
+                    }
+                    TriggerActionType::Highlight => {
+                        ui.horizontal(|ui| {
+                            ui.label("樣式:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("red on blue bold"));
+                        })
+                        .response
+                        .on_hover_text("前景色、on 之後為背景色、bold 為粗體；顏色可用名稱或 #rrggbb，只標示匹配的部分");
+                    }
+                    TriggerActionType::AppendNote => {
+                        ui.horizontal(|ui| {
+                            ui.label("筆記內容:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("任務: $1"));
+                        });
+                    }
+                    TriggerActionType::AppendFile => {
+                        ui.horizontal(|ui| {
+                            ui.label("檔案路徑:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.variable).hint_text("logs/quests.txt"));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("寫入內容:");
+                            ui.add(TextEdit::singleline(&mut trigger_edit.action).hint_text("可使用 $1 代入捕獲"));
+                        });
                     }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("觸發範圍:");
+                    ui.radio_value(&mut trigger_edit.fire_on, FireScope::AnyLine, "所有行");
+                    ui.radio_value(&mut trigger_edit.fire_on, FireScope::PromptOnly, "僅提示符");
+                    ui.radio_value(&mut trigger_edit.fire_on, FireScope::NonPromptOnly, "排除提示符");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("比對對象:");
+                    ui.radio_value(&mut trigger_edit.scope, TriggerScope::Output, "伺服器輸出");
+                    ui.radio_value(&mut trigger_edit.scope, TriggerScope::Input, "玩家輸入");
+                    ui.radio_value(&mut trigger_edit.scope, TriggerScope::Both, "兩者");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("觸發次數上限:");
+                    ui.add(egui::DragValue::new(&mut trigger_edit.max_fires).range(0..=10000).suffix(" 次"))
+                        .on_hover_text("觸發指定次數後自動停用，0 表示不限");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("優先級:");
+                    ui.add(egui::DragValue::new(&mut trigger_edit.priority).range(-1000..=1000))
+                        .on_hover_text("同一行匹配多個觸發器時，數字大者先執行；會 Gag 的觸發器擋下優先級較低者");
+                    ui.label("冷卻:");
+                    ui.add(egui::DragValue::new(&mut trigger_edit.cooldown_ms).range(0..=600_000).speed(100).suffix(" 毫秒"))
+                        .on_hover_text("觸發後這段時間內不再觸發，避免重複送出指令；0 表示不限");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("前置觸發器:");
+                    ui.add(TextEdit::singleline(&mut trigger_edit.requires_prior).hint_text("觸發器名稱").desired_width(120.0))
+                        .on_hover_text("留空表示不限；設定後須在時間內觸發過此觸發器才會觸發");
+                    ui.label("時間內:");
+                    ui.add(egui::DragValue::new(&mut trigger_edit.requires_prior_ms).range(0..=600_000).speed(100).suffix(" 毫秒"));
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("分類標籤:");
-                    ui.text_edit_singleline(trigger_edit_category);
+                    ui.text_edit_singleline(&mut trigger_edit.category);
 
                     // 分類選擇選單
                     ui.menu_button("▼", |ui| {
@@ -1164,8 +1638,8 @@ impl MudApp {
                         let mut categories: Vec<String> = Vec::new();
                         
                         if let Some(session) = session_opt.as_ref() {
-                            categories.extend(session.trigger_manager.list().iter().filter_map(|t| t.category.clone()));
-                            categories.extend(session.alias_manager.list().iter().filter_map(|a| a.category.clone()));
+                            categories.extend(session.engine.trigger_manager.list().iter().filter_map(|t| t.category.clone()));
+                            categories.extend(session.engine.alias_manager.list().iter().filter_map(|a| a.category.clone()));
                         } else if let Some(global) = global_config_opt.as_ref() {
                              categories.extend(global.global_triggers.iter().filter_map(|t| t.category.clone()));
                              categories.extend(global.global_aliases.iter().filter_map(|a| a.category.clone()));
@@ -1182,7 +1656,7 @@ impl MudApp {
                             ui.separator();
                             for cat in categories {
                                 if ui.button(&cat).clicked() {
-                                    *trigger_edit_category = cat;
+                                    trigger_edit.category = cat;
                                     ui.close_menu();
                                 }
                             }
@@ -1190,6 +1664,11 @@ impl MudApp {
                     });
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("說明:");
+                    ui.text_edit_singleline(&mut trigger_edit.description);
+                });
+
                 ui.add_space(10.0);
                 // 3. 優化提示文字
                 ui.label(
@@ -1202,43 +1681,47 @@ impl MudApp {
 
                 ui.horizontal(|ui| {
                     if ui.button("💾 儲存").clicked() {
-                        if !trigger_edit_name.is_empty() && !trigger_edit_pattern.is_empty() {
+                        if !trigger_edit.name.is_empty() && !trigger_edit.pattern.is_empty() {
+                            // 組合模式統一為 `a, b` 格式
+                            let pattern = match trigger_edit.combine {
+                                PatternCombine::Single => trigger_edit.pattern.clone(),
+                                _ => trigger_edit.pattern
+                                    .split(',')
+                                    .map(str::trim)
+                                    .filter(|p| !p.is_empty())
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            };
+                            let config = crate::config::TriggerConfig {
+                                name: trigger_edit.name.clone(),
+                                pattern,
+                                combine: trigger_edit.combine,
+                                action: trigger_edit.action.clone(),
+                                category: if trigger_edit.category.is_empty() { None } else { Some(trigger_edit.category.clone()) },
+                                description: trigger_edit.description.trim().to_string(),
+                                is_script: trigger_edit.is_script,
+                                default_enabled: true,
+                                action_type: trigger_edit.action_type,
+                                variable: trigger_edit.variable.clone(),
+                                fire_on: trigger_edit.fire_on,
+                                scope: trigger_edit.scope,
+                                max_fires: (trigger_edit.max_fires > 0).then_some(trigger_edit.max_fires),
+                                priority: trigger_edit.priority,
+                                cooldown_ms: trigger_edit.cooldown_ms,
+                                requires_prior: Some(trigger_edit.requires_prior.trim().to_string()).filter(|name| !name.is_empty()),
+                                requires_prior_ms: trigger_edit.requires_prior_ms,
+                            };
                             if let Some(session) = session_opt {
                                 // 如果是編輯模式，先刪除舊的
                                 if let Some(ref old_name) = editing_trigger_name {
                                     if !old_name.is_empty() {
-                                        session.trigger_manager.remove(old_name);
+                                        session.engine.trigger_manager.remove(old_name);
                                     }
                                 }
                                 // 新增觸發器
-                                let pattern = if trigger_edit_pattern.contains("(.+)")
-                                    || trigger_edit_pattern.contains("(.*)")
-                                    || trigger_edit_pattern.contains("\\d")
-                                    || trigger_edit_pattern.contains("[")
-                                    || trigger_edit_pattern.contains("$")
-                                    || trigger_edit_pattern.contains("^")
-                                    || trigger_edit_pattern.contains("|")
-                                    || trigger_edit_pattern.contains("?")
-                                {
-                                    TriggerPattern::Regex(trigger_edit_pattern.clone())
-                                } else {
-                                    TriggerPattern::Contains(trigger_edit_pattern.clone())
-                                };
-                                let mut trigger = Trigger::new(
-                                    trigger_edit_name.clone(),
-                                    pattern,
-                                );
-                                if !trigger_edit_action.is_empty() {
-                                    if *trigger_edit_is_script {
-                                        trigger = trigger.add_action(TriggerAction::ExecuteScript(trigger_edit_action.clone()));
-                                    } else {
-                                        trigger = trigger.add_action(TriggerAction::SendCommand(trigger_edit_action.clone()));
-                                    }
+                                if let Some(trigger) = crate::session::Session::create_trigger_from_config(&config) {
+                                    session.engine.trigger_manager.add(trigger);
                                 }
-                                if !trigger_edit_category.is_empty() {
-                                    trigger.category = Some(trigger_edit_category.clone());
-                                }
-                                session.trigger_manager.add(trigger);
                                 *needs_save_flag = true;
                             } else if let Some(global) = global_config_opt {
                                 // Global Config Logic
@@ -1247,20 +1730,13 @@ impl MudApp {
                                         global.global_triggers.retain(|t| &t.name != old_name);
                                         old_name.clone()
                                     } else {
-                                        trigger_edit_name.clone()
+                                        trigger_edit.name.clone()
                                     }
                                 } else {
-                                    trigger_edit_name.clone()
+                                    trigger_edit.name.clone()
                                 };
                                 
-                                global.global_triggers.push(crate::config::TriggerConfig {
-                                    name,
-                                    pattern: trigger_edit_pattern.clone(),
-                                    action: trigger_edit_action.clone(),
-                                    category: if trigger_edit_category.is_empty() { None } else { Some(trigger_edit_category.clone()) },
-                                    is_script: *trigger_edit_is_script,
-                                    enabled: true,
-                                });
+                                global.global_triggers.push(crate::config::TriggerConfig { name, ..config });
                                 *needs_save_flag = true;
                             }
                             *show_trigger_window = false;
@@ -1279,9 +1755,7 @@ impl MudApp {
         ctx: &egui::Context,
         session_opt: Option<&mut crate::session::Session>,
         editing_path_name: &mut Option<String>,
-        path_edit_name: &mut String,
-        path_edit_value: &mut String,
-        path_edit_category: &mut String,
+        path_edit: &mut PathEditorState,
         show_path_window: &mut bool,
         needs_save_flag: &mut bool,
     ) {
@@ -1291,12 +1765,12 @@ impl MudApp {
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("路徑名稱:");
-                    ui.text_edit_singleline(path_edit_name);
+                    ui.text_edit_singleline(&mut path_edit.name);
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("路徑內容:");
-                    ui.text_edit_singleline(path_edit_value);
+                    ui.text_edit_singleline(&mut path_edit.value);
                 });
                 
                 ui.label(
@@ -1307,7 +1781,7 @@ impl MudApp {
 
                 ui.horizontal(|ui| {
                     ui.label("分類:");
-                    ui.text_edit_singleline(path_edit_category);
+                    ui.text_edit_singleline(&mut path_edit.category);
 
                     // 分類選擇選單
                     if let Some(session) = session_opt.as_ref() {
@@ -1316,7 +1790,7 @@ impl MudApp {
                             
                             // 收集現有分類
                             let mut categories: Vec<String> = Vec::new();
-                            categories.extend(session.path_manager.list().iter().filter_map(|p| p.category.clone()));
+                            categories.extend(session.engine.path_manager.list().iter().filter_map(|p| p.category.clone()));
                             
                             categories.retain(|c| !c.is_empty());
                             categories.sort();
@@ -1329,7 +1803,7 @@ impl MudApp {
                                 ui.separator();
                                 for cat in categories {
                                     if ui.button(&cat).clicked() {
-                                        *path_edit_category = cat;
+                                        path_edit.category = cat;
                                         ui.close_menu();
                                     }
                                 }
@@ -1342,23 +1816,23 @@ impl MudApp {
 
                 ui.horizontal(|ui| {
                     if ui.button("💾 儲存").clicked() {
-                        if !path_edit_name.is_empty() && !path_edit_value.is_empty() {
+                        if !path_edit.name.is_empty() && !path_edit.value.is_empty() {
                             if let Some(session) = session_opt {
                                 // 如果是編輯模式，先刪除舊的
                                 if let Some(ref old_name) = editing_path_name {
                                     if !old_name.is_empty() {
-                                        session.path_manager.remove(old_name);
+                                        session.engine.path_manager.remove(old_name);
                                     }
                                 }
                                 // 新增路徑
                                 let mut path = Path::new(
-                                    path_edit_name.clone(),
-                                    path_edit_value.clone(),
+                                    path_edit.name.clone(),
+                                    path_edit.value.clone(),
                                 );
-                                if !path_edit_category.is_empty() {
-                                    path.category = Some(path_edit_category.clone());
+                                if !path_edit.category.is_empty() {
+                                    path.category = Some(path_edit.category.clone());
                                 }
-                                session.path_manager.add(path);
+                                session.engine.path_manager.add(path);
                                 *needs_save_flag = true;
                             }
                             *show_path_window = false;
@@ -1403,15 +1877,74 @@ impl MudApp {
 
     /// 繪製工具分頁 (原有的側邊欄內容)
     fn render_tools_tab(&mut self, ui: &mut egui::Ui, active_window_id: &str, pending_action: &mut Option<PendingAction>) {
+        let mut path_command: Option<String> = None;
+
         if let Some(session) = self.session_manager.active_session() {
             ui.heading("視窗");
             ui.separator();
 
             for window in session.window_manager.windows() {
                 let is_active = window.id == active_window_id;
-                if ui.selectable_label(is_active, &window.title).clicked() {
-                    *pending_action = Some(PendingAction::SwitchWindow(window.id.clone()));
+                ui.horizontal(|ui| {
+                    if ui.selectable_label(is_active, &window.title).clicked() {
+                        *pending_action = Some(PendingAction::SwitchWindow(window.id.clone()));
+                    }
+                    let art_hint = if window.ascii_art { "恢復 CJK 寬度對齊" } else { "ASCII 藝術模式：每個字元一格，不做寬度補位與置中" };
+                    if ui.small_button(if window.ascii_art { "▦" } else { "▤" }).on_hover_text(art_hint).clicked() {
+                        *pending_action = Some(PendingAction::ToggleWindowAsciiArt(window.id.clone()));
+                    }
+                    if window.id == "main" {
+                        return;
+                    }
+                    let strip_hint = if window.strip_ansi { "恢復顯示顏色" } else { "移除顏色，只顯示純文字" };
+                    if ui.small_button(if window.strip_ansi { "⬜" } else { "🎨" }).on_hover_text(strip_hint).clicked() {
+                        *pending_action = Some(PendingAction::ToggleWindowStripAnsi(window.id.clone()));
+                    }
+                    if window.is_auto_created() {
+                        if ui.small_button("📌").on_hover_text("保留此視窗（儲存到 Profile）").clicked() {
+                            *pending_action = Some(PendingAction::PinWindow(window.id.clone()));
+                        }
+                    } else if ui.small_button("✖").on_hover_text("關閉並從 Profile 移除").clicked() {
+                        *pending_action = Some(PendingAction::CloseWindow(window.id.clone()));
+                    }
+                });
+            }
+
+            // 路徑記錄即時顯示（每幀直接讀取 PathRecorder 狀態）
+            let recorder = &session.path_recorder;
+            if recorder.is_recording {
+                ui.add_space(15.0);
+                ui.heading("🧭 路徑記錄中");
+                ui.separator();
+
+                ui.label(format!("步數: {}", recorder.recorded_commands.len()));
+                let speedwalk = recorder.to_speedwalk();
+                ui.label(RichText::new(if speedwalk.is_empty() { "-".to_string() } else { speedwalk }).monospace());
+
+                match recorder.last_loop_status {
+                    LoopStatus::ExactLoop => {
+                        ui.colored_label(Color32::YELLOW, "⚠️ 回到了經過的原點");
+                    }
+                    LoopStatus::PotentialLoop => {
+                        ui.colored_label(Color32::YELLOW, "⚠️ 可能的迴圈");
+                    }
+                    LoopStatus::None => {}
                 }
+
+                ui.horizontal(|ui| {
+                    if ui.button("⏹ 停止").clicked() {
+                        path_command = Some("#path stop".to_string());
+                    }
+                    if ui.button("↩ 復原").clicked() {
+                        path_command = Some("#path undo".to_string());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.path_save_name).hint_text("路徑名稱").desired_width(100.0));
+                    if ui.button("💾 儲存").clicked() && !self.path_save_name.trim().is_empty() {
+                        path_command = Some(format!("#path save {}", self.path_save_name.trim()));
+                    }
+                });
             }
 
             ui.add_space(15.0);
@@ -1424,6 +1957,31 @@ impl MudApp {
             if ui.button("👤 連線管理").clicked() {
                 *pending_action = Some(PendingAction::ToggleProfile);
             }
+            if ui.button("🔍 變數檢視").clicked() {
+                *pending_action = Some(PendingAction::ToggleVariables);
+            }
+            if ui.button("📡 Telnet 選項").clicked() {
+                *pending_action = Some(PendingAction::ToggleTelnetOptions);
+            }
+
+            ui.add_space(15.0);
+            egui::CollapsingHeader::new(format!("📜 連線事件 ({})", session.event_log.len()))
+                .default_open(false)
+                .show(ui, |ui| {
+                    if session.event_log.is_empty() {
+                        ui.label(RichText::new("尚無事件").weak());
+                    }
+                    for (at, event) in session.event_log.iter().rev() {
+                        let secs = at.elapsed().as_secs();
+                        ui.label(format!(
+                            "[{:02}:{:02}:{:02} 前] {}",
+                            secs / 3600,
+                            (secs / 60) % 60,
+                            secs % 60,
+                            event
+                        ));
+                    }
+                });
         } else {
             ui.heading("管理");
             ui.separator();
@@ -1431,6 +1989,17 @@ impl MudApp {
                 *pending_action = Some(PendingAction::ToggleProfile);
             }
         }
+
+        if let Some(cmd) = path_command {
+            let is_save = cmd.starts_with("#path save");
+            if let Some(session) = self.session_manager.active_session_mut() {
+                session.handle_user_input(&cmd);
+            }
+            if is_save {
+                self.path_save_name.clear();
+                self.save_config();
+            }
+        }
     }
 
     /// 繪製攻略分頁
@@ -1550,12 +2119,71 @@ impl MudApp {
          }
     }
 
+    /// 繪製固定提示列（保留 ANSI 顏色）
+    fn render_pinned_prompt(ui: &mut egui::Ui, prompt: &str) {
+        let font_id = FontId::monospace(14.0);
+        let mut job = LayoutJob::default();
+        for span in crate::ansi::parse_ansi(prompt) {
+            job.append(
+                &span.text.replace('\r', ""),
+                0.0,
+                egui::TextFormat {
+                    font_id: font_id.clone(),
+                    color: span.fg_color,
+                    background: span.bg_color.unwrap_or(Color32::TRANSPARENT),
+                    ..Default::default()
+                },
+            );
+        }
+        egui::Frame::none()
+            .fill(ui.visuals().extreme_bg_color)
+            .inner_margin(egui::Margin::symmetric(6.0, 2.0))
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.label(job);
+            });
+    }
+
+    /// 繪製聊天彙整分頁
+    fn render_chat_aggregate(ui: &mut egui::Ui, window: &mudcore::SubWindow) {
+        let font_id = FontId::monospace(14.0);
+        ScrollArea::vertical()
+            .id_salt("chat_aggregate_scroll_area")
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - 40.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                if window.message_count() == 0 {
+                    ui.label(RichText::new("尚無聊天訊息；在 Profile 設定「聊天正則」後，符合的訊息會出現在這裡").italics().color(Color32::GRAY));
+                }
+                for message in window.messages() {
+                    ui.label(RichText::new(message.plain_text().trim_end()).font(font_id.clone()));
+                }
+            });
+    }
+
     /// 繪製輸入區
-    fn render_input_area(ui: &mut egui::Ui, session: &mut crate::session::Session, any_popup_open: bool) {
+    fn render_input_area(
+        ui: &mut egui::Ui,
+        session: &mut crate::session::Session,
+        any_popup_open: bool,
+        completion_key: CompletionKey,
+    ) {
         ui.horizontal(|ui| {
-            // 先攔截 Tab 鍵，避免 egui 預設的焦點切換行為
+            // 先攔截補齊按鍵（預設為 Tab），避免 egui 預設的焦點切換行為
             // 必須在 widget 渲染之前消耗，否則 egui 會先處理焦點切換
-            let tab_pressed = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab));
+            // 先檢查加 Shift 的反向組合，因為不含 Shift 的組合也會匹配按住 Shift 的按鍵
+            let completion = completion_shortcut(completion_key).and_then(|(modifiers, key)| {
+                ui.input_mut(|i| {
+                    if i.consume_key(modifiers | egui::Modifiers::SHIFT, key) {
+                        Some(true)
+                    } else if i.consume_key(modifiers, key) {
+                        Some(false)
+                    } else {
+                        None
+                    }
+                })
+            });
             
             let response = ui.add(
                 TextEdit::singleline(&mut session.input)
@@ -1582,12 +2210,7 @@ impl MudApp {
                 let cmds = if cmds.is_empty() { vec![""] } else { cmds };
 
                 // 記錄歷史 (原始輸入)
-                if !raw_input.is_empty() {
-                    session.input_history.push(raw_input.clone());
-                    if session.input_history.len() > 1000 {
-                        session.input_history.remove(0);
-                    }
-                }
+                session.record_history(&raw_input);
                 session.history_index = None;
                 
                 for cmd in cmds {
@@ -1635,8 +2258,8 @@ impl MudApp {
                     session.tab_completion_prefix = None;
                 }
                 // Tab 補齊 (使用之前攔截的結果)
-                if tab_pressed {
-                    Self::tab_complete_for_session(session);
+                if let Some(backward) = completion {
+                    Self::tab_complete_for_session(session, backward);
                     ui.ctx().request_repaint();
                 }
             }
@@ -1671,7 +2294,7 @@ impl MudApp {
     }
 
     /// Tab 補齊邏輯
-    fn tab_complete_for_session(session: &mut crate::session::Session) {
+    fn tab_complete_for_session(session: &mut crate::session::Session, backward: bool) {
         if session.input.is_empty() {
             session.tab_completion_prefix = None;
             session.last_completed_input = None;
@@ -1761,10 +2384,10 @@ impl MudApp {
         }
 
         if !matches.is_empty() {
-            let index = session.tab_completion_index % matches.len();
+            let (index, next) = crate::session::cycle_completion(session.tab_completion_index, matches.len(), backward);
             session.input = matches[index].clone();
             session.last_completed_input = Some(session.input.clone());
-            session.tab_completion_index += 1;
+            session.tab_completion_index = next;
             session.tab_completed = true;
         } else {
             session.last_completed_input = None;
@@ -1852,13 +2475,61 @@ impl MudApp {
                             self.profile_edit_port = "7777".to_string();
                             self.profile_edit_username = String::new();
                             self.profile_edit_password = String::new();
+                            self.profile_edit_local_echo = LocalEchoMode::Auto;
+                            self.profile_edit_line_ending = LineEnding::Crlf;
+                            self.profile_edit_encoding = Encoding::default();
+                            self.profile_edit_auto_detect_encoding = false;
+                            self.profile_edit_quiet_connect = false;
+                            self.profile_edit_wait_username_echo = false;
+                            self.profile_edit_auto_connect = false;
+                            self.profile_edit_log_original_text = false;
+                            self.profile_edit_capture_banner = false;
+                            self.profile_edit_pin_prompt = false;
+                            self.profile_edit_collapse_blank_lines = false;
+                            self.profile_edit_room_in_title = false;
+                            self.profile_edit_bell_alert = false;
+                            self.profile_edit_persist_history = false;
+                            self.profile_edit_persist_scrollback = false;
+                            self.profile_edit_alias_strict_args = false;
+                            self.profile_edit_abbreviations = String::new();
+                            self.profile_edit_on_disconnect = String::new();
+                            self.profile_edit_click_command = "kill $word".to_string();
+                            self.profile_edit_on_connect = String::new();
+                            self.profile_edit_log_path = String::new();
+                            self.profile_edit_connect_send = String::new();
+                            self.profile_edit_prompt_regex = String::new();
+                            self.profile_edit_chat_pattern = String::new();
+                            self.profile_edit_gag_patterns = Vec::new();
+                            self.profile_edit_max_capture_windows = 20;
+                            self.profile_edit_capture_window_idle_secs = 0;
+                            self.profile_edit_max_reconnect_attempts = 0;
+                            self.profile_edit_anti_spam = AntiSpamConfig::default();
                             self.show_profile_edit_window = true;
                         }
+
+                        // 連線所有尚未連線的 Profile
+                        if ui.button("🔌 全部連線").clicked() {
+                            for name in self.profile_manager.names() {
+                                let already_open = self.session_manager.sessions().iter().any(|s| {
+                                    s.profile_name == name && s.status != crate::session::ConnectionStatus::Disconnected
+                                });
+                                if !already_open {
+                                    self.pending_connect_profiles.push(name);
+                                }
+                            }
+                            self.show_profile_window = false;
+                        }
                     });
                 });
+                ui.horizontal(|ui| {
+                    ui.label("排序:");
+                    let sort = &mut self.global_config.ui.profile_sort;
+                    ui.radio_value(sort, ProfileSort::Name, "名稱");
+                    ui.radio_value(sort, ProfileSort::Recent, "最近連線");
+                });
                 ui.separator();
 
-                let profiles: Vec<_> = self.profile_manager.list().iter().map(|p| {
+                let profiles: Vec<_> = self.profile_manager.sorted(self.global_config.ui.profile_sort).iter().map(|p| {
                     (p.name.clone(), p.display_name.clone(), p.connection.host.clone(), p.connection.port.clone(), p.username.clone())
                 }).collect();
 
@@ -1879,7 +2550,7 @@ impl MudApp {
                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                         // 連線按鈕
                                         if ui.button("🔌 連線").clicked() {
-                                            self.pending_connect_profile = Some(name.clone());
+                                            self.pending_connect_profiles.push(name.clone());
                                             self.show_profile_window = false;
                                         }
                                         
@@ -1894,6 +2565,35 @@ impl MudApp {
                                                     self.profile_edit_port = p.connection.port.clone();
                                                     self.profile_edit_username = p.username.clone().unwrap_or_default();
                                                     self.profile_edit_password = p.password.clone().unwrap_or_default();
+                                                    self.profile_edit_local_echo = p.local_echo;
+                                                    self.profile_edit_line_ending = p.line_ending;
+                                                    self.profile_edit_encoding = p.encoding;
+                                                    self.profile_edit_auto_detect_encoding = p.auto_detect_encoding;
+                                                    self.profile_edit_quiet_connect = p.quiet_connect;
+                                                    self.profile_edit_wait_username_echo = p.wait_username_echo;
+                                                    self.profile_edit_auto_connect = p.auto_connect;
+                                                    self.profile_edit_log_original_text = p.log_original_text;
+                                                    self.profile_edit_capture_banner = p.capture_banner;
+                                                    self.profile_edit_pin_prompt = p.pin_prompt;
+                                                    self.profile_edit_collapse_blank_lines = p.collapse_blank_lines;
+                                                    self.profile_edit_room_in_title = p.room_in_title;
+                                                    self.profile_edit_bell_alert = p.bell_alert;
+                                                    self.profile_edit_persist_history = p.persist_history;
+                                                    self.profile_edit_persist_scrollback = p.persist_scrollback;
+                                                    self.profile_edit_alias_strict_args = p.alias_strict_args;
+                                                    self.profile_edit_abbreviations = p.abbreviations.join(" ");
+                                                    self.profile_edit_on_disconnect = p.on_disconnect.clone().unwrap_or_default();
+                                                    self.profile_edit_click_command = p.click_command.clone();
+                                                    self.profile_edit_on_connect = p.on_connect.clone().unwrap_or_default();
+                                                    self.profile_edit_log_path = p.log_path.clone().unwrap_or_default();
+                                                    self.profile_edit_connect_send = p.connect_send.clone().unwrap_or_default();
+                                                    self.profile_edit_prompt_regex = p.prompt_regex.clone().unwrap_or_default();
+                                                    self.profile_edit_chat_pattern = p.chat_pattern.clone().unwrap_or_default();
+                                                    self.profile_edit_gag_patterns = p.gag_patterns.clone();
+                                                    self.profile_edit_max_capture_windows = p.max_capture_windows;
+                                                    self.profile_edit_capture_window_idle_secs = p.capture_window_idle_secs;
+                                                    self.profile_edit_max_reconnect_attempts = p.max_reconnect_attempts;
+                                                    self.profile_edit_anti_spam = p.anti_spam.clone();
                                                     self.show_profile_edit_window = true;
                                                 }
                                                 ui.close_menu();
@@ -1989,7 +2689,193 @@ impl MudApp {
                     ui.label("密碼 (Password):");
                     ui.add(egui::TextEdit::singleline(&mut self.profile_edit_password).password(true));
                     ui.end_row();
-                });
+
+                    ui.label("本地回顯:");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.profile_edit_local_echo, LocalEchoMode::Auto, "自動");
+                        ui.radio_value(&mut self.profile_edit_local_echo, LocalEchoMode::Always, "總是");
+                        ui.radio_value(&mut self.profile_edit_local_echo, LocalEchoMode::Never, "關閉");
+                    });
+                    ui.end_row();
+
+                    ui.label("換行字元:");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.profile_edit_line_ending, LineEnding::Crlf, "CRLF");
+                        ui.radio_value(&mut self.profile_edit_line_ending, LineEnding::Lf, "LF");
+                        ui.radio_value(&mut self.profile_edit_line_ending, LineEnding::Cr, "CR");
+                    })
+                    .response
+                    .on_hover_text("送出指令時附加的換行；伺服器處理 CRLF 有問題時才需更改");
+                    ui.end_row();
+
+                    ui.label("文字編碼:");
+                    ui.horizontal(|ui| {
+                        for encoding in Encoding::ALL {
+                            ui.radio_value(&mut self.profile_edit_encoding, encoding, encoding.name());
+                        }
+                        ui.checkbox(&mut self.profile_edit_auto_detect_encoding, "自動偵測")
+                            .on_hover_text("依連線後最初的輸出判斷編碼並直接套用；上面選的編碼作為偵測前的預設");
+                    })
+                    .response
+                    .on_hover_text("台灣 MUD 多為 Big5，大陸 MUD 多為 GBK；連線中可用 #encoding 臨時切換");
+                    ui.end_row();
+
+                    ui.label("連線後送出:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_connect_send))
+                        .on_hover_text("連線建立後、自動登入前立即送出（伺服器要求先選擇編碼或選單時使用）；留空不送");
+                    ui.end_row();
+
+                    ui.label("連線訊息:");
+                    ui.checkbox(&mut self.profile_edit_quiet_connect, "隱藏 >>> 狀態訊息");
+                    ui.end_row();
+
+                    ui.label("自動登入:");
+                    ui.checkbox(&mut self.profile_edit_wait_username_echo, "等待帳號回顯後再送密碼");
+                    ui.end_row();
+
+                    ui.label("啟動時:");
+                    ui.checkbox(&mut self.profile_edit_auto_connect, "自動連線");
+                    ui.end_row();
+
+                    ui.label("日誌:");
+                    ui.checkbox(&mut self.profile_edit_log_original_text, "保留替換前的原文");
+                    ui.end_row();
+
+                    ui.label("登入畫面:");
+                    ui.checkbox(&mut self.profile_edit_capture_banner, "登入前的訊息另存到 banner 視窗");
+                    ui.end_row();
+
+                    ui.label("提示符:");
+                    ui.checkbox(&mut self.profile_edit_pin_prompt, "固定在輸入框上方（需伺服器送出 GA/EOR）");
+                    ui.end_row();
+
+                    ui.label("空白行:");
+                    ui.checkbox(&mut self.profile_edit_collapse_blank_lines, "連續的空白行只保留一行（ASCII 藝術模式除外）");
+                    ui.end_row();
+
+                    ui.label("視窗標題:");
+                    ui.checkbox(&mut self.profile_edit_room_in_title, "顯示目前房間名稱（需伺服器支援 GMCP）");
+                    ui.end_row();
+
+                    ui.label("提示音:");
+                    ui.checkbox(&mut self.profile_edit_bell_alert, "伺服器送出 BEL 時提醒（閃爍工作列，3 秒內只提醒一次）");
+                    ui.end_row();
+
+                    ui.label("輸入歷史:");
+                    ui.checkbox(&mut self.profile_edit_persist_history, "儲存到磁碟，下次連線時載入");
+                    ui.end_row();
+
+                    ui.label("畫面記錄:");
+                    ui.checkbox(&mut self.profile_edit_persist_scrollback, "結束程式時保存主視窗內容，下次開啟時載入");
+                    ui.end_row();
+
+                    ui.label("別名參數:");
+                    ui.checkbox(&mut self.profile_edit_alias_strict_args, "參數不足時顯示錯誤，不送出指令")
+                        .on_hover_text("關閉時缺少的 $N 以空字串代入");
+                    ui.end_row();
+
+                    ui.label("指令縮寫:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_abbreviations).hint_text("inventory information ..."))
+                        .on_hover_text("以空白分隔完整指令，輸入不混淆的前綴（至少 2 字）即自動展開");
+                    ui.end_row();
+
+                    ui.label("連線後執行:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_on_connect).hint_text("look;score"))
+                        .on_hover_text("每次連線（含自動重連）在自動登入後執行，可用 ; 分隔多個指令");
+                    ui.end_row();
+
+                    ui.label("斷線前送出:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_on_disconnect).hint_text("quit"))
+                        .on_hover_text("按下斷線時先送出此指令登出，連線意外中斷時不會送出");
+                    ui.end_row();
+
+                    ui.label("點擊單字送出:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_click_command).hint_text("kill $word"))
+                        .on_hover_text("Ctrl+點擊輸出中的單字時送出，$word 代入點擊的單字；留空停用");
+                    ui.end_row();
+
+                    ui.label("日誌路徑:");
+                    ui.add(TextEdit::singleline(&mut self.profile_edit_log_path).hint_text(mudcore::DEFAULT_LOG_PATH_TEMPLATE))
+                        .on_hover_text("可使用 {profile}、{date}、{time}（UTC），目錄不存在時自動建立；留空使用預設");
+                    ui.end_row();
+
+                    ui.label("提示符正則:");
+                    ui.vertical(|ui| {
+                        ui.add(TextEdit::singleline(&mut self.profile_edit_prompt_regex).hint_text(r"^<\d+hp \d+mv>"))
+                            .on_hover_text("用於判斷提示符（房間敘述結束、觸發範圍）；留空使用內建的 (hp.../...) 判斷");
+                        if let Err(e) = regex::Regex::new(&self.profile_edit_prompt_regex) {
+                            ui.label(RichText::new(format!("⚠ {}", e)).small().color(Color32::RED));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("聊天正則:");
+                    ui.vertical(|ui| {
+                        ui.add(TextEdit::singleline(&mut self.profile_edit_chat_pattern).hint_text(r"告訴你|^【閒聊】"))
+                            .on_hover_text("符合的訊息轉送到「聊天彙整」分頁（需在設定 → 一般 啟用）；留空不轉送");
+                        if let Err(e) = regex::Regex::new(&self.profile_edit_chat_pattern) {
+                            ui.label(RichText::new(format!("⚠ {}", e)).small().color(Color32::RED));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("隱藏清單:");
+                    ui.vertical(|ui| {
+                        let mut remove_index = None;
+                        for (i, pattern) in self.profile_edit_gag_patterns.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.add(TextEdit::singleline(pattern).hint_text("子字串或 /正則/").desired_width(220.0));
+                                if ui.small_button("🗑").on_hover_text("移除").clicked() {
+                                    remove_index = Some(i);
+                                }
+                            });
+                            if let Err(e) = regex::Regex::new(&gag_pattern_source(pattern)) {
+                                ui.label(RichText::new(format!("⚠ {}", e)).small().color(Color32::RED));
+                            }
+                        }
+                        if let Some(i) = remove_index {
+                            self.profile_edit_gag_patterns.remove(i);
+                        }
+                        if ui.small_button("➕ 新增").on_hover_text("符合的伺服器訊息不顯示（仍寫入日誌），不經過觸發器").clicked() {
+                            self.profile_edit_gag_patterns.push(String::new());
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("擷取視窗上限:");
+                    ui.add(egui::DragValue::new(&mut self.profile_edit_max_capture_windows).range(0..=100))
+                        .on_hover_text("0 表示不限");
+                    ui.end_row();
+
+                    ui.label("閒置關閉 (秒):");
+                    ui.add(egui::DragValue::new(&mut self.profile_edit_capture_window_idle_secs).range(0..=86400))
+                        .on_hover_text("0 表示不自動關閉");
+                    ui.end_row();
+
+                    ui.label("重連次數上限:");
+                    ui.add(egui::DragValue::new(&mut self.profile_edit_max_reconnect_attempts).range(0..=1000))
+                        .on_hover_text("自動重連連續失敗達到次數後放棄；0 表示不限");
+                    ui.end_row();
+
+                    ui.label("重複指令防呆:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut self.profile_edit_anti_spam.threshold).range(0..=1000).suffix(" 次"))
+                            .on_hover_text("0 表示停用");
+                        ui.radio_value(&mut self.profile_edit_anti_spam.action, AntiSpamAction::SendCommand, "插入指令");
+                        ui.radio_value(&mut self.profile_edit_anti_spam.action, AntiSpamAction::WarnOnly, "僅警告");
+                    });
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.horizontal(|ui| {
+                        ui.add_enabled(
+                            self.profile_edit_anti_spam.action == AntiSpamAction::SendCommand,
+                            egui::TextEdit::singleline(&mut self.profile_edit_anti_spam.command).desired_width(80.0),
+                        );
+                        ui.checkbox(&mut self.profile_edit_anti_spam.exempt_movement, "移動與空指令不計入");
+                    });
+                    ui.end_row();
+                });
 
                 ui.add_space(20.0);
                 
@@ -2017,6 +2903,46 @@ impl MudApp {
                             
                             profile.username = if self.profile_edit_username.is_empty() { None } else { Some(self.profile_edit_username.clone()) };
                             profile.password = if self.profile_edit_password.is_empty() { None } else { Some(self.profile_edit_password.clone()) };
+                            profile.local_echo = self.profile_edit_local_echo;
+                            profile.line_ending = self.profile_edit_line_ending;
+                            profile.encoding = self.profile_edit_encoding;
+                            profile.auto_detect_encoding = self.profile_edit_auto_detect_encoding;
+                            profile.quiet_connect = self.profile_edit_quiet_connect;
+                            profile.wait_username_echo = self.profile_edit_wait_username_echo;
+                            profile.auto_connect = self.profile_edit_auto_connect;
+                            profile.log_original_text = self.profile_edit_log_original_text;
+                            profile.capture_banner = self.profile_edit_capture_banner;
+                            profile.pin_prompt = self.profile_edit_pin_prompt;
+                            profile.collapse_blank_lines = self.profile_edit_collapse_blank_lines;
+                            profile.room_in_title = self.profile_edit_room_in_title;
+                            profile.bell_alert = self.profile_edit_bell_alert;
+                            profile.persist_history = self.profile_edit_persist_history;
+                            profile.persist_scrollback = self.profile_edit_persist_scrollback;
+                            profile.alias_strict_args = self.profile_edit_alias_strict_args;
+                            profile.abbreviations = self.profile_edit_abbreviations.split_whitespace().map(str::to_string).collect();
+                            let log_path = self.profile_edit_log_path.trim();
+                            profile.log_path = (!log_path.is_empty()).then(|| log_path.to_string());
+                            let connect_send = self.profile_edit_connect_send.trim();
+                            profile.connect_send = (!connect_send.is_empty()).then(|| connect_send.to_string());
+                            let on_disconnect = self.profile_edit_on_disconnect.trim();
+                            profile.on_disconnect = (!on_disconnect.is_empty()).then(|| on_disconnect.to_string());
+                            profile.click_command = self.profile_edit_click_command.trim().to_string();
+                            let on_connect = self.profile_edit_on_connect.trim();
+                            profile.on_connect = (!on_connect.is_empty()).then(|| on_connect.to_string());
+                            let prompt_regex = self.profile_edit_prompt_regex.trim();
+                            profile.prompt_regex = (!prompt_regex.is_empty()).then(|| prompt_regex.to_string());
+                            let chat_pattern = self.profile_edit_chat_pattern.trim();
+                            profile.chat_pattern = (!chat_pattern.is_empty()).then(|| chat_pattern.to_string());
+                            profile.gag_patterns = self
+                                .profile_edit_gag_patterns
+                                .iter()
+                                .map(|p| p.trim().to_string())
+                                .filter(|p| !p.is_empty())
+                                .collect();
+                            profile.max_capture_windows = self.profile_edit_max_capture_windows;
+                            profile.capture_window_idle_secs = self.profile_edit_capture_window_idle_secs;
+                            profile.max_reconnect_attempts = self.profile_edit_max_reconnect_attempts;
+                            profile.anti_spam = self.profile_edit_anti_spam.clone();
                             
                             // 儲存
                             if let Err(e) = self.profile_manager.save(profile) {
@@ -2034,6 +2960,109 @@ impl MudApp {
             });
     }
 
+    /// 繪製 Telnet 選項診斷視窗（列出目前 Session 各選項的協商結果）
+    fn render_telnet_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_telnet_window;
+        let session = self.session_manager.active_session();
+
+        egui::Window::new("📡 Telnet 選項")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                let Some(session) = session else {
+                    ui.label(RichText::new("尚無連線中的 Session").weak());
+                    return;
+                };
+                if session.telnet_options.is_empty() {
+                    ui.label(RichText::new("伺服器尚未進行選項協商").weak());
+                    return;
+                }
+
+                egui::Grid::new("telnet_option_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (option, status) in &session.telnet_options {
+                            ui.label(RichText::new(format!("{:?} ({})", option, option.as_byte())).monospace());
+                            let color = if *status == OptionStatus::Active { Color32::GREEN } else { Color32::GRAY };
+                            ui.colored_label(color, status.label());
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        self.show_telnet_window = open;
+    }
+
+    /// 繪製變數檢視視窗（即時列出目前 Session 的腳本變數，可編輯或刪除）
+    fn render_variable_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_variable_window;
+        let editing = &mut self.variable_editing;
+        let session = self.session_manager.active_session();
+
+        egui::Window::new("🔍 變數檢視")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let Some(session) = session else {
+                    ui.label(RichText::new("尚無連線中的 Session").weak());
+                    return;
+                };
+                let engine = &session.engine.script_engine;
+                let vars = engine.variables();
+                if vars.is_empty() {
+                    ui.label(RichText::new("尚無變數（可用 #var 或腳本設定）").weak());
+                    return;
+                }
+
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    egui::Grid::new("variable_inspector_grid")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (name, value) in vars {
+                                ui.label(RichText::new(&name).monospace());
+
+                                match editing {
+                                    Some((editing_name, buffer)) if *editing_name == name => {
+                                        let response = ui.text_edit_singleline(buffer);
+                                        let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                        let (save, cancel) = ui
+                                            .horizontal(|ui| (ui.small_button("✔").clicked(), ui.small_button("✖").clicked()))
+                                            .inner;
+                                        if save || enter {
+                                            engine.set_variable(&name, buffer);
+                                            *editing = None;
+                                        } else if cancel {
+                                            *editing = None;
+                                        }
+                                    }
+                                    _ => {
+                                        ui.label(&value);
+                                        ui.horizontal(|ui| {
+                                            if ui.small_button("✏").on_hover_text("編輯").clicked() {
+                                                *editing = Some((name.clone(), value.clone()));
+                                            }
+                                            if ui.small_button("🗑").on_hover_text("刪除").clicked() {
+                                                engine.remove_variable(&name);
+                                            }
+                                        });
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        self.show_variable_window = open;
+        if !open {
+            self.variable_editing = None;
+        }
+    }
+
     /// 繪製設定視窗 (獨立 Window)
     fn render_settings_window(&mut self, ctx: &egui::Context) {
         let mut should_close = false;
@@ -2078,9 +3107,131 @@ impl MudApp {
                     if self.settings_scope == SettingsScope::Global {
                         ui.colored_label(egui::Color32::LIGHT_BLUE, "ℹ️ 正在編輯全域設定，所有 Profile 預設都會套用這些設定。");
                     }
+                    ui.horizontal(|ui| {
+                        ui.label("📥 匯入 TinTin++:");
+                        ui.add(TextEdit::singleline(&mut self.import_path).hint_text("腳本檔案路徑 (.tin)").desired_width(220.0));
+                        if ui.button("匯入").clicked() && !self.import_path.trim().is_empty() {
+                            self.import_report = Some(match std::fs::read_to_string(self.import_path.trim()) {
+                                Ok(script) => {
+                                    let result = crate::config::import_tintin(&script);
+                                    let summary = format!(
+                                        "已匯入 {} 個別名、{} 個觸發器，略過 {} 項",
+                                        result.aliases.len(),
+                                        result.triggers.len(),
+                                        result.skipped.len()
+                                    );
+                                    match self.settings_scope {
+                                        SettingsScope::Profile => {
+                                            for cfg in &result.aliases {
+                                                let mut alias = Alias::new(&cfg.name, &cfg.pattern, &cfg.replacement);
+                                                alias.category = cfg.category.clone();
+                                                session.engine.alias_manager.add(alias);
+                                            }
+                                            for cfg in &result.triggers {
+                                                if let Some(trigger) = crate::session::Session::create_trigger_from_config(cfg) {
+                                                    session.engine.trigger_manager.add(trigger);
+                                                }
+                                            }
+                                        }
+                                        SettingsScope::Global => {
+                                            let global = &mut self.global_config;
+                                            for cfg in result.aliases {
+                                                global.global_aliases.retain(|a| a.name != cfg.name);
+                                                global.global_aliases.push(cfg);
+                                            }
+                                            for cfg in result.triggers {
+                                                global.global_triggers.retain(|t| t.name != cfg.name);
+                                                global.global_triggers.push(cfg);
+                                            }
+                                        }
+                                    }
+                                    needs_save = true;
+                                    if result.skipped.is_empty() {
+                                        summary
+                                    } else {
+                                        format!("{}：\n{}", summary, result.skipped.join("\n"))
+                                    }
+                                }
+                                Err(e) => format!("讀取失敗: {}", e),
+                            });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("📦 分享包:");
+                        ui.add(TextEdit::singleline(&mut self.share_path).hint_text("分享包路徑 (.json)").desired_width(220.0));
+                        if ui.button("匯入").clicked() && !self.share_path.trim().is_empty() {
+                            self.import_report = Some(match SharePack::load(std::path::Path::new(self.share_path.trim())) {
+                                Ok(mut pack) => {
+                                    let skipped = match self.settings_scope {
+                                        SettingsScope::Profile => pack.resolve_conflicts(
+                                            self.share_strategy,
+                                            |n| session.engine.trigger_manager.get(n).is_some(),
+                                            |n| session.engine.alias_manager.get(n).is_some(),
+                                        ),
+                                        SettingsScope::Global => {
+                                            let global = &self.global_config;
+                                            pack.resolve_conflicts(
+                                                self.share_strategy,
+                                                |n| global.global_triggers.iter().any(|t| t.name == n),
+                                                |n| global.global_aliases.iter().any(|a| a.name == n),
+                                            )
+                                        }
+                                    };
+                                    let summary = format!(
+                                        "已匯入 {} 個別名、{} 個觸發器，略過 {} 項同名項目",
+                                        pack.aliases.len(),
+                                        pack.triggers.len(),
+                                        skipped
+                                    );
+                                    match self.settings_scope {
+                                        SettingsScope::Profile => {
+                                            for cfg in &pack.aliases {
+                                                session.engine.alias_manager.add(crate::session::Session::create_alias_from_config(cfg));
+                                            }
+                                            for cfg in &pack.triggers {
+                                                if let Some(trigger) = crate::session::Session::create_trigger_from_config(cfg) {
+                                                    session.engine.trigger_manager.add(trigger);
+                                                }
+                                            }
+                                        }
+                                        SettingsScope::Global => {
+                                            let global = &mut self.global_config;
+                                            for cfg in pack.aliases {
+                                                global.global_aliases.retain(|a| a.name != cfg.name);
+                                                global.global_aliases.push(cfg);
+                                            }
+                                            for cfg in pack.triggers {
+                                                global.global_triggers.retain(|t| t.name != cfg.name);
+                                                global.global_triggers.push(cfg);
+                                            }
+                                        }
+                                    }
+                                    needs_save = true;
+                                    summary
+                                }
+                                Err(e) => format!("讀取失敗: {}", e),
+                            });
+                        }
+                        ui.label("同名時:");
+                        ui.radio_value(&mut self.share_strategy, MergeStrategy::Skip, "略過");
+                        ui.radio_value(&mut self.share_strategy, MergeStrategy::Rename, "改名");
+                        ui.radio_value(&mut self.share_strategy, MergeStrategy::Overwrite, "覆蓋");
+                    });
+                    if self.settings_tab == SettingsTab::Trigger {
+                        ui.checkbox(&mut self.share_with_aliases, "匯出觸發器時一併匯出指令用到的別名");
+                    }
+                    if let Some(report) = &self.import_report {
+                        ui.label(RichText::new(report).small().weak());
+                    }
                     ui.separator();
                 }
 
+                // 切換分頁或範圍時清除多選
+                if self.settings_selection_key != (self.settings_tab, self.settings_scope) {
+                    self.settings_selection_key = (self.settings_tab, self.settings_scope);
+                    self.settings_selection.clear();
+                }
+
                 // 根據目前的 Tab 渲染內容
                 match self.settings_tab {
                     SettingsTab::Alias => {
@@ -2096,14 +3247,19 @@ impl MudApp {
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 if ui.button("➕ 新增別名").clicked() {
                                     self.editing_alias_name = Some(String::new());
-                                    self.alias_edit_pattern = String::new();
-                                    self.alias_edit_replacement = String::new();
-                                    self.alias_edit_category = String::new();
+                                    self.alias_edit = AliasEditorState::default();
                                     self.show_alias_window = true;
                                 }
+                                if self.settings_scope == SettingsScope::Profile
+                                    && ui.button("💾 儲存啟用狀態").on_hover_text("將目前的啟用狀態（含腳本切換）存為預設值").clicked()
+                                {
+                                    session.save_enabled_state();
+                                    needs_save = true;
+                                }
                             });
                         });
                         ui.add_space(5.0);
+                        let bulk_op = render_bulk_toolbar(ui, &mut self.settings_selection);
                         
                         // 定義別名來源類型
                         #[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
@@ -2113,20 +3269,25 @@ impl MudApp {
                             Override, // 本地設定 (覆蓋全域)
                         }
 
+                        // (名稱, 模式, 替換, 分類, 啟用, 腳本, 來源, 說明)
+                        type AliasRow = (String, String, String, Option<String>, bool, bool, AliasSource, String);
+
                         // 收集 Alias 列表
-                        let mut alias_list: Vec<(String, String, String, Option<String>, bool, bool, AliasSource)> = match self.settings_scope {
+                        let mut alias_list: Vec<AliasRow> = match self.settings_scope {
                             SettingsScope::Profile => {
                                 // Profile 模式: 顯示 Session 中的別名
-                                session.alias_manager.sorted_aliases.iter()
+                                session.engine.alias_manager.sorted_aliases.iter()
                                     .filter_map(|name| {
-                                        session.alias_manager.aliases.get(name).map(|a| {
+                                        session.engine.alias_manager.aliases.get(name).map(|a| {
                                             // 判斷來源
                                             let source = if let Some(global_a) = self.global_config.global_aliases.iter().find(|ga| ga.name == a.name) {
                                                 let global_is_match = global_a.pattern == a.pattern &&
                                                                     global_a.replacement == a.replacement &&
                                                                     global_a.is_script == a.is_script &&
-                                                                    global_a.enabled == a.enabled &&
-                                                                    global_a.category == a.category;
+                                                                    global_a.is_regex == a.is_regex &&
+                                                                    global_a.default_enabled == a.default_enabled &&
+                                                                    global_a.category == a.category &&
+                                                                    global_a.description == a.description;
                                                                     
                                                 if global_is_match {
                                                     AliasSource::Global
@@ -2137,7 +3298,7 @@ impl MudApp {
                                                 AliasSource::Profile
                                             };
 
-                                            (a.name.clone(), a.pattern.clone(), a.replacement.clone(), a.category.clone(), a.enabled, a.is_script, source)
+                                            (a.name.clone(), a.pattern.clone(), a.replacement.clone(), a.category.clone(), a.enabled, a.is_script, source, a.description.clone())
                                         })
                                     })
                                     .collect()
@@ -2145,7 +3306,7 @@ impl MudApp {
                             SettingsScope::Global => {
                                 // Global 模式: 顯示 Global Config 中的別名
                                 self.global_config.global_aliases.iter().map(|a| {
-                                    (a.name.clone(), a.pattern.clone(), a.replacement.clone(), a.category.clone(), a.enabled, a.is_script, AliasSource::Global)
+                                    (a.name.clone(), a.pattern.clone(), a.replacement.clone(), a.category.clone(), a.default_enabled, a.is_script, AliasSource::Global, a.description.clone())
                                 }).collect()
                             }
                         };
@@ -2153,24 +3314,26 @@ impl MudApp {
                         // 搜尋過濾
                         let search = self.alias_search_text.to_lowercase();
                         if !search.is_empty() {
-                            alias_list.retain(|(name, pattern, replacement, cat, _, _, _)| {
+                            alias_list.retain(|(name, pattern, replacement, cat, _, _, _, description)| {
                                 name.to_lowercase().contains(&search) || 
                                 pattern.to_lowercase().contains(&search) ||
                                 replacement.to_lowercase().contains(&search) ||
-                                cat.as_deref().unwrap_or("").to_lowercase().contains(&search)
+                                cat.as_deref().unwrap_or("").to_lowercase().contains(&search) ||
+                                description.to_lowercase().contains(&search)
                             });
                         }
 
                         // Grouping Logic
-                        let mut grouped_aliases: std::collections::BTreeMap<Option<String>, Vec<(String, String, String, Option<String>, bool, bool, AliasSource)>> = std::collections::BTreeMap::new();
+                        let mut grouped_aliases: std::collections::BTreeMap<Option<String>, Vec<AliasRow>> = std::collections::BTreeMap::new();
                         for item in alias_list {
                             grouped_aliases.entry(item.3.clone()).or_default().push(item);
                         }
 
                         let mut to_delete: Option<String> = None;
-                        let mut to_edit: Option<(String, String, String, String, bool)> = None;
+                        let mut to_edit: Option<(String, String, String, String, bool, String)> = None;
                         let mut to_toggle_name: Option<(String, bool)> = None;
                         let mut to_toggle_category: Option<(Option<String>, bool)> = None;
+                        let mut to_rename_category: Option<(String, String)> = None;
 
                         // 操作 Action
                         enum AliasOp {
@@ -2219,7 +3382,20 @@ impl MudApp {
                                          row.col(|_| {}); // Source placeholder
                                          row.col(|ui| {
                                              let cat_name = category.as_deref().unwrap_or("未分類");
-                                             ui.strong(cat_name);
+                                             let response = ui.add(egui::Label::new(RichText::new(cat_name).strong()).sense(egui::Sense::click()));
+                                             if let Some(old) = &category {
+                                                 if response.secondary_clicked() {
+                                                     self.category_rename_input = old.clone();
+                                                 }
+                                                 response.on_hover_text("右鍵重新命名分類").context_menu(|ui| {
+                                                     ui.label("重新命名為（同名則合併）:");
+                                                     ui.text_edit_singleline(&mut self.category_rename_input);
+                                                     if ui.button("✏️ 重新命名").clicked() {
+                                                         to_rename_category = Some((old.clone(), self.category_rename_input.clone()));
+                                                         ui.close_menu();
+                                                     }
+                                                 });
+                                             }
                                          });
                                          row.col(|ui| {
                                              // Batch toggle
@@ -2237,7 +3413,7 @@ impl MudApp {
                                      });
 
                                     if is_expanded {
-                                        for (name, pattern, replacement, cat, enabled, is_script, source) in items {
+                                        for (name, pattern, replacement, cat, enabled, is_script, source, description) in items {
                                             body.row(24.0, |mut row| {
                                                 // 1. 啟用
                                                 row.col(|ui| {
@@ -2261,9 +3437,18 @@ impl MudApp {
                                                     // ui.label(cat.as_deref().unwrap_or("-")); // Optional
                                                 });
 
-                                                // 4. 名稱
+                                                // 4. 名稱（勾選以批次操作）
                                                 row.col(|ui| {
-                                                    ui.label(&name);
+                                                    let mut selected = self.settings_selection.contains(&name);
+                                                    let response = ui.checkbox(&mut selected, &name);
+                                                    let response = if description.is_empty() { response } else { response.on_hover_text(&description) };
+                                                    if response.changed() {
+                                                        if selected {
+                                                            self.settings_selection.insert(name.clone());
+                                                        } else {
+                                                            self.settings_selection.remove(&name);
+                                                        }
+                                                    }
                                                 });
 
                                                 // 5. 指令 (Pattern)
@@ -2292,7 +3477,7 @@ impl MudApp {
                                                      ui.horizontal(|ui| {
                                                         ui.spacing_mut().item_spacing.x = 8.0;
                                                         if ui.button("✏️").on_hover_text("編輯").clicked() {
-                                                            to_edit = Some((name.clone(), pattern.clone(), replacement.clone(), cat.clone().unwrap_or_default(), is_script));
+                                                            to_edit = Some((name.clone(), pattern.clone(), replacement.clone(), cat.clone().unwrap_or_default(), is_script, description.clone()));
                                                         }
 
                                                         if self.settings_scope == SettingsScope::Profile {
@@ -2315,7 +3500,7 @@ impl MudApp {
                                                                             ui.close_menu();
                                                                         }
                                                                         if ui.button("✏️ 覆蓋 (Override)").clicked() {
-                                                                            to_edit = Some((name.clone(), pattern.clone(), replacement.clone(), cat.clone().unwrap_or_default(), is_script));
+                                                                            to_edit = Some((name.clone(), pattern.clone(), replacement.clone(), cat.clone().unwrap_or_default(), is_script, description.clone()));
                                                                             ui.close_menu();
                                                                         }
                                                                     },
@@ -2348,13 +3533,28 @@ impl MudApp {
                         if let Some((cat, enabled)) = to_toggle_category {
                              match self.settings_scope {
                                 SettingsScope::Profile => {
-                                    for alias in session.alias_manager.aliases.values_mut() {
-                                        if alias.category == cat { alias.enabled = enabled; }
+                                    for alias in session.engine.alias_manager.aliases.values_mut() {
+                                        if alias.category == cat { alias.enabled = enabled; alias.default_enabled = enabled; }
                                     }
                                 },
                                 SettingsScope::Global => {
                                     for alias in self.global_config.global_aliases.iter_mut() {
-                                        if alias.category == cat { alias.enabled = enabled; }
+                                        if alias.category == cat { alias.default_enabled = enabled; }
+                                    }
+                                }
+                            }
+                            needs_save = true;
+                        }
+
+                        if let Some((old, new)) = to_rename_category {
+                            match self.settings_scope {
+                                SettingsScope::Profile => { session.engine.alias_manager.rename_category(&old, &new); },
+                                SettingsScope::Global => {
+                                    let new = new.trim();
+                                    for alias in self.global_config.global_aliases.iter_mut() {
+                                        if alias.category.as_deref() == Some(old.as_str()) {
+                                            alias.category = (!new.is_empty()).then(|| new.to_string());
+                                        }
                                     }
                                 }
                             }
@@ -2364,14 +3564,15 @@ impl MudApp {
                         if let Some((name, enabled)) = to_toggle_name {
                              match self.settings_scope {
                                 SettingsScope::Profile => {
-                                    if let Some(alias) = session.alias_manager.aliases.get_mut(&name) {
+                                    if let Some(alias) = session.engine.alias_manager.aliases.get_mut(&name) {
                                         alias.enabled = enabled;
+                                        alias.default_enabled = enabled;
                                         needs_save = true;
                                     }
                                 },
                                 SettingsScope::Global => {
                                     if let Some(alias) = self.global_config.global_aliases.iter_mut().find(|a| a.name == name) {
-                                        alias.enabled = enabled;
+                                        alias.default_enabled = enabled;
                                         needs_save = true;
                                     }
                                 }
@@ -2380,7 +3581,7 @@ impl MudApp {
 
                         if let Some(name) = to_delete {
                             match self.settings_scope {
-                                SettingsScope::Profile => { session.alias_manager.remove(&name); },
+                                SettingsScope::Profile => { session.engine.alias_manager.remove(&name); },
                                 SettingsScope::Global => { 
                                     self.global_config.global_aliases.retain(|a| a.name != name); 
                                 }
@@ -2388,12 +3589,70 @@ impl MudApp {
                             needs_save = true;
                         }
 
-                        if let Some((name, pattern, replacement, category, is_script)) = to_edit {
+                        if let Some(op) = bulk_op {
+                            let names: Vec<String> = self.settings_selection.iter().cloned().collect();
+                            match (op, self.settings_scope) {
+                                (BulkOp::Delete, SettingsScope::Profile) => {
+                                    session.engine.alias_manager.remove_many(&names);
+                                }
+                                (BulkOp::Delete, SettingsScope::Global) => {
+                                    self.global_config.global_aliases.retain(|a| !names.contains(&a.name));
+                                }
+                                (BulkOp::Export, scope) => {
+                                    let aliases: Vec<_> = match scope {
+                                        SettingsScope::Profile => names
+                                            .iter()
+                                            .filter_map(|n| session.engine.alias_manager.get(n))
+                                            .map(crate::session::Session::config_from_alias)
+                                            .collect(),
+                                        SettingsScope::Global => self
+                                            .global_config
+                                            .global_aliases
+                                            .iter()
+                                            .filter(|a| names.contains(&a.name))
+                                            .cloned()
+                                            .collect(),
+                                    };
+                                    let count = aliases.len();
+                                    let path = self.share_path.trim().to_string();
+                                    self.import_report = Some(match SharePack::new(Vec::new(), aliases).save(std::path::Path::new(&path)) {
+                                        Ok(()) => format!("已匯出 {} 個別名到 {}", count, path),
+                                        Err(e) => format!("匯出失敗: {}", e),
+                                    });
+                                }
+                                (op, SettingsScope::Profile) => {
+                                    let enabled = op == BulkOp::Enable;
+                                    for name in &names {
+                                        if let Some(alias) = session.engine.alias_manager.aliases.get_mut(name) {
+                                            alias.enabled = enabled;
+                                            alias.default_enabled = enabled;
+                                        }
+                                    }
+                                }
+                                (op, SettingsScope::Global) => {
+                                    let enabled = op == BulkOp::Enable;
+                                    for alias in self.global_config.global_aliases.iter_mut().filter(|a| names.contains(&a.name)) {
+                                        alias.default_enabled = enabled;
+                                    }
+                                }
+                            }
+                            if op == BulkOp::Delete {
+                                self.settings_selection.clear();
+                            }
+                            needs_save = true;
+                        }
+
+                        if let Some((name, pattern, replacement, category, is_script, description)) = to_edit {
+                            self.alias_edit.pattern = pattern;
+                            self.alias_edit.replacement = replacement;
+                            self.alias_edit.category = category;
+                            self.alias_edit.description = description;
+                            self.alias_edit.is_script = is_script;
+                            self.alias_edit.is_regex = match self.settings_scope {
+                                SettingsScope::Profile => session.engine.alias_manager.get(&name).is_some_and(|a| a.is_regex),
+                                SettingsScope::Global => self.global_config.global_aliases.iter().any(|a| a.name == name && a.is_regex),
+                            };
                             self.editing_alias_name = Some(name);
-                            self.alias_edit_pattern = pattern;
-                            self.alias_edit_replacement = replacement;
-                            self.alias_edit_category = category;
-                            self.alias_edit_is_script = is_script;
                             self.show_alias_window = true;
                         }
 
@@ -2401,14 +3660,16 @@ impl MudApp {
                         if let Some(op) = op_action {
                             match op {
                                 AliasOp::MoveToGlobal(name) | AliasOp::CopyToGlobal(name) => {
-                                    if let Some(a) = session.alias_manager.aliases.get(&name) {
+                                    if let Some(a) = session.engine.alias_manager.aliases.get(&name) {
                                         let new_config = crate::config::AliasConfig {
                                             name: a.name.clone(),
                                             pattern: a.pattern.clone(),
                                             replacement: a.replacement.clone(),
                                             category: a.category.clone(),
+                                            description: a.description.clone(),
                                             is_script: a.is_script,
-                                            enabled: a.enabled,
+                                            is_regex: a.is_regex,
+                                            default_enabled: a.default_enabled,
                                         };
 
                                         if let Some(existing) = self.global_config.global_aliases.iter_mut().find(|ga| ga.name == name) {
@@ -2426,12 +3687,13 @@ impl MudApp {
                                 AliasOp::RevertToGlobal(name) => {
                                     if let Some(ga) = self.global_config.global_aliases.iter().find(|a| a.name == name) {
                                        let mut alias = mudcore::Alias::new(&ga.name, &ga.pattern, &ga.replacement)
-                                           .as_script(ga.is_script);
-                                       alias.enabled = ga.enabled;
+                                           .as_script(ga.is_script)
+                                           .as_regex(ga.is_regex);
+                                       alias = alias.with_default_enabled(ga.default_enabled).with_description(ga.description.clone());
                                        if let Some(ref cat) = ga.category {
                                            alias = alias.with_category(cat);
                                        }
-                                       session.alias_manager.add(alias);
+                                       session.engine.alias_manager.add(alias);
                                        needs_save = true;
                                     }
                                 }
@@ -2451,15 +3713,19 @@ impl MudApp {
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 if ui.button("➕ 新增觸發器").clicked() {
                                     self.editing_trigger_name = Some(String::new());
-                                    self.trigger_edit_name = String::new();
-                                    self.trigger_edit_pattern = String::new();
-                                    self.trigger_edit_action = String::new();
-                                    self.trigger_edit_category = String::new();
+                                    self.trigger_edit = TriggerEditorState::default();
                                     self.show_trigger_window = true;
                                 }
+                                if self.settings_scope == SettingsScope::Profile
+                                    && ui.button("💾 儲存啟用狀態").on_hover_text("將目前的啟用狀態（含腳本切換）存為預設值").clicked()
+                                {
+                                    session.save_enabled_state();
+                                    needs_save = true;
+                                }
                             });
                         });
                         ui.add_space(5.0);
+                        let bulk_op = render_bulk_toolbar(ui, &mut self.settings_selection);
                         
                         // 定義觸發器來源類型
                         #[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
@@ -2469,37 +3735,43 @@ impl MudApp {
                             Override, // 本地設定 (覆蓋全域)
                         }
 
+                        // (顯示文字, 設定, 來源, 正則錯誤)
+                        type TriggerRow = (String, crate::config::TriggerConfig, TriggerSource, Option<String>);
+
                         // 收集 Trigger 列表
-                        let mut trigger_list: Vec<(String, String, String, Option<String>, bool, bool, String, TriggerSource)> = match self.settings_scope {
+                        // (顯示文字, 設定, 來源, 正則錯誤)
+                        let mut trigger_list: Vec<TriggerRow> = match self.settings_scope {
                             SettingsScope::Profile => {
-                                session.trigger_manager.order.iter()
+                                session.engine.trigger_manager.order.iter()
                                     .filter_map(|name| {
-                                        session.trigger_manager.triggers.get(name).map(|t| {
+                                        session.engine.trigger_manager.triggers.get(name).map(|t| {
                                             let pattern_text = match &t.pattern {
                                                 TriggerPattern::Contains(s) => format!("包含: {}", s),
                                                 TriggerPattern::StartsWith(s) => format!("開頭: {}", s),
                                                 TriggerPattern::EndsWith(s) => format!("結尾: {}", s),
                                                 TriggerPattern::Regex(s) => format!("正則: {}", s),
+                                                TriggerPattern::AnyOf(_) => format!("任一: {}", t.pattern.source()),
+                                                TriggerPattern::AllOf(_) => format!("全部: {}", t.pattern.source()),
+                                                TriggerPattern::MultiLine(_) => format!("多行: {}", t.pattern.source()),
                                             };
-                                            let clean_pattern = match &t.pattern {
-                                                TriggerPattern::Contains(s) | TriggerPattern::StartsWith(s) |
-                                                TriggerPattern::EndsWith(s) | TriggerPattern::Regex(s) => s.clone(),
-                                            };
-                                            let (action_str, is_script) = t.actions.iter().find_map(|a| {
-                                                match a {
-                                                    TriggerAction::SendCommand(cmd) => Some((cmd.clone(), false)),
-                                                    TriggerAction::ExecuteScript(code) => Some((code.clone(), true)),
-                                                    _ => None,
-                                                }
-                                            }).unwrap_or_default();
+                                            let config = crate::session::Session::config_from_trigger(t);
                                             
                                             // 判斷來源
                                             let source = if let Some(global_t) = self.global_config.global_triggers.iter().find(|gt| gt.name == t.name) {
-                                                let global_is_match = clean_pattern_string(&global_t.pattern) == clean_pattern &&
-                                                                    global_t.action == action_str &&
-                                                                    global_t.is_script == is_script &&
-                                                                    global_t.enabled == t.enabled &&
-                                                                    global_t.category == t.category;
+                                                let global_is_match = clean_pattern_string(&global_t.pattern) == config.pattern &&
+                                                                    global_t.action == config.action &&
+                                                                    global_t.is_script == config.is_script &&
+                                                                    global_t.default_enabled == config.default_enabled &&
+                                                                    global_t.category == config.category &&
+                                                                    global_t.description == config.description &&
+                                                                    global_t.max_fires == config.max_fires &&
+                                                                    global_t.priority == config.priority &&
+                                                                    global_t.cooldown_ms == config.cooldown_ms &&
+                                                                    global_t.requires_prior == config.requires_prior &&
+                                                                    global_t.requires_prior_ms == config.requires_prior_ms &&
+                                                                    global_t.action_type == config.action_type &&
+                                                                    global_t.combine == config.combine &&
+                                                                    global_t.variable == config.variable;
                                                                     
                                                 if global_is_match {
                                                     TriggerSource::Global
@@ -2510,7 +3782,7 @@ impl MudApp {
                                                 TriggerSource::Profile
                                             };
 
-                                            (t.name.clone(), pattern_text, clean_pattern, t.category.clone(), t.enabled, is_script, action_str, source)
+                                            (pattern_text, config, source, t.error.clone())
                                         })
                                     })
                                     .collect()
@@ -2518,7 +3790,8 @@ impl MudApp {
                             SettingsScope::Global => {
                                 self.global_config.global_triggers.iter().map(|t| {
                                     let pattern_text = format!("(Global) {}", t.pattern);
-                                    (t.name.clone(), pattern_text, t.pattern.clone(), t.category.clone(), t.enabled, t.is_script, t.action.clone(), TriggerSource::Global)
+                                    let error = crate::session::Session::create_trigger_from_config(t).and_then(|t| t.error);
+                                    (pattern_text, t.clone(), TriggerSource::Global, error)
                                 }).collect()
                             }
                         };
@@ -2526,23 +3799,25 @@ impl MudApp {
                         // 搜尋過濾
                         let search = self.trigger_search_text.to_lowercase();
                         if !search.is_empty() {
-                            trigger_list.retain(|(name, p_text, _, cat, _, _, _, _)| {
-                                name.to_lowercase().contains(&search) || 
+                            trigger_list.retain(|(p_text, cfg, _, _)| {
+                                cfg.name.to_lowercase().contains(&search) || 
                                 p_text.to_lowercase().contains(&search) ||
-                                cat.as_deref().unwrap_or("").to_lowercase().contains(&search)
+                                cfg.category.as_deref().unwrap_or("").to_lowercase().contains(&search) ||
+                                cfg.description.to_lowercase().contains(&search)
                             });
                         }
                         
                         // Grouping Logic
-                        let mut grouped_triggers: std::collections::BTreeMap<Option<String>, Vec<(String, String, String, Option<String>, bool, bool, String, TriggerSource)>> = std::collections::BTreeMap::new();
+                        let mut grouped_triggers: std::collections::BTreeMap<Option<String>, Vec<TriggerRow>> = std::collections::BTreeMap::new();
                         for item in trigger_list {
-                            grouped_triggers.entry(item.3.clone()).or_default().push(item);
+                            grouped_triggers.entry(item.1.category.clone()).or_default().push(item);
                         }
 
                         let mut to_delete: Option<String> = None;
-                        let mut to_edit: Option<(String, String, String, bool, String)> = None;
+                        let mut to_edit: Option<crate::config::TriggerConfig> = None;
                         let mut to_toggle_name: Option<(String, bool)> = None;
                         let mut to_toggle_category: Option<(Option<String>, bool)> = None;
+                        let mut to_rename_category: Option<(String, String)> = None;
                         
                         // 操作 Action
                         enum TriggerOp {
@@ -2589,12 +3864,25 @@ impl MudApp {
                                         row.col(|_| {}); // Source placeholder
                                         row.col(|ui| {
                                             let cat_name = category.as_deref().unwrap_or("未分類");
-                                            ui.strong(cat_name);
+                                            let response = ui.add(egui::Label::new(RichText::new(cat_name).strong()).sense(egui::Sense::click()));
+                                            if let Some(old) = &category {
+                                                if response.secondary_clicked() {
+                                                    self.category_rename_input = old.clone();
+                                                }
+                                                response.on_hover_text("右鍵重新命名分類").context_menu(|ui| {
+                                                    ui.label("重新命名為（同名則合併）:");
+                                                    ui.text_edit_singleline(&mut self.category_rename_input);
+                                                    if ui.button("✏️ 重新命名").clicked() {
+                                                        to_rename_category = Some((old.clone(), self.category_rename_input.clone()));
+                                                        ui.close_menu();
+                                                    }
+                                                });
+                                            }
                                         });
                                         row.col(|ui| {
                                             // Batch toggle category enabled
                                             if !items.is_empty() {
-                                                let all_enabled = items.iter().all(|i| i.4);
+                                                let all_enabled = items.iter().all(|i| i.1.default_enabled);
                                                 let mut current_all = all_enabled;
                                                 if ui.checkbox(&mut current_all, "(全選)").changed() {
                                                     to_toggle_category = Some((category.clone(), current_all));
@@ -2606,7 +3894,9 @@ impl MudApp {
                                     });
 
                                     if is_expanded {
-                                        for (name, pattern_text, clean_pattern, cat, enabled, is_script, action_str, source) in items {
+                                        for (pattern_text, cfg, source, error) in items {
+                                            let name = cfg.name.clone();
+                                            let enabled = cfg.default_enabled;
                                             body.row(24.0, |mut row| {
                                                 // 1. 啟用
                                                 row.col(|ui| {
@@ -2630,13 +3920,26 @@ impl MudApp {
                                                     // ui.label(cat.as_deref().unwrap_or("-")); // Optional: Leave empty to reduce clutter
                                                 });
 
-                                                // 4. 名稱
+                                                // 4. 名稱（勾選以批次操作）
                                                 row.col(|ui| {
-                                                    ui.label(&name);
+                                                    let mut selected = self.settings_selection.contains(&name);
+                                                    let response = ui.checkbox(&mut selected, &name);
+                                                    let response = if cfg.description.is_empty() { response } else { response.on_hover_text(&cfg.description) };
+                                                    if response.changed() {
+                                                        if selected {
+                                                            self.settings_selection.insert(name.clone());
+                                                        } else {
+                                                            self.settings_selection.remove(&name);
+                                                        }
+                                                    }
                                                 });
 
                                                 // 5. 觸發內容
                                                 row.col(|ui| {
+                                                    if let Some(error) = &error {
+                                                        ui.colored_label(egui::Color32::RED, "⛔")
+                                                            .on_hover_text(format!("正則錯誤，已停用:\n{}", error));
+                                                    }
                                                     ui.label(&pattern_text).on_hover_text(&pattern_text);
                                                 });
 
@@ -2646,7 +3949,7 @@ impl MudApp {
                                                         ui.spacing_mut().item_spacing.x = 8.0; 
                                                         
                                                         if ui.button("✏️").on_hover_text("編輯").clicked() {
-                                                            to_edit = Some((name.clone(), clean_pattern.clone(), action_str.clone(), is_script, cat.clone().unwrap_or_default()));
+                                                            to_edit = Some(cfg.clone());
                                                         }
 
                                                         if self.settings_scope == SettingsScope::Profile {
@@ -2669,7 +3972,7 @@ impl MudApp {
                                                                             ui.close_menu();
                                                                         }
                                                                         if ui.button("✏️ 覆蓋 (Override)").clicked() {
-                                                                            to_edit = Some((name.clone(), clean_pattern.clone(), action_str.clone(), is_script, cat.clone().unwrap_or_default()));
+                                                                            to_edit = Some(cfg.clone());
                                                                             ui.close_menu();
                                                                         }
                                                                     },
@@ -2702,13 +4005,28 @@ impl MudApp {
                         if let Some((cat, enabled)) = to_toggle_category {
                             match self.settings_scope {
                                 SettingsScope::Profile => {
-                                    for trigger in session.trigger_manager.triggers.values_mut() {
-                                        if trigger.category == cat { trigger.enabled = enabled; }
+                                    for trigger in session.engine.trigger_manager.triggers.values_mut() {
+                                        if trigger.category == cat { trigger.enabled = enabled; trigger.default_enabled = enabled; }
                                     }
                                 },
                                 SettingsScope::Global => {
                                     for trigger in self.global_config.global_triggers.iter_mut() {
-                                        if trigger.category == cat { trigger.enabled = enabled; }
+                                        if trigger.category == cat { trigger.default_enabled = enabled; }
+                                    }
+                                }
+                            }
+                            needs_save = true;
+                        }
+
+                        if let Some((old, new)) = to_rename_category {
+                            match self.settings_scope {
+                                SettingsScope::Profile => { session.engine.trigger_manager.rename_category(&old, &new); },
+                                SettingsScope::Global => {
+                                    let new = new.trim();
+                                    for trigger in self.global_config.global_triggers.iter_mut() {
+                                        if trigger.category.as_deref() == Some(old.as_str()) {
+                                            trigger.category = (!new.is_empty()).then(|| new.to_string());
+                                        }
                                     }
                                 }
                             }
@@ -2718,14 +4036,15 @@ impl MudApp {
                         if let Some((name, enabled)) = to_toggle_name {
                              match self.settings_scope {
                                 SettingsScope::Profile => {
-                                    if let Some(trigger) = session.trigger_manager.triggers.get_mut(&name) {
+                                    if let Some(trigger) = session.engine.trigger_manager.triggers.get_mut(&name) {
                                         trigger.enabled = enabled;
+                                        trigger.default_enabled = enabled;
                                         needs_save = true;
                                     }
                                 },
                                 SettingsScope::Global => {
                                     if let Some(trigger) = self.global_config.global_triggers.iter_mut().find(|t| t.name == name) {
-                                        trigger.enabled = enabled;
+                                        trigger.default_enabled = enabled;
                                         needs_save = true;
                                     }
                                 }
@@ -2734,19 +4053,88 @@ impl MudApp {
 
                         if let Some(name) = to_delete {
                             match self.settings_scope {
-                                SettingsScope::Profile => { session.trigger_manager.remove(&name); },
+                                SettingsScope::Profile => { session.engine.trigger_manager.remove(&name); },
                                 SettingsScope::Global => { self.global_config.global_triggers.retain(|t| t.name != name); }
                             }
                             needs_save = true;
                         }
 
-                        if let Some((name, pattern, action, is_script, category)) = to_edit {
-                            self.editing_trigger_name = Some(name.clone());
-                            self.trigger_edit_name = name;
-                            self.trigger_edit_pattern = pattern;
-                            self.trigger_edit_action = action;
-                            self.trigger_edit_category = category;
-                            self.trigger_edit_is_script = is_script;
+                        if let Some(op) = bulk_op {
+                            let names: Vec<String> = self.settings_selection.iter().cloned().collect();
+                            match (op, self.settings_scope) {
+                                (BulkOp::Delete, SettingsScope::Profile) => {
+                                    session.engine.trigger_manager.remove_many(&names);
+                                }
+                                (BulkOp::Delete, SettingsScope::Global) => {
+                                    self.global_config.global_triggers.retain(|t| !names.contains(&t.name));
+                                }
+                                (BulkOp::Export, scope) => {
+                                    let (triggers, all_aliases): (Vec<_>, Vec<_>) = match scope {
+                                        SettingsScope::Profile => (
+                                            names
+                                                .iter()
+                                                .filter_map(|n| session.engine.trigger_manager.get(n))
+                                                .map(crate::session::Session::config_from_trigger)
+                                                .collect(),
+                                            session.engine.alias_manager.list().into_iter().map(crate::session::Session::config_from_alias).collect(),
+                                        ),
+                                        SettingsScope::Global => (
+                                            self.global_config.global_triggers.iter().filter(|t| names.contains(&t.name)).cloned().collect(),
+                                            self.global_config.global_aliases.clone(),
+                                        ),
+                                    };
+                                    let aliases = if self.share_with_aliases {
+                                        crate::config::alias_dependencies(&triggers, &all_aliases)
+                                    } else {
+                                        Vec::new()
+                                    };
+                                    let summary = format!("已匯出 {} 個觸發器、{} 個別名", triggers.len(), aliases.len());
+                                    let path = self.share_path.trim().to_string();
+                                    self.import_report = Some(match SharePack::new(triggers, aliases).save(std::path::Path::new(&path)) {
+                                        Ok(()) => format!("{}到 {}", summary, path),
+                                        Err(e) => format!("匯出失敗: {}", e),
+                                    });
+                                }
+                                (op, SettingsScope::Profile) => {
+                                    let enabled = op == BulkOp::Enable;
+                                    for name in &names {
+                                        if let Some(trigger) = session.engine.trigger_manager.get_mut(name) {
+                                            trigger.enabled = enabled;
+                                            trigger.default_enabled = enabled;
+                                        }
+                                    }
+                                }
+                                (op, SettingsScope::Global) => {
+                                    let enabled = op == BulkOp::Enable;
+                                    for trigger in self.global_config.global_triggers.iter_mut().filter(|t| names.contains(&t.name)) {
+                                        trigger.default_enabled = enabled;
+                                    }
+                                }
+                            }
+                            if op == BulkOp::Delete {
+                                self.settings_selection.clear();
+                            }
+                            needs_save = true;
+                        }
+
+                        if let Some(cfg) = to_edit {
+                            self.editing_trigger_name = Some(cfg.name.clone());
+                            self.trigger_edit.name = cfg.name;
+                            self.trigger_edit.pattern = cfg.pattern;
+                            self.trigger_edit.combine = cfg.combine;
+                            self.trigger_edit.action = cfg.action;
+                            self.trigger_edit.category = cfg.category.unwrap_or_default();
+                            self.trigger_edit.description = cfg.description;
+                            self.trigger_edit.is_script = cfg.is_script;
+                            self.trigger_edit.action_type = cfg.action_type;
+                            self.trigger_edit.variable = cfg.variable;
+                            self.trigger_edit.fire_on = cfg.fire_on;
+                            self.trigger_edit.scope = cfg.scope;
+                            self.trigger_edit.max_fires = cfg.max_fires.unwrap_or(0);
+                            self.trigger_edit.priority = cfg.priority;
+                            self.trigger_edit.cooldown_ms = cfg.cooldown_ms;
+                            self.trigger_edit.requires_prior = cfg.requires_prior.unwrap_or_default();
+                            self.trigger_edit.requires_prior_ms = cfg.requires_prior_ms;
                             self.show_trigger_window = true;
                         }
 
@@ -2754,26 +4142,8 @@ impl MudApp {
                         if let Some(op) = op_action {
                             match op {
                                 TriggerOp::MoveToGlobal(name) | TriggerOp::CopyToGlobal(name) => {
-                                    if let Some(t) = session.trigger_manager.get(&name) {
-                                        let (action_str, is_script) = t.actions.iter().find_map(|a| {
-                                            match a {
-                                                TriggerAction::SendCommand(cmd) => Some((cmd.clone(), false)),
-                                                TriggerAction::ExecuteScript(code) => Some((code.clone(), true)),
-                                                _ => None,
-                                            }
-                                        }).unwrap_or_default();
-                                        
-                                        let new_config = crate::config::TriggerConfig {
-                                            name: t.name.clone(),
-                                            pattern: match &t.pattern {
-                                                TriggerPattern::Contains(s) | TriggerPattern::StartsWith(s) | 
-                                                TriggerPattern::EndsWith(s) | TriggerPattern::Regex(s) => s.clone(),
-                                            },
-                                            action: action_str,
-                                            category: t.category.clone(),
-                                            is_script,
-                                            enabled: t.enabled,
-                                        };
+                                    if let Some(t) = session.engine.trigger_manager.get(&name) {
+                                        let new_config = crate::session::Session::config_from_trigger(t);
 
                                         if let Some(existing) = self.global_config.global_triggers.iter_mut().find(|gt| gt.name == name) {
                                             *existing = new_config;
@@ -2790,7 +4160,7 @@ impl MudApp {
                                 TriggerOp::RevertToGlobal(name) => {
                                     if let Some(gt) = self.global_config.global_triggers.iter().find(|t| t.name == name) {
                                         if let Some(trigger) = crate::session::Session::create_trigger_from_config(gt) {
-                                            session.trigger_manager.add(trigger);
+                                            session.engine.trigger_manager.add(trigger);
                                             needs_save = true;
                                         }
                                     }
@@ -2804,23 +4174,22 @@ impl MudApp {
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 if ui.button("➕ 新增路徑").clicked() {
                                     self.editing_path_name = Some(String::new());
-                                    self.path_edit_name = String::new();
-                                    self.path_edit_value = String::new();
-                                    self.path_edit_category = String::new();
+                                    self.path_edit = PathEditorState::default();
                                     self.show_path_window = true;
                                 }
                             });
                         });
                         ui.add_space(5.0);
 
-                        // 收集路徑列表
-                        let path_list: Vec<(String, String, Option<String>)> = {
-                            session.path_manager.list().iter()
+                        // 收集路徑列表 (名稱, 內容, 分類)
+                        type PathRow = (String, String, Option<String>);
+                        let path_list: Vec<PathRow> = {
+                            session.engine.path_manager.list().iter()
                                 .map(|p| (p.name.clone(), p.value.clone(), p.category.clone()))
                                 .collect()
                         };
 
-                        let mut grouped_paths: std::collections::BTreeMap<Option<String>, Vec<(String, String, Option<String>)>> = std::collections::BTreeMap::new();
+                        let mut grouped_paths: std::collections::BTreeMap<Option<String>, Vec<PathRow>> = std::collections::BTreeMap::new();
                         for item in path_list {
                             grouped_paths.entry(item.2.clone()).or_default().push(item);
                         }
@@ -2860,14 +4229,14 @@ impl MudApp {
                         });
 
                         if let Some(name) = to_delete {
-                            session.path_manager.remove(&name);
+                            session.engine.path_manager.remove(&name);
                             needs_save = true;
                         }
                         if let Some((name, value, category)) = to_edit {
                             self.editing_path_name = Some(name.clone());
-                            self.path_edit_name = name;
-                            self.path_edit_value = value;
-                            self.path_edit_category = category;
+                            self.path_edit.name = name;
+                            self.path_edit.value = value;
+                            self.path_edit.category = category;
                             self.show_path_window = true;
                         }
                     }
@@ -2892,8 +4261,10 @@ impl MudApp {
                         } else {
                             ui.label("狀態: 未啟動");
                             if ui.button("開始記錄").clicked() {
-                                let path = format!("logs/mud_log_{}.txt", chrono_lite_timestamp());
-                                let _ = session.logger.start(&path);
+                                let template = session.log_path_template.clone();
+                                if let Err(e) = session.start_log(&template) {
+                                    session.window_manager.send_to_main(format!("[System] ⚠️ 無法啟動日誌: {}", e));
+                                }
                             }
                         }
                     }
@@ -2902,6 +4273,38 @@ impl MudApp {
                         ui.add_space(10.0);
                         
                         ui.checkbox(&mut session.auto_scroll, "自動捲動畫面");
+                        ui.checkbox(&mut session.trigger_debug, "觸發器除錯")
+                            .on_hover_text("每次觸發器匹配時，在「觸發器除錯」視窗列出名稱、匹配文字與捕獲");
+                        ui.checkbox(&mut session.send_blocked, "安全模式（不送出任何指令）")
+                            .on_hover_text("閱讀記錄或調整設定時使用：指令只在本地回顯並標示 [blocked]");
+                        ui.checkbox(&mut session.colored_echo, "指令回顯上色")
+                            .on_hover_text("一般指令灰色、客戶端指令（# 或 /）青色、別名展開黃色");
+                        let mut auto_reconnect = session.auto_reconnect;
+                        if ui.checkbox(&mut auto_reconnect, "斷線後自動重連").changed() {
+                            session.set_auto_reconnect(auto_reconnect);
+                        }
+                        if ui.checkbox(&mut self.global_config.share_history, "同一 Profile 的分頁共用輸入歷史").changed() {
+                            needs_save = true;
+                        }
+                        if ui.checkbox(&mut self.global_config.chat_aggregator, "聊天彙整分頁")
+                            .on_hover_text("各分頁符合 Profile「聊天正則」的訊息，加上分頁名稱後彙整到同一個分頁")
+                            .changed()
+                        {
+                            needs_save = true;
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("補齊按鍵:");
+                            let key = &mut self.global_config.ui.completion_key;
+                            let before = *key;
+                            ui.radio_value(key, CompletionKey::Tab, "Tab");
+                            ui.radio_value(key, CompletionKey::CtrlSpace, "Ctrl+Space");
+                            ui.radio_value(key, CompletionKey::Off, "停用");
+                            if *key != before {
+                                needs_save = true;
+                            }
+                        })
+                        .response
+                        .on_hover_text("同時按住 Shift 反向循環候選");
                         ui.add_space(5.0);
                         ui.label(format!("當前補齊字典大小: {} 個單字", session.screen_words.len()));
                         ui.label("更多設定即將推出...");
@@ -2932,21 +4335,65 @@ impl eframe::App for MudApp {
         self.check_reconnect(ctx);
 
         // 處理待連線的 Profile
-        if let Some(profile_name) = self.pending_connect_profile.take() {
+        // 逐一處理，單一 Profile 失敗不影響其他
+        for profile_name in std::mem::take(&mut self.pending_connect_profiles) {
             self.connect_to_profile(&profile_name, ctx.clone());
         }
 
         // 處理所有 Session 的計時器（即使非活躍分頁也要執行）
+        let mut bell = false;
+        let mut connected = Vec::new();
         for session in self.session_manager.sessions_mut() {
             session.check_timers();
+            session.prune_idle_windows();
+            session.flush_stale_partial_line();
+            bell |= session.take_bell();
+            if session.take_connected() {
+                connected.push(session.profile_name.clone());
+            }
+        }
+        if bell {
+            ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(egui::UserAttentionType::Informational));
+        }
+
+        // 記錄最後連線時間
+        let now = crate::config::current_timestamp();
+        for name in connected {
+            if let Some(profile) = self.profile_manager.mark_connected(&name, now).cloned() {
+                if let Err(e) = self.profile_manager.save(profile) {
+                    tracing::error!("Failed to save profile {}: {}", name, e);
+                }
+            }
+        }
+        self.session_manager.sync_history(self.global_config.share_history);
+        self.session_manager.sync_chat(self.global_config.chat_aggregator);
+
+        // 目前檢視的擷取視窗被自動關閉時切回主視窗
+        if let Some(session) = self.session_manager.active_session() {
+            if session.window_manager.get(&self.active_window_id).is_none() {
+                self.active_window_id = "main".to_string();
+            }
         }
         
+        // 依目前分頁的房間名稱（若啟用）或伺服器標題更新原生視窗標題
+        let title = match self.session_manager.active_session().and_then(|s| {
+            s.current_room.as_ref().filter(|_| s.room_in_title).map(|room| room.name.as_str()).or(s.server_title.as_deref())
+        }) {
+            Some(name) => format!("{} - MUD Client", name),
+            None => "MUD Client".to_string(),
+        };
+        if title != self.viewport_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.viewport_title = title;
+        }
+
         // 計算最近的計時器到期時間以喚醒 UI
         let mut next_wake: Option<std::time::Duration> = None;
         let now = Instant::now();
         for session in self.session_manager.sessions_mut() {
-            for timer in &session.active_timers {
-                let remaining = timer.expires_at.saturating_duration_since(now);
+            let deadlines = session.active_timers.iter().map(|t| t.expires_at).chain(session.partial_line_deadline());
+            for expires_at in deadlines {
+                let remaining = expires_at.saturating_duration_since(now);
                 match next_wake {
                     None => next_wake = Some(remaining),
                     Some(d) if remaining < d => next_wake = Some(remaining),
@@ -2954,6 +4401,11 @@ impl eframe::App for MudApp {
                 }
             }
         }
+        // 狀態列的連線/閒置時間每秒更新
+        if self.session_manager.active_session().is_some_and(|s| s.connected_at.is_some()) {
+            let second = std::time::Duration::from_secs(1);
+            next_wake = Some(next_wake.map_or(second, |d| d.min(second)));
+        }
         if let Some(duration) = next_wake {
             ctx.request_repaint_after(duration + std::time::Duration::from_millis(10));
         }
@@ -2975,10 +4427,7 @@ impl eframe::App for MudApp {
                 session_opt, // 不能同時借用 self.session_manager 與 self.global_config (如果是 Global mode, session_opt 是 None, 安全)
                 global_opt,
                 &mut self.editing_alias_name,
-                &mut self.alias_edit_pattern,
-                &mut self.alias_edit_replacement,
-                &mut self.alias_edit_category,
-                &mut self.alias_edit_is_script,
+                &mut self.alias_edit,
                 &mut self.show_alias_window,
                 &mut needs_save,
             );
@@ -3001,11 +4450,7 @@ impl eframe::App for MudApp {
                 session_opt_trigger,
                 global_opt_trigger,
                 &mut self.editing_trigger_name,
-                &mut self.trigger_edit_name,
-                &mut self.trigger_edit_pattern,
-                &mut self.trigger_edit_action,
-                &mut self.trigger_edit_category,
-                &mut self.trigger_edit_is_script,
+                &mut self.trigger_edit,
                 &mut self.show_trigger_window,
                 &mut needs_save,
             );
@@ -3015,9 +4460,7 @@ impl eframe::App for MudApp {
                 ctx,
                 self.session_manager.active_session_mut(),
                 &mut self.editing_path_name,
-                &mut self.path_edit_name,
-                &mut self.path_edit_value,
-                &mut self.path_edit_category,
+                &mut self.path_edit,
                 &mut self.show_path_window,
                 &mut needs_save,
             );
@@ -3064,12 +4507,35 @@ impl eframe::App for MudApp {
                         }
                         SessionStatus::Connected(_) => {
                             ui.label(RichText::new("● 已連線").color(Color32::GREEN));
+                            if let Some(duration) = session.connected_duration() {
+                                ui.label(RichText::new(format!("連線 {}", crate::session::format_duration(duration))).small().weak());
+                            }
+                            ui.label(RichText::new(format!("閒置 {}", crate::session::format_duration(session.idle_duration()))).small().weak());
+                            if let Some(room) = &session.current_room {
+                                ui.separator();
+                                let label = ui.label(RichText::new(format!("📍 {}", room.name)).color(Color32::LIGHT_BLUE));
+                                if let Some(area) = &room.area {
+                                    label.on_hover_text(area);
+                                }
+                            }
+                            if let Some(vitals) = session.vitals.as_ref().map(|v| v.summary()).filter(|s| !s.is_empty()) {
+                                ui.separator();
+                                ui.label(RichText::new(format!("❤ {}", vitals)).color(Color32::LIGHT_RED));
+                            }
                         }
                         SessionStatus::Reconnecting => {
                             ui.spinner();
                             ui.label(RichText::new("⟳ 重連中...").color(Color32::YELLOW));
                         }
                     }
+                    if session.last_bell.is_some_and(|at| at.elapsed() < crate::session::BELL_COOLDOWN) {
+                        ui.label(RichText::new("🔔").color(Color32::YELLOW)).on_hover_text("伺服器送出提示音");
+                    }
+                    if session.send_blocked {
+                        ui.separator();
+                        ui.label(RichText::new("🛑 安全模式：指令不會送出").color(Color32::LIGHT_RED))
+                            .on_hover_text("可在設定 → 一般 關閉");
+                    }
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         match &session.status {
@@ -3104,15 +4570,22 @@ impl eframe::App for MudApp {
                 if self.session_manager.len() > 0 {
                     let mut close_id = None;
                     for i in 0..self.session_manager.len() {
-                        let is_active = i == self.session_manager.active_index();
+                        let is_active = !self.show_chat_aggregate && i == self.session_manager.active_index();
                         if let Some(s) = self.session_manager.sessions().get(i) {
                             // 使用 group 讓分頁標籤與關閉按鈕視覺上結合
                             ui.group(|ui| {
                                 ui.horizontal(|ui| {
                                     ui.spacing_mut().item_spacing.x = 4.0;
-                                    if ui.selectable_label(is_active, s.tab_title()).clicked() {
+                                    let tab_response = ui.selectable_label(is_active, s.tab_title());
+                                    if tab_response.clicked() {
                                         pending_action = Some(PendingAction::SwitchTab(i));
                                     }
+                                    tab_response.context_menu(|ui| {
+                                        if ui.button("📑 複製分頁").clicked() {
+                                            pending_action = Some(PendingAction::DuplicateSession(s.id));
+                                            ui.close_menu();
+                                        }
+                                    });
                                     // 關閉按鈕 (x)
                                     if ui.add(egui::Button::new("x").small().frame(false)).clicked() {
                                         close_id = Some(s.id);
@@ -3124,6 +4597,11 @@ impl eframe::App for MudApp {
                     if let Some(id) = close_id {
                         pending_action = Some(PendingAction::CloseSession(id));
                     }
+                    if self.global_config.chat_aggregator
+                        && ui.selectable_label(self.show_chat_aggregate, "💬 聊天彙整").clicked()
+                    {
+                        pending_action = Some(PendingAction::ShowChatAggregate);
+                    }
                 }
                 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -3138,21 +4616,49 @@ impl eframe::App for MudApp {
         self.render_side_panel(ctx, active_window_id.clone(), active_id, &mut pending_action);
 
         // === 底部：輸入區 ===
+        let completion_key = self.global_config.ui.completion_key;
         if let Some(id) = active_id {
             egui::TopBottomPanel::bottom("input_panel").show(ctx, |ui| {
                 if let Some(session) = self.session_manager.get_mut(id) {
+                    if let Some(encoding) = session.encoding_suggestion {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(format!("💡 伺服器輸出看起來像 {} 編碼，是否切換？", encoding.name())).color(Color32::YELLOW));
+                            if ui.button("切換").clicked() {
+                                session.accept_encoding_suggestion();
+                            }
+                            if ui.button("忽略").clicked() {
+                                session.dismiss_encoding_suggestion();
+                            }
+                        });
+                    }
+                    if let Some(prompt) = session.pinned_prompt.as_deref().filter(|_| session.pin_prompt) {
+                        ui.add_space(3.0);
+                        Self::render_pinned_prompt(ui, prompt);
+                    }
                     ui.add_space(5.0);
-                    Self::render_input_area(ui, session, any_popup_open);
+                    Self::render_input_area(ui, session, any_popup_open, completion_key);
                     ui.add_space(5.0);
                 }
             });
 
             // === 中央：訊息區 ===
+            let mut trigger_disabled = false;
             egui::CentralPanel::default().show(ctx, |ui| {
-                if let Some(session) = self.session_manager.get_mut(id) {
-                    Self::render_message_area(ui, session, &active_window_id);
+                if self.show_chat_aggregate && self.global_config.chat_aggregator {
+                    Self::render_chat_aggregate(ui, &self.session_manager.chat_window);
+                } else if let Some(session) = self.session_manager.get_mut(id) {
+                    trigger_disabled = Self::render_message_area(
+                        ui,
+                        session,
+                        &active_window_id,
+                        &mut self.scroll_positions,
+                        &mut self.scroll_key,
+                    );
                 }
             });
+            if trigger_disabled {
+                self.save_config();
+            }
 
             // 處理快捷鍵 (不直接傳遞 session，避免借用衝突)
             self.handle_keyboard_shortcuts(ctx, &mut pending_action);
@@ -3170,17 +4676,48 @@ impl eframe::App for MudApp {
                 PendingAction::Connect(id) => self.start_connection(id, ctx.clone()),
                 PendingAction::Disconnect(id) => {
                     if let Some(session) = self.session_manager.get_mut(id) {
-                        if let Some(tx) = &session.command_tx {
-                            let _ = tx.blocking_send(crate::session::Command::Disconnect);
-                        }
+                        session.request_disconnect();
                     }
                 }
-                PendingAction::SwitchTab(idx) => { self.session_manager.switch_tab(idx); }
+                PendingAction::SwitchTab(idx) => {
+                    self.show_chat_aggregate = false;
+                    self.session_manager.switch_tab(idx);
+                }
+                PendingAction::ShowChatAggregate => { self.show_chat_aggregate = true; }
                 PendingAction::PrevTab => { self.session_manager.prev_tab(); }
                 PendingAction::NextTab => { self.session_manager.next_tab(); }
                 PendingAction::SwitchWindow(win_id) => { self.active_window_id = win_id; }
+                PendingAction::PinWindow(win_id) => {
+                    if let Some(window) = self.session_manager.active_session_mut().and_then(|s| s.window_manager.get_mut(&win_id)) {
+                        window.pin();
+                    }
+                    self.save_config();
+                }
+                PendingAction::ToggleWindowStripAnsi(win_id) => {
+                    if let Some(window) = self.session_manager.active_session_mut().and_then(|s| s.window_manager.get_mut(&win_id)) {
+                        window.strip_ansi = !window.strip_ansi;
+                    }
+                    self.save_config();
+                }
+                PendingAction::ToggleWindowAsciiArt(win_id) => {
+                    if let Some(window) = self.session_manager.active_session_mut().and_then(|s| s.window_manager.get_mut(&win_id)) {
+                        window.ascii_art = !window.ascii_art;
+                    }
+                    self.save_config();
+                }
+                PendingAction::CloseWindow(win_id) => {
+                    if let Some(session) = self.session_manager.active_session_mut() {
+                        session.window_manager.remove_window(&win_id);
+                    }
+                    if self.active_window_id == win_id {
+                        self.active_window_id = "main".to_string();
+                    }
+                    self.save_config();
+                }
                 PendingAction::ToggleSettings => { self.show_settings_window = !self.show_settings_window; }
                 PendingAction::ToggleProfile => { self.show_profile_window = !self.show_profile_window; }
+                PendingAction::ToggleVariables => { self.show_variable_window = !self.show_variable_window; }
+                PendingAction::ToggleTelnetOptions => { self.show_telnet_window = !self.show_telnet_window; }
                 PendingAction::ClearActiveWindow => {
                     if let Some(id) = active_id {
                         if let Some(session) = self.session_manager.get_mut(id) {
@@ -3190,12 +4727,19 @@ impl eframe::App for MudApp {
                         }
                     }
                 }
+                PendingAction::DuplicateSession(id) => {
+                    let profile_name = self.session_manager.get(id).map(|s| s.profile_name.clone());
+                    if let Some(profile) = profile_name.and_then(|name| self.profile_manager.get(&name)) {
+                        if let Some(new_id) = self.session_manager.duplicate_session(id, profile) {
+                            self.start_connection(new_id, ctx.clone());
+                        }
+                    }
+                }
                 PendingAction::CloseSession(id) => {
                     // 先發送斷線指令給網路執行緒
                     if let Some(session) = self.session_manager.get_mut(id) {
-                        if let Some(tx) = session.command_tx.take() {
-                            let _ = tx.blocking_send(crate::session::Command::Disconnect);
-                        }
+                        session.request_disconnect();
+                        session.command_tx = None;
                     }
                     self.session_manager.close_session(id);
                 }
@@ -3212,13 +4756,40 @@ impl eframe::App for MudApp {
             self.render_settings_window(ctx);
         }
 
-        // 僅在有活躍連線時持續刷新（處理持續收到的伺服器訊息）
-        let has_active_connection = self.session_manager.sessions().iter().any(|s| {
-            matches!(s.status, crate::session::ConnectionStatus::Connected(_) | crate::session::ConnectionStatus::Connecting)
-        });
-        if has_active_connection {
-            ctx.request_repaint();
+        // 變數檢視視窗
+        if self.show_variable_window {
+            self.render_variable_window(ctx);
         }
+
+        // Telnet 選項診斷視窗
+        if self.show_telnet_window {
+            self.render_telnet_window(ctx);
+        }
+
+        // 不再每幀無條件刷新：網路執行緒送出訊息時（RepaintingSender）會喚醒 UI，
+        // 計時器與重連倒數則由上方的 request_repaint_after / check_reconnect 處理，
+        // 閒置的連線不會持續消耗 CPU。
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.shutdown();
+    }
+}
+
+/// 網路執行緒使用的訊息發送端：每送出一則訊息就喚醒 UI 重繪
+struct RepaintingSender {
+    tx: mpsc::Sender<crate::session::NetworkMessage>,
+    ctx: egui::Context,
+}
+
+impl RepaintingSender {
+    async fn send(
+        &self,
+        message: crate::session::NetworkMessage,
+    ) -> Result<(), mpsc::error::SendError<crate::session::NetworkMessage>> {
+        let result = self.tx.send(message).await;
+        self.ctx.request_repaint();
+        result
     }
 }
 
@@ -3227,26 +4798,24 @@ enum PendingAction {
     Connect(crate::session::SessionId),
     Disconnect(crate::session::SessionId),
     SwitchTab(usize),
+    ShowChatAggregate,
     PrevTab,
     NextTab,
     SwitchWindow(String),
+    PinWindow(String),
+    ToggleWindowStripAnsi(String),
+    ToggleWindowAsciiArt(String),
+    CloseWindow(String),
     ToggleSettings,
     ToggleProfile,
+    ToggleVariables,
+    ToggleTelnetOptions,
     ClearActiveWindow,
     CloseSession(crate::session::SessionId),
+    DuplicateSession(crate::session::SessionId),
 }
 
 
-/// 簡易時間戳記（避免引入大型時間庫）
-fn chrono_lite_timestamp() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let secs = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    format!("{}", secs)
-}
-
 /// 清理 pattern 字串，移除可能的 Debug 格式（如 Contains("...")）
 #[allow(dead_code)]
 fn clean_pattern_string(pattern: &str) -> String {
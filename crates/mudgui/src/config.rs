@@ -5,41 +5,162 @@
 //! - `Profile`: 單一帳號/伺服器的設定（連線資訊、專屬別名/觸發器）
 //! - `ProfileManager`: Profile 的 CRUD 操作
 
+use mudcore::telnet::LineEnding;
+use mudcore::Encoding;
+use mudcore::{FireScope, TriggerScope};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // ============================================================================
 // 基礎設定結構（與舊版相容）
 // ============================================================================
 
 /// 別名設定（可序列化版本）
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AliasConfig {
     pub name: String,
     pub pattern: String,
     pub replacement: String,
     #[serde(default)]
     pub category: Option<String>,
+    /// 說明（顯示於設定表格的提示）
+    #[serde(default)]
+    pub description: String,
     #[serde(default)]
     pub is_script: bool,
-    #[serde(default = "default_true")]
-    pub enabled: bool,
+    /// 模式是否為正則表達式（替換內容可用 `$1` 與 `${name}`）
+    #[serde(default)]
+    pub is_regex: bool,
+    /// 啟動時是否啟用（舊設定檔的 `enabled` 視為此值）
+    #[serde(default = "default_true", alias = "enabled")]
+    pub default_enabled: bool,
+}
+
+/// 與反序列化的預設值一致（預設啟用）
+impl Default for AliasConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pattern: String::new(),
+            replacement: String::new(),
+            category: None,
+            description: String::new(),
+            is_script: false,
+            is_regex: false,
+            default_enabled: true,
+        }
+    }
+}
+
+/// 觸發器動作類型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum TriggerActionType {
+    /// 發送指令或執行腳本（依 is_script 決定）
+    #[default]
+    Command,
+    /// 直接送出指令（不經別名與觸發器，action 支援 $1）
+    RawCommand,
+    /// 設定變數（action 為值，支援 $1）
+    SetVariable,
+    /// 累加變數（action 為增量，預設 1）
+    IncrementVariable,
+    /// 替換顯示（action 為替換文字，支援 $1）
+    Substitute,
+    /// 以顏色標示匹配範圍（action 為樣式，如 `red on blue bold`）
+    Highlight,
+    /// 附加到筆記（action 為文字，支援 $1）
+    AppendNote,
+    /// 附加到檔案（variable 為檔案路徑，action 為文字，皆支援 $1）
+    AppendFile,
+}
+
+/// 觸發器匹配文字的組合方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PatternCombine {
+    /// 單一模式
+    #[default]
+    Single,
+    /// 以逗號分隔多個模式，任一符合即觸發
+    AnyOf,
+    /// 以逗號分隔多個模式，全部符合才觸發
+    AllOf,
+    /// 以逗號分隔多個模式，依序符合連續的幾行才觸發（最後一個為目前這行）
+    MultiLine,
 }
 
 /// 觸發器設定（可序列化版本）
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TriggerConfig {
     pub name: String,
     pub pattern: String,
     pub action: String,
     #[serde(default)]
     pub category: Option<String>,
+    /// 說明（顯示於設定表格的提示）
+    #[serde(default)]
+    pub description: String,
     #[serde(default)]
     pub is_script: bool,
-    #[serde(default = "default_true")]
-    pub enabled: bool,
+    /// 啟動時是否啟用（舊設定檔的 `enabled` 視為此值）
+    #[serde(default = "default_true", alias = "enabled")]
+    pub default_enabled: bool,
+    /// 動作類型
+    #[serde(default)]
+    pub action_type: TriggerActionType,
+    /// 變數動作的目標變數名稱（附加到檔案時為檔案路徑）
+    #[serde(default)]
+    pub variable: String,
+    /// 觸發範圍（所有行 / 僅提示符 / 僅非提示符）
+    #[serde(default)]
+    pub fire_on: FireScope,
+    /// 比對對象（伺服器輸出 / 玩家輸入 / 兩者）
+    #[serde(default)]
+    pub scope: TriggerScope,
+    /// 觸發次數上限，用完後自動停用（不填表示不限）
+    #[serde(default)]
+    pub max_fires: Option<u32>,
+    /// 前置觸發器名稱：須在 `requires_prior_ms` 毫秒內觸發過才會觸發
+    #[serde(default)]
+    pub requires_prior: Option<String>,
+    /// 前置觸發器的有效時間（毫秒）
+    #[serde(default)]
+    pub requires_prior_ms: u64,
+    /// 匹配文字的組合方式
+    #[serde(default)]
+    pub combine: PatternCombine,
+    /// 優先級：數字大者先執行
+    #[serde(default)]
+    pub priority: i32,
+    /// 冷卻時間（毫秒）：上次觸發後此時間內不再觸發，0 表示不限
+    #[serde(default)]
+    pub cooldown_ms: u64,
+}
+
+/// 與反序列化的預設值一致（預設啟用）
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pattern: String::new(),
+            action: String::new(),
+            category: None,
+            description: String::new(),
+            is_script: false,
+            default_enabled: true,
+            action_type: TriggerActionType::default(),
+            variable: String::new(),
+            fire_on: FireScope::default(),
+            scope: TriggerScope::default(),
+            max_fires: None,
+            requires_prior: None,
+            requires_prior_ms: 0,
+            combine: PatternCombine::default(),
+            priority: 0,
+            cooldown_ms: 0,
+        }
+    }
 }
 
 /// 路徑設定（可序列化版本）
@@ -51,6 +172,86 @@ pub struct PathConfig {
     pub category: Option<String>,
 }
 
+/// 子視窗配置（啟動時預先建立）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowLayoutConfig {
+    pub id: String,
+    pub title: String,
+    /// 訊息緩衝區容量
+    #[serde(default = "default_window_capacity")]
+    pub capacity: usize,
+    #[serde(default = "default_true")]
+    pub visible: bool,
+    /// 移除 ANSI 顏色，只保存純文字
+    #[serde(default)]
+    pub strip_ansi: bool,
+    /// ASCII 藝術模式（每個字元一格，不做 CJK 寬度補位）
+    #[serde(default)]
+    pub ascii_art: bool,
+}
+
+fn default_window_capacity() -> usize {
+    1000
+}
+
+/// 本地回顯模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LocalEchoMode {
+    /// 依伺服器協商（伺服器 WILL ECHO 時不回顯）
+    #[default]
+    Auto,
+    /// 一律回顯
+    Always,
+    /// 一律不回顯
+    Never,
+}
+
+/// 重複指令防呆觸發時的動作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum AntiSpamAction {
+    /// 自動插入指定指令
+    #[default]
+    SendCommand,
+    /// 僅顯示警告
+    WarnOnly,
+}
+
+/// 重複指令防呆設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AntiSpamConfig {
+    /// 連續重複幾次後觸發（0 表示停用）
+    #[serde(default = "default_anti_spam_threshold")]
+    pub threshold: usize,
+    /// 觸發時的動作
+    #[serde(default)]
+    pub action: AntiSpamAction,
+    /// 自動插入的指令（空字串表示不插入）
+    #[serde(default = "default_anti_spam_command")]
+    pub command: String,
+    /// 空指令與移動指令不計入重複次數
+    #[serde(default)]
+    pub exempt_movement: bool,
+}
+
+impl Default for AntiSpamConfig {
+    fn default() -> Self {
+        Self {
+            threshold: default_anti_spam_threshold(),
+            action: AntiSpamAction::default(),
+            command: default_anti_spam_command(),
+            exempt_movement: false,
+        }
+    }
+}
+
+fn default_anti_spam_threshold() -> usize {
+    20
+}
+
+fn default_anti_spam_command() -> String {
+    "save".to_string()
+}
+
 /// 連線設定
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ConnectionConfig {
@@ -58,10 +259,18 @@ pub struct ConnectionConfig {
     pub port: String,
 }
 
+fn default_click_command() -> String {
+    "kill $word".to_string()
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn default_max_capture_windows() -> usize {
+    20
+}
+
 // ============================================================================
 // 新架構：Profile
 // ============================================================================
@@ -85,6 +294,36 @@ pub struct Profile {
     /// Profile 專屬路徑
     #[serde(default)]
     pub paths: Vec<PathConfig>,
+    /// Profile 專屬子視窗配置
+    #[serde(default)]
+    pub windows: Vec<WindowLayoutConfig>,
+    /// 指令縮寫表（完整指令，輸入不混淆的前綴即展開）
+    #[serde(default)]
+    pub abbreviations: Vec<String>,
+    /// 使用者主動斷線前送出的指令（如 `quit`）
+    #[serde(default)]
+    pub on_disconnect: Option<String>,
+    /// Ctrl+點擊輸出中的單字時送出的指令模板（`$word` 為點擊的單字，留空停用）
+    #[serde(default = "default_click_command")]
+    pub click_command: String,
+    /// 每次連線（含自動重連）完成自動登入後送出的指令，可用 `;` 分隔多個
+    #[serde(default)]
+    pub on_connect: Option<String>,
+    /// 提示符正則（未設定時使用內建的 `(hp.../...)` 判斷）
+    #[serde(default)]
+    pub prompt_regex: Option<String>,
+    /// 聊天訊息正則（符合的行轉送到跨分頁的聊天彙整）
+    #[serde(default)]
+    pub chat_pattern: Option<String>,
+    /// 直接隱藏的伺服器訊息（子字串，或以 `/.../` 包住的正則；仍會寫入日誌）
+    #[serde(default)]
+    pub gag_patterns: Vec<String>,
+    /// 日誌路徑樣板（支援 `{profile}`、`{date}`、`{time}`；未設定時使用預設樣板）
+    #[serde(default)]
+    pub log_path: Option<String>,
+    /// 連線建立後、自動登入前立即送出的字串（如伺服器要求的握手或選單選項）
+    #[serde(default)]
+    pub connect_send: Option<String>,
     /// 自訂腳本載入路徑（可選）
     #[serde(default)]
     pub script_paths: Vec<String>,
@@ -101,6 +340,67 @@ pub struct Profile {
     #[serde(default)]
     pub password: Option<String>,
 
+    /// 本地回顯模式
+    #[serde(default)]
+    pub local_echo: LocalEchoMode,
+    /// 送出指令時附加的換行字元
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// 伺服器文字編碼
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// 連線後依伺服器輸出自動偵測並套用編碼
+    #[serde(default)]
+    pub auto_detect_encoding: bool,
+    /// 隱藏 `>>>` 連線狀態訊息
+    #[serde(default)]
+    pub quiet_connect: bool,
+    /// 自動登入時等待伺服器回顯帳號後再送密碼
+    #[serde(default)]
+    pub wait_username_echo: bool,
+    /// 程式啟動時自動連線
+    #[serde(default)]
+    pub auto_connect: bool,
+    /// 日誌記錄替換觸發器套用前的原文
+    #[serde(default)]
+    pub log_original_text: bool,
+    /// 登入前的伺服器訊息另外保存到 banner 視窗
+    #[serde(default)]
+    pub capture_banner: bool,
+    /// 以 GA/EOR 結尾的提示符固定顯示在輸入框上方
+    #[serde(default)]
+    pub pin_prompt: bool,
+    /// 主視窗連續的空白行只保留一行
+    #[serde(default)]
+    pub collapse_blank_lines: bool,
+    /// 以 GMCP 房間名稱作為視窗標題
+    #[serde(default)]
+    pub room_in_title: bool,
+    /// 伺服器送出 BEL (0x07) 時提醒（請求視窗注意）
+    #[serde(default)]
+    pub bell_alert: bool,
+    /// 將輸入歷史儲存到磁碟，下次連線時載入
+    #[serde(default)]
+    pub persist_history: bool,
+    /// 結束程式時保存主視窗記錄，下次開啟時載入
+    #[serde(default)]
+    pub persist_scrollback: bool,
+    /// 別名參數少於模式所需時顯示錯誤而不送出（關閉時缺少的參數代入空字串）
+    #[serde(default)]
+    pub alias_strict_args: bool,
+    /// 自動建立的擷取視窗數量上限（0 表示不限）
+    #[serde(default = "default_max_capture_windows")]
+    pub max_capture_windows: usize,
+    /// 擷取視窗閒置多少秒後自動關閉（0 表示不關閉）
+    #[serde(default)]
+    pub capture_window_idle_secs: u64,
+    /// 自動重連的最多嘗試次數，達到後放棄（0 表示不限）
+    #[serde(default)]
+    pub max_reconnect_attempts: u32,
+    /// 重複指令防呆
+    #[serde(default)]
+    pub anti_spam: AntiSpamConfig,
+
     /// 建立時間 (Unix timestamp)
     #[serde(default)]
     pub created_at: u64,
@@ -118,10 +418,40 @@ impl Default for Profile {
             aliases: Vec::new(),
             triggers: Vec::new(),
             paths: Vec::new(),
+            windows: Vec::new(),
+            abbreviations: Vec::new(),
+            on_disconnect: None,
+            click_command: default_click_command(),
+            on_connect: None,
+            prompt_regex: None,
+            chat_pattern: None,
+            gag_patterns: Vec::new(),
+            log_path: None,
+            connect_send: None,
             script_paths: Vec::new(),
             notes: String::new(),
             username: None,
             password: None,
+            local_echo: LocalEchoMode::default(),
+            line_ending: LineEnding::default(),
+            encoding: Encoding::default(),
+            auto_detect_encoding: false,
+            quiet_connect: false,
+            wait_username_echo: false,
+            auto_connect: false,
+            log_original_text: false,
+            capture_banner: false,
+            pin_prompt: false,
+            collapse_blank_lines: false,
+            room_in_title: false,
+            bell_alert: false,
+            persist_history: false,
+            persist_scrollback: false,
+            alias_strict_args: false,
+            max_capture_windows: default_max_capture_windows(),
+            capture_window_idle_secs: 0,
+            max_reconnect_attempts: 0,
+            anti_spam: AntiSpamConfig::default(),
             created_at: current_timestamp(),
             last_connected: None,
         }
@@ -168,6 +498,75 @@ pub struct UiConfig {
     /// 自動重連
     #[serde(default = "default_true")]
     pub auto_reconnect: bool,
+    /// 上次選取的子視窗
+    #[serde(default = "default_active_window")]
+    pub active_window: String,
+    /// 上次選取的側邊欄標籤頁（無效值視為預設）
+    #[serde(default, deserialize_with = "or_default")]
+    pub side_panel_tab: SidePanelTab,
+    /// 上次選取的設定中心標籤頁（無效值視為預設）
+    #[serde(default, deserialize_with = "or_default")]
+    pub settings_tab: SettingsTab,
+    /// 輸入補齊的按鍵（無效值視為預設）
+    #[serde(default, deserialize_with = "or_default")]
+    pub completion_key: CompletionKey,
+    /// Profile 列表的排序方式（無效值視為預設）
+    #[serde(default, deserialize_with = "or_default")]
+    pub profile_sort: ProfileSort,
+}
+
+/// Profile 列表的排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ProfileSort {
+    /// 依顯示名稱
+    #[default]
+    Name,
+    /// 最近連線的在前（從未連線的排在最後）
+    Recent,
+}
+
+/// 輸入補齊使用的按鍵（同時按住 Shift 為反向循環）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum CompletionKey {
+    #[default]
+    Tab,
+    CtrlSpace,
+    /// 停用補齊（Tab 留給其他用途）
+    Off,
+}
+
+/// 設定中心標籤頁
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum SettingsTab {
+    #[default]
+    Alias,
+    Trigger,
+    Path,
+    Logger,
+    General,
+}
+
+/// 側邊欄標籤頁
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum SidePanelTab {
+    #[default]
+    Tools,
+    Guide,
+    Notes,
+}
+
+fn default_active_window() -> String {
+    "main".to_string()
+}
+
+/// 反序列化失敗（如舊版或手動修改的未知值）時使用預設值，而不是讓整份設定載入失敗
+fn or_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned + Default,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).unwrap_or_default())
 }
 
 fn default_window_width() -> f32 {
@@ -187,12 +586,17 @@ impl Default for UiConfig {
             window_height: default_window_height(),
             font_size: default_font_size(),
             auto_reconnect: true,
+            active_window: default_active_window(),
+            side_panel_tab: SidePanelTab::default(),
+            settings_tab: SettingsTab::default(),
+            completion_key: CompletionKey::default(),
+            profile_sort: ProfileSort::default(),
         }
     }
 }
 
 /// 全域設定（跨 Profile 共用）
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalConfig {
     /// 全域別名（所有連線生效）
     #[serde(default)]
@@ -206,6 +610,12 @@ pub struct GlobalConfig {
     /// UI 設定
     #[serde(default)]
     pub ui: UiConfig,
+    /// 同一 Profile 的分頁共用輸入歷史
+    #[serde(default)]
+    pub share_history: bool,
+    /// 將各分頁符合聊天正則的訊息彙整到同一個分頁
+    #[serde(default)]
+    pub chat_aggregator: bool,
     /// 設定檔版本（用於未來遷移）
     #[serde(default = "default_config_version")]
     pub config_version: u32,
@@ -215,6 +625,20 @@ fn default_config_version() -> u32 {
     2 // 版本 2 = 多 Profile 架構
 }
 
+impl Default for GlobalConfig {
+    fn default() -> Self {
+        Self {
+            global_aliases: Vec::new(),
+            global_triggers: Vec::new(),
+            auto_connect_profiles: Vec::new(),
+            ui: UiConfig::default(),
+            share_history: false,
+            chat_aggregator: false,
+            config_version: default_config_version(),
+        }
+    }
+}
+
 impl GlobalConfig {
     /// 獲取全域設定檔路徑
     pub fn config_path() -> PathBuf {
@@ -244,6 +668,34 @@ impl GlobalConfig {
     }
 }
 
+// ============================================================================
+// 輸入歷史
+// ============================================================================
+
+/// 輸入歷史保留的最大筆數
+pub const MAX_INPUT_HISTORY: usize = 1000;
+
+/// 讀取歷史檔（每行一筆），只保留最後 `MAX_INPUT_HISTORY` 筆；檔案不存在時回傳空列表
+pub fn load_history_file(path: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let mut history: Vec<String> = content.lines().filter(|l| !l.is_empty()).map(str::to_string).collect();
+    if history.len() > MAX_INPUT_HISTORY {
+        history.drain(..history.len() - MAX_INPUT_HISTORY);
+    }
+    history
+}
+
+/// 寫入歷史檔，只保存最後 `MAX_INPUT_HISTORY` 筆
+pub fn save_history_file(path: &Path, history: &[String]) -> Result<(), std::io::Error> {
+    ensure_parent_dir(path)?;
+    let start = history.len().saturating_sub(MAX_INPUT_HISTORY);
+    let mut content = history[start..].join("\n");
+    content.push('\n');
+    fs::write(path, content)
+}
+
 // ============================================================================
 // ProfileManager
 // ============================================================================
@@ -271,6 +723,16 @@ impl ProfileManager {
         config_dir().join("profiles")
     }
 
+    /// Profile 的輸入歷史檔路徑
+    pub fn history_path(name: &str) -> PathBuf {
+        Self::profiles_dir().join(format!("{}.history", name))
+    }
+
+    /// Profile 的主視窗記錄檔路徑
+    pub fn scrollback_path(name: &str) -> PathBuf {
+        Self::profiles_dir().join(format!("{}.scrollback", name))
+    }
+
     /// 載入所有 Profile
     pub fn load_all(&mut self) {
         let dir = Self::profiles_dir();
@@ -297,6 +759,30 @@ impl ProfileManager {
         self.profiles.values().collect()
     }
 
+    /// 依指定方式排序的 Profile 列表
+    pub fn sorted(&self, sort: ProfileSort) -> Vec<&Profile> {
+        let mut profiles = self.list();
+        profiles.sort_by(|a, b| {
+            let by_name = a
+                .display_name
+                .to_lowercase()
+                .cmp(&b.display_name.to_lowercase())
+                .then_with(|| a.name.cmp(&b.name));
+            match sort {
+                ProfileSort::Name => by_name,
+                ProfileSort::Recent => b.last_connected.cmp(&a.last_connected).then(by_name),
+            }
+        });
+        profiles
+    }
+
+    /// 記錄 Profile 的最後連線時間，回傳更新後的 Profile（需另行 `save` 寫入磁碟）
+    pub fn mark_connected(&mut self, name: &str, timestamp: u64) -> Option<&Profile> {
+        let profile = self.profiles.get_mut(name)?;
+        profile.last_connected = Some(timestamp);
+        Some(profile)
+    }
+
     /// 取得 Profile 名稱列表（排序）
     pub fn names(&self) -> Vec<String> {
         let mut names: Vec<_> = self.profiles.keys().cloned().collect();
@@ -333,6 +819,11 @@ impl ProfileManager {
         if path.exists() {
             fs::remove_file(&path)?;
         }
+        for extra in [Self::history_path(name), Self::scrollback_path(name)] {
+            if extra.exists() {
+                fs::remove_file(&extra)?;
+            }
+        }
         self.profiles.remove(name);
         Ok(())
     }
@@ -516,10 +1007,40 @@ pub fn migrate_legacy_config() -> MigrationResult {
         aliases: legacy.aliases,
         triggers: legacy.triggers,
         paths: Vec::new(),
+        windows: Vec::new(),
+        abbreviations: Vec::new(),
+        on_disconnect: None,
+        click_command: default_click_command(),
+        on_connect: None,
+        prompt_regex: None,
+        chat_pattern: None,
+        gag_patterns: Vec::new(),
+        log_path: None,
+        connect_send: None,
         script_paths: Vec::new(),
         notes: String::new(),
         username: None,
         password: None,
+        local_echo: LocalEchoMode::default(),
+        line_ending: LineEnding::default(),
+        encoding: Encoding::default(),
+        auto_detect_encoding: false,
+        quiet_connect: false,
+        wait_username_echo: false,
+        auto_connect: false,
+        log_original_text: false,
+        capture_banner: false,
+        pin_prompt: false,
+        collapse_blank_lines: false,
+        room_in_title: false,
+        bell_alert: false,
+        persist_history: false,
+        persist_scrollback: false,
+        alias_strict_args: false,
+        max_capture_windows: default_max_capture_windows(),
+        capture_window_idle_secs: 0,
+        max_reconnect_attempts: 0,
+        anti_spam: AntiSpamConfig::default(),
         created_at: current_timestamp(),
         last_connected: None,
     };
@@ -538,6 +1059,428 @@ pub fn migrate_legacy_config() -> MigrationResult {
     }
 }
 
+// ============================================================================
+// TinTin++ 匯入
+// ============================================================================
+
+/// 匯入時套用的分類名稱
+pub const TINTIN_IMPORT_CATEGORY: &str = "TinTin++ 匯入";
+
+/// TinTin++ 匯入結果
+#[derive(Debug, Default)]
+pub struct ImportResult {
+    pub aliases: Vec<AliasConfig>,
+    pub triggers: Vec<TriggerConfig>,
+    /// 無法轉換而略過的項目說明
+    pub skipped: Vec<String>,
+}
+
+/// 本程式可直接執行的 `#` 指令，其餘 TinTin++ 指令視為不支援
+const SUPPORTED_INLINE_COMMANDS: &[&str] = &["loop", "delay", "var", "unvar", "path"];
+
+/// 解析 TinTin++ 腳本中的 `#alias`、`#action`、`#gag`
+///
+/// 只支援大括號形式的參數（如 `#action {%1 tells you %2} {reply %2}`）。
+/// `%0` 轉為 `$*`、`%1`~`%99` 轉為 `$1`~`$99`；`#gag` 轉為呼叫
+/// `mud.gag_message()` 的腳本觸發器。其餘指令記錄於 `skipped`。
+pub fn import_tintin(script: &str) -> ImportResult {
+    let mut result = ImportResult::default();
+
+    for (command, args) in parse_tintin_statements(script) {
+        let name = command.to_lowercase();
+        let outcome = if is_tintin_command(&name, "alias") {
+            tintin_alias(&args).map(|a| result.aliases.push(a))
+        } else if is_tintin_command(&name, "action") {
+            tintin_action(&args).map(|t| result.triggers.push(t))
+        } else if name == "gag" {
+            tintin_gag(&args).map(|t| result.triggers.push(t))
+        } else if is_tintin_command(&name, "nop") {
+            Ok(())
+        } else {
+            Err(format!("不支援的指令 #{}", command))
+        };
+
+        if let Err(reason) = outcome {
+            tracing::warn!("TinTin++ 匯入略過: {}", reason);
+            result.skipped.push(reason);
+        }
+    }
+
+    result
+}
+
+/// TinTin++ 指令可縮寫（至少 3 個字元）
+fn is_tintin_command(input: &str, full: &str) -> bool {
+    input.len() >= 3 && full.starts_with(input)
+}
+
+/// 將腳本切分為 (指令名稱, 參數列表)
+fn parse_tintin_statements(script: &str) -> Vec<(String, Vec<String>)> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut statements = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() || chars[i] == ';' {
+            i += 1;
+            continue;
+        }
+        if chars[i] != '#' {
+            // 非指令行，整行略過
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        i += 1;
+        let start = i;
+        while i < chars.len() && chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        let command: String = chars[start..i].iter().collect();
+
+        let mut args = Vec::new();
+        loop {
+            while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+                i += 1;
+            }
+            if i >= chars.len() || chars[i] == '\n' || chars[i] == ';' {
+                break;
+            }
+            if chars[i] == '{' {
+                let arg_start = i + 1;
+                let mut depth = 0;
+                while i < chars.len() {
+                    match chars[i] {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                args.push(chars[arg_start..i.min(chars.len())].iter().collect());
+                i += 1;
+            } else {
+                let arg_start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ';' {
+                    i += 1;
+                }
+                args.push(chars[arg_start..i].iter().collect());
+            }
+        }
+
+        statements.push((command, args));
+    }
+
+    statements
+}
+
+/// 將 `%0` / `%N` 轉為 `$*` / `$N`
+fn tintin_args_to_dollar(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '%' && chars.peek().is_some_and(|d| d.is_ascii_digit()) {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            if digits.parse::<usize>() == Ok(0) {
+                out.push_str("$*");
+            } else {
+                out.push('$');
+                out.push_str(&digits);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// 取得文字中引用的最大參數編號（`%N`）
+fn tintin_max_arg(text: &str) -> usize {
+    let mut max = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            max = max.max(digits.parse().unwrap_or(0));
+        }
+    }
+    max
+}
+
+/// 檢查指令中是否含有本程式無法執行的 TinTin++ 指令
+fn check_tintin_commands(commands: &str) -> Result<(), String> {
+    for part in commands.split(';') {
+        if let Some(rest) = part.trim().strip_prefix('#') {
+            let word: String = rest.chars().take_while(|c| c.is_alphanumeric()).collect();
+            if !SUPPORTED_INLINE_COMMANDS.contains(&word.to_lowercase().as_str()) {
+                return Err(format!("不支援的內嵌指令 #{}", word));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `#alias {名稱} {指令}`
+fn tintin_alias(args: &[String]) -> Result<AliasConfig, String> {
+    let [name, commands, ..] = args else {
+        return Err(format!("#alias 參數不足: {:?}", args));
+    };
+    check_tintin_commands(commands).map_err(|e| format!("#alias {}: {}", name, e))?;
+
+    let pattern = if name.contains('%') {
+        tintin_args_to_dollar(name)
+    } else {
+        // TinTin++ 的別名自動接收參數，本程式需在模式中明列
+        match tintin_max_arg(commands) {
+            0 if commands.contains("%0") => format!("{} $*", name),
+            0 => name.clone(),
+            n => (1..=n).fold(name.clone(), |p, i| format!("{} ${}", p, i)),
+        }
+    };
+
+    Ok(AliasConfig {
+        name: name.split_whitespace().next().unwrap_or(name).to_string(),
+        pattern,
+        replacement: tintin_args_to_dollar(commands),
+        category: Some(TINTIN_IMPORT_CATEGORY.to_string()),
+        description: String::new(),
+        is_script: false,
+        is_regex: false,
+        default_enabled: true,
+    })
+}
+
+/// `#action {模式} {指令}`
+fn tintin_action(args: &[String]) -> Result<TriggerConfig, String> {
+    let [pattern, commands, ..] = args else {
+        return Err(format!("#action 參數不足: {:?}", args));
+    };
+    check_tintin_commands(commands).map_err(|e| format!("#action {}: {}", pattern, e))?;
+
+    Ok(TriggerConfig {
+        name: pattern.clone(),
+        pattern: tintin_pattern_to_regex(pattern)?,
+        action: tintin_args_to_dollar(commands),
+        category: Some(TINTIN_IMPORT_CATEGORY.to_string()),
+        default_enabled: true,
+        ..Default::default()
+    })
+}
+
+/// `#gag {模式}`
+fn tintin_gag(args: &[String]) -> Result<TriggerConfig, String> {
+    let Some(pattern) = args.first() else {
+        return Err("#gag 缺少模式".to_string());
+    };
+
+    Ok(TriggerConfig {
+        name: format!("gag: {}", pattern),
+        pattern: tintin_pattern_to_regex(pattern)?,
+        action: "mud.gag_message()".to_string(),
+        category: Some(TINTIN_IMPORT_CATEGORY.to_string()),
+        is_script: true,
+        default_enabled: true,
+        ..Default::default()
+    })
+}
+
+/// 將 TinTin++ 模式轉為正則表達式
+///
+/// 不含萬用字元與錨點的模式原樣回傳（以包含比對）。
+fn tintin_pattern_to_regex(pattern: &str) -> Result<String, String> {
+    let has_wildcard = pattern.starts_with('^') || pattern.ends_with('$') || pattern.contains('%');
+    if !has_wildcard {
+        return Ok(pattern.to_string());
+    }
+
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '^' && i == 0 {
+            regex.push('^');
+        } else if c == '$' && i == chars.len() - 1 {
+            regex.push('$');
+        } else if c == '%' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            i += 1;
+            if next.is_ascii_digit() || next == '*' {
+                while i + 1 < chars.len() && chars[i + 1].is_ascii_digit() {
+                    i += 1;
+                }
+                // 位於模式結尾時須貪婪比對，否則只會抓到空字串
+                let at_end = i + 1 == chars.len() || (i + 2 == chars.len() && chars[i + 1] == '$');
+                regex.push_str(if at_end { "(.*)" } else { "(.*?)" });
+            } else {
+                match next {
+                    'd' => regex.push_str(r"(\d+)"),
+                    'w' => regex.push_str(r"([\w]+)"),
+                    's' => regex.push_str(r"([\s]+)"),
+                    '%' => regex.push('%'),
+                    other if other.is_ascii_alphabetic() => {
+                        return Err(format!("{}: 不支援的萬用字元 %{}", pattern, other));
+                    }
+                    other => {
+                        regex.push('%');
+                        regex.push_str(&regex::escape(&other.to_string()));
+                    }
+                }
+            }
+        } else {
+            regex.push_str(&regex::escape(&c.to_string()));
+        }
+        i += 1;
+    }
+
+    Ok(regex)
+}
+
+// ============================================================================
+// 分享包（匯出/匯入選取的觸發器與別名）
+// ============================================================================
+
+/// 分享包格式版本
+pub const SHARE_PACK_VERSION: u32 = 1;
+
+/// 分享包：可單獨交給他人匯入的觸發器與別名
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SharePack {
+    /// 格式版本
+    pub version: u32,
+    /// 匯出時間（Unix timestamp）
+    #[serde(default)]
+    pub exported_at: u64,
+    #[serde(default)]
+    pub triggers: Vec<TriggerConfig>,
+    #[serde(default)]
+    pub aliases: Vec<AliasConfig>,
+}
+
+/// 匯入時名稱衝突的處理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// 保留現有項目，略過匯入的同名項目
+    #[default]
+    Skip,
+    /// 匯入的項目改名為 `名稱 (2)`、`名稱 (3)`…
+    Rename,
+    /// 以匯入的項目取代現有項目
+    Overwrite,
+}
+
+impl SharePack {
+    /// 建立分享包
+    pub fn new(triggers: Vec<TriggerConfig>, aliases: Vec<AliasConfig>) -> Self {
+        Self {
+            version: SHARE_PACK_VERSION,
+            exported_at: current_timestamp(),
+            triggers,
+            aliases,
+        }
+    }
+
+    /// 寫入檔案
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        ensure_parent_dir(path)?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+
+    /// 從檔案讀取；版本較新時回傳錯誤
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let content = fs::read_to_string(path)?;
+        let pack: Self = serde_json::from_str(&content)?;
+        if pack.version > SHARE_PACK_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("不支援的分享包版本 {}（目前支援 {}）", pack.version, SHARE_PACK_VERSION),
+            ));
+        }
+        Ok(pack)
+    }
+
+    /// 依處理方式解決與現有名稱的衝突；回傳被略過的項目數
+    ///
+    /// 處理後的項目可直接加入（`Overwrite` 時同名項目應由呼叫端取代）。
+    pub fn resolve_conflicts(
+        &mut self,
+        strategy: MergeStrategy,
+        trigger_exists: impl Fn(&str) -> bool,
+        alias_exists: impl Fn(&str) -> bool,
+    ) -> usize {
+        let before = self.triggers.len() + self.aliases.len();
+        resolve_names(&mut self.triggers, |t| &mut t.name, strategy, trigger_exists);
+        resolve_names(&mut self.aliases, |a| &mut a.name, strategy, alias_exists);
+        before - self.triggers.len() - self.aliases.len()
+    }
+}
+
+/// 對單一種類的項目套用衝突處理
+fn resolve_names<T>(
+    items: &mut Vec<T>,
+    name: fn(&mut T) -> &mut String,
+    strategy: MergeStrategy,
+    exists: impl Fn(&str) -> bool,
+) {
+    match strategy {
+        MergeStrategy::Overwrite => {}
+        MergeStrategy::Skip => items.retain_mut(|item| !exists(name(item))),
+        MergeStrategy::Rename => {
+            let mut taken: Vec<String> = Vec::new();
+            for item in items.iter_mut() {
+                let base = name(item).clone();
+                let mut candidate = base.clone();
+                let mut n = 2;
+                while exists(&candidate) || taken.contains(&candidate) {
+                    candidate = format!("{} ({})", base, n);
+                    n += 1;
+                }
+                taken.push(candidate.clone());
+                *name(item) = candidate;
+            }
+        }
+    }
+}
+
+/// 觸發器指令會用到的別名（非腳本指令以 `;` 分隔，任一段符合別名即列入）
+pub fn alias_dependencies(triggers: &[TriggerConfig], aliases: &[AliasConfig]) -> Vec<AliasConfig> {
+    let commands: Vec<&str> = triggers
+        .iter()
+        .filter(|t| t.action_type == TriggerActionType::Command && !t.is_script)
+        .flat_map(|t| t.action.split(';'))
+        .map(str::trim)
+        .filter(|cmd| !cmd.is_empty())
+        .collect();
+
+    aliases
+        .iter()
+        .filter(|cfg| {
+            let alias = mudcore::Alias::new(&cfg.name, &cfg.pattern, &cfg.replacement).as_regex(cfg.is_regex);
+            commands.iter().any(|cmd| alias.try_expand(cmd).is_some())
+        })
+        .cloned()
+        .collect()
+}
+
 // ============================================================================
 // 工具函數
 // ============================================================================
@@ -552,7 +1495,7 @@ pub fn config_dir() -> PathBuf {
 }
 
 /// 確保目錄存在
-fn ensure_parent_dir(path: &PathBuf) -> Result<(), std::io::Error> {
+pub(crate) fn ensure_parent_dir(path: &Path) -> Result<(), std::io::Error> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
@@ -560,7 +1503,7 @@ fn ensure_parent_dir(path: &PathBuf) -> Result<(), std::io::Error> {
 }
 
 /// 取得當前 Unix timestamp
-fn current_timestamp() -> u64 {
+pub fn current_timestamp() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|d| d.as_secs())
@@ -595,6 +1538,111 @@ mod tests {
         assert_eq!(profile.name, deserialized.name);
     }
 
+    #[test]
+    fn test_auto_connect_roundtrip() {
+        let mut profile = Profile::new("alt", "分身");
+        assert!(!profile.auto_connect);
+
+        profile.auto_connect = true;
+        let json = serde_json::to_string(&profile).unwrap();
+        let deserialized: Profile = serde_json::from_str(&json).unwrap();
+        assert!(deserialized.auto_connect);
+
+        // 舊設定檔沒有此欄位時預設不自動連線
+        let legacy = json.replace("\"auto_connect\":true,", "");
+        assert!(!legacy.contains("auto_connect"));
+        let deserialized: Profile = serde_json::from_str(&legacy).unwrap();
+        assert!(!deserialized.auto_connect);
+    }
+
+    #[test]
+    fn test_window_layout_roundtrip() {
+        let mut profile = Profile::new("layout", "配置");
+        profile.windows.push(WindowLayoutConfig {
+            id: "chat".to_string(),
+            title: "聊天".to_string(),
+            capacity: 500,
+            visible: false,
+            strip_ansi: true,
+            ascii_art: true,
+        });
+        let json = serde_json::to_string(&profile).unwrap();
+        let deserialized: Profile = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.windows, profile.windows);
+
+        // 省略的欄位使用預設值
+        let layout: WindowLayoutConfig = serde_json::from_str(r#"{"id":"map","title":"地圖"}"#).unwrap();
+        assert_eq!(layout.capacity, 1000);
+        assert!(layout.visible);
+        assert!(!layout.strip_ansi);
+        assert!(!layout.ascii_art);
+    }
+
+    #[test]
+    fn test_default_matches_deserialized_defaults() {
+        let trigger: TriggerConfig = serde_json::from_str(r#"{"name":"t","pattern":"p","action":"a"}"#).unwrap();
+        let expected = TriggerConfig { name: "t".to_string(), pattern: "p".to_string(), action: "a".to_string(), ..Default::default() };
+        assert_eq!(trigger, expected);
+        assert!(expected.default_enabled);
+
+        let alias: AliasConfig = serde_json::from_str(r#"{"name":"k","pattern":"k","replacement":"kill"}"#).unwrap();
+        assert_eq!(alias.default_enabled, AliasConfig::default().default_enabled);
+    }
+
+    #[test]
+    fn test_description_roundtrip() {
+        let alias = AliasConfig {
+            name: "k".to_string(),
+            pattern: "k $1".to_string(),
+            replacement: "kill $1".to_string(),
+            description: "攻擊指定目標".to_string(),
+            ..Default::default()
+        };
+        let trigger = TriggerConfig {
+            name: "heal".to_string(),
+            pattern: "你受傷了".to_string(),
+            action: "cast heal".to_string(),
+            description: "受傷時自動補血".to_string(),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&alias).unwrap();
+        let deserialized: AliasConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.description, "攻擊指定目標");
+        let json = serde_json::to_string(&trigger).unwrap();
+        assert_eq!(serde_json::from_str::<TriggerConfig>(&json).unwrap(), trigger);
+
+        // 舊設定檔沒有說明欄位
+        let legacy: TriggerConfig = serde_json::from_str(r#"{"name":"a","pattern":"b","action":"c"}"#).unwrap();
+        assert!(legacy.description.is_empty());
+    }
+
+    #[test]
+    fn test_history_file_roundtrip() {
+        let path = std::env::temp_dir().join("mudclient_test_history").join("test.history");
+        let history: Vec<String> = (0..MAX_INPUT_HISTORY + 5).map(|i| format!("cmd {}", i)).collect();
+        save_history_file(&path, &history).unwrap();
+
+        let loaded = load_history_file(&path);
+        assert_eq!(loaded.len(), MAX_INPUT_HISTORY);
+        assert_eq!(loaded.first().map(String::as_str), Some("cmd 5"));
+        assert_eq!(loaded, history[5..]);
+
+        let _ = fs::remove_file(&path);
+        assert!(load_history_file(&path).is_empty());
+    }
+
+    #[test]
+    fn test_legacy_enabled_field() {
+        let json = r#"{"name":"kk","pattern":"kk","replacement":"kill kobold","enabled":false}"#;
+        let alias: AliasConfig = serde_json::from_str(json).unwrap();
+        assert!(!alias.default_enabled);
+
+        let saved = serde_json::to_string(&alias).unwrap();
+        assert!(saved.contains("\"default_enabled\":false"));
+        assert!(!saved.contains("\"enabled\""));
+    }
+
     #[test]
     fn test_global_config_defaults() {
         let config = GlobalConfig::default();
@@ -604,4 +1652,169 @@ mod tests {
         assert!(config.auto_connect_profiles.is_empty());
         assert_eq!(config.config_version, 2);
     }
+
+    #[test]
+    fn test_import_tintin() {
+        let script = r#"
+#nop 匯入測試
+#alias {k} {kill %1}
+#alias {gg} {get all;get all from corpse}
+#action {%1 tells you '%2'} {reply 收到 %2}
+#act {^你餓了} {eat bread}
+#gag {廣告}
+#highlight {red} {危險}
+#action {%1 到了} {#showme 到了}
+"#;
+        let result = import_tintin(script);
+
+        assert_eq!(result.aliases.len(), 2);
+        assert_eq!(result.aliases[0].pattern, "k $1");
+        assert_eq!(result.aliases[0].replacement, "kill $1");
+        assert_eq!(result.aliases[1].pattern, "gg");
+        assert_eq!(result.aliases[1].replacement, "get all;get all from corpse");
+
+        let alias = mudcore::Alias::new(
+            &result.aliases[0].name,
+            &result.aliases[0].pattern,
+            &result.aliases[0].replacement,
+        );
+        assert_eq!(alias.try_expand("k orc"), Some("kill orc".to_string()));
+
+        assert_eq!(result.triggers.len(), 3);
+        assert_eq!(result.triggers[0].pattern, "(.*?) tells you '(.*?)'");
+        assert_eq!(result.triggers[0].action, "reply 收到 $2");
+        assert_eq!(result.triggers[1].pattern, "^你餓了");
+        assert_eq!(result.triggers[1].action, "eat bread");
+        assert_eq!(result.triggers[2].pattern, "廣告");
+        assert!(result.triggers[2].is_script);
+
+        // #highlight 與含 #showme 的 #action 被略過
+        assert_eq!(result.skipped.len(), 2);
+    }
+
+    #[test]
+    fn test_tintin_pattern_trailing_wildcard() {
+        assert_eq!(tintin_pattern_to_regex("%1 說：%2").unwrap(), "(.*?) 說：(.*)");
+        assert_eq!(tintin_pattern_to_regex("^HP: %d/%d$").unwrap(), r"^HP: (\d+)/(\d+)$");
+        assert!(tintin_pattern_to_regex("%i大小寫").is_err());
+    }
+
+    #[test]
+    fn test_ui_state_roundtrip() {
+        let mut config = GlobalConfig::default();
+        config.ui.active_window = "chat".to_string();
+        config.ui.side_panel_tab = SidePanelTab::Notes;
+        config.ui.settings_tab = SettingsTab::Trigger;
+        config.ui.completion_key = CompletionKey::CtrlSpace;
+        config.ui.profile_sort = ProfileSort::Recent;
+
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: GlobalConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.ui.active_window, "chat");
+        assert_eq!(loaded.ui.side_panel_tab, SidePanelTab::Notes);
+        assert_eq!(loaded.ui.settings_tab, SettingsTab::Trigger);
+        assert_eq!(loaded.ui.completion_key, CompletionKey::CtrlSpace);
+        assert_eq!(loaded.ui.profile_sort, ProfileSort::Recent);
+
+        // 舊版設定檔缺少欄位、或儲存了未知的值時回到預設
+        let loaded: GlobalConfig = serde_json::from_str(r#"{"ui": {"font_size": 16.0}}"#).unwrap();
+        assert_eq!(loaded.ui.active_window, "main");
+        assert_eq!(loaded.ui.side_panel_tab, SidePanelTab::Tools);
+        assert_eq!(loaded.ui.completion_key, CompletionKey::Tab);
+        assert_eq!(loaded.ui.profile_sort, ProfileSort::Name);
+        let loaded: GlobalConfig =
+            serde_json::from_str(r#"{"ui": {"side_panel_tab": "Map", "settings_tab": 3}}"#).unwrap();
+        assert_eq!(loaded.ui.side_panel_tab, SidePanelTab::Tools);
+        assert_eq!(loaded.ui.settings_tab, SettingsTab::Alias);
+        assert_eq!(loaded.ui.font_size, 14.0);
+    }
+
+    #[test]
+    fn test_profile_recency_sort() {
+        let mut manager = ProfileManager { profiles: HashMap::new() };
+        for (name, display_name, last_connected) in
+            [("a", "Alpha", Some(100)), ("b", "beta", None), ("c", "Gamma", Some(300)), ("d", "Delta", None)]
+        {
+            let mut profile = Profile::new(name, display_name);
+            profile.last_connected = last_connected;
+            manager.profiles.insert(name.to_string(), profile);
+        }
+        let order = |manager: &ProfileManager, sort| -> Vec<String> {
+            manager.sorted(sort).iter().map(|p| p.name.clone()).collect()
+        };
+        assert_eq!(order(&manager, ProfileSort::Name), vec!["a", "b", "d", "c"]);
+        assert_eq!(order(&manager, ProfileSort::Recent), vec!["c", "a", "b", "d"]);
+
+        // 連線後浮到最前面
+        assert_eq!(manager.mark_connected("d", 500).unwrap().last_connected, Some(500));
+        assert_eq!(order(&manager, ProfileSort::Recent), vec!["d", "c", "a", "b"]);
+        assert!(manager.mark_connected("missing", 500).is_none());
+    }
+
+    #[test]
+    fn test_share_pack_roundtrip() {
+        let trigger = |name: &str, pattern: &str, action: &str| TriggerConfig {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            action: action.to_string(),
+            default_enabled: true,
+            ..Default::default()
+        };
+        let triggers = vec![trigger("flee", "你快死了", "flee"), trigger("loot", "(.+) 死了", "k $1")];
+        let aliases = vec![
+            AliasConfig { name: "k".to_string(), pattern: "k $1".to_string(), replacement: "kill $1".to_string(), ..Default::default() },
+            AliasConfig { name: "gg".to_string(), pattern: "gg".to_string(), replacement: "get all".to_string(), ..Default::default() },
+        ];
+        let dependencies = alias_dependencies(&triggers, &aliases);
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].name, "k");
+
+        let path = std::env::temp_dir().join("mudclient_test_share").join("combat.json");
+        SharePack::new(triggers.clone(), dependencies).save(&path).unwrap();
+        let mut pack = SharePack::load(&path).unwrap();
+        assert_eq!(pack.version, SHARE_PACK_VERSION);
+        assert_eq!(pack.triggers, triggers);
+
+        // 匯入空的管理器：沒有衝突
+        let mut manager = mudcore::TriggerManager::new();
+        assert_eq!(pack.resolve_conflicts(MergeStrategy::Skip, |n| manager.get(n).is_some(), |_| false), 0);
+        for cfg in &pack.triggers {
+            manager.add(crate::session::Session::create_trigger_from_config(cfg).unwrap());
+        }
+        assert!(manager.get("flee").is_some());
+        assert!(manager.get("loot").is_some());
+        assert_eq!(pack.aliases.len(), 1);
+    }
+
+    #[test]
+    fn test_share_pack_conflicts() {
+        let pack = SharePack::new(
+            vec![
+                TriggerConfig { name: "flee".to_string(), ..Default::default() },
+                TriggerConfig { name: "new".to_string(), ..Default::default() },
+            ],
+            vec![AliasConfig { name: "k".to_string(), ..Default::default() }],
+        );
+        let exists = |n: &str| n == "flee" || n == "flee (2)" || n == "k";
+
+        let mut skipped = pack.clone();
+        assert_eq!(skipped.resolve_conflicts(MergeStrategy::Skip, exists, exists), 2);
+        assert_eq!(skipped.triggers.len(), 1);
+        assert_eq!(skipped.triggers[0].name, "new");
+
+        let mut renamed = pack.clone();
+        assert_eq!(renamed.resolve_conflicts(MergeStrategy::Rename, exists, exists), 0);
+        assert_eq!(renamed.triggers[0].name, "flee (3)");
+        assert_eq!(renamed.triggers[1].name, "new");
+        assert_eq!(renamed.aliases[0].name, "k (2)");
+
+        let mut overwritten = pack.clone();
+        assert_eq!(overwritten.resolve_conflicts(MergeStrategy::Overwrite, exists, exists), 0);
+        assert_eq!(overwritten.triggers[0].name, "flee");
+
+        // 較新的版本無法讀取
+        let path = std::env::temp_dir().join("mudclient_test_share").join("future.json");
+        SharePack { version: SHARE_PACK_VERSION + 1, ..pack }.save(&path).unwrap();
+        assert!(SharePack::load(&path).is_err());
+    }
 }
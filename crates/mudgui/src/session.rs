@@ -10,18 +10,33 @@
 use std::collections::HashMap;
 use std::time::Instant;
 use mudcore::{
-    Alias, AliasManager, Logger, ScriptEngine, Trigger, TriggerAction,
-    TriggerManager, TriggerPattern, WindowManager, WindowMessage,
-    MudContext, Path, PathManager, PathRecorder, LoopStatus,
+    Alias, Encoding, Engine, GmcpMessage, InputStep, LineActions, PreparedCommand, Logger, MessageBuffer, RoomInfo, Trigger, TriggerAction,
+    Severity, SubWindow, TriggerMatch, TriggerPattern, WindowManager, WindowMessage,
+    ConnectEvent, MudContext, Path, PathRecorder, LoopStatus, Vitals, is_movement_command,
     map::Room,
 };
+use mudcore::ansi::{wrap_ranges, AnsiState, Color};
+use mudcore::logger::{expand_log_path, LogError, DEFAULT_LOG_PATH_TEMPLATE};
+use mudcore::telnet::{LineEnding, OptionStatus, TelnetOption};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
 use tokio::sync::mpsc;
-use crate::config::{AliasConfig, Profile, TriggerConfig};
+use crate::config::{AliasConfig, AntiSpamAction, AntiSpamConfig, LocalEchoMode, PatternCombine, Profile, ProfileManager, TriggerActionType, TriggerConfig, WindowLayoutConfig, MAX_INPUT_HISTORY};
 use lazy_static::lazy_static;
 
+/// 連線事件記錄的最大筆數
+pub const MAX_EVENT_LOG: usize = 100;
+
+/// 保存登入前訊息的視窗 ID
+pub const BANNER_WINDOW_ID: &str = "banner";
+
+/// 觸發器除錯訊息的視窗 ID
+pub const TRIGGER_DEBUG_WINDOW_ID: &str = "trigger_debug";
+
+/// 編碼偵測信心度低於此值時不主動提示，自動套用時則警告使用者
+pub const LOW_ENCODING_CONFIDENCE: f32 = 0.6;
+
 lazy_static! {
     static ref ANSI_STRIP_RE: regex::Regex = regex::Regex::new(r"\x1b\[[0-9;]*[mK]").unwrap();
     static ref MOB_BRACKET_RE: regex::Regex = regex::Regex::new(r"\(([^)]+)\)").unwrap();
@@ -82,9 +97,77 @@ impl Default for ConnectionStatus {
 /// 發送給網路執行緒的命令
 #[derive(Debug)]
 pub enum Command {
-    Connect(String, u16, Option<String>, Option<String>), // Host, Port, Username, Password
+    Connect(String, u16, Option<String>, Option<String>, bool), // Host, Port, Username, Password, 等待帳號回顯
     Send(String),
+    SetEncoding(Encoding),
+    /// 訊息區大小改變（欄數, 列數），供 NAWS 回報
+    WindowSize(u16, u16),
     Disconnect,
+    /// 先送出登出指令，稍候再斷線
+    Logout(String),
+}
+
+// ============================================================================
+// NetworkMessage
+// ============================================================================
+
+/// 網路執行緒送回 Session 的訊息
+#[derive(Debug)]
+pub enum NetworkMessage {
+    /// 伺服器文字（內容, 原始位元組寬度）
+    Text(String, Vec<u8>),
+    /// 伺服器送出 GA / EOR，表示提示符已完整
+    PromptEnd,
+    /// GMCP 結構化資料
+    Gmcp(GmcpMessage),
+    /// Telnet 選項協商結果（有變化時送出完整快照）
+    OptionStates(Vec<(TelnetOption, OptionStatus)>),
+    /// 伺服器在文字中送出 BEL (0x07)
+    Bell,
+    /// 連線狀態更新
+    Status(StatusUpdate),
+}
+
+/// 連線狀態更新
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusUpdate {
+    /// 已連線
+    Connected { host: String, port: u16 },
+    /// 連線失敗
+    ConnectFailed(String),
+    /// 伺服器關閉連線（可能附帶錯誤）
+    Closed(Option<String>),
+    /// 使用者主動斷線
+    Disconnected,
+    /// 已送出自動登入
+    AutoLoginSent,
+    /// 自動登入失敗（步驟, 錯誤）
+    AutoLoginFailed { step: &'static str, error: String },
+    /// 指令發送失敗
+    SendFailed(String),
+    /// 編碼偵測結果與目前不同（編碼, 信心度）
+    EncodingDetected(Encoding, f32),
+    /// 伺服器回顯狀態改變
+    EchoChanged(bool),
+    /// 伺服器以 OSC 序列設定視窗標題
+    TitleChanged(String),
+}
+
+impl StatusUpdate {
+    /// 顯示於畫面的訊息（不含 `>>> ` 前綴）；內部狀態變更回傳 `None`
+    pub fn message(&self) -> Option<String> {
+        match self {
+            StatusUpdate::Connected { host, port } => Some(format!("已連線到 {}:{}", host, port)),
+            StatusUpdate::ConnectFailed(e) => Some(format!("連線已關閉 (連線失敗: {})", e)),
+            StatusUpdate::Closed(None) => Some("連線已關閉".to_string()),
+            StatusUpdate::Closed(Some(e)) => Some(format!("連線已關閉 (錯誤: {})", e)),
+            StatusUpdate::Disconnected => Some("已斷開連線".to_string()),
+            StatusUpdate::AutoLoginSent => Some("已嘗試自動登入".to_string()),
+            StatusUpdate::AutoLoginFailed { step, error } => Some(format!("自動登入({})失敗: {}", step, error)),
+            StatusUpdate::SendFailed(e) => Some(format!("發送失敗: {}", e)),
+            StatusUpdate::EncodingDetected(..) | StatusUpdate::EchoChanged(_) | StatusUpdate::TitleChanged(_) => None,
+        }
+    }
 }
 
 // ============================================================================
@@ -98,6 +181,8 @@ pub struct ActiveTimer {
     pub expires_at: Instant,
     /// 腳本代碼
     pub lua_code: String,
+    /// 註冊時捕獲、觸發時注入為全域變數的值
+    pub globals: Option<HashMap<String, String>>,
 }
 
 // ============================================================================
@@ -132,6 +217,123 @@ pub struct Session {
     /// 登入密碼
     pub password: Option<String>,
 
+    /// 自動登入時等待帳號回顯後再送密碼
+    pub wait_username_echo: bool,
+
+    /// 擷取視窗閒置多久後自動關閉（None 表示不關閉）
+    pub capture_window_idle: Option<Duration>,
+
+    /// 本地回顯模式
+    pub local_echo: LocalEchoMode,
+
+    /// 送出指令時附加的換行字元
+    pub line_ending: LineEnding,
+
+    /// 伺服器文字編碼（連線時套用，`#encoding` 切換後重連沿用）
+    pub encoding: Encoding,
+
+    /// 是否自動套用偵測到的編碼（不詢問使用者）
+    pub auto_detect_encoding: bool,
+
+    /// 連線建立後、自動登入前立即送出的字串
+    pub connect_send: Option<String>,
+
+    /// 伺服器是否負責回顯（WILL ECHO）
+    pub server_echo: bool,
+
+    /// 伺服器設定的視窗標題
+    pub server_title: Option<String>,
+
+    /// 是否隱藏 `>>>` 連線狀態訊息
+    pub quiet_connect: bool,
+
+    /// 日誌記錄替換前的原文（否則記錄替換後的內容）
+    pub log_original_text: bool,
+
+    /// 是否將登入前的訊息另存到 banner 視窗
+    pub capture_banner: bool,
+
+    /// 觸發器除錯：每次匹配都列在除錯視窗（觸發器名稱、匹配文字、捕獲）
+    pub trigger_debug: bool,
+
+    /// 依指令類型為本地回顯上色（一般指令、客戶端指令、別名展開）
+    pub colored_echo: bool,
+
+    /// 目前正在處理的別名展開層數（用於判斷回顯類型）
+    alias_depth: usize,
+
+    /// 安全模式：不送出任何指令，只在本地回顯並標示 `[blocked]`
+    pub send_blocked: bool,
+
+    /// 連線後尚未登入（自動登入完成或使用者首次送出指令前）
+    pub pre_login: bool,
+
+    /// 是否將 GA/EOR 提示符固定在輸入框上方
+    pub pin_prompt: bool,
+
+    /// 主視窗連續的空白行只保留一行（ASCII 藝術模式下不合併）
+    pub collapse_blank_lines: bool,
+
+    /// 主視窗最後一行是否為空白行
+    last_main_blank: bool,
+
+    /// 目前房間（伺服器以 GMCP `Room.Info` 提供；不支援 GMCP 時為 `None`）
+    pub current_room: Option<RoomInfo>,
+
+    /// 角色狀態（伺服器以 GMCP `Char.Vitals` 提供，逐次合併）
+    pub vitals: Option<Vitals>,
+
+    /// 最近一次量到的訊息區大小（欄數, 列數）
+    pub window_size: Option<(u16, u16)>,
+
+    /// Telnet 選項協商結果（供診斷視窗顯示）
+    pub telnet_options: Vec<(TelnetOption, OptionStatus)>,
+
+    /// 是否以房間名稱作為視窗標題
+    pub room_in_title: bool,
+
+    /// 伺服器送出 BEL 時是否提醒
+    pub bell_alert: bool,
+
+    /// 上次提醒的時間（冷卻用）
+    pub last_bell: Option<Instant>,
+
+    /// 尚未被 UI 取走的提醒
+    bell_pending: bool,
+
+    /// 連線成功後尚未被 UI 取走的通知（用於更新最後連線時間）
+    connected_pending: bool,
+
+    /// 使用者主動斷線前送出的指令
+    pub on_disconnect: Option<String>,
+
+    /// Ctrl+點擊單字送出的指令模板（`$word`）
+    pub click_command: String,
+
+    /// 每次連線後（排在自動登入之後）送出的指令
+    pub on_connect: Option<String>,
+
+    /// 本次連線是否已送出 `on_connect`（避免重複的連線狀態造成重送）
+    on_connect_sent: bool,
+
+    /// Profile 設定的提示符正則（`None` 時使用內建判斷）
+    pub prompt_regex: Option<regex::Regex>,
+
+    /// Profile 設定的聊天正則（符合的行轉送到聊天彙整）
+    pub chat_regex: Option<regex::Regex>,
+
+    /// 尚未被 SessionManager 取走的聊天訊息
+    new_chat: Vec<String>,
+
+    /// 最新的固定提示符（含 ANSI）
+    pub pinned_prompt: Option<String>,
+
+    /// 正在處理 GA/EOR 提示符（主視窗改寫入固定提示列）
+    routing_prompt: bool,
+
+    /// 正在處理以 GA/EOR 結尾的行（即伺服器標示的提示符）
+    at_prompt_end: bool,
+
     
     /// 連線狀態
     pub status: ConnectionStatus,
@@ -140,7 +342,10 @@ pub struct Session {
     pub command_tx: Option<mpsc::Sender<Command>>,
     
     /// 從網路執行緒接收訊息的 channel (內容, 原始位元組寬度)
-    pub message_rx: Option<mpsc::Receiver<(String, Vec<u8>)>>,
+    pub message_rx: Option<mpsc::Receiver<NetworkMessage>>,
+
+    /// 引擎暫存未換行文字的起始時間（等待後續資料、GA/EOR 或逾時）
+    partial_since: Option<Instant>,
     
     /// 連線開始時間
     pub connected_at: Option<Instant>,
@@ -148,34 +353,40 @@ pub struct Session {
     /// 當前房間 ID
     pub current_room_id: Option<String>,
 
+    /// 最近送出、尚未抵達新房間的移動方向（供自動地圖連結出口）
+    pending_move: Option<String>,
+
     // === 獨立的管理器（Profile 專屬） ===
-    /// 別名管理器
-    pub alias_manager: AliasManager,
-    
-    /// 觸發器管理器
-    pub trigger_manager: TriggerManager,
-    
-    /// 路徑管理器
-    pub path_manager: PathManager,
+    /// 核心引擎（別名、觸發器、路徑、縮寫與腳本）
+    pub engine: Engine,
     
     /// 路徑記錄器
     pub path_recorder: PathRecorder,
     
-    /// 腳本引擎
-    pub script_engine: ScriptEngine,
-    
     /// 視窗管理器
     pub window_manager: WindowManager,
     
     /// 日誌記錄器
     pub logger: Logger,
 
+    /// 日誌路徑樣板
+    pub log_path_template: String,
+
     // === 會話狀態 ===
     /// 輸入框內容
     pub input: String,
     
     /// 輸入歷史
     pub input_history: Vec<String>,
+
+    /// 是否將輸入歷史儲存到磁碟
+    pub persist_history: bool,
+
+    /// 是否在結束時保存主視窗記錄
+    pub persist_scrollback: bool,
+
+    /// 尚未同步（儲存/共用）的新歷史
+    new_history: Vec<String>,
     
     /// 歷史索引
     pub history_index: Option<usize>,
@@ -194,6 +405,9 @@ pub struct Session {
     
     /// 畫面單字字典（用於智慧補齊）
     pub screen_words: HashMap<String, WordMetadata>,
+
+    /// 批次處理多行時共用的時間戳（期間延後字典清理）
+    word_batch_time: Option<Instant>,
     
     /// 是否正在接收房間敘述
     #[allow(dead_code)]
@@ -212,13 +426,15 @@ pub struct Session {
     /// 重連等待時間點
     pub reconnect_delay_until: Option<Instant>,
 
-    /// 最後活動時間
-    #[allow(dead_code)]
+    /// 最後一次送出指令的時間（閒置計時）
     pub last_active: Instant,
 
     /// 活躍的計時器
     pub active_timers: Vec<ActiveTimer>,
 
+    /// 腳本以 mud.highlight 註冊的高亮 (正則, 顏色)
+    pub highlights: Vec<(regex::Regex, Color)>,
+
     // === 多視窗預留 ===
     /// 當 Session 被拆分為獨立視窗時的視窗 ID
     #[allow(dead_code)]
@@ -230,9 +446,29 @@ pub struct Session {
     
     /// 重複指令計數
     pub repeat_command_count: usize,
+
+    /// 重複指令防呆設定
+    pub anti_spam: AntiSpamConfig,
     
     /// 用於識別房間特徵的行緩衝區
     pub line_buffer: std::collections::VecDeque<String>,
+
+    // === 編碼偵測 ===
+    /// 等待使用者確認的編碼建議
+    pub encoding_suggestion: Option<Encoding>,
+
+    /// 是否已提示過編碼建議（每個 Session 僅提示一次）
+    pub encoding_prompted: bool,
+
+    // === 連線事件記錄 ===
+    /// 連線事件（時間, 描述），與 MUD 畫面分開，最多保留 MAX_EVENT_LOG 筆
+    pub event_log: Vec<(Instant, String)>,
+
+    /// 目前連續重連的次數（連線成功後歸零）
+    pub reconnect_attempts: u32,
+
+    /// 自動重連的最多嘗試次數（0 表示不限）
+    pub max_reconnect_attempts: u32,
 }
 
 /// 畫面單字的中繼資料
@@ -244,12 +480,26 @@ pub struct WordMetadata {
     pub is_mob: bool,
 }
 
+/// 未設定日誌路徑時使用的樣板
+#[cfg(not(test))]
+fn default_log_path_template() -> String {
+    DEFAULT_LOG_PATH_TEMPLATE.to_string()
+}
+
+/// 測試時將日誌寫到暫存目錄，避免在工作目錄留下檔案
+#[cfg(test)]
+fn default_log_path_template() -> String {
+    std::env::temp_dir()
+        .join("mudclient_test")
+        .join(DEFAULT_LOG_PATH_TEMPLATE)
+        .to_string_lossy()
+        .into_owned()
+}
+
 impl Session {
     /// 從 Profile 建立新的 Session
     pub fn from_profile(profile: &Profile) -> Self {
-        let mut alias_manager = AliasManager::new();
-        let mut trigger_manager = TriggerManager::new();
-        let mut path_manager = PathManager::new();
+        let mut engine = Engine::new();
         
         let username = profile.username.clone();
         let password = profile.password.clone();
@@ -258,36 +508,81 @@ impl Session {
         for path_cfg in &profile.paths {
             let mut path = Path::new(&path_cfg.name, &path_cfg.value);
             path.category = path_cfg.category.clone();
-            path_manager.add(path);
+            engine.path_manager.add(path);
+        }
+
+        // 編譯 Profile 的提示符正則（無效時退回內建判斷）
+        let prompt_regex = profile
+            .prompt_regex
+            .as_deref()
+            .filter(|pattern| !pattern.trim().is_empty())
+            .and_then(|pattern| match regex::Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    tracing::warn!("無效的提示符正則 '{}': {}", pattern, e);
+                    None
+                }
+            });
+
+        let chat_regex = profile
+            .chat_pattern
+            .as_deref()
+            .filter(|pattern| !pattern.trim().is_empty())
+            .and_then(|pattern| match regex::Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    tracing::warn!("無效的聊天正則 '{}': {}", pattern, e);
+                    None
+                }
+            });
+
+        // 載入 Profile 的縮寫表
+        for word in &profile.abbreviations {
+            engine.abbreviations.add(word);
         }
 
         // 載入 Profile 的別名
         for alias_cfg in &profile.aliases {
-            let mut alias = Alias::new(&alias_cfg.name, &alias_cfg.pattern, &alias_cfg.replacement);
+            let mut alias = Alias::new(&alias_cfg.name, &alias_cfg.pattern, &alias_cfg.replacement).as_regex(alias_cfg.is_regex);
             alias.category = alias_cfg.category.clone();
-            alias.enabled = alias_cfg.enabled;
+            alias.description = alias_cfg.description.clone();
+            alias.default_enabled = alias_cfg.default_enabled;
+            alias.enabled = alias_cfg.default_enabled;
             alias.is_script = alias_cfg.is_script;
-            alias_manager.add(alias);
+            engine.alias_manager.add(alias);
         }
+        engine.alias_manager.strict_args = profile.alias_strict_args;
 
         // 載入 Profile 的觸發器
         for trigger_cfg in &profile.triggers {
             if let Some(trigger) = Self::create_trigger_from_config(trigger_cfg) {
-                trigger_manager.add(trigger);
+                engine.trigger_manager.add(trigger);
             }
         }
+        engine.set_gag_patterns(&profile.gag_patterns);
 
-        // 建立日誌記錄器
-        let mut logger = Logger::new();
-        let log_path = format!(
-            "logs/{}_{}.txt",
-            profile.name,
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0)
-        );
-        let _ = logger.start(&log_path);
+        // 擷取視窗數量上限
+        let mut window_manager = WindowManager::new();
+        window_manager.set_max_auto_windows((profile.max_capture_windows > 0).then_some(profile.max_capture_windows));
+
+        // 預先建立 Profile 保存的子視窗
+        for layout in &profile.windows {
+            if layout.id == "main" {
+                continue;
+            }
+            let mut window = SubWindow::new(&layout.id, &layout.title)
+                .with_capacity(layout.capacity)
+                .with_strip_ansi(layout.strip_ansi)
+                .with_ascii_art(layout.ascii_art);
+            window.visible = layout.visible;
+            window_manager.add_window(window);
+        }
+
+        let log_path_template = profile
+            .log_path
+            .clone()
+            .filter(|template| !template.trim().is_empty())
+            .unwrap_or_else(default_log_path_template);
 
         let mut session = Self {
             id: SessionId::new(),
@@ -298,26 +593,74 @@ impl Session {
             port: profile.connection.port.clone(),
             username,
             password,
+            wait_username_echo: profile.wait_username_echo,
+            capture_window_idle: (profile.capture_window_idle_secs > 0)
+                .then(|| Duration::from_secs(profile.capture_window_idle_secs)),
+            local_echo: profile.local_echo,
+            line_ending: profile.line_ending,
+            encoding: profile.encoding,
+            auto_detect_encoding: profile.auto_detect_encoding,
+            connect_send: profile.connect_send.clone(),
+            server_echo: false,
+            server_title: None,
+            quiet_connect: profile.quiet_connect,
+            log_original_text: profile.log_original_text,
+            capture_banner: profile.capture_banner,
+            trigger_debug: false,
+            colored_echo: false,
+            alias_depth: 0,
+            send_blocked: false,
+            pre_login: false,
+            pin_prompt: profile.pin_prompt,
+            collapse_blank_lines: profile.collapse_blank_lines,
+            last_main_blank: false,
+            current_room: None,
+            vitals: None,
+            window_size: None,
+            telnet_options: Vec::new(),
+            room_in_title: profile.room_in_title,
+            bell_alert: profile.bell_alert,
+            last_bell: None,
+            bell_pending: false,
+            connected_pending: false,
+            on_disconnect: profile.on_disconnect.clone().filter(|cmd| !cmd.trim().is_empty()),
+            click_command: profile.click_command.clone(),
+            on_connect: profile.on_connect.clone().filter(|cmd| !cmd.trim().is_empty()),
+            on_connect_sent: false,
+            prompt_regex,
+            chat_regex,
+            new_chat: Vec::new(),
+            pinned_prompt: None,
+            routing_prompt: false,
+            at_prompt_end: false,
             status: ConnectionStatus::Disconnected,
             command_tx: None,
             message_rx: None,
+            partial_since: None,
             connected_at: None,
             current_room_id: None,
-            alias_manager,
-            trigger_manager,
-            path_manager,
+            pending_move: None,
+            engine,
             path_recorder: PathRecorder::new(),
-            script_engine: ScriptEngine::new(),
-            window_manager: WindowManager::new(),
-            logger,
+            window_manager,
+            logger: Logger::new(),
+            log_path_template,
             input: String::new(),
-            input_history: Vec::new(),
+            input_history: if profile.persist_history {
+                crate::config::load_history_file(&ProfileManager::history_path(&profile.name))
+            } else {
+                Vec::new()
+            },
+            persist_history: profile.persist_history,
+            persist_scrollback: profile.persist_scrollback,
+            new_history: Vec::new(),
             history_index: None,
             tab_completion_prefix: None,
             tab_completion_index: 0,
             tab_completed: false,
             last_completed_input: None,
             screen_words: HashMap::new(),
+            word_batch_time: None,
             in_room_description: false,
             auto_scroll: true,
             scroll_to_bottom_on_next_frame: false,
@@ -325,52 +668,252 @@ impl Session {
             reconnect_delay_until: None,
             last_active: Instant::now(),
             active_timers: Vec::new(),
+            highlights: Vec::new(),
             detached_window_id: None,
             last_sent_command: None,
             repeat_command_count: 0,
+            anti_spam: profile.anti_spam.clone(),
             line_buffer: std::collections::VecDeque::with_capacity(20),
+            encoding_suggestion: None,
+            encoding_prompted: false,
+            event_log: Vec::new(),
+            reconnect_attempts: 0,
+            max_reconnect_attempts: profile.max_reconnect_attempts,
         };
 
+        // 開始記錄日誌（無法建立目錄或檔案時提示，不影響連線）
+        let template = session.log_path_template.clone();
+        if let Err(e) = session.start_log(&template) {
+            session.system_message(&format!("⚠️ 無法啟動日誌: {}", e));
+        }
+
+        // 載入上次結束時保存的主視窗記錄
+        if session.persist_scrollback {
+            session.restore_scrollback(&ProfileManager::scrollback_path(&profile.name));
+        }
+
         // 自動載入 scripts/ 目錄下的腳本
         session.load_startup_scripts();
 
         session
     }
 
+    /// 依路徑樣板開始記錄日誌，回傳實際的檔案路徑
+    pub fn start_log(&mut self, template: &str) -> Result<std::path::PathBuf, LogError> {
+        let path = expand_log_path(template, &self.profile_name, std::time::SystemTime::now());
+        self.logger.start(&path)?;
+        Ok(path)
+    }
+
     /// 從設定建立觸發器
     pub fn create_trigger_from_config(config: &TriggerConfig) -> Option<Trigger> {
         let clean_pattern = clean_pattern_string(&config.pattern);
 
-        // 自動偵測正則表達式模式
-        let pattern = if clean_pattern.contains("(.+)")
-            || clean_pattern.contains("(.*)")
-            || clean_pattern.contains("\\d")
-            || clean_pattern.contains("[")
-            || clean_pattern.contains("$")
-            || clean_pattern.contains("^")
-            || clean_pattern.contains("|")
-            || clean_pattern.contains("?")
-        {
-            TriggerPattern::Regex(clean_pattern)
-        } else {
-            TriggerPattern::Contains(clean_pattern)
+        // 組合模式：以逗號分隔多個子模式
+        let pattern = match config.combine {
+            PatternCombine::Single => detect_trigger_pattern(clean_pattern),
+            PatternCombine::AnyOf => TriggerPattern::AnyOf(split_trigger_patterns(&clean_pattern)),
+            PatternCombine::AllOf => TriggerPattern::AllOf(split_trigger_patterns(&clean_pattern)),
+            PatternCombine::MultiLine => TriggerPattern::MultiLine(split_trigger_patterns(&clean_pattern)),
         };
 
         let mut trigger = Trigger::new(&config.name, pattern);
 
-        if !config.action.is_empty() {
-            if config.is_script {
-                trigger = trigger.add_action(TriggerAction::ExecuteScript(config.action.clone()));
-            } else {
-                trigger = trigger.add_action(TriggerAction::SendCommand(config.action.clone()));
+        match config.action_type {
+            TriggerActionType::Command => {
+                if !config.action.is_empty() {
+                    if config.is_script {
+                        trigger = trigger.add_action(TriggerAction::ExecuteScript(config.action.clone()));
+                    } else {
+                        trigger = trigger.add_action(TriggerAction::SendCommand(config.action.clone()));
+                    }
+                }
+            }
+            TriggerActionType::RawCommand => {
+                if !config.action.is_empty() {
+                    trigger = trigger.add_action(TriggerAction::SendRawCommand(config.action.clone()));
+                }
+            }
+            TriggerActionType::SetVariable => {
+                trigger = trigger.add_action(TriggerAction::SetVariable {
+                    name: config.variable.clone(),
+                    value: config.action.clone(),
+                });
+            }
+            TriggerActionType::IncrementVariable => {
+                let by = config.action.trim().parse::<i64>().unwrap_or(1);
+                trigger = trigger.add_action(TriggerAction::IncrementVariable {
+                    name: config.variable.clone(),
+                    by,
+                });
+            }
+            TriggerActionType::Substitute => {
+                trigger = trigger.add_action(TriggerAction::Substitute {
+                    replacement: config.action.clone(),
+                });
+            }
+            TriggerActionType::AppendNote => {
+                trigger = trigger.add_action(TriggerAction::AppendNote(config.action.clone()));
+            }
+            TriggerActionType::Highlight => {
+                let (fg, bg, bold) = parse_highlight_style(&config.action);
+                trigger = trigger.add_action(TriggerAction::Highlight { fg, bg, bold });
+            }
+            TriggerActionType::AppendFile => {
+                trigger = trigger.add_action(TriggerAction::AppendFile {
+                    path: config.variable.clone(),
+                    text: config.action.clone(),
+                });
             }
         }
 
         trigger.category = config.category.clone();
-        trigger.enabled = config.enabled;
+        trigger.description = config.description.clone();
+        trigger.default_enabled = config.default_enabled;
+        trigger.enabled = config.default_enabled;
+        trigger.fire_on = config.fire_on;
+        trigger.scope = config.scope;
+        trigger.max_fires = config.max_fires.filter(|&max| max > 0);
+        trigger.priority = config.priority;
+        trigger.cooldown = Duration::from_millis(config.cooldown_ms);
+        trigger.requires_prior = config
+            .requires_prior
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| (name.to_string(), Duration::from_millis(config.requires_prior_ms)));
+        // 正則無效時停用（設定中的預設啟用狀態不變，修正後即恢復）
+        if let Some(error) = &trigger.error {
+            tracing::warn!("觸發器 {} 的正則無效，已停用: {}", trigger.name, error);
+            trigger.enabled = false;
+        }
         Some(trigger)
     }
 
+    /// 將觸發器轉回設定（以第一個可序列化的動作為準）
+    pub fn config_from_trigger(trigger: &Trigger) -> TriggerConfig {
+        let combine = match &trigger.pattern {
+            TriggerPattern::AnyOf(_) => PatternCombine::AnyOf,
+            TriggerPattern::AllOf(_) => PatternCombine::AllOf,
+            TriggerPattern::MultiLine(_) => PatternCombine::MultiLine,
+            _ => PatternCombine::Single,
+        };
+
+        let mut config = TriggerConfig {
+            name: trigger.name.clone(),
+            pattern: trigger.pattern.source(),
+            combine,
+            category: trigger.category.clone(),
+            description: trigger.description.clone(),
+            default_enabled: trigger.default_enabled,
+            fire_on: trigger.fire_on,
+            scope: trigger.scope,
+            max_fires: trigger.max_fires,
+            priority: trigger.priority,
+            cooldown_ms: trigger.cooldown.as_millis() as u64,
+            requires_prior: trigger.requires_prior.as_ref().map(|(name, _)| name.clone()),
+            requires_prior_ms: trigger.requires_prior.as_ref().map_or(0, |(_, within)| within.as_millis() as u64),
+            ..Default::default()
+        };
+
+        let first_action = trigger.actions.iter().find(|a| {
+            matches!(
+                a,
+                TriggerAction::SendCommand(_)
+                    | TriggerAction::SendRawCommand(_)
+                    | TriggerAction::ExecuteScript(_)
+                    | TriggerAction::SetVariable { .. }
+                    | TriggerAction::IncrementVariable { .. }
+                    | TriggerAction::Substitute { .. }
+                    | TriggerAction::Highlight { .. }
+                    | TriggerAction::AppendNote(_)
+                    | TriggerAction::AppendFile { .. }
+            )
+        });
+        match first_action {
+            Some(TriggerAction::SendCommand(cmd)) => config.action = cmd.clone(),
+            Some(TriggerAction::SendRawCommand(cmd)) => {
+                config.action_type = TriggerActionType::RawCommand;
+                config.action = cmd.clone();
+            }
+            Some(TriggerAction::ExecuteScript(code)) => {
+                config.action = code.clone();
+                config.is_script = true;
+            }
+            Some(TriggerAction::SetVariable { name, value }) => {
+                config.action_type = TriggerActionType::SetVariable;
+                config.variable = name.clone();
+                config.action = value.clone();
+            }
+            Some(TriggerAction::IncrementVariable { name, by }) => {
+                config.action_type = TriggerActionType::IncrementVariable;
+                config.variable = name.clone();
+                config.action = by.to_string();
+            }
+            Some(TriggerAction::Substitute { replacement }) => {
+                config.action_type = TriggerActionType::Substitute;
+                config.action = replacement.clone();
+            }
+            Some(TriggerAction::Highlight { fg, bg, bold }) => {
+                config.action_type = TriggerActionType::Highlight;
+                config.action = format_highlight_style(*fg, *bg, *bold);
+            }
+            Some(TriggerAction::AppendNote(text)) => {
+                config.action_type = TriggerActionType::AppendNote;
+                config.action = text.clone();
+            }
+            Some(TriggerAction::AppendFile { path, text }) => {
+                config.action_type = TriggerActionType::AppendFile;
+                config.variable = path.clone();
+                config.action = text.clone();
+            }
+            _ => {}
+        }
+        config
+    }
+
+    /// 從設定建立別名
+    pub fn create_alias_from_config(config: &AliasConfig) -> Alias {
+        let mut alias = Alias::new(&config.name, &config.pattern, &config.replacement).as_regex(config.is_regex);
+        alias.category = config.category.clone();
+        alias.description = config.description.clone();
+        alias.default_enabled = config.default_enabled;
+        alias.enabled = config.default_enabled;
+        alias.is_script = config.is_script;
+        alias
+    }
+
+    /// 將別名轉回設定
+    pub fn config_from_alias(alias: &Alias) -> AliasConfig {
+        AliasConfig {
+            name: alias.name.clone(),
+            pattern: alias.pattern.clone(),
+            replacement: alias.replacement.clone(),
+            category: alias.category.clone(),
+            description: alias.description.clone(),
+            is_script: alias.is_script,
+            is_regex: alias.is_regex,
+            default_enabled: alias.default_enabled,
+        }
+    }
+
+    /// 目前需要保存的子視窗配置（不含主視窗與自動建立的擷取視窗）
+    pub fn window_layout(&self) -> Vec<WindowLayoutConfig> {
+        self.window_manager
+            .windows()
+            .into_iter()
+            .filter(|w| w.id != "main" && !w.is_auto_created())
+            .map(|w| WindowLayoutConfig {
+                id: w.id.clone(),
+                title: w.title.clone(),
+                capacity: w.capacity,
+                visible: w.visible,
+                strip_ansi: w.strip_ansi,
+                ascii_art: w.ascii_art,
+            })
+            .collect()
+    }
+
     /// 合併全域觸發器/別名
     pub fn merge_global_config(
         &mut self,
@@ -380,24 +923,26 @@ impl Session {
         // 全域別名（加在 Profile 別名之前，優先度較低）
         for alias_cfg in global_aliases {
             // 如果 Profile 已有同名別名，跳過
-            if self.alias_manager.get(&alias_cfg.name).is_some() {
+            if self.engine.alias_manager.get(&alias_cfg.name).is_some() {
                 continue;
             }
-            let mut alias = Alias::new(&alias_cfg.name, &alias_cfg.pattern, &alias_cfg.replacement);
+            let mut alias = Alias::new(&alias_cfg.name, &alias_cfg.pattern, &alias_cfg.replacement).as_regex(alias_cfg.is_regex);
             alias.category = alias_cfg.category.clone();
-            alias.enabled = alias_cfg.enabled;
+            alias.description = alias_cfg.description.clone();
+            alias.default_enabled = alias_cfg.default_enabled;
+            alias.enabled = alias_cfg.default_enabled;
             alias.is_script = alias_cfg.is_script;
-            self.alias_manager.add(alias);
+            self.engine.alias_manager.add(alias);
         }
 
         // 全域觸發器
         for trigger_cfg in global_triggers {
             // 如果 Profile 已有同名觸發器，跳過
-            if self.trigger_manager.get(&trigger_cfg.name).is_some() {
+            if self.engine.trigger_manager.get(&trigger_cfg.name).is_some() {
                 continue;
             }
             if let Some(trigger) = Self::create_trigger_from_config(trigger_cfg) {
-                self.trigger_manager.add(trigger);
+                self.engine.trigger_manager.add(trigger);
             }
         }
     }
@@ -543,16 +1088,24 @@ impl Session {
         if self.current_room_id.as_deref() != Some(&id) {
             tracing::info!("Room Detected: {} (ID: {})", name, id);
         }
+
+        // 自動地圖：記錄房間，剛移動過則連結上一個房間的出口
+        self.engine.room_graph.add_room(&id, &name);
+        if let (Some(direction), Some(from)) = (self.pending_move.take(), self.current_room_id.as_deref()) {
+            if from != id {
+                self.engine.room_graph.link(from, &direction, &id);
+            }
+        }
         
         self.current_room_id = Some(id.clone());
-        self.script_engine.set_current_room_id(Some(id.clone()));
+        self.engine.script_engine.set_current_room_id(Some(id.clone()));
         
         // 觸發 Lua Hook: on_room_detected(id, name)
         // 使用 run_code 或是我們需要新增一個 invoke_hook_with_args?
         // 為了簡單，我們借用 invoke_hook (name, arg, clean_arg)
         // 把 id 當作 arg, name 當作 clean_arg
         // 觸發 Lua Hook: on_room_detected(id, name)
-        match self.script_engine.invoke_hook("on_room_detected", &id, &name) {
+        match self.engine.script_engine.invoke_hook("on_room_detected", &id, &name) {
             Ok(Some(context)) => {
                 self.apply_script_context(context);
             }
@@ -563,6 +1116,19 @@ impl Session {
         }
     }
 
+    /// 將目前別名與觸發器的啟用狀態存為預設值（下次儲存時寫入設定檔）
+    pub fn save_enabled_state(&mut self) {
+        self.engine.alias_manager.save_enabled_as_default();
+        self.engine.trigger_manager.save_enabled_as_default();
+    }
+
+    /// 關閉閒置過久的擷取視窗
+    pub fn prune_idle_windows(&mut self) {
+        if let Some(max_age) = self.capture_window_idle {
+            self.window_manager.prune_idle(max_age);
+        }
+    }
+
     /// 檢查並執行到期的計時器
     pub fn check_timers(&mut self) {
         if self.active_timers.is_empty() {
@@ -574,15 +1140,22 @@ impl Session {
 
         self.active_timers.retain(|timer| {
             if now >= timer.expires_at {
-                expired.push(timer.lua_code.clone());
+                expired.push((timer.lua_code.clone(), timer.globals.clone()));
                 false
             } else {
                 true
             }
         });
 
-        for code in expired {
-            if let Ok(context) = self.script_engine.execute_inline(&code, "TIMER_EXPIRED", &[], false) {
+        for (code, globals) in expired {
+            let result = match globals {
+                Some(globals) => self
+                    .engine
+                    .script_engine
+                    .execute_inline_with_globals(&code, "TIMER_EXPIRED", &[], false, &globals),
+                None => self.engine.script_engine.execute_inline(&code, "TIMER_EXPIRED", &[], false),
+            };
+            if let Ok(context) = result {
                 self.apply_script_context(context);
             }
         }
@@ -599,7 +1172,7 @@ impl Session {
         };
 
         // 設定 scripts_dir 的絕對路徑，供 dofile 查找
-        self.script_engine.set_scripts_dir(scripts_dir.to_string_lossy().as_ref());
+        self.engine.script_engine.set_scripts_dir(scripts_dir.to_string_lossy().as_ref());
 
         match std::fs::read_dir(&scripts_dir) {
             Ok(entries) => {
@@ -615,7 +1188,7 @@ impl Session {
                 for path in valid_scripts {
                     if let Ok(code) = std::fs::read_to_string(&path) {
                         let filename = path.file_name().unwrap_or_default().to_string_lossy();
-                        match self.script_engine.execute_inline(&code, "STARTUP", &[], false) {
+                        match self.engine.script_engine.execute_inline(&code, "STARTUP", &[], false) {
                             Ok(context) => {
                                 self.apply_script_context(context);
                                 let _ = self.logger.log(&format!("已自動載入腳本: {}", filename));
@@ -625,6 +1198,8 @@ impl Session {
                                     preserve_ansi: true,
                                     byte_widths: Vec::new(),
                                     repeat_count: 1,
+                                    start_state: AnsiState::default(),
+                                    severity: Severity::Info,
                                 });
                             }
                             Err(e) => {
@@ -676,8 +1251,12 @@ impl Session {
 
     /// 核心：將腳本執行結果套用到 Session
     pub fn apply_script_context(&mut self, context: MudContext) {
-        // 1. 發送指令
-        if let Some(tx) = &self.command_tx {
+        // 1. 發送指令（安全模式下只回顯）
+        if self.send_blocked {
+            for cmd in context.commands {
+                self.echo_input(&format!("{} [blocked]", cmd));
+            }
+        } else if let Some(tx) = &self.command_tx {
             for cmd in context.commands {
                 let _ = tx.blocking_send(Command::Send(cmd));
             }
@@ -697,16 +1276,24 @@ impl Session {
                     preserve_ansi: true,
                     byte_widths: Vec::new(),
                     repeat_count: 1,
+                    start_state: AnsiState::default(),
+                    severity: Severity::Info,
                 },
             );
         }
 
+        // 重要事件彙整
+        for (text, severity) in context.alerts {
+            self.window_manager.post_alert(&text, severity);
+        }
+
         // 4. 計時器註冊
         let now = Instant::now();
-        for (delay_ms, code) in context.timers {
+        for (delay_ms, code, globals) in context.timers {
             self.active_timers.push(ActiveTimer {
                 expires_at: now + Duration::from_millis(delay_ms),
                 lua_code: code,
+                globals,
             });
         }
 
@@ -717,23 +1304,50 @@ impl Session {
 
         // 6. 觸發器狀態更新
         for (name, enabled) in context.trigger_updates {
-            if let Some(trigger) = self.trigger_manager.get_mut(&name) {
+            if let Some(trigger) = self.engine.trigger_manager.get_mut(&name) {
                 trigger.enabled = enabled;
                 tracing::info!("Script updated trigger '{}' enabled: {}", name, enabled);
             }
         }
+        for (pattern, code) in context.temp_triggers {
+            self.engine
+                .trigger_manager
+                .add_temp(TriggerPattern::Regex(pattern), TriggerAction::ExecuteScript(code));
+        }
         
-        // 7. 日誌控制
+        // 7. 高亮註冊（同一模式重複註冊時更新顏色）
+        if context.clear_highlights {
+            self.highlights.clear();
+        }
+        for (pattern, color) in context.highlights {
+            match regex::Regex::new(&pattern) {
+                Ok(re) => {
+                    self.highlights.retain(|(existing, _)| existing.as_str() != pattern);
+                    self.highlights.push((re, color));
+                }
+                Err(e) => self.system_message(&format!("無效的高亮模式 '{}': {}", pattern, e)),
+            }
+        }
+
+        // 8. 腳本要求斷線（如 mud.disconnect_after 到期）：定時登出不應自動重連
+        if context.disconnect {
+            self.auto_reconnect = false;
+            self.log_event("腳本要求斷線");
+            if let Some(tx) = &self.command_tx {
+                let _ = tx.try_send(Command::Disconnect);
+            }
+        }
+
+        // 9. 日誌控制
         if let Some(control) = context.log_control {
             match control {
-                mudcore::script::LogControl::Start(path) => {
-                    if let Err(e) = self.logger.start(&path) {
+                mudcore::script::LogControl::Start(template) => match self.start_log(&template) {
+                    Ok(path) => self.system_message(&format!("📝 開始記錄日誌至 '{}'", path.display())),
+                    Err(e) => {
                         let _ = self.logger.log(&format!("無法啟動日誌: {}", e));
-                        self.system_message(&format!("⚠️ 無法啟動日誌 '{}': {}", path, e));
-                    } else {
-                        self.system_message(&format!("📝 開始記錄日誌至 '{}'", path));
+                        self.system_message(&format!("⚠️ 無法啟動日誌 '{}': {}", template, e));
                     }
-                }
+                },
                 mudcore::script::LogControl::Stop => {
                     if let Err(e) = self.logger.stop() {
                         let _ = self.logger.log(&format!("無法停止日誌: {}", e));
@@ -743,50 +1357,200 @@ impl Session {
                 }
             }
         }
+
+        // 10. 切換文字編碼
+        if let Some(encoding) = context.encoding {
+            self.set_encoding(encoding);
+        }
+
+        // 11. 附加筆記
+        for note in context.notes {
+            self.append_note(&note);
+        }
+    }
+
+    /// 將觸發器除錯訊息寫入除錯視窗（不存在時建立）
+    fn push_trigger_debug(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        if self.window_manager.get(TRIGGER_DEBUG_WINDOW_ID).is_none() {
+            self.window_manager.add_window(SubWindow::new(TRIGGER_DEBUG_WINDOW_ID, "觸發器除錯"));
+        }
+        if let Some(window) = self.window_manager.get_mut(TRIGGER_DEBUG_WINDOW_ID) {
+            for line in lines {
+                window.push(WindowMessage::new(line));
+            }
+        }
+    }
+
+    /// 附加一行到 Session 筆記
+    pub fn append_note(&mut self, text: &str) {
+        if !self.notes.is_empty() && !self.notes.ends_with('\n') {
+            self.notes.push('\n');
+        }
+        self.notes.push_str(text);
+        self.notes.push('\n');
+    }
+
+    /// 附加一行到檔案（不存在時建立）；失敗時顯示系統訊息
+    pub fn append_to_file(&mut self, path: &str, text: &str) {
+        use std::io::Write;
+
+        let path = std::path::Path::new(path);
+        let result = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::OpenOptions::new().create(true).append(true).open(path))
+            .and_then(|mut file| writeln!(file, "{}", text));
+        if let Err(e) = result {
+            self.system_message(&format!("無法寫入檔案 {}: {}", path.display(), e));
+        }
+    }
+
+    /// 以文字描述 `apply_script_context` 會執行的動作，不實際執行
+    pub fn preview_script_context(&self, context: &MudContext) -> Vec<String> {
+        let mut lines = Vec::new();
+        for cmd in &context.commands {
+            lines.push(format!("送出: {}", cmd));
+        }
+        if context.gag {
+            lines.push("隱藏目前這一行".to_string());
+        }
+        for echo in &context.echos {
+            lines.push(format!("回顯: {}", echo));
+        }
+        for (win_id, text) in &context.window_outputs {
+            lines.push(format!("輸出到視窗 {}: {}", win_id, text));
+        }
+        for (text, severity) in &context.alerts {
+            lines.push(format!("重要事件 [{}]: {}", severity.name(), text));
+        }
+        for (delay_ms, code, _) in &context.timers {
+            lines.push(format!("{:.1} 秒後執行: {}", *delay_ms as f64 / 1000.0, code));
+        }
+        for log_msg in &context.log_messages {
+            lines.push(format!("寫入日誌: {}", log_msg));
+        }
+        let mut variables: Vec<_> = context.variables.iter().collect();
+        variables.sort();
+        for (key, value) in variables {
+            if self.engine.script_engine.get_variable(key).as_deref() != Some(value.as_str()) {
+                lines.push(format!("設定變數 {} = {}", key, value));
+            }
+        }
+        for (name, enabled) in &context.trigger_updates {
+            lines.push(format!("{}觸發器 {}", if *enabled { "啟用" } else { "停用" }, name));
+        }
+        for (pattern, _) in &context.temp_triggers {
+            lines.push(format!("註冊一次性觸發器 {}", pattern));
+        }
+        if context.clear_highlights {
+            lines.push("清除所有高亮".to_string());
+        }
+        for (pattern, color) in &context.highlights {
+            lines.push(format!("高亮 {} ({})", pattern, color.to_hex()));
+        }
+        if context.disconnect {
+            lines.push("斷線".to_string());
+        }
+        match &context.log_control {
+            Some(mudcore::script::LogControl::Start(path)) => lines.push(format!("開始記錄日誌至 {}", path)),
+            Some(mudcore::script::LogControl::Stop) => lines.push("停止記錄日誌".to_string()),
+            None => {}
+        }
+        if let Some(encoding) = context.encoding {
+            lines.push(format!("切換編碼為 {}", encoding.name()));
+        }
+        for note in &context.notes {
+            lines.push(format!("附加筆記: {}", note));
+        }
+        lines
     }
 
-    /// 處理接收到的文字與觸發器
     /// 處理接收到的文字與觸發器
     pub fn handle_text(&mut self, text: &str, is_echo: bool) -> bool {
+        if text.contains('\n') {
+            let lines: Vec<&str> = text.split('\n').collect();
+            return self.handle_lines(&lines, is_echo);
+        }
         self.handle_text_with_widths(text, is_echo, None)
     }
 
+    /// 一次處理多行文字（如重播日誌或大量輸出）
+    ///
+    /// 結果與逐行呼叫 `handle_text` 相同，但整批共用單字字典的時間戳，
+    /// 字典大小的清理也只在最後做一次。
+    pub fn handle_lines(&mut self, lines: &[&str], is_echo: bool) -> bool {
+        self.handle_lines_with_widths(lines, is_echo, None)
+    }
+
+    /// 帶有位元組寬度的多行處理；`byte_widths` 為各行寬度依序串接（每行後多一個換行符的寬度）
+    fn handle_lines_with_widths(&mut self, lines: &[&str], is_echo: bool, byte_widths: Option<&[u8]>) -> bool {
+        let outermost = self.begin_word_batch();
+        let mut result = true;
+        let mut current_pos = 0;
+
+        for line in lines {
+            // 計算該行的位元組寬度切片
+            let line_widths = byte_widths.map(|widths| {
+                let start = current_pos;
+                let end = (start + line.chars().count()).min(widths.len());
+                // 下一行的起始位置需跳過這行的字元數 + 1 (換行符)
+                current_pos += line.chars().count() + 1;
+                widths.get(start..end).unwrap_or(&[])
+            });
+
+            result &= self.handle_text_with_widths(line, is_echo, line_widths);
+        }
+        self.end_word_batch(outermost);
+        result
+    }
+
+    /// 判斷是否為提示符行：伺服器以 GA/EOR 標示，或符合 Profile 的提示符正則
+    fn is_prompt(&self, clean_text: &str) -> bool {
+        self.at_prompt_end || self.prompt_regex.as_ref().is_some_and(|re| re.is_match(clean_text))
+    }
+
+    /// 開始批次更新單字字典；回傳是否為最外層批次
+    fn begin_word_batch(&mut self) -> bool {
+        let outermost = self.word_batch_time.is_none();
+        if outermost {
+            self.word_batch_time = Some(Instant::now());
+        }
+        outermost
+    }
+
+    /// 結束批次更新（只有最外層會清理字典）
+    fn end_word_batch(&mut self, outermost: bool) {
+        if outermost {
+            self.word_batch_time = None;
+            self.prune_screen_words();
+        }
+    }
+
+    /// 限制單字字典大小
+    fn prune_screen_words(&mut self) {
+        if self.screen_words.len() > 1000 {
+            let cutoff = Instant::now() - Duration::from_secs(300); // 5 分鐘前
+            self.screen_words.retain(|_, m| m.last_seen > cutoff);
+        }
+    }
+
     /// 帶有位元組寬度的文字處理
     pub fn handle_text_with_widths(&mut self, text: &str, is_echo: bool, byte_widths: Option<&[u8]>) -> bool {
         // 如果文字包含換行符，則逐行處理
         if text.contains('\n') {
-            let mut result = true;
-            let mut current_pos = 0;
-            
             let lines: Vec<&str> = text.split('\n').collect();
-            for line in lines {
-                // 計算該行的位元組寬度切片
-                let line_widths = if let Some(widths) = byte_widths {
-                    let char_count = line.chars().count();
-                    let start = current_pos;
-                    let end = (start + char_count).min(widths.len());
-                    
-                    // 下一行的起始位置需跳過這行的字元數 + 1 (換行符)
-                    current_pos += char_count + 1;
-                    
-                    if start < widths.len() {
-                        Some(&widths[start..end])
-                    } else {
-                        Some(&[] as &[u8])
-                    }
-                } else {
-                    None
-                };
-
-                result &= self.handle_text_with_widths(line, is_echo, line_widths);
-            }
-            return result;
+            return self.handle_lines_with_widths(&lines, is_echo, byte_widths);
         }
 
 
 
         let mut gagged = false;
         let mut targets = vec!["main".to_string()];
+        let mut actions = LineActions::default();
 
         if !is_echo {
             // 提取單字用於自動補齊與狀態判斷
@@ -797,73 +1561,60 @@ impl Session {
                 text.to_string()
             };
 
-            // 0. 呼叫全域鉤子 (Global Hook)
-            // 這允許 Lua 腳本直接處理每一行伺服器訊息，無需透過正則表達式觸發器
-            match self.script_engine.invoke_hook("on_server_message", text, &clean_text) {
-                Ok(Some(context)) => {
-                    if context.gag {
-                        gagged = true;
-                    }
-                    self.apply_script_context(context);
-                },
-                Ok(None) => {}, // Hook not defined
-                Err(e) => {
-                    tracing::error!("on_server_message hook error: {}", e);
-                    self.window_manager.send_to_main(format!("{{r[System] Hook Error: {}{{x}}", e));
+            // 隱藏清單、全域鉤子 (on_server_message) 與觸發器（提示符行只觸發允許的範圍）
+            // 符合隱藏清單的行不經過鉤子與觸發器，也不路由到任何視窗，只寫入日誌
+            let is_prompt = self.is_prompt(&clean_text);
+            let Some(line_actions) = self.engine.process_line(text, is_prompt) else {
+                let _ = self.logger.log(text);
+                return true;
+            };
+            actions = line_actions;
+
+            if self.chat_regex.as_ref().is_some_and(|re| re.is_match(&clean_text)) {
+                self.new_chat.push(clean_text.trim_end_matches('\r').to_string());
+            }
+
+            if let Some(context) = actions.hook.take() {
+                self.apply_script_context(context);
+            }
+            if let Some(e) = actions.hook_error.take() {
+                tracing::error!("on_server_message hook error: {}", e);
+                self.window_manager.send_to_main(format!("{{r[System] Hook Error: {}{{x}}", e));
+            }
+            if self.trigger_debug {
+                self.push_trigger_debug(describe_trigger_matches("輸出", &actions.matches));
+            }
+
+            // 支援以擷取群組命名的視窗（如 chat_$1），不存在時自動建立
+            for win_id in &actions.windows {
+                self.window_manager.ensure_window(win_id);
+                if !targets.contains(win_id) {
+                    targets.push(win_id.clone());
                 }
             }
+            gagged |= actions.gag;
 
-            // 處理觸發器
-            let triggers = self.trigger_manager.process(text);
-            
-            // 暫存要執行的動作，避免借用衝突
-            let mut pending_scripts = Vec::new();
-            let mut pending_commands = Vec::new();
-            
-            // 執行觸發器動作
-            for (trigger, m) in triggers {
-                // Gag 檢查
-                // if trigger.gag {
-                //     gagged = true;
-                // }
-
-                // 執行動作
-                for action in &trigger.actions {
-                    match action {
-                        TriggerAction::SendCommand(cmd) => {
-                            if let Some(_tx) = &self.command_tx {
-                                let mut expanded = cmd.clone();
-                                for (i, cap) in m.captures.iter().enumerate() {
-                                    expanded = expanded.replace(&format!("${}", i + 1), cap);
-                                }
-                                pending_commands.push(expanded);
-                            }
-                        }
-                        TriggerAction::ExecuteScript(code) => {
-                            pending_scripts.push((code.clone(), m.captures.clone()));
-                        }
-                        TriggerAction::RouteToWindow(win_id) => {
-                            if !targets.contains(win_id) {
-                                targets.push(win_id.clone());
-                            }
-                        }
-                        TriggerAction::Gag => {
-                            gagged = true;
-                        }
-                        _ => {}
-                    }
-                }
+            // 附加筆記與檔案
+            for note in &actions.notes {
+                self.append_note(note);
+            }
+            for (path, text) in &actions.file_appends {
+                self.append_to_file(path, text);
             }
 
-            // 執行收集到的指令
-            for cmd in pending_commands {
-                // 使用 handle_user_input 處理觸發器指令，以支援分號拆分與別名
-                self.handle_user_input(&cmd);
+            // 執行收集到的指令（經 handle_user_input 以支援分號拆分與別名）
+            if self.command_tx.is_some() {
+                for cmd in &actions.commands {
+                    self.handle_user_input(cmd);
+                }
+            }
+            for cmd in &actions.raw_commands {
+                self.send_raw(cmd);
             }
 
             // 執行收集到的腳本
-            for (code, captures) in pending_scripts {
-                if let Ok(context) = self.script_engine.execute_inline(&code, text, &captures, false) {
+            for (code, captures) in &actions.scripts {
+                if let Ok(context) = self.engine.script_engine.execute_inline(code, text, captures, false) {
                     if context.gag {
                         gagged = true;
                     }
@@ -872,6 +1623,14 @@ impl Session {
             }
         }
 
+        // 登入前的訊息另存到 banner 視窗
+        if self.capture_banner && self.pre_login && !is_echo {
+            if self.window_manager.get(BANNER_WINDOW_ID).is_none() {
+                self.window_manager.add_window(SubWindow::new(BANNER_WINDOW_ID, "登入畫面"));
+            }
+            targets.push(BANNER_WINDOW_ID.to_string());
+        }
+
         // 如果被 Gag，則從主要輸出目標中移除 "main"
         if gagged {
             targets.retain(|t| t != "main");
@@ -904,16 +1663,12 @@ impl Session {
             }
         };
 
-        let clean_lower = clean_text.to_lowercase();
-        // 優化提示字元偵測：不分大小寫
-        // 1. 標準 Prompt: (hp.../...)
-        // 2. 純數值 Prompt: (123/123 456/456 ...)
-        let is_prompt = clean_lower.starts_with('(') && clean_lower.contains('/') && clean_lower.contains(')');
+        let is_prompt = self.is_prompt(&clean_text);
 
         // 如果是房間敘述，且非出口行、非 Prompt、非 Echo，則進行標點轉換
         let is_exit_line = clean_text.contains("[出口:");
         
-        let final_text = text.to_string();
+        let mut final_text = text.to_string();
         let mut final_widths = Vec::new();
 
         // 預查原始寬度
@@ -929,6 +1684,15 @@ impl Session {
             }
         }
 
+        // 套用觸發器的高亮與替換
+        (final_text, final_widths) = actions.render(&final_text, &final_widths);
+        let log_text = if self.log_original_text { text.to_string() } else { final_text.clone() };
+
+        // 套用腳本註冊的高亮
+        if !is_echo && !self.highlights.is_empty() {
+            (final_text, final_widths) = apply_highlights(&final_text, &final_widths, &self.highlights);
+        }
+
         // 判定是否為指令回顯，並提取核心內容用於狀態判斷
         let (is_command_echo, detection_text) = if is_echo && clean_text.trim().starts_with('>') {
             (true, clean_text.trim().trim_start_matches('>').trim())
@@ -942,11 +1706,26 @@ impl Session {
 
         // 路由到視窗
         for target_id in targets {
+            if self.routing_prompt && target_id == "main" {
+                self.pinned_prompt = Some(final_text.clone());
+                continue;
+            }
+            if target_id == "main" {
+                let blank = clean_text.trim().is_empty();
+                let collapse = self.collapse_blank_lines && !self.window_manager.main_window().ascii_art;
+                if blank && collapse && self.last_main_blank {
+                    continue;
+                }
+                self.last_main_blank = blank;
+            }
             let msg = WindowMessage {
                 content: final_text.clone(),
-                preserve_ansi: !is_echo,
+                // 回顯一般不含顏色；上色回顯需保留 ANSI
+                preserve_ansi: !is_echo || text.contains('\x1b'),
                 byte_widths: final_widths.clone(),
                 repeat_count: 1,
+                start_state: AnsiState::default(),
+                severity: Severity::Info,
             };
             
             self.window_manager.route_message_with_widths(
@@ -1007,13 +1786,13 @@ impl Session {
 
         // 如果符合任一條件，提取單字
         if has_mob_brackets || self.in_room_description || is_exit_line || is_slash_line {
-            let now = Instant::now();
+            let now = self.word_batch_time.unwrap_or_else(Instant::now);
             
             // 1. 提取括號內的內容 (優先級高)
             for cap in MOB_BRACKET_RE.captures_iter(&clean_text) {
                 let content = &cap[1];
-                for word in content.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '-') {
-                    if word.len() >= 2 && word.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                for word in content.split(|c: char| !is_word_char(c)) {
+                    if word.len() >= 2 && word.chars().all(is_word_char) {
                         self.screen_words.insert(word.to_string(), WordMetadata {
                             last_seen: now,
                             is_mob: true,
@@ -1025,8 +1804,8 @@ impl Session {
             // 2. 提取斜線後的內容 (針對 "中文/ID" 格式)
             if let Some(slash_idx) = clean_text.rfind('/') {
                 let after_slash = &clean_text[slash_idx+1..];
-                for word in after_slash.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '-') {
-                    if word.len() >= 2 && word.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                for word in after_slash.split(|c: char| !is_word_char(c)) {
+                    if word.len() >= 2 && word.chars().all(is_word_char) {
                         self.screen_words.insert(word.to_string(), WordMetadata {
                             last_seen: now,
                             is_mob: true, // 假設斜線後通常是 ID
@@ -1036,8 +1815,8 @@ impl Session {
             }
 
             // 3. 提取整行所有英文單字 (通用兜底)
-            for word in clean_text.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '-') {
-                if word.len() >= 2 && word.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            for word in clean_text.split(|c: char| !is_word_char(c)) {
+                if word.len() >= 2 && word.chars().all(is_word_char) {
                     let entry = self.screen_words.entry(word.to_string()).or_insert(WordMetadata {
                         last_seen: now,
                         is_mob: false,
@@ -1047,136 +1826,61 @@ impl Session {
             }
         }
 
-        // 限制字典大小
-        if self.screen_words.len() > 1000 {
-            let cutoff = Instant::now() - Duration::from_secs(300); // 5 分鐘前
-            self.screen_words.retain(|_, m| m.last_seen > cutoff);
+        // 限制字典大小（批次處理時延到最後）
+        if self.word_batch_time.is_none() {
+            self.prune_screen_words();
         }
 
         // 日誌記錄
-        let _ = self.logger.log(text);
-
-        self.last_active = Instant::now();
+        let _ = self.logger.log(&log_text);
 
         true
     }
 
     /// 處理使用者輸入的指令 (包含特殊指令如 #loop, #delay, /lua)
     pub fn handle_user_input(&mut self, input: &str) {
+        // 先顯示暫存的提示符，避免指令回顯出現在提示符之前
+        self.flush_partial_line();
+        self.pre_login = false;
+        self.last_active = Instant::now();
         self.handle_user_input_with_depth(input, 0);
     }
 
     fn handle_user_input_with_depth(&mut self, input: &str, depth: usize) {
-        let input = input.trim();
-        
-        // 防止無限遞迴
-        if depth > 50 {
-            self.system_message(&format!("Error: Command recursion limit reached for '{}'", input));
-            return;
-        }
-
-        // 1. 分號拆分 (Semicolon Splitting)
-        if input.contains(';') {
-            for part in input.split(';') {
-                self.handle_user_input_with_depth(part, depth + 1);
-            }
-            return;
-        }
-
-        // 2. 變數展開 (Variable Expansion)
-        // 移至最前，確保 Trigger 和 Alias 都能看到展開後的變數
-        let input = self.script_engine.expand_variables(input);
-
-        // 3. 觸發器處理 (Local Echo Triggers)
-        // 移至 Alias 之前，以支援 "Input Trigger" (針對玩家輸入的原始指令觸發)
-        // 若 Alias 發生展開，遞迴呼叫會再次觸發針對展開後指令的 Trigger，達成多層觸發效果。
-        tracing::info!("Checking input triggers for: '{}'", input);
-        let matches = self.trigger_manager.process(&input);
-        
-        let mut pending_commands = Vec::new();
-        let mut pending_scripts = Vec::new();
-
-        for (trigger, m) in matches {
-            tracing::info!("Match trigger: {}", trigger.name);
-            for action in &trigger.actions {
-                match action {
-                    mudcore::TriggerAction::SendCommand(cmd) => {
-                        let mut expanded = cmd.clone();
-                        for (i, cap) in m.captures.iter().enumerate() {
-                            expanded = expanded.replace(&format!("${}", i + 1), cap);
-                        }
-                        pending_commands.push(expanded);
+        // 分號拆分、變數展開、輸入觸發器、別名與路徑由引擎展開，一次一層
+        for step in self.engine.expand_input(input, depth) {
+            match step {
+                InputStep::Expand { text, from_alias } => {
+                    if from_alias {
+                        self.alias_depth += 1;
                     }
-                    mudcore::TriggerAction::ExecuteScript(code) => {
-                        pending_scripts.push((code.clone(), m.captures.clone()));
+                    self.handle_user_input_with_depth(&text, depth + 1);
+                    if from_alias {
+                        self.alias_depth -= 1;
                     }
-                    _ => {}
                 }
-            }
-        }
-        
-        for (script, captures) in pending_scripts {
-            match self.script_engine.execute_inline(&script, &input, &captures, false) {
-                Ok(ctx) => self.apply_script_context(ctx),
-                Err(e) => {
-                    tracing::error!("Trigger script error: {}", e);
-                    self.system_message(&format!("Trigger Script Error: {}", e));
-                }
-            }
-        }
-        
-        for cmd in pending_commands {
-            self.handle_user_input_with_depth(&cmd, depth + 1);
-        }
-
-        // 4. Alias 處理
-        use mudcore::alias::AliasMatchResult;
-        match self.alias_manager.process_match(&input) {
-            AliasMatchResult::Replacement(expanded) => {
-                self.handle_user_input_with_depth(&expanded, depth + 1);
-                return;
-            }
-            AliasMatchResult::Script(code) => {
-                match self.script_engine.execute_inline(&code, &input, &[], false) {
-                    Ok(ctx) => self.apply_script_context(ctx),
-                    Err(e) => {
-                        tracing::error!("Alias script error: {}", e);
-                        self.system_message(&format!("Alias Script Error: {}", e));
+                InputStep::Command(text) => self.dispatch_command(text, depth),
+                InputStep::Raw(cmd) => self.send_raw(&cmd),
+                InputStep::Script(ctx) => self.apply_script_context(*ctx),
+                InputStep::Error(message) => self.system_message(&message),
+                InputStep::Matched(matches) => {
+                    if self.trigger_debug {
+                        self.push_trigger_debug(describe_trigger_matches("輸入", &matches));
                     }
                 }
-                return;
             }
-            AliasMatchResult::None => {}
-        }
-
-        // 5. Path 與 Speedwalk 解析
-        // 這邊處理兩件事：
-        // a. Path Expansion: 如果輸入符合已定義的 path name，展開為 path value
-        // b. Speedwalk Parsing: 如果輸入 (或展開後的內容) 對應 speedwalk 格式，則分解指令
-        
-        let path_value = if let Some(path) = self.path_manager.get(&input) {
-            path.value.clone()
-        } else {
-            input.to_string()
-        };
-
-        // 嘗試解析為 Speedwalk
-        // 需引入 mudcore::parse_speedwalk
-        if let Some(commands) = mudcore::parse_speedwalk(&path_value) {
-             for cmd in commands {
-                 self.handle_user_input_with_depth(&cmd, depth + 1);
-             }
-             return;
-        }
-
-        // 如果發生了 path expansion 但不符合 speedwalk 格式 (例如純指令替換)，也需要遞迴處理
-        if path_value != input {
-             self.handle_user_input_with_depth(&path_value, depth + 1);
-             return;
         }
+    }
 
-        // 6. 處理特殊指令 (Client-Side Commands)
-        if input.starts_with("#") || input.starts_with("/") {
+    /// 處理展開完成的指令：客戶端指令在此執行，其餘經縮寫與送出鉤子後送出
+    fn dispatch_command(&mut self, input: String, depth: usize) {
+        // 處理特殊指令 (Client-Side Commands)
+        let is_client_command = input.starts_with("#") || input.starts_with("/");
+        if is_client_command {
+            // 上色回顯時客戶端指令也一併回顯，方便在捲動記錄中區分
+            if self.colored_echo && self.should_local_echo() {
+                self.echo_input(&input);
+            }
             let parts: Vec<&str> = input.split_whitespace().collect();
             let cmd = parts[0];
 
@@ -1203,6 +1907,7 @@ impl Session {
                             self.active_timers.push(ActiveTimer {
                                 expires_at: Instant::now() + std::time::Duration::from_millis(ms),
                                 lua_code,
+                                globals: None,
                             });
                             self.system_message(&format!("Delayed execution of '{}' by {}ms", sub_cmd, ms));
                             return;
@@ -1214,7 +1919,7 @@ impl Session {
                 "/lua" => {
                     if parts.len() >= 2 {
                         let code = parts[1..].join(" ");
-                        match self.script_engine.execute_inline(&code, "CLI", &[], true) {
+                        match self.engine.script_engine.execute_inline(&code, "CLI", &[], true) {
                             Ok(ctx) => self.apply_script_context(ctx),
                             Err(e) => self.system_message(&format!("Lua Error: {}", e)),
                         }
@@ -1223,12 +1928,32 @@ impl Session {
                     self.system_message("Usage: /lua <code>");
                     return;
                 }
+                "#dryrun" => {
+                    if parts.len() >= 2 {
+                        let code = parts[1..].join(" ");
+                        match self.engine.script_engine.dry_run(&code, "CLI", &[]) {
+                            Ok(ctx) => {
+                                let preview = self.preview_script_context(&ctx);
+                                if preview.is_empty() {
+                                    self.system_message("Dry run: no actions");
+                                }
+                                for line in preview {
+                                    self.system_message(&format!("Dry run: {}", line));
+                                }
+                            }
+                            Err(e) => self.system_message(&format!("Lua Error: {}", e)),
+                        }
+                        return;
+                    }
+                    self.system_message("Usage: #dryrun <code>");
+                    return;
+                }
                 "#var" => {
                     if parts.len() >= 3 {
                         let key = parts[1];
                         let value = parts[2..].join(" ");
                         let code = format!("mud.variables['{}'] = \"{}\"", key, value.replace("\"", "\\\""));
-                        if let Err(e) = self.script_engine.execute_inline(&code, "CLI", &[], false) {
+                        if let Err(e) = self.engine.script_engine.execute_inline(&code, "CLI", &[], false) {
                             self.system_message(&format!("Failed to set variable: {}", e));
                         } else {
                             self.system_message(&format!("Variable '{}' set to '{}'", key, value));
@@ -1238,11 +1963,53 @@ impl Session {
                     self.system_message("Usage: #var <key> <value>");
                     return;
                 }
+                "#testregex" => {
+                    let args = input.trim_start().strip_prefix("#testregex").unwrap_or_default();
+                    let Some((pattern, text)) = parse_testregex_args(args) else {
+                        self.system_message("Usage: #testregex <pattern> => <text>");
+                        return;
+                    };
+                    match regex::Regex::new(pattern) {
+                        Ok(re) => match re.captures(text) {
+                            Some(caps) => {
+                                self.system_message(&format!("Match: '{}'", &caps[0]));
+                                for (i, cap) in caps.iter().enumerate().skip(1) {
+                                    let value = cap.map(|m| m.as_str()).unwrap_or("(none)");
+                                    self.system_message(&format!("  ${} = '{}'", i, value));
+                                }
+                            }
+                            None => self.system_message("No match."),
+                        },
+                        Err(e) => self.system_message(&format!("Invalid regex: {}", e)),
+                    }
+                    return;
+                }
+                "#reconnect" => {
+                    match parts.get(1).copied() {
+                        Some("on") => {
+                            self.set_auto_reconnect(true);
+                            self.system_message("Auto-reconnect enabled.");
+                        }
+                        Some("off") => {
+                            self.set_auto_reconnect(false);
+                            self.system_message("Auto-reconnect disabled.");
+                        }
+                        _ => self.system_message("Usage: #reconnect <on|off>"),
+                    }
+                    return;
+                }
+                "#encoding" => {
+                    match parts.get(1).and_then(|name| Encoding::from_name(name)) {
+                        Some(encoding) => self.set_encoding(encoding),
+                        None => self.system_message("Usage: #encoding <big5|gbk|gb2312|utf8>"),
+                    }
+                    return;
+                }
                 "#unvar" => {
                     if parts.len() >= 2 {
                         let key = parts[1];
                         let code = format!("mud.variables['{}'] = nil", key);
-                        if let Err(e) = self.script_engine.execute_inline(&code, "CLI", &[], false) {
+                        if let Err(e) = self.engine.script_engine.execute_inline(&code, "CLI", &[], false) {
                             self.system_message(&format!("Failed to unset variable: {}", e));
                         } else {
                             self.system_message(&format!("Variable '{}' unset", key));
@@ -1252,9 +2019,26 @@ impl Session {
                     self.system_message("Usage: #unvar <key>");
                     return;
                 }
+                "#go" => {
+                    let Some(target) = parse_go_target(&input) else {
+                        self.system_message("Usage: #go <room name>");
+                        return;
+                    };
+                    match self.route_to(target) {
+                        Some(route) => {
+                            self.system_message(&format!("Walking to '{}': {}", target, route));
+                            self.handle_user_input_with_depth(&route, depth + 1);
+                        }
+                        None => self.system_message(&format!(
+                            "No known route to '{}'. Walk there once so the mapper learns the way, or save a path with #path save {}.",
+                            target, target
+                        )),
+                    }
+                    return;
+                }
                 "#path" => {
                     if parts.len() < 2 {
-                        self.system_message("Usage: #path <start|stop|loop|clear|undo|back|show|save>");
+                        self.system_message("Usage: #path <start|stop|loop|clear|undo|back|show|save|preview>");
                         return;
                     }
                     match parts[1] {
@@ -1317,11 +2101,33 @@ impl Session {
                                     self.system_message("Cannot save empty path.");
                                 } else {
                                     let path = mudcore::Path::new(name, &path_str);
-                                    self.path_manager.add(path);
+                                    self.engine.path_manager.add(path);
                                     self.system_message(&format!("Path saved as '{}'", name));
                                 }
                             }
                         }
+                        "preview" => {
+                            if parts.len() < 3 {
+                                self.system_message("Usage: #path preview <speedwalk|path name>");
+                            } else {
+                                // 只展開不送出，可預覽已儲存的路徑
+                                let arg = parts[2..].join(" ");
+                                let value = self
+                                    .engine
+                                    .path_manager
+                                    .get(&arg)
+                                    .map(|p| p.value.clone())
+                                    .unwrap_or(arg);
+                                match mudcore::try_parse_speedwalk(&value) {
+                                    Ok(commands) => self.system_message(&format!(
+                                        "Speedwalk preview ({} steps): {}",
+                                        commands.len(),
+                                        commands.join(", ")
+                                    )),
+                                    Err(e) => self.system_message(&format!("Invalid speedwalk '{}': {}", value, e)),
+                                }
+                            }
+                        }
                         "loop" => {
                             if parts.len() < 3 {
                                 self.system_message(&format!("Loop detection is currently: {}", if self.path_recorder.enable_loop_detection { "ON" } else { "OFF" }));
@@ -1341,7 +2147,7 @@ impl Session {
                             }
                         }
                         _ => {
-                             self.system_message("Unknown path command. Usage: #path <start|stop|loop|clear|undo|back|show|save>");
+                             self.system_message("Unknown path command. Usage: #path <start|stop|loop|clear|undo|back|show|save|preview>");
                         }
                     }
                     return;
@@ -1352,175 +2158,697 @@ impl Session {
             }
         }
 
+        // 縮寫展開與送出鉤子（mud.on_send）：可改寫或取消指令；鉤子出錯時照原樣送出
+        let input = match self.engine.prepare_command(input) {
+            PreparedCommand::Send { command, context } => {
+                if let Some(context) = context {
+                    self.apply_script_context(context);
+                }
+                command
+            }
+            PreparedCommand::Cancelled { command, context } => {
+                self.apply_script_context(context);
+                self.echo_input(&format!("{} [cancelled]", command));
+                return;
+            }
+            PreparedCommand::HookError { command, error } => {
+                self.system_message(&format!("on_send 鉤子錯誤: {}", error));
+                command
+            }
+        };
+
+        // 安全模式：不論回顯設定都顯示指令並標示未送出，也不計入防呆與路徑記錄
+        if self.send_blocked {
+            self.echo_input(&format!("{} [blocked]", input));
+            return;
+        }
+
         // 標準指令處理 (本地回顯 + 發送)
         // 改進回顯格式：緊隨 Prompt 且使用明顯前綴，並透過 handle_text 觸發狀態機
-        self.handle_text(&format!("> {}\n", input), true);
+        if self.should_local_echo() && !(self.colored_echo && is_client_command) {
+            self.echo_input(&input);
+        }
 
         // Clone tx to avoid borrow check issues when calling system_message
         if let Some(tx) = self.command_tx.clone() {
             // === 防呆機制：檢查重複指令 ===
-            let current_cmd = input.to_string();
-            
-            if let Some(last) = &self.last_sent_command {
-                if last == &current_cmd {
-                    self.repeat_command_count += 1;
-                } else {
-                    self.repeat_command_count = 1;
-                    self.last_sent_command = Some(current_cmd.clone());
-                }
-            } else {
-                self.repeat_command_count = 1;
-                self.last_sent_command = Some(current_cmd.clone());
-            }
-
-            // 如果重複次數達到 20，自動插入 save
-            if self.repeat_command_count >= 20 {
-                self.system_message("Anti-spam: Repeated command limit reached (20). Auto-inserting 'save'.");
-                let _ = tx.blocking_send(crate::session::Command::Send("save".to_string()));
-                // 重置計數器，讓使用者可以繼續輸入（或根據需求重置為 1）
-                self.repeat_command_count = 0;
+            if let Some(inject) = self.check_repeat_command(&input) {
+                let _ = tx.blocking_send(crate::session::Command::Send(inject));
             }
             
             // 記錄路徑 (在送出前記錄)
             if self.path_recorder.is_recording {
                  self.path_recorder.record(&input);
             }
+            if is_movement_command(input.trim()) {
+                self.pending_move = Some(input.trim().to_string());
+            }
 
             let _ = tx.blocking_send(crate::session::Command::Send(input.to_string()));
         }
     }
 
-    /// 顯示系統訊息
-    fn system_message(&mut self, msg: &str) {
-        self.window_manager.route_message("main", mudcore::window::WindowMessage {
-            content: format!("\n[System] {}\n", msg),
-            preserve_ansi: true,
-            byte_widths: Vec::new(),
-            repeat_count: 1,
-        });
+    /// 原樣送出指令：不經分號拆分、變數展開、別名與觸發器，仍遵守安全模式與本地回顯
+    fn send_raw(&mut self, cmd: &str) {
+        if self.send_blocked {
+            self.echo_input(&format!("{} [blocked]", cmd));
+            return;
+        }
+        if self.should_local_echo() {
+            self.echo_input(cmd);
+        }
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.blocking_send(Command::Send(cmd.to_string()));
+        }
     }
 
+    /// 累計重複指令次數，達到門檻時顯示警告並回傳需自動插入的指令
+    fn check_repeat_command(&mut self, input: &str) -> Option<String> {
+        let threshold = self.anti_spam.threshold;
+        if threshold == 0 {
+            return None;
+        }
+        if self.anti_spam.exempt_movement && (input.trim().is_empty() || is_movement_command(input.trim())) {
+            return None;
+        }
 
+        if self.last_sent_command.as_deref() == Some(input) {
+            self.repeat_command_count += 1;
+        } else {
+            self.repeat_command_count = 1;
+            self.last_sent_command = Some(input.to_string());
+        }
 
+        if self.repeat_command_count < threshold {
+            return None;
+        }
+        // 重置計數器，讓使用者可以繼續輸入
+        self.repeat_command_count = 0;
 
-    /// 取得分頁標題
-    pub fn tab_title(&self) -> String {
-        let status_icon = match &self.status {
-            ConnectionStatus::Disconnected => "⚪",
-            ConnectionStatus::Connecting => "🔄",
-            ConnectionStatus::Connected(_) => "🟢",
-            ConnectionStatus::Reconnecting => "🟡",
-        };
-        format!("{} {}", status_icon, self.display_name)
+        let command = self.anti_spam.command.trim().to_string();
+        if self.anti_spam.action == AntiSpamAction::WarnOnly || command.is_empty() {
+            self.system_message(&format!("Anti-spam: Repeated command limit reached ({}).", threshold));
+            None
+        } else {
+            self.system_message(&format!(
+                "Anti-spam: Repeated command limit reached ({}). Auto-inserting '{}'.",
+                threshold, command
+            ));
+            Some(command)
+        }
     }
 
-    /// 是否已連線
-    #[allow(dead_code)]
-    pub fn is_connected(&self) -> bool {
-        matches!(self.status, ConnectionStatus::Connected(_))
+    /// 收到網路執行緒的編碼偵測結果
+    ///
+    /// 自動偵測時直接套用（信心度偏低時警告）；否則僅在信心度足夠時提示使用者，
+    /// 且使用者指定 GBK 系列時不提示。純 ASCII（信心度 0）時維持目前編碼。
+    pub fn on_encoding_detected(&mut self, encoding: Encoding, confidence: f32) {
+        if encoding == self.encoding || confidence <= 0.0 {
+            return;
+        }
+        if self.auto_detect_encoding {
+            self.set_encoding(encoding);
+            if confidence < LOW_ENCODING_CONFIDENCE {
+                self.system_message(&format!(
+                    "⚠️ 編碼偵測信心度偏低（{:.0}%），若出現亂碼請用 #encoding 切換",
+                    confidence * 100.0
+                ));
+            }
+        } else if confidence >= LOW_ENCODING_CONFIDENCE && matches!(self.encoding, Encoding::Big5 | Encoding::Utf8) {
+            self.suggest_encoding(encoding);
+        }
     }
 
-    /// 是否正在連線
-    #[allow(dead_code)]
-    pub fn is_connecting(&self) -> bool {
-        matches!(self.status, ConnectionStatus::Connecting | ConnectionStatus::Reconnecting)
+    /// 提示使用者切換編碼（僅提示一次）
+    pub fn suggest_encoding(&mut self, encoding: Encoding) {
+        if self.encoding_prompted {
+            return;
+        }
+        self.encoding_prompted = true;
+        self.encoding_suggestion = Some(encoding);
     }
-}
-
-// ============================================================================
-// SessionManager
-// ============================================================================
-
-/// Session 管理器
-///
-/// 管理所有活躍的 Session 並提供分頁切換功能
-pub struct SessionManager {
-    /// 所有活躍的 Session（依序）
-    sessions: Vec<Session>,
-    
-    /// 目前選中的分頁索引
-    active_index: usize,
-    
-    /// 全域別名（套用到所有 Session）
-    global_aliases: Vec<AliasConfig>,
-    
-    /// 全域觸發器（套用到所有 Session）
-    global_triggers: Vec<TriggerConfig>,
-}
 
-#[allow(dead_code)]
-impl SessionManager {
-    /// 建立新的 SessionManager
-    pub fn new() -> Self {
-        Self {
-            sessions: Vec::new(),
-            active_index: 0,
-            global_aliases: Vec::new(),
-            global_triggers: Vec::new(),
+    /// 記錄一筆輸入歷史（空白輸入忽略）
+    pub fn record_history(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
         }
+        self.push_history(entry);
+        self.new_history.push(entry.to_string());
     }
 
-    /// 設定全域別名/觸發器
-    pub fn set_global_config(
-        &mut self,
-        aliases: Vec<AliasConfig>,
-        triggers: Vec<TriggerConfig>,
-    ) {
-        self.global_aliases = aliases;
-        self.global_triggers = triggers;
+    /// 加入歷史，超過上限時捨棄最舊的
+    fn push_history(&mut self, entry: &str) {
+        self.input_history.push(entry.to_string());
+        if self.input_history.len() > MAX_INPUT_HISTORY {
+            self.input_history.drain(..self.input_history.len() - MAX_INPUT_HISTORY);
+        }
     }
 
-    /// 從 Profile 建立並新增 Session
-    pub fn create_session(&mut self, profile: &Profile) -> SessionId {
-        let mut session = Session::from_profile(profile);
-        session.merge_global_config(&self.global_aliases, &self.global_triggers);
-        
-        let id = session.id;
-        self.sessions.push(session);
-        
-        // 自動切換到新分頁
-        self.active_index = self.sessions.len() - 1;
-        
-        id
+    /// 接受編碼建議並通知網路執行緒切換
+    pub fn accept_encoding_suggestion(&mut self) {
+        let Some(encoding) = self.encoding_suggestion.take() else {
+            return;
+        };
+        self.set_encoding(encoding);
     }
 
-    /// 關閉 Session
-    pub fn close_session(&mut self, id: SessionId) -> bool {
-        if let Some(pos) = self.sessions.iter().position(|s| s.id == id) {
-            self.sessions.remove(pos);
-            
-            // 調整 active_index
-            if self.active_index >= self.sessions.len() && !self.sessions.is_empty() {
-                self.active_index = self.sessions.len() - 1;
-            }
-            return true;
+    /// 通知網路執行緒切換文字編碼（不需重新連線）
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.blocking_send(Command::SetEncoding(encoding));
         }
-        false
-    }
-
-    /// 取得目前選中的 Session
-    pub fn active_session(&self) -> Option<&Session> {
-        self.sessions.get(self.active_index)
+        self.system_message(&format!("已切換編碼為 {}", encoding.name()));
     }
 
-    /// 取得目前選中的 Session（可變）
-    pub fn active_session_mut(&mut self) -> Option<&mut Session> {
-        self.sessions.get_mut(self.active_index)
+    /// 更新訊息區大小，改變時通知網路執行緒（伺服器同意 NAWS 時會回報）
+    pub fn report_window_size(&mut self, cols: u16, rows: u16) {
+        if self.window_size == Some((cols, rows)) {
+            return;
+        }
+        self.window_size = Some((cols, rows));
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.try_send(Command::WindowSize(cols, rows));
+        }
     }
 
-    /// 取得目前選中的 Session ID
-    pub fn active_id(&self) -> Option<SessionId> {
-        self.active_session().map(|s| s.id)
+    /// 忽略編碼建議
+    pub fn dismiss_encoding_suggestion(&mut self) {
+        self.encoding_suggestion = None;
     }
 
-    /// 依 ID 取得 Session
-    pub fn get(&self, id: SessionId) -> Option<&Session> {
-        self.sessions.iter().find(|s| s.id == id)
+    /// 將主視窗記錄（含 ANSI 碼）寫入檔案
+    pub fn save_scrollback(&self, path: &std::path::Path) -> Result<(), std::io::Error> {
+        let window = self.window_manager.main_window();
+        let mut buffer = MessageBuffer::new(window.capacity);
+        for message in window.messages() {
+            buffer.push(message.content.clone());
+        }
+        crate::config::ensure_parent_dir(path)?;
+        buffer.save_to_file(path)
     }
 
-    /// 依 ID 取得 Session（可變）
-    pub fn get_mut(&mut self, id: SessionId) -> Option<&mut Session> {
-        self.sessions.iter_mut().find(|s| s.id == id)
+    /// 從檔案載入主視窗記錄；檔案不存在時略過
+    fn restore_scrollback(&mut self, path: &std::path::Path) {
+        let capacity = self.window_manager.main_window().capacity;
+        let buffer = match MessageBuffer::load_from_file(path, capacity) {
+            Ok(buffer) if !buffer.is_empty() => buffer,
+            Ok(_) => return,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                tracing::warn!("無法載入主視窗記錄 ({}): {}", path.display(), e);
+                return;
+            }
+        };
+        for content in buffer.iter() {
+            self.window_manager.main_window_mut().push(WindowMessage::new(content.clone()));
+        }
+        self.system_message(&format!("── 以上為上次保存的 {} 則訊息 ──", buffer.len()));
+    }
+
+    /// 顯示系統訊息
+    fn system_message(&mut self, msg: &str) {
+        self.window_manager.route_message("main", mudcore::window::WindowMessage {
+            content: format!("\n[System] {}\n", msg),
+            preserve_ansi: true,
+            byte_widths: Vec::new(),
+            repeat_count: 1,
+            start_state: AnsiState::default(),
+            severity: Severity::Info,
+        });
+    }
+
+
+
+
+    /// 記錄連線事件
+    pub fn log_event(&mut self, event: impl Into<String>) {
+        self.event_log.push((Instant::now(), event.into()));
+        if self.event_log.len() > MAX_EVENT_LOG {
+            let excess = self.event_log.len() - MAX_EVENT_LOG;
+            self.event_log.drain(..excess);
+        }
+    }
+
+    /// 使用者主動斷線：已連線且設定了 `on_disconnect` 時先送出該指令
+    ///
+    /// 連線中斷、腳本要求斷線等情況不經過這裡，不會送出登出指令。
+    pub fn request_disconnect(&mut self) {
+        if self.command_tx.is_none() {
+            return;
+        }
+        let command = match self.on_disconnect.clone().filter(|_| self.is_connected()) {
+            Some(logout) => {
+                self.log_event(format!("送出登出指令: {}", logout));
+                Command::Logout(logout)
+            }
+            None => Command::Disconnect,
+        };
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.try_send(command);
+        }
+    }
+
+    /// 查詢前往目標房間的路線（speedwalk 或以 `;` 分隔的指令）
+    ///
+    /// 優先以自動地圖從目前房間計算最短路線，地圖找不到時再找同名的已儲存路徑（名稱不分大小寫）。
+    pub fn route_to(&self, target: &str) -> Option<String> {
+        let mapped = self
+            .current_room_id
+            .as_deref()
+            .and_then(|from| self.engine.room_graph.route(from, target))
+            .filter(|steps| !steps.is_empty())
+            .map(|steps| steps.join(";"));
+        mapped.or_else(|| {
+            self.engine.path_manager
+                .list()
+                .into_iter()
+                .find(|path| path.name.eq_ignore_ascii_case(target))
+                .map(|path| path.value.clone())
+        })
+    }
+
+    /// 處理伺服器送來的 GMCP 訊息
+    pub fn handle_gmcp(&mut self, message: &GmcpMessage) {
+        if let Some(room) = RoomInfo::from_gmcp(message) {
+            self.current_room = Some(room);
+        }
+        if let Some(update) = Vitals::from_gmcp(message) {
+            self.vitals.get_or_insert_with(Vitals::default).merge(update);
+        }
+    }
+
+    /// 開啟/關閉自動重連；關閉時取消等待中的重連
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        self.auto_reconnect = enabled;
+        if !enabled && matches!(self.status, ConnectionStatus::Reconnecting) {
+            self.reconnect_delay_until = None;
+            self.status = ConnectionStatus::Disconnected;
+            self.log_event("已取消自動重連");
+        }
+    }
+
+    /// 開始連線（或自動重連）時呼叫
+    pub fn begin_connecting(&mut self) {
+        if matches!(self.status, ConnectionStatus::Reconnecting) {
+            self.reconnect_attempts += 1;
+            let attempt = self.reconnect_attempts;
+            self.log_event(format!("重連嘗試 #{}", attempt));
+        } else {
+            let target = format!("連線中 {}:{}", self.host, self.port);
+            self.log_event(target);
+        }
+        self.status = ConnectionStatus::Connecting;
+    }
+
+    /// 回顯使用者輸入；開啟上色時依指令類型加上顏色
+    fn echo_input(&mut self, input: &str) {
+        if self.colored_echo {
+            let color = EchoKind::of(input, self.alias_depth > 0).color();
+            self.handle_text(&format!("{}> {}\x1b[0m\n", color, input), true);
+        } else {
+            self.handle_text(&format!("> {}\n", input), true);
+        }
+    }
+
+    /// 依本地回顯設定與伺服器回顯狀態決定是否回顯輸入
+    pub fn should_local_echo(&self) -> bool {
+        match self.local_echo {
+            LocalEchoMode::Auto => !self.server_echo,
+            LocalEchoMode::Always => true,
+            LocalEchoMode::Never => false,
+        }
+    }
+
+    /// 處理網路執行緒送來的一則訊息
+    pub fn handle_network_message(&mut self, message: NetworkMessage) {
+        match message {
+            NetworkMessage::Text(text, widths) => self.handle_server_text(&text, &widths),
+            NetworkMessage::PromptEnd => self.handle_prompt_end(),
+            NetworkMessage::Gmcp(message) => self.handle_gmcp(&message),
+            NetworkMessage::OptionStates(states) => self.telnet_options = states,
+            NetworkMessage::Bell => self.handle_bell(),
+            NetworkMessage::Status(update) => self.apply_status(update),
+        }
+    }
+
+    /// 伺服器送出 BEL：啟用提醒且已過冷卻時間時記下待 UI 處理的提醒
+    pub fn handle_bell(&mut self) {
+        if !self.bell_alert || self.last_bell.is_some_and(|at| at.elapsed() < BELL_COOLDOWN) {
+            return;
+        }
+        self.last_bell = Some(Instant::now());
+        self.bell_pending = true;
+    }
+
+    /// 取走待處理的提醒
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell_pending)
+    }
+
+    /// 取走連線成功的通知
+    pub fn take_connected(&mut self) -> bool {
+        std::mem::take(&mut self.connected_pending)
+    }
+
+    /// 處理伺服器文字；結尾沒有換行的部分由引擎暫存，待補齊後再以完整行處理
+    pub fn handle_server_text(&mut self, text: &str, widths: &[u8]) {
+        let lines = self.engine.push_text(text, widths);
+        if lines.is_empty() {
+            self.partial_since.get_or_insert_with(Instant::now);
+            return;
+        }
+        self.partial_since = None;
+
+        // 完整行處理完才放回剩餘部分，避免觸發器送指令時提前處理未完成的行
+        let rest = self.engine.take_partial_line();
+        let outermost = self.begin_word_batch();
+        for (line, line_widths) in &lines {
+            self.handle_text_with_widths(line, false, Some(line_widths));
+        }
+        self.end_word_batch(outermost);
+        if let Some((rest, rest_widths)) = rest {
+            self.engine.push_text(&rest, &rest_widths);
+            self.partial_since = Some(Instant::now());
+        }
+    }
+
+    /// 收到 GA/EOR：暫存的行即為提示符，啟用固定時只更新提示列而不進入捲動紀錄
+    pub fn handle_prompt_end(&mut self) {
+        self.routing_prompt = self.pin_prompt;
+        self.at_prompt_end = true;
+        self.flush_partial_line();
+        self.at_prompt_end = false;
+        self.routing_prompt = false;
+    }
+
+    /// 將暫存的不完整行視為完整一行處理（收到 GA/EOR、逾時或斷線時）
+    pub fn flush_partial_line(&mut self) {
+        self.partial_since = None;
+        if let Some((line, widths)) = self.engine.take_partial_line() {
+            self.handle_text_with_widths(&line, false, Some(&widths));
+        }
+    }
+
+    /// 暫存行逾時仍未補齊則直接處理
+    pub fn flush_stale_partial_line(&mut self) {
+        if self.partial_since.is_some_and(|since| since.elapsed() >= PARTIAL_LINE_TIMEOUT) {
+            self.flush_partial_line();
+        }
+    }
+
+    /// 暫存行預計逾時的時間（供 UI 排程喚醒）
+    pub fn partial_line_deadline(&self) -> Option<Instant> {
+        self.partial_since.map(|since| since + PARTIAL_LINE_TIMEOUT)
+    }
+
+    /// 依網路執行緒送來的狀態更新連線狀態，並視設定顯示 `>>>` 訊息
+    pub fn apply_status(&mut self, update: StatusUpdate) {
+        let message = update.message();
+
+        match update {
+            StatusUpdate::Connected { host, port } => {
+                let info = format!("{}:{}", host, port);
+                self.status = ConnectionStatus::Connected(info.clone());
+                self.connected_at = Some(Instant::now());
+                self.reconnect_attempts = 0;
+                self.pre_login = true;
+                self.connected_pending = true;
+                self.log_event(format!("已連線 {}", info));
+            }
+            StatusUpdate::ConnectFailed(_) | StatusUpdate::Closed(_) | StatusUpdate::Disconnected => {
+                let was_connected = std::mem::take(&mut self.on_connect_sent);
+                self.flush_partial_line();
+                self.connected_at = None;
+                self.server_echo = false;
+                self.pre_login = false;
+                self.pinned_prompt = None;
+                self.current_room = None;
+                self.vitals = None;
+                if let Some(message) = &message {
+                    self.log_event(message.clone());
+                }
+                let gave_up = self.max_reconnect_attempts > 0 && self.reconnect_attempts >= self.max_reconnect_attempts;
+                if self.auto_reconnect && !gave_up {
+                    self.reconnect_delay_until = Some(Instant::now() + Duration::from_secs(3));
+                    self.status = ConnectionStatus::Reconnecting;
+                } else {
+                    self.reconnect_delay_until = None;
+                    self.status = ConnectionStatus::Disconnected;
+                }
+                if self.auto_reconnect && gave_up {
+                    let notice = format!("重連 {} 次仍失敗，放棄自動重連", self.reconnect_attempts);
+                    self.reconnect_attempts = 0;
+                    self.log_event(notice.clone());
+                    self.system_message(&notice);
+                }
+                if was_connected {
+                    self.run_connect_hook(ConnectEvent::Disconnected);
+                }
+            }
+            StatusUpdate::AutoLoginSent => {
+                self.pre_login = false;
+                self.log_event("已送出自動登入");
+            }
+            StatusUpdate::AutoLoginFailed { .. } => {
+                if let Some(message) = &message {
+                    self.log_event(message.clone());
+                }
+            }
+            StatusUpdate::SendFailed(_) => {}
+            StatusUpdate::EncodingDetected(encoding, confidence) => self.on_encoding_detected(encoding, confidence),
+            StatusUpdate::EchoChanged(enabled) => self.server_echo = enabled,
+            StatusUpdate::TitleChanged(title) => {
+                let title = title.trim().to_string();
+                self.server_title = (!title.is_empty()).then_some(title);
+            }
+        }
+
+        if let Some(message) = message {
+            if !self.quiet_connect {
+                self.handle_text(&format!(">>> {}\n", message), false);
+            }
+        }
+
+        if self.is_connected() {
+            self.send_on_connect();
+        }
+    }
+
+    /// 每次連線只送出一次 `on_connect`
+    ///
+    /// 網路執行緒完成連線字串與自動登入後才開始處理指令佇列，
+    /// 因此在連線當下送出即可保證排在登入之後。
+    fn send_on_connect(&mut self) {
+        if self.on_connect_sent {
+            return;
+        }
+        self.on_connect_sent = true;
+        if let Some(command) = self.on_connect.clone() {
+            self.log_event(format!("送出連線指令: {}", command));
+            self.handle_user_input(&command);
+        }
+        self.run_connect_hook(ConnectEvent::Connected);
+    }
+
+    /// Ctrl+點擊輸出中的單字：以點擊指令模板展開後當作輸入送出，回傳是否已送出（模板為空時不動作）
+    pub fn send_clicked_word(&mut self, word: &str) -> bool {
+        let template = self.click_command.trim();
+        if template.is_empty() || word.is_empty() {
+            return false;
+        }
+        let command = template.replace("$word", word);
+        self.handle_user_input(&command);
+        true
+    }
+
+    /// 執行腳本以 `mud.on_connect` / `mud.on_disconnect` 註冊的處理函數
+    fn run_connect_hook(&mut self, event: ConnectEvent) {
+        match self.engine.script_engine.run_connect_hook(event) {
+            Ok(Some(context)) => self.apply_script_context(context),
+            Ok(None) => {}
+            Err(e) => self.system_message(&format!("連線事件腳本錯誤: {}", e)),
+        }
+    }
+
+    /// 建立連線命令（每次連線或重連都從目前設定取得，確保重連時同樣自動登入）
+    pub fn connect_command(&self) -> Command {
+        Command::Connect(
+            self.host.clone(),
+            self.port.parse::<u16>().unwrap_or(7777),
+            self.username.clone(),
+            self.password.clone(),
+            self.wait_username_echo,
+        )
+    }
+
+    /// 已連線多久（未連線時為 `None`）
+    pub fn connected_duration(&self) -> Option<Duration> {
+        self.connected_at.map(|at| at.elapsed())
+    }
+
+    /// 距離上次送出指令多久
+    pub fn idle_duration(&self) -> Duration {
+        self.last_active.elapsed()
+    }
+
+    /// 取得分頁標題
+    pub fn tab_title(&self) -> String {
+        let status_icon = match &self.status {
+            ConnectionStatus::Disconnected => "⚪",
+            ConnectionStatus::Connecting => "🔄",
+            ConnectionStatus::Connected(_) => "🟢",
+            ConnectionStatus::Reconnecting => "🟡",
+        };
+        format!("{} {}", status_icon, self.display_name)
+    }
+
+    /// 是否已連線
+    #[allow(dead_code)]
+    pub fn is_connected(&self) -> bool {
+        matches!(self.status, ConnectionStatus::Connected(_))
+    }
+
+    /// 是否正在連線
+    #[allow(dead_code)]
+    pub fn is_connecting(&self) -> bool {
+        matches!(self.status, ConnectionStatus::Connecting | ConnectionStatus::Reconnecting)
+    }
+
+    /// 連線已建立或正在建立（此時不應再開啟新連線）
+    pub fn connection_active(&self) -> bool {
+        matches!(self.status, ConnectionStatus::Connecting | ConnectionStatus::Connected(_))
+    }
+}
+
+// ============================================================================
+// SessionManager
+// ============================================================================
+
+/// Session 管理器
+///
+/// 管理所有活躍的 Session 並提供分頁切換功能
+pub struct SessionManager {
+    /// 所有活躍的 Session（依序）
+    sessions: Vec<Session>,
+    
+    /// 目前選中的分頁索引
+    active_index: usize,
+    
+    /// 全域別名（套用到所有 Session）
+    global_aliases: Vec<AliasConfig>,
+    
+    /// 全域觸發器（套用到所有 Session）
+    global_triggers: Vec<TriggerConfig>,
+
+    /// 跨分頁的聊天彙整視窗
+    pub chat_window: SubWindow,
+}
+
+#[allow(dead_code)]
+impl SessionManager {
+    /// 建立新的 SessionManager
+    pub fn new() -> Self {
+        Self {
+            sessions: Vec::new(),
+            active_index: 0,
+            global_aliases: Vec::new(),
+            global_triggers: Vec::new(),
+            chat_window: SubWindow::new("chat_aggregate", "聊天彙整").with_capacity(2000),
+        }
+    }
+
+    /// 設定全域別名/觸發器
+    pub fn set_global_config(
+        &mut self,
+        aliases: Vec<AliasConfig>,
+        triggers: Vec<TriggerConfig>,
+    ) {
+        self.global_aliases = aliases;
+        self.global_triggers = triggers;
+    }
+
+    /// 從 Profile 建立並新增 Session
+    pub fn create_session(&mut self, profile: &Profile) -> SessionId {
+        let mut session = Session::from_profile(profile);
+        session.merge_global_config(&self.global_aliases, &self.global_triggers);
+        
+        let id = session.id;
+        self.sessions.push(session);
+        
+        // 自動切換到新分頁
+        self.active_index = self.sessions.len() - 1;
+        
+        id
+    }
+
+    /// 以相同 Profile 複製 Session（新分頁擁有獨立的管理器與緩衝區）
+    ///
+    /// 來源 Session 不存在或不屬於此 Profile 時回傳 `None`。
+    pub fn duplicate_session(&mut self, id: SessionId, profile: &Profile) -> Option<SessionId> {
+        let source = self.get(id)?;
+        if source.profile_name != profile.name {
+            return None;
+        }
+        Some(self.create_session(profile))
+    }
+
+    /// 程式結束前呼叫：通知所有網路執行緒斷線並停止日誌（寫出緩衝內容）
+    pub fn shutdown_all(&mut self) {
+        for session in &mut self.sessions {
+            if let Some(tx) = session.command_tx.take() {
+                let _ = tx.try_send(Command::Disconnect);
+            }
+            if session.persist_scrollback {
+                let path = ProfileManager::scrollback_path(&session.profile_name);
+                if let Err(e) = session.save_scrollback(&path) {
+                    tracing::warn!("無法儲存主視窗記錄 ({}): {}", session.profile_name, e);
+                }
+            }
+            if let Err(e) = session.logger.stop() {
+                tracing::error!("停止日誌失敗 ({}): {}", session.profile_name, e);
+            }
+        }
+    }
+
+    /// 關閉 Session
+    pub fn close_session(&mut self, id: SessionId) -> bool {
+        if let Some(pos) = self.sessions.iter().position(|s| s.id == id) {
+            self.sessions.remove(pos);
+            
+            // 調整 active_index
+            if self.active_index >= self.sessions.len() && !self.sessions.is_empty() {
+                self.active_index = self.sessions.len() - 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// 取得目前選中的 Session
+    pub fn active_session(&self) -> Option<&Session> {
+        self.sessions.get(self.active_index)
+    }
+
+    /// 取得目前選中的 Session（可變）
+    pub fn active_session_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.get_mut(self.active_index)
+    }
+
+    /// 取得目前選中的 Session ID
+    pub fn active_id(&self) -> Option<SessionId> {
+        self.active_session().map(|s| s.id)
+    }
+
+    /// 依 ID 取得 Session
+    pub fn get(&self, id: SessionId) -> Option<&Session> {
+        self.sessions.iter().find(|s| s.id == id)
+    }
+
+    /// 依 ID 取得 Session（可變）
+    pub fn get_mut(&mut self, id: SessionId) -> Option<&mut Session> {
+        self.sessions.iter_mut().find(|s| s.id == id)
     }
 
     /// 切換到指定分頁
@@ -1576,18 +2904,313 @@ impl SessionManager {
     pub fn get_by_index_mut(&mut self, index: usize) -> Option<&mut Session> {
         self.sessions.get_mut(index)
     }
-}
 
-impl Default for SessionManager {
-    fn default() -> Self {
-        Self::new()
+    /// 處理各分頁新增的輸入歷史：`share` 時同步到同 Profile 的其他分頁，並儲存啟用持久化的歷史
+    pub fn sync_history(&mut self, share: bool) {
+        for i in 0..self.sessions.len() {
+            let entries = std::mem::take(&mut self.sessions[i].new_history);
+            if entries.is_empty() {
+                continue;
+            }
+            let profile_name = self.sessions[i].profile_name.clone();
+            if share {
+                for (j, other) in self.sessions.iter_mut().enumerate() {
+                    if j != i && other.profile_name == profile_name {
+                        for entry in &entries {
+                            other.push_history(entry);
+                        }
+                    }
+                }
+            }
+            let session = &self.sessions[i];
+            if session.persist_history {
+                let path = ProfileManager::history_path(&profile_name);
+                if let Err(e) = crate::config::save_history_file(&path, &session.input_history) {
+                    tracing::warn!("無法儲存輸入歷史 ({}): {}", profile_name, e);
+                }
+            }
+        }
     }
-}
+
+    /// 取走各分頁的聊天訊息；啟用彙整時加上分頁名稱前綴送到彙整視窗
+    pub fn sync_chat(&mut self, enabled: bool) {
+        for session in &mut self.sessions {
+            let lines = std::mem::take(&mut session.new_chat);
+            if !enabled {
+                continue;
+            }
+            for line in lines {
+                self.chat_window.push(WindowMessage::new(format!("[{}] {}\n", session.display_name, line)));
+            }
+        }
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 訊息區的捲動位置（切換視窗或分頁時保存，切回來時恢復）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollPosition {
+    /// 垂直捲動距離
+    pub offset: f32,
+    /// 是否停在底部（恢復時繼續跟隨新訊息）
+    pub at_bottom: bool,
+}
+
+impl ScrollPosition {
+    /// 距離底部在此範圍內即視為停在底部
+    const BOTTOM_TOLERANCE: f32 = 4.0;
+
+    /// 由目前的捲動距離、內容高度與可視高度記錄位置
+    pub fn capture(offset: f32, content_height: f32, viewport_height: f32) -> Self {
+        let max_offset = (content_height - viewport_height).max(0.0);
+        Self {
+            offset,
+            at_bottom: offset >= max_offset - Self::BOTTOM_TOLERANCE,
+        }
+    }
+}
+
+/// 本地回顯的指令類型（決定回顯顏色）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoKind {
+    /// 一般指令
+    Normal,
+    /// 客戶端指令（`#` 或 `/` 開頭）
+    ClientCommand,
+    /// 別名展開後的指令
+    Alias,
+}
+
+impl EchoKind {
+    /// 判斷輸入的回顯類型；客戶端指令優先於別名展開
+    pub fn of(input: &str, from_alias: bool) -> Self {
+        if input.starts_with('#') || input.starts_with('/') {
+            EchoKind::ClientCommand
+        } else if from_alias {
+            EchoKind::Alias
+        } else {
+            EchoKind::Normal
+        }
+    }
+
+    /// 回顯使用的 ANSI 顏色碼
+    pub fn color(self) -> &'static str {
+        match self {
+            EchoKind::Normal => "\x1b[90m",
+            EchoKind::ClientCommand => "\x1b[36m",
+            EchoKind::Alias => "\x1b[33m",
+        }
+    }
+}
 
 // ============================================================================
 // 工具函數
 // ============================================================================
 
+/// 將高亮套用到一行文字：只比對轉義碼以外的文字，並同步插入對應的寬度
+///
+/// 高亮結束後以 `ESC[0m` 加上先前出現過的 SGR 序列還原原本的顏色。
+fn apply_highlights(text: &str, widths: &[u8], highlights: &[(regex::Regex, Color)]) -> (String, Vec<u8>) {
+    let escapes: Vec<(usize, usize)> = ANSI_STRIP_RE.find_iter(text).map(|m| (m.start(), m.end())).collect();
+
+    // 收集各純文字片段中的比對區間 (起, 迄, 顏色序列)
+    let mut ranges = Vec::new();
+    let mut segment_start = 0;
+    for &(start, end) in escapes.iter().chain(std::iter::once(&(text.len(), text.len()))) {
+        let segment = &text[segment_start..start];
+        for (re, color) in highlights {
+            for m in re.find_iter(segment).filter(|m| !m.is_empty()) {
+                let code = format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b);
+                ranges.push((segment_start + m.start(), segment_start + m.end(), code));
+            }
+        }
+        segment_start = end;
+    }
+    wrap_ranges(text, widths, &escapes, ranges)
+}
+
+/// 循環選擇補齊候選：`next` 為下一個要顯示的索引（目前顯示的是 `next - 1`）
+///
+/// 回傳（要顯示的索引, 新的 `next`）；反向時從第一個往前會繞回最後一個，
+/// 尚未補齊過（`next == 0`）時直接顯示最後一個。`len` 不可為 0。
+pub fn cycle_completion(next: usize, len: usize, backward: bool) -> (usize, usize) {
+    let shown = if !backward {
+        next % len
+    } else if next == 0 {
+        len - 1
+    } else {
+        ((next - 1) % len + len - 1) % len
+    };
+    (shown, shown + 1)
+}
+
+/// 將時間長度格式化為 `HH:MM:SS`，超過一天時加上天數（如 `1d 02:03:04`）
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d {:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    }
+}
+
+/// 不完整行（如沒有 GA 的提示符）最多暫存多久
+pub const PARTIAL_LINE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// 兩次 BEL 提醒的最短間隔（避免戰鬥中連續響鈴）
+pub const BELL_COOLDOWN: Duration = Duration::from_secs(3);
+
+/// 送出登出指令後等待多久才關閉連線
+pub const LOGOUT_GRACE: Duration = Duration::from_millis(500);
+
+/// 等待帳號回顯的逾時時間（逾時後直接送出密碼）
+pub const USERNAME_ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 連線後伺服器閒置這麼久即視為 banner 已送完，以現有取樣猜測編碼
+pub const ENCODING_IDLE_DETECT: Duration = Duration::from_secs(1);
+
+/// 判斷伺服器輸出中是否已回顯帳號
+///
+/// 任一行（去除 ANSI 後）等於帳號，或以帳號結尾且前一字元非英數字
+/// （如 `帳號：alice`、`Name: alice`）即視為已回顯。
+pub fn username_echoed(output: &str, username: &str) -> bool {
+    let username = username.trim();
+    if username.is_empty() {
+        return false;
+    }
+
+    let clean = ANSI_STRIP_RE.replace_all(output, "");
+    clean.lines().any(|line| {
+        let line = line.trim();
+        match line.strip_suffix(username) {
+            Some("") => true,
+            Some(prefix) => !prefix.chars().last().is_some_and(|c| c.is_alphanumeric()),
+            None => false,
+        }
+    })
+}
+
+/// 取出 `#go <房間名稱>` 的目標（名稱可含空白）
+fn parse_go_target(input: &str) -> Option<&str> {
+    let target = input.trim().strip_prefix("#go")?;
+    if !target.is_empty() && !target.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = target.trim();
+    (!target.is_empty()).then_some(target)
+}
+
+/// 拆分 `#testregex` 的參數：以 ` => ` 分隔模式與測試文字（兩者都可含空白）
+fn parse_testregex_args(args: &str) -> Option<(&str, &str)> {
+    let (pattern, text) = args.split_once(" => ")?;
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return None;
+    }
+    Some((pattern, text))
+}
+
+/// 觸發器除錯訊息：每個匹配一行，列出觸發器名稱、匹配文字與捕獲
+fn describe_trigger_matches(source: &str, matches: &[TriggerMatch]) -> Vec<String> {
+    matches
+        .iter()
+        .map(|m| {
+            let mut line = format!("[{}] {} ← 「{}」", source, m.trigger_name, m.matched_text);
+            if !m.captures.is_empty() {
+                let captures: Vec<String> = m.captures.iter().enumerate().map(|(i, c)| format!("${}={}", i + 1, c)).collect();
+                line.push_str(&format!(" 捕獲: {}", captures.join(", ")));
+            }
+            line
+        })
+        .collect()
+}
+
+/// 解析高亮樣式文字：`red on #000080 bold`（前景色、`on` 後為背景色、`bold` 為粗體，皆可省略）
+fn parse_highlight_style(spec: &str) -> (Option<Color>, Option<Color>, bool) {
+    let (mut fg, mut bg, mut bold) = (None, None, false);
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        if word.eq_ignore_ascii_case("bold") {
+            bold = true;
+        } else if word.eq_ignore_ascii_case("on") {
+            bg = words.next().and_then(Color::parse);
+        } else if let Some(color) = Color::parse(word) {
+            fg = Some(color);
+        }
+    }
+    (fg, bg, bold)
+}
+
+/// 將高亮樣式轉回 [`parse_highlight_style`] 可解析的文字
+fn format_highlight_style(fg: Option<Color>, bg: Option<Color>, bold: bool) -> String {
+    let mut parts = Vec::new();
+    if let Some(c) = fg {
+        parts.push(c.to_hex());
+    }
+    if let Some(c) = bg {
+        parts.push(format!("on {}", c.to_hex()));
+    }
+    if bold {
+        parts.push("bold".to_string());
+    }
+    parts.join(" ")
+}
+
+/// 自動偵測正則表達式模式，否則視為包含文字
+fn detect_trigger_pattern(pattern: String) -> TriggerPattern {
+    if pattern.contains("(.+)")
+        || pattern.contains("(.*)")
+        || pattern.contains("\\d")
+        || pattern.contains("[")
+        || pattern.contains("$")
+        || pattern.contains("^")
+        || pattern.contains("|")
+        || pattern.contains("?")
+    {
+        TriggerPattern::Regex(pattern)
+    } else {
+        TriggerPattern::Contains(pattern)
+    }
+}
+
+/// 是否為單字字元（畫面單字擷取與點擊取字共用）
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// 取出文字中第 `char_index` 個字元所在的單字
+///
+/// 游標落在單字結尾之後時仍視為點到該單字；點在空白或標點上時回傳 `None`。
+pub fn word_at(text: &str, char_index: usize) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let index = if chars.get(char_index).is_some_and(|&c| is_word_char(c)) {
+        char_index
+    } else if char_index > 0 && chars.get(char_index - 1).is_some_and(|&c| is_word_char(c)) {
+        char_index - 1
+    } else {
+        return None;
+    };
+    let start = chars[..index].iter().rposition(|&c| !is_word_char(c)).map_or(0, |p| p + 1);
+    let end = chars[index..].iter().position(|&c| !is_word_char(c)).map_or(chars.len(), |p| index + p);
+    Some(chars[start..end].iter().collect())
+}
+
+/// 以逗號拆分組合模式的子模式（忽略空白項）
+fn split_trigger_patterns(pattern: &str) -> Vec<TriggerPattern> {
+    pattern
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| detect_trigger_pattern(p.to_string()))
+        .collect()
+}
+
 /// 清理可能的 Debug 格式
 fn clean_pattern_string(pattern: &str) -> String {
     let s = pattern.trim();
@@ -1608,7 +3231,8 @@ fn clean_pattern_string(pattern: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ConnectionConfig, Profile};
+    use mudcore::FireScope;
+    use crate::config::{ConnectionConfig, PathConfig, Profile};
 
     #[test]
     fn test_session_id_unique() {
@@ -1631,10 +3255,40 @@ mod tests {
             script_paths: vec![],
             username: None,
             password: None,
+            local_echo: LocalEchoMode::Auto,
+            line_ending: LineEnding::Crlf,
+            encoding: Encoding::Big5,
+            auto_detect_encoding: false,
+            quiet_connect: false,
+            wait_username_echo: false,
+            auto_connect: false,
+            log_original_text: false,
+            capture_banner: false,
+            pin_prompt: false,
+            collapse_blank_lines: false,
+            room_in_title: false,
+            bell_alert: false,
+            persist_history: false,
+            persist_scrollback: false,
+            alias_strict_args: false,
+            max_capture_windows: 20,
+            capture_window_idle_secs: 0,
+            max_reconnect_attempts: 0,
+            anti_spam: AntiSpamConfig::default(),
             created_at: 0,
             last_connected: None,
             notes: String::new(),
             paths: vec![],
+            windows: vec![],
+            abbreviations: vec![],
+            on_disconnect: None,
+            click_command: "kill $word".to_string(),
+            on_connect: None,
+            prompt_regex: None,
+            chat_pattern: None,
+            gag_patterns: vec![],
+            log_path: None,
+            connect_send: None,
         };
 
         let session = Session::from_profile(&profile);
@@ -1664,4 +3318,1439 @@ mod tests {
         manager.switch_tab(1);
         assert_eq!(manager.active_session().unwrap().id, id2);
     }
+
+    #[test]
+    fn test_prune_idle_windows_keeps_main() {
+        let profile = Profile::new("win", "Windows").with_connection("localhost", "7777");
+        let mut session = Session::from_profile(&profile);
+        session.window_manager.ensure_window("chat_OOC");
+
+        // 未啟用時不關閉
+        session.prune_idle_windows();
+        assert!(session.window_manager.get("chat_OOC").is_some());
+
+        session.capture_window_idle = Some(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(5));
+        session.prune_idle_windows();
+        assert!(session.window_manager.get("chat_OOC").is_none());
+        assert!(session.window_manager.get("main").is_some());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::ZERO), "00:00:00");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "00:00:59");
+        assert_eq!(format_duration(Duration::from_secs(60)), "00:01:00");
+        assert_eq!(format_duration(Duration::from_secs(3600 + 23 * 60 + 45)), "01:23:45");
+        assert_eq!(format_duration(Duration::from_secs(86399)), "23:59:59");
+        assert_eq!(format_duration(Duration::from_secs(86400)), "1d 00:00:00");
+        assert_eq!(format_duration(Duration::from_secs(3 * 86400 + 2 * 3600 + 3 * 60 + 4)), "3d 02:03:04");
+    }
+
+    #[test]
+    fn test_username_echoed() {
+        assert!(username_echoed("請輸入您的英文名字：alice\r\n", "alice"));
+        assert!(username_echoed("Welcome!\nalice\n", "alice"));
+        assert!(username_echoed("Name: \x1b[1malice\x1b[0m\n", "alice"));
+        assert!(!username_echoed("請輸入您的英文名字：", "alice"));
+        assert!(!username_echoed("malice is here", "alice"));
+        assert!(!username_echoed("Name: malice", "alice"));
+        assert!(!username_echoed("anything", ""));
+    }
+
+    fn sent_commands(rx: &mut mpsc::Receiver<Command>) -> Vec<String> {
+        let mut sent = Vec::new();
+        while let Ok(cmd) = rx.try_recv() {
+            if let Command::Send(text) = cmd {
+                sent.push(text);
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn test_word_at() {
+        let line = "一隻野狼(wolf) 站在 north-gate 旁。";
+        let index_of = |needle: &str| line[..line.find(needle).unwrap()].chars().count();
+
+        assert_eq!(word_at(line, index_of("wolf")).as_deref(), Some("wolf"));
+        assert_eq!(word_at(line, index_of("olf")).as_deref(), Some("wolf"));
+        // 游標在單字結尾之後
+        assert_eq!(word_at(line, index_of(") ")).as_deref(), Some("wolf"));
+        assert_eq!(word_at(line, index_of("gate")).as_deref(), Some("north-gate"));
+        assert_eq!(word_at(line, index_of("野狼")).as_deref(), Some("一隻野狼"));
+        assert_eq!(word_at(line, index_of(" 站")), None);
+        assert_eq!(word_at(line, 999), None);
+
+        let mut session = Session::from_profile(&Profile::new("click", "Click"));
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        assert!(session.send_clicked_word("wolf"));
+        session.click_command = String::new();
+        assert!(!session.send_clicked_word("wolf"));
+        assert_eq!(sent_commands(&mut rx), vec!["kill wolf"]);
+    }
+
+    #[test]
+    fn test_connect_event_hooks() {
+        let mut profile = Profile::new("events", "Events");
+        profile.on_connect = Some("look".to_string());
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        session
+            .engine
+            .script_engine
+            .execute_inline(
+                r#"mud.on_connect(function() mud.send("score"); mud.variables.online = "yes" end)
+                   mud.on_disconnect(function() mud.variables.online = "no" end)"#,
+                "",
+                &[],
+                false,
+            )
+            .unwrap();
+
+        // 連線失敗不觸發中斷事件
+        session.apply_status(StatusUpdate::ConnectFailed("refused".to_string()));
+        assert_eq!(session.engine.script_engine.get_variable("online"), None);
+
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 4000 });
+        assert_eq!(sent_commands(&mut rx), vec!["look", "score"]);
+        assert_eq!(session.engine.script_engine.get_variable("online").as_deref(), Some("yes"));
+
+        session.apply_status(StatusUpdate::Closed(None));
+        assert_eq!(session.engine.script_engine.get_variable("online").as_deref(), Some("no"));
+    }
+
+    #[test]
+    fn test_send_hook_rewrites_and_cancels() {
+        let mut session = Session::from_profile(&Profile::new("hook", "Hook"));
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        session
+            .engine
+            .script_engine
+            .execute_inline(
+                r#"mud.on_send(function(cmd)
+                    if cmd == "drop all" then return nil end
+                    return (cmd:gsub("^say (.*)", function(s) return "say " .. s:upper() end))
+                end)"#,
+                "",
+                &[],
+                false,
+            )
+            .unwrap();
+
+        session.handle_user_input("say hello");
+        session.handle_user_input("drop all");
+        session.handle_user_input("look");
+        assert_eq!(sent_commands(&mut rx), vec!["say HELLO", "look"]);
+        assert!(session.window_manager.main_window().messages().any(|m| m.content.contains("drop all [cancelled]")));
+    }
+
+    #[test]
+    fn test_send_blocked() {
+        let mut session = Session::from_profile(&Profile::new("safe", "Safe"));
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        session.send_blocked = true;
+
+        session.handle_user_input("look;kill orc");
+        let context = session.engine.script_engine.execute_inline("mud.send('score')", "", &[], false).unwrap();
+        session.apply_script_context(context);
+        assert!(sent_commands(&mut rx).is_empty());
+
+        let echoed: Vec<String> = session.window_manager.main_window().messages().map(|m| m.content.clone()).collect();
+        for cmd in ["look", "kill orc", "score"] {
+            assert!(echoed.iter().any(|m| m.contains(&format!("> {} [blocked]", cmd))), "{:?}", echoed);
+        }
+
+        session.send_blocked = false;
+        session.handle_user_input("look");
+        assert_eq!(sent_commands(&mut rx), vec!["look"]);
+    }
+
+    #[test]
+    fn test_raw_trigger_command_skips_aliases() {
+        let mut profile = Profile::new("raw", "Raw");
+        profile.aliases.push(AliasConfig {
+            name: "k".to_string(),
+            pattern: "k $1".to_string(),
+            replacement: "kill $1".to_string(),
+            default_enabled: true,
+            ..Default::default()
+        });
+        profile.triggers.push(TriggerConfig {
+            name: "processed".to_string(),
+            pattern: r"^(\w+) arrives\.$".to_string(),
+            action: "k $1".to_string(),
+            default_enabled: true,
+            ..Default::default()
+        });
+        profile.triggers.push(TriggerConfig {
+            name: "raw".to_string(),
+            pattern: r"^(\w+) leaves\.$".to_string(),
+            action: "k $1".to_string(),
+            action_type: TriggerActionType::RawCommand,
+            default_enabled: true,
+            ..Default::default()
+        });
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_text("orc arrives.\n", false);
+        assert_eq!(sent_commands(&mut rx), vec!["kill orc"]);
+
+        session.handle_text("orc leaves.\n", false);
+        assert_eq!(sent_commands(&mut rx), vec!["k orc"]);
+
+        let config = Session::config_from_trigger(session.engine.trigger_manager.get("raw").unwrap());
+        assert_eq!(config.action_type, TriggerActionType::RawCommand);
+        assert_eq!(config.action, "k $1");
+    }
+
+    #[test]
+    fn test_trigger_scope_input_vs_output() {
+        let mut profile = Profile::new("scope", "Scope");
+        profile.triggers.push(TriggerConfig {
+            name: "macro".to_string(),
+            pattern: r"^kill (\w+)$".to_string(),
+            action: "wield sword".to_string(),
+            default_enabled: true,
+            scope: mudcore::TriggerScope::Input,
+            ..Default::default()
+        });
+        profile.triggers.push(TriggerConfig {
+            name: "cheer".to_string(),
+            pattern: "kill".to_string(),
+            action: "cheer".to_string(),
+            default_enabled: true,
+            ..Default::default()
+        });
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_user_input("kill rat");
+        assert_eq!(sent_commands(&mut rx), vec!["wield sword", "kill rat"]);
+
+        session.handle_text("Bob tries to kill rat.\n", false);
+        assert_eq!(sent_commands(&mut rx), vec!["cheer"]);
+    }
+
+    #[test]
+    fn test_abbreviation_expanded_before_send() {
+        let mut profile = Profile::new("abbr", "Abbr");
+        profile.abbreviations = vec!["inventory".to_string(), "information".to_string()];
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_user_input("inv;info all;in");
+        assert_eq!(sent_commands(&mut rx), vec!["inventory", "information all", "in"]);
+    }
+
+    #[test]
+    fn test_anti_spam_threshold_and_command() {
+        let mut profile = Profile::new("spam", "Spam");
+        profile.anti_spam.threshold = 3;
+        profile.anti_spam.command = "score".to_string();
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        for _ in 0..3 {
+            session.handle_user_input("kill rat");
+        }
+        assert_eq!(sent_commands(&mut rx), vec!["kill rat", "kill rat", "score", "kill rat"]);
+    }
+
+    #[test]
+    fn test_anti_spam_warn_only_sends_nothing_extra() {
+        let mut profile = Profile::new("spam", "Spam");
+        profile.anti_spam.threshold = 2;
+        profile.anti_spam.action = AntiSpamAction::WarnOnly;
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        for _ in 0..4 {
+            session.handle_user_input("kill rat");
+        }
+        assert_eq!(sent_commands(&mut rx), vec!["kill rat"; 4]);
+        let warned = session
+            .window_manager
+            .main_window()
+            .messages()
+            .any(|m| m.content.contains("Anti-spam"));
+        assert!(warned);
+    }
+
+    #[test]
+    fn test_anti_spam_exempts_movement() {
+        let mut profile = Profile::new("spam", "Spam");
+        profile.anti_spam.threshold = 2;
+        profile.anti_spam.exempt_movement = true;
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        for _ in 0..3 {
+            session.handle_user_input("n");
+        }
+        assert_eq!(sent_commands(&mut rx), vec!["n"; 3]);
+    }
+
+    #[test]
+    fn test_chat_aggregator_prefixes_source() {
+        let mut manager = SessionManager::new();
+        let mut ids = Vec::new();
+        for (name, display_name, pattern) in [("a", "戰士", r"^\S+告訴你"), ("b", "法師", r"^【閒聊】")] {
+            let mut profile = Profile::new(name, display_name);
+            profile.chat_pattern = Some(pattern.to_string());
+            ids.push(manager.create_session(&profile));
+        }
+
+        manager.get_mut(ids[0]).unwrap().handle_text("小明告訴你：在嗎？\n你砍了野狼一刀。", false);
+        manager.get_mut(ids[1]).unwrap().handle_text("\x1b[36m【閒聊】阿花：好熱\x1b[0m\r", false);
+        manager.sync_chat(true);
+
+        let lines: Vec<String> = manager.chat_window.messages().map(|m| m.plain_text()).collect();
+        assert_eq!(lines, vec!["[戰士] 小明告訴你：在嗎？\n", "[法師] 【閒聊】阿花：好熱\n"]);
+
+        // 未啟用彙整時訊息被丟棄，不會在啟用後補送
+        manager.get_mut(ids[0]).unwrap().handle_text("小明告訴你：人呢？", false);
+        manager.sync_chat(false);
+        manager.sync_chat(true);
+        assert_eq!(manager.chat_window.message_count(), 2);
+    }
+
+    #[test]
+    fn test_session_manager_duplicate() {
+        let mut manager = SessionManager::new();
+        let mut profile = Profile::new("p1", "Profile 1").with_connection("host1", "7777");
+        profile.aliases.push(AliasConfig {
+            name: "k".to_string(),
+            pattern: "k $1".to_string(),
+            replacement: "kill $1".to_string(),
+            default_enabled: true,
+            ..Default::default()
+        });
+
+        let id1 = manager.create_session(&profile);
+        let id2 = manager.duplicate_session(id1, &profile).unwrap();
+
+        assert_ne!(id1, id2);
+        assert_eq!(manager.len(), 2);
+        assert_eq!(manager.active_session().unwrap().id, id2);
+
+        let copy = manager.get(id2).unwrap();
+        assert_eq!(copy.profile_name, "p1");
+        assert_eq!(copy.host, "host1");
+        assert_eq!(copy.engine.alias_manager.list().len(), 1);
+
+        // 兩者的管理器互相獨立
+        manager.get_mut(id2).unwrap().engine.alias_manager.remove("k");
+        assert_eq!(manager.get(id1).unwrap().engine.alias_manager.list().len(), 1);
+
+        assert!(manager.duplicate_session(id1, &Profile::new("other", "Other")).is_none());
+    }
+
+    #[test]
+    fn test_session_manager_shutdown_all() {
+        let mut manager = SessionManager::new();
+        let id = manager.create_session(&Profile::new("p1", "Profile 1"));
+        let (tx, mut rx) = mpsc::channel(4);
+        manager.get_mut(id).unwrap().command_tx = Some(tx);
+
+        manager.shutdown_all();
+
+        let session = manager.get(id).unwrap();
+        assert!(session.command_tx.is_none());
+        assert!(!session.logger.is_recording());
+        assert!(matches!(rx.try_recv(), Ok(Command::Disconnect)));
+    }
+
+    #[test]
+    fn test_on_disconnect_sent_only_on_graceful_disconnect() {
+        let mut profile = Profile::new("quit", "Quit").with_connection("localhost", "7777");
+        profile.on_disconnect = Some("quit".to_string());
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(4);
+        session.command_tx = Some(tx);
+
+        // 尚未連線：直接斷線
+        session.request_disconnect();
+        assert!(matches!(rx.try_recv(), Ok(Command::Disconnect)));
+
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        session.request_disconnect();
+        assert!(matches!(rx.try_recv(), Ok(Command::Logout(cmd)) if cmd == "quit"));
+
+        // 連線中斷不送出任何指令
+        session.auto_reconnect = false;
+        session.apply_status(StatusUpdate::Closed(None));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reconnect_repeats_login_and_on_connect() {
+        let mut profile = Profile::new("relog", "Relog").with_connection("localhost", "7777");
+        profile.username = Some("alice".to_string());
+        profile.password = Some("secret".to_string());
+        profile.on_connect = Some("look;score".to_string());
+        let mut session = Session::from_profile(&profile);
+        session.auto_reconnect = true;
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        let sent = |rx: &mut mpsc::Receiver<Command>| {
+            let mut sent = Vec::new();
+            while let Ok(command) = rx.try_recv() {
+                if let Command::Send(text) = command {
+                    sent.push(text);
+                }
+            }
+            sent
+        };
+        let connected = StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 };
+
+        for _ in 0..2 {
+            session.begin_connecting();
+            assert!(session.connection_active());
+            assert!(matches!(
+                session.connect_command(),
+                Command::Connect(host, 7777, Some(user), Some(pass), false)
+                    if host == "localhost" && user == "alice" && pass == "secret"
+            ));
+            session.apply_status(connected.clone());
+            assert_eq!(sent(&mut rx), vec!["look", "score"]);
+
+            // 重複的狀態更新不會重送
+            session.apply_status(StatusUpdate::AutoLoginSent);
+            assert!(sent(&mut rx).is_empty());
+
+            session.apply_status(StatusUpdate::Closed(None));
+            assert!(matches!(session.status, ConnectionStatus::Reconnecting));
+            assert!(!session.connection_active());
+        }
+    }
+
+    #[test]
+    fn test_disconnect_after_enqueues_disconnect() {
+        let profile = Profile::new("afk", "AFK").with_connection("localhost", "7777");
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(4);
+        session.command_tx = Some(tx);
+
+        let context = session
+            .engine
+            .script_engine
+            .execute_inline("mud.disconnect_after(5)", "", &[], false)
+            .unwrap();
+        session.apply_script_context(context);
+
+        assert_eq!(session.active_timers.len(), 1);
+        let remaining = session.active_timers[0].expires_at.saturating_duration_since(Instant::now());
+        assert!(remaining > Duration::from_secs(4) && remaining <= Duration::from_secs(5));
+
+        // 尚未到期
+        session.check_timers();
+        assert!(rx.try_recv().is_err());
+
+        session.active_timers[0].expires_at = Instant::now();
+        session.check_timers();
+        assert!(session.active_timers.is_empty());
+        assert!(!session.auto_reconnect);
+        assert!(matches!(rx.try_recv(), Ok(Command::Disconnect)));
+    }
+
+    #[test]
+    fn test_dry_run_preview_sends_nothing() {
+        let profile = Profile::new("dry", "Dry").with_connection("localhost", "7777");
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(4);
+        session.command_tx = Some(tx);
+
+        let context = session
+            .engine
+            .script_engine
+            .dry_run(r#"mud.send("x"); mud.variables.hp = "10""#, "", &[])
+            .unwrap();
+        assert_eq!(context.commands, vec!["x"]);
+        assert_eq!(session.engine.script_engine.get_variable("hp"), None);
+
+        assert_eq!(session.preview_script_context(&context), vec!["送出: x", "設定變數 hp = 10"]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_timer_receives_registered_context() {
+        let profile = Profile::new("timer", "Timer").with_connection("localhost", "7777");
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(4);
+        session.command_tx = Some(tx);
+
+        let context = session
+            .engine
+            .script_engine
+            .execute_inline(r#"mud.timer(1, "mud.send('kill ' .. target)", { target = captures[1] })"#, "", &["orc".to_string()], false)
+            .unwrap();
+        session.apply_script_context(context);
+        assert_eq!(session.active_timers.len(), 1);
+
+        // 觸發前 captures 已被其他執行覆寫
+        session.engine.script_engine.execute_inline("", "", &["goblin".to_string()], false).unwrap();
+
+        session.active_timers[0].expires_at = Instant::now();
+        session.check_timers();
+        assert_eq!(sent_commands(&mut rx), vec!["kill orc"]);
+    }
+
+    #[test]
+    fn test_encoding_suggestion_prompts_once() {
+        let profile = Profile::new("enc", "Encoding").with_connection("localhost", "7777");
+        let mut session = Session::from_profile(&profile);
+
+        session.suggest_encoding(Encoding::Utf8);
+        assert_eq!(session.encoding_suggestion, Some(Encoding::Utf8));
+
+        session.dismiss_encoding_suggestion();
+        session.suggest_encoding(Encoding::Utf8);
+        assert_eq!(session.encoding_suggestion, None);
+    }
+
+    #[test]
+    fn test_auto_detect_encoding_applies_result() {
+        let mut profile = Profile::new("auto", "Auto");
+        profile.auto_detect_encoding = true;
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        // 純 ASCII 無從判斷，維持原編碼
+        session.on_encoding_detected(Encoding::Utf8, 0.0);
+        assert_eq!(session.encoding, Encoding::Big5);
+
+        session.on_encoding_detected(Encoding::Gbk, 0.3);
+        assert_eq!(session.encoding, Encoding::Gbk);
+        assert_eq!(session.encoding_suggestion, None);
+        assert!(matches!(rx.try_recv(), Ok(Command::SetEncoding(Encoding::Gbk))));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content.contains("信心度偏低")));
+
+        // 未開啟自動偵測時，低信心度結果不提示
+        let mut session = Session::from_profile(&Profile::new("manual", "Manual"));
+        session.on_encoding_detected(Encoding::Gbk, 0.3);
+        assert_eq!(session.encoding_suggestion, None);
+        session.on_encoding_detected(Encoding::Gbk, 0.9);
+        assert_eq!(session.encoding_suggestion, Some(Encoding::Gbk));
+    }
+
+    #[test]
+    fn test_scrollback_roundtrip() {
+        let path = std::env::temp_dir().join("mudclient_test_scrollback").join("main.scrollback");
+        let mut session = Session::from_profile(&Profile::new("scroll", "Scroll"));
+        session.handle_text("\x1b[32m你站在廣場上。\x1b[0m\n出口：北、東", false);
+        session.save_scrollback(&path).unwrap();
+        let saved: Vec<String> = session.window_manager.main_window().messages().map(|m| m.content.clone()).collect();
+
+        let mut restored = Session::from_profile(&Profile::new("scroll", "Scroll"));
+        restored.restore_scrollback(&path);
+        let messages: Vec<String> = restored.window_manager.main_window().messages().map(|m| m.content.clone()).collect();
+        let (last, restored_lines) = messages.split_last().unwrap();
+        assert!(last.contains("上次保存"));
+        assert!(restored_lines.ends_with(&saved));
+        std::fs::remove_file(&path).unwrap();
+
+        // 檔案不存在時不顯示任何訊息
+        let mut empty = Session::from_profile(&Profile::new("scroll", "Scroll"));
+        let before = empty.window_manager.main_window().message_count();
+        empty.restore_scrollback(&path);
+        assert_eq!(empty.window_manager.main_window().message_count(), before);
+    }
+
+    #[test]
+    fn test_profile_encoding_kept_after_switch() {
+        let mut profile = Profile::new("gbk", "GBK");
+        profile.encoding = Encoding::Gbk;
+        let mut session = Session::from_profile(&profile);
+        assert_eq!(session.encoding, Encoding::Gbk);
+
+        // 連線中切換的編碼在重新連線時沿用
+        session.handle_user_input("#encoding gb2312");
+        assert_eq!(session.encoding, Encoding::Gb2312);
+    }
+
+    #[test]
+    fn test_increment_variable_trigger() {
+        let profile = Profile::new("vars", "Vars");
+        let mut session = Session::from_profile(&profile);
+        session.engine.trigger_manager.add(
+            Trigger::new("kills", TriggerPattern::Contains("你殺死了".to_string()))
+                .add_action(TriggerAction::IncrementVariable { name: "kills".to_string(), by: 1 }),
+        );
+
+        session.handle_text("你殺死了小兔子。\n你殺死了野狼。", false);
+
+        assert_eq!(session.engine.script_engine.get_variable("kills").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_set_variable_trigger_with_capture() {
+        let profile = Profile::new("vars", "Vars");
+        let mut session = Session::from_profile(&profile);
+        let config = TriggerConfig {
+            name: "gold".to_string(),
+            pattern: r"你得到了 (\d+) 枚金幣".to_string(),
+            action: "$1".to_string(),
+            default_enabled: true,
+            action_type: TriggerActionType::SetVariable,
+            variable: "last_gold".to_string(),
+            ..Default::default()
+        };
+        let trigger = Session::create_trigger_from_config(&config).unwrap();
+        session.engine.trigger_manager.add(trigger);
+
+        session.handle_text("你得到了 250 枚金幣。", false);
+
+        assert_eq!(session.engine.script_engine.get_variable("last_gold").as_deref(), Some("250"));
+        assert_eq!(Session::config_from_trigger(session.engine.trigger_manager.get("gold").unwrap()), config);
+    }
+
+    #[test]
+    fn test_invalid_regex_trigger_loaded_disabled() {
+        let mut profile = Profile::new("regex", "Regex");
+        for (name, pattern) in [("bad", r"^你得到了 (\d+ 枚"), ("good", r"^你得到了 (\d+) 枚")] {
+            profile.triggers.push(TriggerConfig {
+                name: name.to_string(),
+                pattern: pattern.to_string(),
+                action: "say $1".to_string(),
+                default_enabled: true,
+                ..Default::default()
+            });
+        }
+        let session = Session::from_profile(&profile);
+
+        let bad = session.engine.trigger_manager.get("bad").unwrap();
+        assert!(!bad.enabled);
+        assert!(bad.error.is_some());
+        assert!(Session::config_from_trigger(bad).default_enabled, "設定檔中的啟用狀態不變");
+
+        let good = session.engine.trigger_manager.get("good").unwrap();
+        assert!(good.enabled);
+        assert_eq!(good.error, None);
+    }
+
+    #[test]
+    fn test_trigger_priority_order() {
+        let mut profile = Profile::new("prio", "Priority");
+        for (name, action, priority) in [("attack", "kill orc", 0), ("flee", "flee", 10)] {
+            profile.triggers.push(TriggerConfig {
+                name: name.to_string(),
+                pattern: "HP: 1[0-9]/".to_string(),
+                action: action.to_string(),
+                default_enabled: true,
+                priority,
+                ..Default::default()
+            });
+        }
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_text("HP: 12/300\n", false);
+        assert_eq!(sent_commands(&mut rx), vec!["flee", "kill orc"]);
+        let flee = session.engine.trigger_manager.get("flee").unwrap();
+        assert_eq!(Session::config_from_trigger(flee).priority, 10);
+    }
+
+    #[test]
+    fn test_runtime_toggle_not_persisted_until_saved() {
+        let mut profile = Profile::new("state", "State");
+        profile.triggers.push(TriggerConfig {
+            name: "loot".to_string(),
+            pattern: "倒下了".to_string(),
+            action: "get all from corpse".to_string(),
+            default_enabled: false,
+            ..Default::default()
+        });
+        let mut session = Session::from_profile(&profile);
+        assert!(!session.engine.trigger_manager.get("loot").unwrap().enabled);
+
+        // 腳本於執行期啟用
+        let mut context = MudContext::new();
+        context.trigger_updates.push(("loot".to_string(), true));
+        session.apply_script_context(context);
+        let trigger = session.engine.trigger_manager.get("loot").unwrap();
+        assert!(trigger.enabled);
+        assert!(!Session::config_from_trigger(trigger).default_enabled);
+
+        session.save_enabled_state();
+        assert!(Session::config_from_trigger(session.engine.trigger_manager.get("loot").unwrap()).default_enabled);
+    }
+
+    #[test]
+    fn test_connection_event_log() {
+        let mut session = Session::from_profile(&Profile::new("test", "測試").with_connection("localhost", "7777"));
+
+        session.begin_connecting();
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        assert!(matches!(&session.status, ConnectionStatus::Connected(info) if info == "localhost:7777"));
+        session.apply_status(StatusUpdate::AutoLoginSent);
+        session.apply_status(StatusUpdate::Closed(None));
+        assert!(matches!(session.status, ConnectionStatus::Reconnecting));
+
+        session.begin_connecting();
+
+        let events: Vec<&str> = session.event_log.iter().map(|(_, e)| e.as_str()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "連線中 localhost:7777",
+                "已連線 localhost:7777",
+                "已送出自動登入",
+                "連線已關閉",
+                "重連嘗試 #1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prompt_regex_ends_room_description() {
+        let mut profile = Profile::new("prompt", "Prompt");
+        profile.prompt_regex = Some(r"^<\d+hp \d+mv>".to_string());
+        let mut session = Session::from_profile(&profile);
+
+        session.handle_text("look", true);
+        assert!(session.in_room_description);
+        session.handle_text("(守衛/guard) 站在這裡", false);
+        assert!(session.in_room_description);
+        session.handle_text("<100hp 50mv>", false);
+        assert!(!session.in_room_description);
+
+        // 未設定正則時只認伺服器以 GA/EOR 標示的提示符
+        let mut plain = Session::from_profile(&Profile::new("plain", "Plain"));
+        plain.handle_text("look", true);
+        plain.handle_text("<100hp 50mv>", false);
+        assert!(plain.in_room_description);
+        plain.handle_server_text("<100hp 50mv> ", &[1; 13]);
+        plain.handle_prompt_end();
+        assert!(!plain.in_room_description);
+    }
+
+    #[test]
+    fn test_fire_scope_uses_prompt_end_flag() {
+        let mut session = Session::from_profile(&Profile::new("scope", "Scope"));
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        session.engine.trigger_manager.add(
+            Trigger::new("gauge", TriggerPattern::Contains("hp".to_string()))
+                .with_fire_on(FireScope::PromptOnly)
+                .add_action(TriggerAction::SendCommand("gauge".to_string())),
+        );
+
+        // 長得像提示符但沒有 GA/EOR 的行不算提示符
+        session.handle_text("(100hp/100)\n", false);
+        assert!(sent_commands(&mut rx).is_empty());
+
+        session.handle_server_text("(100hp/100) ", &[1; 12]);
+        session.handle_prompt_end();
+        assert_eq!(sent_commands(&mut rx), vec!["gauge"]);
+    }
+
+    #[test]
+    fn test_handle_lines_matches_per_line() {
+        let lines = [
+            "你看到一隻哥布林 (goblin) 在這裡。",
+            "守衛/guard 站在門口",
+            "[出口: north south]",
+            "普通的一行文字",
+            "\x1b[31m紅色的 (red dragon)\x1b[0m",
+        ];
+
+        let mut per_line = Session::from_profile(&Profile::new("a", "A"));
+        for line in lines {
+            per_line.handle_text(line, false);
+        }
+        let mut batched = Session::from_profile(&Profile::new("b", "B"));
+        assert!(batched.handle_lines(&lines, false));
+
+        assert_eq!(
+            batched.window_manager.main_window().message_count(),
+            per_line.window_manager.main_window().message_count()
+        );
+        let words = |session: &Session| {
+            let mut words: Vec<(String, bool)> = session.screen_words.iter().map(|(w, m)| (w.clone(), m.is_mob)).collect();
+            words.sort();
+            words
+        };
+        assert!(!words(&batched).is_empty());
+        assert_eq!(words(&batched), words(&per_line));
+        assert!(batched.word_batch_time.is_none());
+    }
+
+    #[test]
+    fn test_go_command() {
+        assert_eq!(parse_go_target("#go inn"), Some("inn"));
+        assert_eq!(parse_go_target("#go  Town Square "), Some("Town Square"));
+        assert_eq!(parse_go_target("#go"), None);
+        assert_eq!(parse_go_target("#gossip hi"), None);
+
+        let mut profile = Profile::new("go", "Go");
+        profile.paths.push(PathConfig { name: "Inn".to_string(), value: "/2ne".to_string(), category: None });
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_user_input("#go inn");
+        assert_eq!(sent_commands(&mut rx), vec!["recall", "ne", "ne"]);
+
+        session.handle_user_input("#go Town Square");
+        assert!(sent_commands(&mut rx).is_empty());
+        assert!(session
+            .window_manager
+            .main_window()
+            .messages()
+            .any(|m| m.content.contains("No known route to 'Town Square'")));
+    }
+
+    #[test]
+    fn test_go_uses_mapped_rooms() {
+        let mut session = Session::from_profile(&Profile::new("map", "Map"));
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+
+        session.handle_text("Town Square\nThe center of town.\n[Exits: n]\n", false);
+        for (dir, room) in [
+            ("n", "Main Street\nA busy street.\n[Exits: s e]\n"),
+            ("e", "The Inn\nWarm and cozy.\n[Exits: w]\n"),
+            ("w", "Main Street\nA busy street.\n[Exits: s e]\n"),
+            ("s", "Town Square\nThe center of town.\n[Exits: n]\n"),
+        ] {
+            session.handle_user_input(dir);
+            session.handle_text(room, false);
+        }
+        assert_eq!(session.engine.room_graph.len(), 3);
+        sent_commands(&mut rx);
+
+        session.handle_user_input("#go the inn");
+        assert_eq!(sent_commands(&mut rx), vec!["n", "e"]);
+    }
+
+    #[test]
+    fn test_parse_testregex_args() {
+        assert_eq!(
+            parse_testregex_args(" ^(\\w+) tells you (.+)$ => Bob tells you hi there"),
+            Some(("^(\\w+) tells you (.+)$", "Bob tells you hi there"))
+        );
+        // 只在第一個分隔符拆分，文字可再含 =>
+        assert_eq!(parse_testregex_args(" a b => x => y"), Some(("a b", "x => y")));
+        assert_eq!(parse_testregex_args(" => text"), None);
+        assert_eq!(parse_testregex_args(" no delimiter"), None);
+    }
+
+    #[test]
+    fn test_reconnect_gives_up_after_max_attempts() {
+        let mut profile = Profile::new("down", "Down").with_connection("localhost", "7777");
+        profile.max_reconnect_attempts = 2;
+        let mut session = Session::from_profile(&profile);
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        session.apply_status(StatusUpdate::Closed(None));
+
+        for attempt in 1..=2 {
+            assert!(matches!(session.status, ConnectionStatus::Reconnecting));
+            session.begin_connecting();
+            assert_eq!(session.reconnect_attempts, attempt);
+            session.apply_status(StatusUpdate::ConnectFailed("refused".to_string()));
+        }
+
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+        assert!(session.reconnect_delay_until.is_none());
+        assert_eq!(session.reconnect_attempts, 0);
+        assert!(session.window_manager.main_window().messages().any(|m| m.content.contains("重連 2 次仍失敗")));
+    }
+
+    #[test]
+    fn test_disable_auto_reconnect_cancels_pending() {
+        let mut session = Session::from_profile(&Profile::new("test", "測試").with_connection("localhost", "7777"));
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        session.apply_status(StatusUpdate::Closed(None));
+        assert!(matches!(session.status, ConnectionStatus::Reconnecting));
+        assert!(session.reconnect_delay_until.is_some());
+
+        session.handle_user_input("#reconnect off");
+        assert!(!session.auto_reconnect);
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+        assert!(session.reconnect_delay_until.is_none());
+
+        // 重新開啟不會自行觸發重連，但下次斷線會
+        session.handle_user_input("#reconnect on");
+        assert!(session.auto_reconnect);
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        session.apply_status(StatusUpdate::Closed(None));
+        assert!(matches!(session.status, ConnectionStatus::Reconnecting));
+    }
+
+    #[test]
+    fn test_status_update_does_not_parse_text() {
+        let mut session = Session::from_profile(&Profile::new("test", "測試"));
+        session.auto_reconnect = false;
+
+        // 伺服器文字中出現相同字樣不應改變狀態
+        session.handle_text("某人說：已連線到 example.com:23", false);
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+
+        session.apply_status(StatusUpdate::Connected { host: "example.com".to_string(), port: 23 });
+        assert!(session.is_connected());
+        session.apply_status(StatusUpdate::Disconnected);
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+    }
+
+    #[test]
+    fn test_handle_network_message_variants() {
+        let mut session = Session::from_profile(&Profile::new("net", "Net"));
+        session.auto_reconnect = false;
+        let main_contains = |session: &Session, needle: &str| {
+            session.window_manager.main_window().messages().any(|m| m.content.contains(needle))
+        };
+
+        session.handle_network_message(NetworkMessage::Text("歡迎光臨\n".to_string(), vec![2, 2, 2, 2, 1]));
+        assert!(main_contains(&session, "歡迎光臨"));
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+
+        session.handle_network_message(NetworkMessage::Status(StatusUpdate::Connected {
+            host: "mud.example".to_string(),
+            port: 4000,
+        }));
+        assert!(matches!(&session.status, ConnectionStatus::Connected(info) if info == "mud.example:4000"));
+        assert!(session.connected_at.is_some());
+
+        session.handle_network_message(NetworkMessage::Status(StatusUpdate::SendFailed("broken pipe".to_string())));
+        assert!(session.is_connected());
+        assert!(main_contains(&session, "發送失敗: broken pipe"));
+
+        session.handle_network_message(NetworkMessage::Status(StatusUpdate::AutoLoginFailed {
+            step: "密碼",
+            error: "timeout".to_string(),
+        }));
+        assert!(session.is_connected());
+        assert!(main_contains(&session, "自動登入(密碼)失敗: timeout"));
+
+        session.handle_network_message(NetworkMessage::Status(StatusUpdate::Disconnected));
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+        assert!(session.connected_at.is_none());
+
+        session.handle_network_message(NetworkMessage::Status(StatusUpdate::ConnectFailed("refused".to_string())));
+        assert!(matches!(session.status, ConnectionStatus::Disconnected));
+        assert!(main_contains(&session, "連線失敗: refused"));
+    }
+
+    #[test]
+    fn test_partial_line_joined_with_next_chunk() {
+        let mut profile = Profile::new("split", "Split");
+        profile.triggers.push(TriggerConfig {
+            name: "foobar".to_string(),
+            pattern: "^foobar$".to_string(),
+            action: "cheer".to_string(),
+            default_enabled: true,
+            ..Default::default()
+        });
+        let mut session = Session::from_profile(&profile);
+        let (tx, mut rx) = mpsc::channel(16);
+        session.command_tx = Some(tx);
+        let before = session.window_manager.main_window().message_count();
+
+        session.handle_network_message(NetworkMessage::Text("foo".to_string(), vec![1; 3]));
+        assert_eq!(session.window_manager.main_window().message_count(), before);
+        assert!(session.partial_line_deadline().is_some());
+
+        session.handle_network_message(NetworkMessage::Text("bar\n".to_string(), vec![1; 4]));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "foobar"));
+        assert!(!session.window_manager.main_window().messages().any(|m| m.content == "foo"));
+        assert_eq!(sent_commands(&mut rx), vec!["cheer"]);
+        assert!(session.partial_line_deadline().is_none());
+    }
+
+    #[test]
+    fn test_prompt_end_flushes_partial_line() {
+        let mut session = Session::from_profile(&Profile::new("prompt", "Prompt"));
+
+        session.handle_network_message(NetworkMessage::Text("line\nHP:100> ".to_string(), vec![1; 13]));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "line"));
+        assert!(!session.window_manager.main_window().messages().any(|m| m.content.contains("HP:100")));
+
+        session.handle_network_message(NetworkMessage::PromptEnd);
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "HP:100> "));
+        assert!(session.partial_line_deadline().is_none());
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() {
+        let text = "你走進了大廳。\n\n\r\n   \n這裡很安靜。\n\nHP:100>";
+        let main_lines = |session: &Session| -> Vec<String> {
+            session.window_manager.main_window().messages().map(|m| m.content.clone()).collect()
+        };
+
+        let mut profile = Profile::new("blank", "Blank");
+        profile.collapse_blank_lines = true;
+        let mut session = Session::from_profile(&profile);
+        session.handle_text(text, false);
+        assert_eq!(main_lines(&session), vec!["你走進了大廳。", "", "這裡很安靜。", "", "HP:100>"]);
+
+        // 未啟用時保留原樣
+        let mut session = Session::from_profile(&Profile::new("blank", "Blank"));
+        session.handle_text(text, false);
+        assert_eq!(main_lines(&session).len(), 7);
+
+        // ASCII 藝術模式不合併
+        let mut session = Session::from_profile(&profile);
+        session.window_manager.main_window_mut().ascii_art = true;
+        session.handle_text(text, false);
+        assert_eq!(main_lines(&session).len(), 7);
+    }
+
+    #[test]
+    fn test_pinned_prompt_updates_in_place() {
+        let mut profile = Profile::new("prompt", "Prompt");
+        profile.pin_prompt = true;
+        let mut session = Session::from_profile(&profile);
+
+        session.handle_network_message(NetworkMessage::Text("line\nHP:100> ".to_string(), vec![1; 13]));
+        session.handle_network_message(NetworkMessage::PromptEnd);
+        assert_eq!(session.pinned_prompt.as_deref(), Some("HP:100> "));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "line"));
+        assert!(!session.window_manager.main_window().messages().any(|m| m.content.contains("HP:")));
+
+        session.handle_network_message(NetworkMessage::Text("HP:90> ".to_string(), vec![1; 7]));
+        session.handle_network_message(NetworkMessage::PromptEnd);
+        assert_eq!(session.pinned_prompt.as_deref(), Some("HP:90> "));
+        assert!(!session.window_manager.main_window().messages().any(|m| m.content.contains("HP:")));
+
+        // 未以 GA/EOR 結尾的行（如逾時）照常進入捲動紀錄
+        session.handle_network_message(NetworkMessage::Text("HP:80> ".to_string(), vec![1; 7]));
+        session.flush_partial_line();
+        assert_eq!(session.pinned_prompt.as_deref(), Some("HP:90> "));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "HP:80> "));
+    }
+
+    #[test]
+    fn test_script_highlight_wraps_matches() {
+        let mut session = Session::from_profile(&Profile::new("hl", "Highlight"));
+        let mut context = MudContext::new();
+        context.highlights.push((r"\bOrc\b".to_string(), Color::rgb(255, 85, 85)));
+        context.highlights.push(("([".to_string(), Color::rgb(0, 0, 255)));
+        session.apply_script_context(context);
+        assert_eq!(session.highlights.len(), 1);
+
+        session.handle_text("\x1b[33mAn Orc and an Orcish blade\x1b[0m", false);
+        let msg = session.window_manager.main_window().messages().last().unwrap();
+        assert_eq!(msg.content, "\x1b[33mAn \x1b[38;2;255;85;85mOrc\x1b[0m\x1b[33m and an Orcish blade\x1b[0m");
+        assert_eq!(msg.byte_widths.len(), msg.content.chars().count());
+
+        let mut context = MudContext::new();
+        context.clear_highlights = true;
+        session.apply_script_context(context);
+        assert!(session.highlights.is_empty());
+    }
+
+    #[test]
+    fn test_substitute_trigger_rewrites_display() {
+        let mut session = Session::from_profile(&Profile::new("sub", "Substitute"));
+        session.engine.trigger_manager.add(
+            Trigger::new("dmg", TriggerPattern::Regex(r"(\S+)對你造成了 (\d+) 點傷害".to_string()))
+                .add_action(TriggerAction::Substitute { replacement: "[$1 -$2]".to_string() })
+                .add_action(TriggerAction::SetVariable { name: "last_hit".to_string(), value: "$2".to_string() }),
+        );
+
+        session.handle_text("\x1b[31m野狼對你造成了 \x1b[1m12\x1b[0m 點傷害。", false);
+        let msg = session.window_manager.main_window().messages().last().unwrap();
+        assert_eq!(msg.content, "\x1b[31m[野狼 -12]\x1b[1m\x1b[0m。");
+        assert_eq!(msg.byte_widths.len(), msg.content.chars().count());
+        assert_eq!(session.engine.script_engine.get_variable("last_hit").as_deref(), Some("12"));
+
+        let config = Session::config_from_trigger(session.engine.trigger_manager.get("dmg").unwrap());
+        assert_eq!(config.action_type, TriggerActionType::Substitute);
+        assert_eq!(config.action, "[$1 -$2]");
+    }
+
+    #[test]
+    fn test_highlight_trigger_wraps_match() {
+        let mut session = Session::from_profile(&Profile::new("thl", "TriggerHighlight"));
+        session.engine.trigger_manager.add(
+            Trigger::new("foe", TriggerPattern::Regex(r"野狼|山賊".to_string())).add_action(TriggerAction::Highlight {
+                fg: Some(Color::rgb(255, 0, 0)),
+                bg: Some(Color::rgb(0, 0, 128)),
+                bold: true,
+            }),
+        );
+
+        session.handle_text("\x1b[32m一隻野狼\x1b[1m撲向你。\x1b[0m", false);
+        let msg = session.window_manager.main_window().messages().last().unwrap();
+        assert_eq!(
+            msg.content,
+            "\x1b[32m一隻\x1b[1;38;2;255;0;0;48;2;0;0;128m野狼\x1b[0m\x1b[32m\x1b[1m撲向你。\x1b[0m"
+        );
+        assert_eq!(msg.byte_widths.len(), msg.content.chars().count());
+
+        let config = Session::config_from_trigger(session.engine.trigger_manager.get("foe").unwrap());
+        assert_eq!(config.action_type, TriggerActionType::Highlight);
+        assert_eq!(config.action, "#ff0000 on #000080 bold");
+        let trigger = Session::create_trigger_from_config(&config).unwrap();
+        assert!(matches!(
+            trigger.actions[0],
+            TriggerAction::Highlight { fg: Some(Color { r: 255, g: 0, b: 0 }), bg: Some(_), bold: true }
+        ));
+    }
+
+    #[test]
+    fn test_from_profile_creates_layout_windows() {
+        let mut profile = Profile::new("layout", "Layout");
+        profile.windows = vec![
+            WindowLayoutConfig { id: "chat".to_string(), title: "聊天".to_string(), capacity: 200, visible: true, strip_ansi: true, ascii_art: true },
+            WindowLayoutConfig { id: "map".to_string(), title: "地圖".to_string(), capacity: 1000, visible: false, strip_ansi: false, ascii_art: false },
+        ];
+        let mut session = Session::from_profile(&profile);
+
+        let chat = session.window_manager.get("chat").unwrap();
+        assert_eq!(chat.title, "聊天");
+        assert_eq!(chat.capacity, 200);
+        assert!(chat.strip_ansi);
+        assert!(chat.ascii_art);
+        assert!(!chat.is_auto_created());
+        assert!(!session.window_manager.get("map").unwrap().visible);
+
+        // 自動建立的擷取視窗不寫回配置，固定後才保存
+        session.window_manager.ensure_window("chat_OOC");
+        assert_eq!(session.window_layout(), profile.windows);
+        session.window_manager.get_mut("chat_OOC").unwrap().pin();
+        assert_eq!(session.window_layout().len(), 3);
+    }
+
+    #[test]
+    fn test_banner_captured_until_login() {
+        let mut profile = Profile::new("banner", "Banner");
+        profile.capture_banner = true;
+        let mut session = Session::from_profile(&profile);
+        session.quiet_connect = true;
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        assert!(session.pre_login);
+
+        session.handle_text("歡迎來到測試泥巴\n", false);
+        let banner = session.window_manager.get(BANNER_WINDOW_ID).unwrap();
+        assert!(banner.messages().any(|m| m.content == "歡迎來到測試泥巴"));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "歡迎來到測試泥巴"));
+
+        session.apply_status(StatusUpdate::AutoLoginSent);
+        assert!(!session.pre_login);
+        session.handle_text("你醒了過來。\n", false);
+        let banner = session.window_manager.get(BANNER_WINDOW_ID).unwrap();
+        assert!(!banner.messages().any(|m| m.content == "你醒了過來。"));
+        assert!(session.window_manager.main_window().messages().any(|m| m.content == "你醒了過來。"));
+    }
+
+    #[test]
+    fn test_quiet_connect_hides_status_lines() {
+        let mut session = Session::from_profile(&Profile::new("test", "測試"));
+        let before = session.window_manager.main_window().message_count();
+
+        session.quiet_connect = true;
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 7777 });
+        assert_eq!(session.window_manager.main_window().message_count(), before);
+        assert!(session.is_connected());
+
+        session.quiet_connect = false;
+        session.apply_status(StatusUpdate::Closed(Some("reset".to_string())));
+        assert!(session
+            .window_manager
+            .main_window()
+            .messages()
+            .any(|m| m.content.contains("連線已關閉 (錯誤: reset)")));
+    }
+
+    #[test]
+    fn test_event_log_capped() {
+        let mut session = Session::from_profile(&Profile::new("test", "測試"));
+        for i in 0..MAX_EVENT_LOG + 10 {
+            session.log_event(format!("事件 {}", i));
+        }
+        assert_eq!(session.event_log.len(), MAX_EVENT_LOG);
+        assert_eq!(session.event_log[0].1, "事件 10");
+    }
+
+    #[test]
+    fn test_route_to_dynamic_window() {
+        let mut session = Session::from_profile(&Profile::new("chat", "Chat"));
+        session.engine.trigger_manager.add(
+            Trigger::new("channel", TriggerPattern::Regex(r"^\[(\w+)\]".to_string()))
+                .add_action(TriggerAction::RouteToWindow("chat_$1".to_string())),
+        );
+
+        session.handle_text("[OOC] hi", false);
+
+        let window = session.window_manager.get("chat_OOC").expect("視窗應自動建立");
+        assert_eq!(window.message_count(), 1);
+        assert!(window.messages().next().unwrap().content.contains("hi"));
+    }
+
+    #[test]
+    fn test_local_echo_modes() {
+        let mut session = Session::from_profile(&Profile::new("echo", "Echo"));
+
+        let cases = [
+            (LocalEchoMode::Auto, false, true),
+            (LocalEchoMode::Auto, true, false),
+            (LocalEchoMode::Always, false, true),
+            (LocalEchoMode::Always, true, true),
+            (LocalEchoMode::Never, false, false),
+            (LocalEchoMode::Never, true, false),
+        ];
+        for (mode, server_echo, expected) in cases {
+            session.local_echo = mode;
+            session.server_echo = server_echo;
+            assert_eq!(session.should_local_echo(), expected, "{:?} / server_echo={}", mode, server_echo);
+        }
+
+        session.apply_status(StatusUpdate::EchoChanged(true));
+        assert!(session.server_echo);
+    }
+
+    #[test]
+    fn test_echo_kind() {
+        assert_eq!(EchoKind::of("look", false), EchoKind::Normal);
+        assert_eq!(EchoKind::of("#loop 3 look", false), EchoKind::ClientCommand);
+        assert_eq!(EchoKind::of("/who", true), EchoKind::ClientCommand);
+        assert_eq!(EchoKind::of("kill orc", true), EchoKind::Alias);
+        assert_ne!(EchoKind::Normal.color(), EchoKind::Alias.color());
+
+        let mut session = Session::from_profile(&Profile::new("echo", "Echo"));
+        session.colored_echo = true;
+        session.engine.alias_manager.add(mudcore::Alias::new("k", "k $1", "kill $1"));
+        session.handle_user_input("k orc");
+        session.handle_user_input("look");
+        let messages: Vec<String> = session.window_manager.main_window().messages().map(|m| m.content.clone()).collect();
+        assert!(messages.iter().any(|m| m.contains("\x1b[33m> kill orc")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("\x1b[90m> look")), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_bell_alert_cooldown() {
+        let mut session = Session::from_profile(&Profile::new("bell", "Bell"));
+        session.handle_network_message(NetworkMessage::Bell);
+        assert!(!session.take_bell(), "未啟用時不提醒");
+
+        session.bell_alert = true;
+        session.handle_network_message(NetworkMessage::Bell);
+        assert!(session.take_bell());
+        assert!(!session.take_bell());
+
+        // 冷卻時間內不重複提醒
+        session.handle_network_message(NetworkMessage::Bell);
+        assert!(!session.take_bell());
+        session.last_bell = Some(Instant::now() - BELL_COOLDOWN);
+        session.handle_network_message(NetworkMessage::Bell);
+        assert!(session.take_bell());
+    }
+
+    #[test]
+    fn test_connect_success_notifies_once() {
+        let mut session = Session::from_profile(&Profile::new("recent", "Recent"));
+        session.apply_status(StatusUpdate::ConnectFailed("refused".to_string()));
+        assert!(!session.take_connected(), "連線失敗不算");
+
+        session.apply_status(StatusUpdate::Connected { host: "localhost".to_string(), port: 4000 });
+        assert!(session.take_connected());
+        assert!(!session.take_connected(), "只通知一次");
+    }
+
+    #[test]
+    fn test_title_changed() {
+        let mut session = Session::from_profile(&Profile::new("title", "Title"));
+
+        session.apply_status(StatusUpdate::TitleChanged("Dragon MUD".to_string()));
+        assert_eq!(session.server_title.as_deref(), Some("Dragon MUD"));
+
+        session.apply_status(StatusUpdate::TitleChanged("  ".to_string()));
+        assert_eq!(session.server_title, None);
+    }
+
+    #[test]
+    fn test_gmcp_room_info_updates_current_room() {
+        let mut session = Session::from_profile(&Profile::new("gmcp", "GMCP"));
+        session.auto_reconnect = false;
+        assert_eq!(session.current_room, None);
+
+        // 其他 GMCP 訊息不影響房間
+        let vitals = GmcpMessage::parse(br#"Char.Vitals {"hp":100}"#).unwrap();
+        session.handle_network_message(NetworkMessage::Gmcp(vitals));
+        assert_eq!(session.current_room, None);
+        assert_eq!(session.vitals.as_ref().and_then(|v| v.hp), Some(100));
+
+        let room = GmcpMessage::parse(br#"Room.Info {"num":1,"name":"Town Square","exits":{"n":2}}"#).unwrap();
+        session.handle_network_message(NetworkMessage::Gmcp(room));
+        assert_eq!(session.current_room.as_ref().map(|r| r.name.as_str()), Some("Town Square"));
+
+        session.apply_status(StatusUpdate::Disconnected);
+        assert_eq!(session.current_room, None);
+        assert_eq!(session.vitals, None);
+    }
+
+    #[test]
+    fn test_gag_patterns_hide_but_log() {
+        let mut profile = Profile::new("gag", "Gag");
+        profile.gag_patterns = vec!["[OOC]".to_string(), r"/^\w+ yawns\.$/".to_string(), "/(/".to_string()];
+        let mut session = Session::from_profile(&profile);
+        session.engine.trigger_manager.add(
+            Trigger::new("channel", TriggerPattern::Regex(r"^\[(\w+)\]".to_string()))
+                .add_action(TriggerAction::RouteToWindow("chat_$1".to_string())),
+        );
+
+        let log_path = std::env::temp_dir().join("mudclient_test_gag").join("gag.txt");
+        let _ = std::fs::remove_file(&log_path);
+        session.logger.stop().unwrap();
+        session.logger.start(&log_path).unwrap();
+
+        session.handle_text("\x1b[1;33m[OOC] spam spam\x1b[0m", false);
+        session.handle_text("Bob yawns.", false);
+        session.handle_text("Bob yawns loudly.", false);
+
+        // 符合清單的訊息不出現在任何視窗，觸發器也不會建立擷取視窗
+        let main = session.window_manager.main_window();
+        assert!(!main.messages().any(|m| m.content.contains("spam") || m.content == "Bob yawns."));
+        assert!(main.messages().any(|m| m.content == "Bob yawns loudly."));
+        assert!(session.window_manager.get("chat_OOC").is_none());
+
+        // 仍寫入日誌
+        session.logger.stop().unwrap();
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("[OOC] spam spam"));
+        assert!(log.contains("Bob yawns.\n"));
+    }
+
+    #[test]
+    fn test_cycle_completion() {
+        // 正向：0, 1, 2, 0
+        let mut next = 0;
+        let mut shown = Vec::new();
+        for _ in 0..4 {
+            let (index, n) = cycle_completion(next, 3, false);
+            shown.push(index);
+            next = n;
+        }
+        assert_eq!(shown, vec![0, 1, 2, 0]);
+
+        // 反向：從頭開始先到最後一個，經過 0 後繞回
+        let mut next = 0;
+        let mut shown = Vec::new();
+        for _ in 0..4 {
+            let (index, n) = cycle_completion(next, 3, true);
+            shown.push(index);
+            next = n;
+        }
+        assert_eq!(shown, vec![2, 1, 0, 2]);
+
+        // 正向後反向回到上一個
+        assert_eq!(cycle_completion(1, 3, true), (2, 3));
+        assert_eq!(cycle_completion(3, 3, true), (1, 2));
+        assert_eq!(cycle_completion(1, 1, true), (0, 1));
+    }
+
+    #[test]
+    fn test_scroll_position_at_bottom() {
+        // 內容比視窗短：永遠視為在底部
+        assert!(ScrollPosition::capture(0.0, 100.0, 400.0).at_bottom);
+        // 捲到最底或差距在容許範圍內
+        assert!(ScrollPosition::capture(600.0, 1000.0, 400.0).at_bottom);
+        assert!(ScrollPosition::capture(597.5, 1000.0, 400.0).at_bottom);
+        // 往上捲動閱讀中
+        let position = ScrollPosition::capture(250.0, 1000.0, 400.0);
+        assert!(!position.at_bottom);
+        assert_eq!(position.offset, 250.0);
+    }
+
+    #[test]
+    fn test_trigger_appends_note_and_file() {
+        let mut session = Session::from_profile(&Profile::new("quest", "Quest"));
+        session.notes = "既有筆記".to_string();
+        let file_path = std::env::temp_dir().join("mudclient_test_notes").join("quests.txt");
+        let _ = std::fs::remove_file(&file_path);
+
+        session.engine.trigger_manager.add(
+            Trigger::new("quest", TriggerPattern::Regex(r"^你接下了任務「(.+)」".to_string()))
+                .add_action(TriggerAction::AppendNote("任務: $1".to_string()))
+                .add_action(TriggerAction::AppendFile {
+                    path: file_path.to_string_lossy().to_string(),
+                    text: "%1".to_string(),
+                }),
+        );
+
+        session.handle_text("你接下了任務「尋找失落的劍」。", false);
+        session.handle_text("天氣晴朗。", false);
+        session.handle_text("你接下了任務「護送商隊」。", false);
+
+        assert_eq!(session.notes, "既有筆記\n任務: 尋找失落的劍\n任務: 護送商隊\n");
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "尋找失落的劍\n護送商隊\n");
+
+        // 設定檔往返
+        let config = Session::config_from_trigger(session.engine.trigger_manager.get("quest").unwrap());
+        assert_eq!(config.action_type, TriggerActionType::AppendNote);
+        assert_eq!(config.action, "任務: $1");
+
+        // 寫入失敗時顯示系統訊息
+        let dir = std::env::temp_dir();
+        session.append_to_file(&dir.to_string_lossy(), "x");
+        assert!(session.window_manager.main_window().messages().any(|m| m.content.contains("無法寫入檔案")));
+    }
+
+    #[test]
+    fn test_combined_pattern_config_roundtrip() {
+        let config = TriggerConfig {
+            name: "flee".to_string(),
+            pattern: "你快死了, ^(\\w+) hits you".to_string(),
+            action: "flee".to_string(),
+            combine: PatternCombine::AnyOf,
+            default_enabled: true,
+            ..Default::default()
+        };
+        let trigger = Session::create_trigger_from_config(&config).unwrap();
+        assert_eq!(trigger.try_match("orc hits you").unwrap().captures, vec!["orc"]);
+        assert!(trigger.try_match("你快死了！").is_some());
+        assert_eq!(Session::config_from_trigger(&trigger), config);
+    }
+
+    #[test]
+    fn test_trigger_debug_entries() {
+        let mut session = Session::from_profile(&Profile::new("debug", "Debug"));
+        session.engine.trigger_manager.add(Trigger::new("tell", TriggerPattern::Regex(r"^(\w+) tells you (.+)$".to_string())));
+        session.engine.trigger_manager.add(Trigger::new("hi", TriggerPattern::Contains("hi".to_string())));
+        session.engine.trigger_manager.add(
+            Trigger::new("typed", TriggerPattern::Contains("score".to_string())).with_scope(mudcore::TriggerScope::Input),
+        );
+
+        // 預設關閉：不建立除錯視窗
+        session.handle_text("Bob tells you hi", false);
+        assert!(session.window_manager.get(TRIGGER_DEBUG_WINDOW_ID).is_none());
+
+        session.trigger_debug = true;
+        session.handle_text("Bob tells you hi", false);
+        session.handle_text("quiet line", false);
+        session.handle_user_input("score");
+
+        let lines: Vec<String> = session
+            .window_manager
+            .get(TRIGGER_DEBUG_WINDOW_ID)
+            .unwrap()
+            .messages()
+            .map(|m| m.content.clone())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "[輸出] tell ← 「Bob tells you hi」 捕獲: $1=Bob, $2=hi",
+                "[輸出] hi ← 「hi」",
+                "[輸入] typed ← 「score」",
+            ]
+        );
+    }
 }